@@ -0,0 +1,118 @@
+//! Finding suspicious tracking data, such as periods left running by mistake.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::{totals::split_days, Sheet};
+
+/// Something suspicious about the events in a sheet. Events are identified by their index in the
+/// sheet's list of events, together with their start time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A finished event that lasted longer than expected.
+    LongSession {
+        index: usize,
+        start: DateTime<Utc>,
+        length: Duration,
+    },
+    /// A finished event that lasted less than a minute.
+    ShortSession {
+        index: usize,
+        start: DateTime<Utc>,
+        length: Duration,
+    },
+    /// A local date on which more time was tracked than expected.
+    LongDay { date: NaiveDate, total: Duration },
+    /// An event with the same start and stop times as an earlier event.
+    Duplicate {
+        index: usize,
+        start: DateTime<Utc>,
+        /// The index of the earlier event.
+        of: usize,
+    },
+}
+
+impl Anomaly {
+    /// Get the index of the event this anomaly is about, if it's about a single event.
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            Anomaly::LongSession { index, .. }
+            | Anomaly::ShortSession { index, .. }
+            | Anomaly::Duplicate { index, .. } => Some(*index),
+            Anomaly::LongDay { .. } => None,
+        }
+    }
+}
+
+/// Find the anomalies among the finished events in the given sheet that overlap with the time
+/// between the two given instants: events longer than `max_session` or shorter than a minute,
+/// dates with more than `max_day` tracked in total, and duplicated events.
+///
+/// The sheet should be a single user's [view][view] of a shared sheet.
+///
+/// [view]: ../sheet/struct.Sheet.html#method.user_view
+pub fn find(
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+    max_session: Duration,
+    max_day: Duration,
+) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+    let mut days: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+
+    for (index, event) in sheet.events().iter().enumerate() {
+        let Some(stop) = event.stop else {
+            continue;
+        };
+
+        if event.start >= end || stop <= begin {
+            continue;
+        }
+
+        let length = stop - event.start;
+
+        if length > max_session {
+            anomalies.push(Anomaly::LongSession {
+                index,
+                start: event.start,
+                length,
+            });
+        } else if length < Duration::minutes(1) {
+            anomalies.push(Anomaly::ShortSession {
+                index,
+                start: event.start,
+                length,
+            });
+        }
+
+        // Events are sorted by start time, so duplicates are next to each other.
+        let of = sheet.events()[..index]
+            .iter()
+            .rev()
+            .take_while(|e| e.start == event.start)
+            .position(|e| e.stop == event.stop)
+            .map(|back| index - 1 - back);
+
+        if let Some(of) = of {
+            anomalies.push(Anomaly::Duplicate {
+                index,
+                start: event.start,
+                of,
+            });
+        }
+
+        for (date, time) in split_days(event.start, stop) {
+            *days.entry(date).or_insert_with(Duration::zero) += time;
+        }
+    }
+
+    anomalies.extend(
+        days.into_iter()
+            .filter(|(_, total)| *total > max_day)
+            .map(|(date, total)| Anomaly::LongDay { date, total }),
+    );
+
+    anomalies
+}
@@ -0,0 +1,294 @@
+//! Bundling everything Punch Clock keeps into a single archive, and restoring it.
+//!
+//! A backup is a gzipped tar archive, with the config file and the signing key and `hooks`
+//! directory next to it under `config/`, and the sheet, its backup and checksum, the archive of old
+//! tracking periods, the history, the invoice registry and the `snapshots` directory under `data/`.
+//! The cache of daily totals is left out, since it's rebuilt from the sheet. Restoring puts each
+//! file back where the current locations say it belongs, so a backup made on one machine can be
+//! restored on another.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use tar::{Archive, Builder};
+use thiserror::Error;
+
+use crate::{
+    checksum::Checksum,
+    history::History,
+    invoices::Invoices,
+    sheet::{SheetError, SheetFormat},
+    snapshot::Snapshot,
+    Sheet,
+};
+
+/// Where the files that are backed up are kept.
+#[derive(Clone, Debug)]
+pub struct Locations {
+    /// The config file.
+    pub config: PathBuf,
+    /// The sheet file, next to which the other data files are kept.
+    pub sheet: PathBuf,
+}
+
+impl Locations {
+    /// Get the directory hook scripts are kept in, next to the config file.
+    pub fn hooks_dir(&self) -> PathBuf {
+        self.config.with_file_name("hooks")
+    }
+
+    /// Get the directory snapshots of the sheet are kept in.
+    pub fn snapshots_dir(&self) -> PathBuf {
+        Snapshot::dir_for(&self.sheet)
+    }
+
+    /// Get the files next to the config that are backed up, as the paths they're kept at and their
+    /// names in a backup.
+    fn config_files(&self) -> Vec<(PathBuf, &'static str)> {
+        vec![
+            (self.config.clone(), "config/config.toml"),
+            (
+                self.config.with_file_name("signing.key"),
+                "config/signing.key",
+            ),
+        ]
+    }
+
+    /// Get the data files that are backed up, as the paths they're kept at and their names in a
+    /// backup.
+    fn data_files(&self) -> Vec<(PathBuf, String)> {
+        let name = file_name(&self.sheet);
+
+        [
+            self.sheet.clone(),
+            self.sheet.with_file_name(format!("{}.bak", name)),
+            Checksum::loc_for(&self.sheet),
+            self.sheet.with_file_name("archive.json"),
+            History::loc_for(&self.sheet),
+            Invoices::loc_for(&self.sheet),
+        ]
+        .into_iter()
+        .map(|path| {
+            let name = format!("data/{}", file_name(&path));
+            (path, name)
+        })
+        .collect()
+    }
+
+    /// Get the path the file with the given name in a backup is restored to, or `None` if the
+    /// name isn't one a backup would have.
+    fn target(&self, name: &Path) -> Option<PathBuf> {
+        let mut parts = Vec::new();
+
+        for component in name.components() {
+            match component {
+                Component::Normal(part) => parts.push(part),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+
+        match parts.as_slice() {
+            [dir, _] if *dir == "config" => self
+                .config_files()
+                .into_iter()
+                .find(|(_, backed_up)| name == Path::new(backed_up))
+                .map(|(path, _)| path),
+            [dir, hooks, rest @ ..]
+                if *dir == "config" && *hooks == "hooks" && !rest.is_empty() =>
+            {
+                Some(
+                    rest.iter()
+                        .fold(self.hooks_dir(), |path, part| path.join(part)),
+                )
+            }
+            [dir, file] if *dir == "data" => Some(self.sheet.with_file_name(file)),
+            [dir, snapshots, file] if *dir == "data" && *snapshots == "snapshots" => {
+                Some(self.snapshots_dir().join(file))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Errors arising while making or restoring a backup.
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("unable to add {} to the backup", .0.display())]
+    AddFile(PathBuf, #[source] io::Error),
+    #[error("unable to write the backup")]
+    WriteBackup(#[source] io::Error),
+    #[error("unable to read the backup")]
+    ReadBackup(#[source] io::Error),
+    #[error("unexpected file {} in the backup", .0.display())]
+    UnexpectedFile(PathBuf),
+    #[error("unable to restore {}", .0.display())]
+    RestoreFile(PathBuf, #[source] io::Error),
+    #[error("no {} in the backup", .0.display())]
+    NoSheet(PathBuf),
+    #[error("unable to read the sheet in the backup")]
+    ReadSheet(#[source] SheetError),
+}
+
+/// Write a backup of the files kept at the given locations, returning the paths of the files that
+/// were included. Files that don't exist are skipped.
+pub fn dump<W>(locations: &Locations, out: W) -> Result<Vec<PathBuf>, BackupError>
+where
+    W: Write,
+{
+    let mut builder = Builder::new(GzEncoder::new(out, Compression::default()));
+    let mut included = Vec::new();
+
+    for (path, name) in locations.config_files() {
+        if path.is_file() {
+            builder
+                .append_path_with_name(&path, name)
+                .map_err(|err| BackupError::AddFile(path.clone(), err))?;
+            included.push(path);
+        }
+    }
+
+    let hooks = locations.hooks_dir();
+
+    if hooks.is_dir() {
+        builder
+            .append_dir_all("config/hooks", &hooks)
+            .map_err(|err| BackupError::AddFile(hooks.clone(), err))?;
+        included.push(hooks);
+    }
+
+    for (path, name) in locations.data_files() {
+        if path.is_file() {
+            builder
+                .append_path_with_name(&path, name)
+                .map_err(|err| BackupError::AddFile(path.clone(), err))?;
+            included.push(path);
+        }
+    }
+
+    let snapshots = locations.snapshots_dir();
+
+    if snapshots.is_dir() {
+        builder
+            .append_dir_all("data/snapshots", &snapshots)
+            .map_err(|err| BackupError::AddFile(snapshots.clone(), err))?;
+        included.push(snapshots);
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut out| out.flush())
+        .map_err(BackupError::WriteBackup)?;
+
+    Ok(included)
+}
+
+/// Get the paths the files in the backup at the given path would be restored to.
+pub fn contents<P>(locations: &Locations, path: P) -> Result<Vec<PathBuf>, BackupError>
+where
+    P: AsRef<Path>,
+{
+    let mut archive = open(path)?;
+    let mut targets = Vec::new();
+
+    for entry in archive.entries().map_err(BackupError::ReadBackup)? {
+        let entry = entry.map_err(BackupError::ReadBackup)?;
+
+        if entry.header().entry_type().is_file() {
+            let name = entry.path().map_err(BackupError::ReadBackup)?;
+            let target = locations
+                .target(&name)
+                .ok_or_else(|| BackupError::UnexpectedFile(name.into_owned()))?;
+            targets.push(target);
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Restore the files in the backup at the given path where they belong, replacing any already
+/// there, returning the paths they were restored to.
+pub fn restore<P>(locations: &Locations, path: P) -> Result<Vec<PathBuf>, BackupError>
+where
+    P: AsRef<Path>,
+{
+    let mut archive = open(path)?;
+    let mut restored = Vec::new();
+
+    for entry in archive.entries().map_err(BackupError::ReadBackup)? {
+        let mut entry = entry.map_err(BackupError::ReadBackup)?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path().map_err(BackupError::ReadBackup)?;
+        let target = locations
+            .target(&name)
+            .ok_or_else(|| BackupError::UnexpectedFile(name.into_owned()))?;
+
+        target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| entry.unpack(&target).map(|_| ()))
+            .map_err(|err| BackupError::RestoreFile(target.clone(), err))?;
+        restored.push(target);
+    }
+
+    Ok(restored)
+}
+
+/// Whether the file at the given path looks like a backup, rather than a sheet, from it being
+/// gzipped.
+pub fn is_backup<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    let mut magic = [0; 2];
+
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| magic == [0x1f, 0x8b])
+}
+
+/// Read the sheet in the backup at the given path, which has the same name as the sheet at the
+/// given locations.
+pub fn read_sheet<P>(locations: &Locations, path: P) -> Result<Sheet, BackupError>
+where
+    P: AsRef<Path>,
+{
+    let name = Path::new("data").join(file_name(&locations.sheet));
+    let mut archive = open(path)?;
+
+    for entry in archive.entries().map_err(BackupError::ReadBackup)? {
+        let entry = entry.map_err(BackupError::ReadBackup)?;
+
+        if entry.path().map_err(BackupError::ReadBackup)? == name {
+            let format = SheetFormat::from_path(&locations.sheet);
+            return Sheet::read(BufReader::new(entry), format).map_err(BackupError::ReadSheet);
+        }
+    }
+
+    Err(BackupError::NoSheet(name))
+}
+
+/// Open the backup at the given path for reading.
+fn open<P>(path: P) -> Result<Archive<impl Read>, BackupError>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(path).map_err(BackupError::ReadBackup)?;
+    Ok(Archive::new(GzDecoder::new(file)))
+}
+
+/// Get the name of the file at the given path, or an empty string if it has none.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,127 @@
+//! Syncing tracking periods with calendars on a CalDAV server ([RFC 4791]).
+//!
+//! Requests are made with `curl`, logging in with the configured user name and the password in
+//! `PUNCH_CALDAV_PASSWORD` or printed by a configured command. Each tracking period is stored as an
+//! event whose UID comes from its start, so pushing it again replaces the event instead of adding
+//! another. Events can also be pulled from a second calendar, to be added to the sheet.
+//!
+//! [RFC 4791]: https://www.rfc-editor.org/rfc/rfc4791
+
+use std::io;
+
+use chrono::{DateTime, Utc};
+use punch_clock::{config::CaldavConfig, Event};
+use thiserror::Error;
+
+use crate::{
+    http::{self, encode, send, HttpError},
+    ical,
+};
+
+/// Errors arising while syncing with a CalDAV server.
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("no calendar: set push.caldav.url")]
+    NoUrl,
+    #[error("unable to run password command")]
+    RunPasswordCommand(#[source] io::Error),
+    #[error("no password: set PUNCH_CALDAV_PASSWORD or push.caldav.password_command")]
+    NoPassword,
+    #[error("{0}")]
+    Http(#[from] HttpError),
+}
+
+/// Store each of the given events that has finished in the calendar, replacing any earlier copy,
+/// returning the number stored.
+pub fn push(config: &CaldavConfig, events: &[Event]) -> Result<usize, SyncError> {
+    let url = config.url.as_deref().ok_or(SyncError::NoUrl)?;
+    let url = url.trim_end_matches('/');
+    let password = password(config)?;
+    let login = login(config, password.as_deref());
+
+    let mut pushed = 0;
+
+    for event in events.iter().filter(|event| event.stop.is_some()) {
+        let body = ical::calendar(std::slice::from_ref(event));
+        let event_url = format!("{}/{}.ics", url, encode(&ical::uid(event)));
+
+        send(
+            "PUT",
+            &event_url,
+            login,
+            &["Content-Type: text/calendar; charset=utf-8"],
+            Some(&body),
+        )?;
+        pushed += 1;
+    }
+
+    Ok(pushed)
+}
+
+/// Get the events in the calendar at `push.caldav.pull_url` that overlap the time between the two
+/// given instants, with their UIDs, or none if there is no such calendar.
+pub fn pull(
+    config: &CaldavConfig,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Option<Vec<(String, Event)>>, SyncError> {
+    let Some(url) = config.pull_url.as_deref() else {
+        return Ok(None);
+    };
+    let password = password(config)?;
+
+    // `Period::All` begins earlier than iCalendar times can be written.
+    let begin = begin.max(DateTime::UNIX_EPOCH);
+    let query = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data/></d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR">
+      <c:comp-filter name="VEVENT">
+        <c:time-range start="{}" end="{}"/>
+      </c:comp-filter>
+    </c:comp-filter>
+  </c:filter>
+</c:calendar-query>"#,
+        ical::format_time(begin),
+        ical::format_time(end)
+    );
+
+    let response = send(
+        "REPORT",
+        url,
+        login(config, password.as_deref()),
+        &["Depth: 1", "Content-Type: application/xml; charset=utf-8"],
+        Some(&query),
+    )?;
+
+    Ok(Some(ical::parse(&unescape_xml(&response))))
+}
+
+/// Get the password to log in with, if there's a user name to log in as.
+fn password(config: &CaldavConfig) -> Result<Option<String>, SyncError> {
+    if config.username.is_none() {
+        return Ok(None);
+    }
+
+    http::token("PUNCH_CALDAV_PASSWORD", config.password_command.as_deref())
+        .map_err(SyncError::RunPasswordCommand)?
+        .ok_or(SyncError::NoPassword)
+        .map(Some)
+}
+
+/// Pair the configured user name with the given password, if there are both.
+fn login<'a>(config: &'a CaldavConfig, password: Option<&'a str>) -> Option<(&'a str, &'a str)> {
+    config.username.as_deref().zip(password)
+}
+
+/// Undo the escaping of the calendar data in an XML response.
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&amp;", "&")
+}
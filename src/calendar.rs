@@ -0,0 +1,95 @@
+//! Drawing a month as a calendar, with the time worked on each day.
+
+use std::io::{self, IsTerminal};
+
+use chrono::{Datelike, Days, Duration, Months, NaiveDate};
+use punch_clock::{midnight, totals, Sheet};
+
+/// The width of each day in the grid.
+const CELL: usize = 7;
+/// The 256-colour palette backgrounds that days are shaded with, from the least worked to the most.
+const SHADES: [u8; 4] = [22, 28, 34, 40];
+
+/// Print the month starting on the given date as a grid of weeks, from Monday to Sunday, with the
+/// hours worked on each day under its number, shaded by how much was worked if printing to a
+/// terminal.
+pub fn print(sheet: &Sheet, first: NaiveDate) {
+    let next = first + Months::new(1);
+    let (start, end) = (midnight(first).into(), midnight(next).into());
+    let days = totals::totals_by_date(sheet, start, end);
+    let max = days.values().max().copied().unwrap_or_else(Duration::zero);
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    let title = first.format("%B %Y").to_string();
+    say!(
+        "{}",
+        format!("{:^width$}", title, width = CELL * 7).trim_end()
+    );
+    say!(
+        "{}",
+        ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+            .iter()
+            .map(|day| format!("{:^CELL$}", crate::i18n::translate(day)))
+            .collect::<String>()
+            .trim_end()
+    );
+
+    let mut monday = first - Days::new(first.weekday().num_days_from_monday().into());
+
+    while monday < next {
+        let week: Vec<_> = (0..7).map(|day| monday + Days::new(day)).collect();
+        let in_month = |date: &NaiveDate| date.month() == first.month();
+
+        let numbers: String = week
+            .iter()
+            .map(|date| match in_month(date) {
+                true => format!("{:^CELL$}", date.day()),
+                false => " ".repeat(CELL),
+            })
+            .collect();
+
+        let hours: String = week
+            .iter()
+            .map(|date| {
+                let time = days.get(date).copied().unwrap_or_else(Duration::zero);
+
+                match (in_month(date), heat_level(time, max)) {
+                    (false, _) => " ".repeat(CELL),
+                    (true, None) => format!("{:^CELL$}", "·"),
+                    (true, Some(level)) => {
+                        let text = format!("{:^CELL$}", format!("{:.1}h", hours(time)));
+
+                        if color {
+                            format!("\x1b[48;5;{}m{}\x1b[0m", SHADES[level], text)
+                        } else {
+                            text
+                        }
+                    }
+                }
+            })
+            .collect();
+
+        say!("{}", numbers.trim_end());
+        say!("{}", hours.trim_end());
+
+        monday = monday + Days::new(7);
+    }
+}
+
+/// Scale an amount of time worked to one of the levels of a heatmap, relative to the most worked
+/// in the time being shown, returning `None` if nothing was worked.
+pub fn heat_level(time: Duration, max: Duration) -> Option<usize> {
+    if time <= Duration::zero() || max <= Duration::zero() {
+        return None;
+    }
+
+    let levels = SHADES.len() as i64;
+    let level = (time.num_seconds() * levels - 1) / max.num_seconds();
+
+    Some(level.clamp(0, levels - 1) as usize)
+}
+
+/// Get the number of hours in a length of time.
+fn hours(time: Duration) -> f64 {
+    time.num_seconds() as f64 / 3600.0
+}
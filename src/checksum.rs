@@ -0,0 +1,208 @@
+//! Checksums of sheet files, for noticing when a sheet has been changed behind Punch Clock's back.
+//!
+//! Each time the sheet is written, the SHA-256 checksum of the file and the number of tracking
+//! periods in it are recorded in a file next to it, named after the sheet with `.sum` added (e.g.
+//! `sheet.json.sum`). A sheet that no longer matches its checksum may have been corrupted, or only
+//! partly synced from another machine, so it shouldn't be written over until that's looked into.
+//!
+//! The size and modification time of the file are recorded too, and a sheet that still has both is
+//! taken to match without reading it, so that checking a large sheet before every command is cheap.
+//! Before writing over the sheet, and in `punch doctor`, it's always read, since a file can change
+//! without changing its size or modification time.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// The checksum of a sheet file as it was last written.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    /// The SHA-256 checksum of the file's contents, in hexadecimal.
+    pub sha256: String,
+    /// The number of tracking periods in the file.
+    pub events: usize,
+    /// The size of the file in bytes, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// When the file was last modified, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<SystemTime>,
+}
+
+impl Checksum {
+    /// Get the path to the checksum file for the sheet at the given path.
+    pub fn loc_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut path = sheet_path.as_ref().as_os_str().to_owned();
+        path.push(".sum");
+        PathBuf::from(path)
+    }
+
+    /// Attempt to load the checksum recorded for the sheet at the given path, or `None` if none
+    /// has been recorded.
+    pub fn load<P>(sheet_path: P) -> Result<Option<Checksum>, ChecksumError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = match File::open(Self::loc_for(sheet_path)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(ChecksumError::ReadChecksum(err)),
+        };
+
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(ChecksumError::ParseChecksum)
+    }
+
+    /// Compute the checksum of the sheet at the given path, which holds the given number of
+    /// tracking periods, and record it next to the sheet.
+    pub fn record<P>(sheet_path: P, events: usize) -> Result<Checksum, ChecksumError>
+    where
+        P: AsRef<Path>,
+    {
+        let metadata = fs::metadata(&sheet_path).map_err(ChecksumError::ReadSheet)?;
+        let checksum = Checksum {
+            sha256: digest(&sheet_path).map_err(ChecksumError::ReadSheet)?,
+            events,
+            size: Some(metadata.len()),
+            modified: metadata.modified().ok(),
+        };
+
+        let json = serde_json::to_vec(&checksum).unwrap();
+        fs::write(Self::loc_for(sheet_path), json).map_err(ChecksumError::WriteChecksum)?;
+
+        Ok(checksum)
+    }
+
+    /// Remove the checksum recorded for the sheet at the given path, if there is one.
+    pub fn remove<P>(sheet_path: P) -> Result<(), ChecksumError>
+    where
+        P: AsRef<Path>,
+    {
+        match fs::remove_file(Self::loc_for(sheet_path)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(ChecksumError::WriteChecksum(err))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check that the sheet at the given path matches the checksum recorded for it, returning the
+/// checksum, or `None` if none has been recorded.
+///
+/// The sheet is only read if its size or modification time differ from the ones recorded. A sheet
+/// that's missing when a checksum has been recorded for it doesn't match.
+pub fn verify<P>(sheet_path: P) -> Result<Option<Checksum>, ChecksumError>
+where
+    P: AsRef<Path>,
+{
+    verify_with(sheet_path, true)
+}
+
+/// Check that the sheet at the given path matches the checksum recorded for it like
+/// [`verify()`][verify], but always reading the sheet, so that changes that kept its size and
+/// modification time are noticed too.
+///
+/// [verify]: fn.verify.html
+pub fn verify_full<P>(sheet_path: P) -> Result<Option<Checksum>, ChecksumError>
+where
+    P: AsRef<Path>,
+{
+    verify_with(sheet_path, false)
+}
+
+/// Check that the sheet at the given path matches the checksum recorded for it, taking it to match
+/// without reading it if `trust_metadata` is set and its size and modification time are the ones
+/// recorded.
+fn verify_with<P>(sheet_path: P, trust_metadata: bool) -> Result<Option<Checksum>, ChecksumError>
+where
+    P: AsRef<Path>,
+{
+    let Some(expected) = Checksum::load(&sheet_path)? else {
+        return Ok(None);
+    };
+
+    if let (true, Some(size), Some(modified), Ok(metadata)) = (
+        trust_metadata,
+        expected.size,
+        expected.modified,
+        fs::metadata(&sheet_path),
+    ) {
+        if metadata.len() == size && metadata.modified().ok() == Some(modified) {
+            return Ok(Some(expected));
+        }
+    }
+
+    match digest(&sheet_path) {
+        Ok(found) if found == expected.sha256 => Ok(Some(expected)),
+        Ok(found) => Err(ChecksumError::Mismatch { expected, found }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err(ChecksumError::Missing { expected })
+        }
+        Err(err) => Err(ChecksumError::ReadSheet(err)),
+    }
+}
+
+/// Compute the SHA-256 checksum of the contents of the file at the given path, in hexadecimal.
+pub fn digest<P>(path: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        }))
+}
+
+/// Errors arising while recording or checking checksums.
+#[derive(Error, Debug)]
+pub enum ChecksumError {
+    #[error("unable to read sheet file")]
+    ReadSheet(#[source] io::Error),
+    #[error("unable to read checksum file")]
+    ReadChecksum(#[source] io::Error),
+    #[error("unable to parse checksum file")]
+    ParseChecksum(#[source] serde_json::Error),
+    #[error("unable to write checksum file")]
+    WriteChecksum(#[source] io::Error),
+    #[error(
+        "the sheet was last written with {} tracking periods and checksum {}, but now has checksum {found}",
+        .expected.events,
+        .expected.sha256
+    )]
+    Mismatch { expected: Checksum, found: String },
+    #[error(
+        "the sheet was last written with {} tracking periods, but is missing",
+        .expected.events
+    )]
+    Missing { expected: Checksum },
+}
+
+impl ChecksumError {
+    /// Whether the error is due to the sheet not matching its checksum, rather than a checksum
+    /// that couldn't be read or written.
+    pub fn is_mismatch(&self) -> bool {
+        matches!(
+            self,
+            ChecksumError::Mismatch { .. } | ChecksumError::Missing { .. }
+        )
+    }
+}
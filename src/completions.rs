@@ -0,0 +1,165 @@
+//! Printing scripts that complete commands in shells.
+//!
+//! The scripts are generated by clap, and then extended so that bash, zsh and fish complete the
+//! names of projects and tags by running the hidden `punch complete` command, which prints the
+//! names in the sheet. Other shells only complete commands and options.
+
+use std::collections::BTreeSet;
+
+use punch_clock::{Config, Sheet};
+use structopt::{clap::Shell, StructOpt};
+
+use crate::opt::Opt;
+
+/// The options that take the name of a project.
+const PROJECT_OPTIONS: [&str; 1] = ["--project"];
+/// The options that take the name of a tag.
+const TAG_OPTIONS: [&str; 3] = ["--tag", "--add-tag", "--remove-tag"];
+
+/// Print a script that completes commands in the given shell.
+pub fn print(shell: Shell) {
+    let mut generated = Vec::new();
+    Opt::clap().gen_completions_to("punch", shell, &mut generated);
+    let generated = String::from_utf8_lossy(&generated);
+
+    let script = match shell {
+        Shell::Bash => format!("{}\n{}", generated, BASH),
+        Shell::Zsh => zsh(&generated),
+        Shell::Fish => fish(&generated),
+        _ => generated.into_owned(),
+    };
+
+    print!("{}", script);
+}
+
+/// Completes names in place of clap's `_punch` where they're expected, and otherwise calls it.
+const BASH: &str = r#"_punch_names() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" names=""
+
+    case "${prev}" in
+        --project)
+            names="projects"
+            ;;
+        --tag|--add-tag|--remove-tag)
+            names="tags"
+            ;;
+        in)
+            [[ ${COMP_CWORD} -eq 2 && ${cur} != -* ]] && names="projects"
+            ;;
+    esac
+
+    if [[ -n ${names} ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$(punch complete ${names} 2>/dev/null)" -- "${cur}"))
+        return 0
+    fi
+
+    _punch
+}
+
+complete -F _punch_names -o bashdefault -o default punch
+"#;
+
+/// Functions completing names, defined before clap's script calls `_punch`.
+const ZSH: &str = r#"(( $+functions[_punch_projects] )) ||
+_punch_projects() {
+    local -a projects
+    projects=(${(f)"$(punch complete projects 2>/dev/null)"})
+    _describe -t projects 'project' projects
+}
+(( $+functions[_punch_tags] )) ||
+_punch_tags() {
+    local -a tags
+    tags=(${(f)"$(punch complete tags 2>/dev/null)"})
+    _describe -t tags 'tag' tags
+}
+
+"#;
+
+/// Make clap's zsh script complete names for the project argument of `punch in` and for options
+/// taking names, which it otherwise completes as files or not at all.
+fn zsh(generated: &str) -> String {
+    let mut script = String::new();
+
+    for line in generated.lines() {
+        let option = line
+            .trim_start_matches(['\'', '*'])
+            .split_once("=[")
+            .and_then(|(option, _)| names_for(option));
+
+        if line.starts_with("'::project -- ") {
+            script.push_str(&line.replace(":_files'", ":_punch_projects'"));
+        } else if line == "_punch \"$@\"" {
+            script.push_str(ZSH);
+            script.push_str(line);
+        } else if let Some(names) = option {
+            script.push_str(&line.replacen("]'", &format!("]: :_punch_{}'", names), 1));
+        } else {
+            script.push_str(line);
+        }
+
+        script.push('\n');
+    }
+
+    script
+}
+
+/// Get the names of the projects or tags in the given sheet, leaving out archived projects.
+pub fn names<'a>(config: &Config, sheet: &'a Sheet, names: &str) -> BTreeSet<&'a str> {
+    match names {
+        "projects" => sheet
+            .events()
+            .iter()
+            .filter_map(|event| event.project.as_deref())
+            .filter(|project| !config.projects.is_archived(project))
+            .collect(),
+        _ => sheet
+            .events()
+            .iter()
+            .flat_map(|event| event.tags.iter().map(String::as_str))
+            .collect(),
+    }
+}
+
+/// Get what the given option takes the name of: projects or tags.
+pub fn names_for(option: &str) -> Option<&'static str> {
+    if PROJECT_OPTIONS.contains(&option) {
+        Some("projects")
+    } else if TAG_OPTIONS.contains(&option) {
+        Some("tags")
+    } else {
+        None
+    }
+}
+
+/// Add completions of names to clap's fish script, and leave out the hidden `complete` command.
+fn fish(generated: &str) -> String {
+    let mut script: String = generated
+        .lines()
+        .filter(|line| !line.contains(r#"-a "complete" "#))
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    script.push_str(
+        "complete -c punch -n \"__fish_seen_subcommand_from in\" -f -a \"(punch complete projects \
+         2>/dev/null)\"\n",
+    );
+
+    for (options, names) in [
+        (&PROJECT_OPTIONS[..], "projects"),
+        (&TAG_OPTIONS[..], "tags"),
+    ] {
+        let options: Vec<_> = options
+            .iter()
+            .map(|option| format!("-l {}", option.trim_start_matches('-')))
+            .collect();
+
+        script.push_str(&format!(
+            "complete -c punch {} -x -a \"(punch complete {} 2>/dev/null)\"\n",
+            options.join(" "),
+            names
+        ));
+    }
+
+    script
+}
@@ -0,0 +1,1184 @@
+//! Loading user configuration.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Days, Duration, Local, Months, NaiveTime, Utc};
+use directories::{BaseDirs, ProjectDirs};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    deductions::Deduction,
+    on_call::OnCall,
+    period::midnight,
+    sheet::{SheetError, SheetFormat},
+    Counting, Event, Period, RateHistory, Rates, Rounding, RoundingPolicy, Schedule, Sheet,
+    YearStart,
+};
+
+/// User configuration, read from a TOML file.
+///
+/// Every setting is optional, and settings that are left out take their default values.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The language to print messages in, as a code such as `de` (default: the language given by
+    /// the `LC_ALL`, `LC_MESSAGES` or `LANG` environment variable).
+    pub language: Option<String>,
+    /// The name of the user recorded on new tracking periods, whose tracking periods commands act
+    /// on when the sheet is shared with other users (default: the current login name).
+    pub user: Option<String>,
+    /// The hours that are expected to be worked on each day of the week.
+    pub schedule: Schedule,
+    /// The month and day on which each fiscal year starts, as `MM-DD`, for the fiscal year and
+    /// quarter periods (default: `"01-01"`).
+    pub fiscal_year_start: YearStart,
+    /// Settings for tracking periods.
+    pub sessions: SessionConfig,
+    /// Settings for projects.
+    pub projects: ProjectsConfig,
+    /// The hourly rates charged for work, for `punch earnings` and invoices.
+    pub rates: Rates,
+    /// How tracked time is rounded by `punch count`, `punch report` and invoices.
+    pub rounding: Rounding,
+    /// Whether `punch count` and `punch report` count the breaks between tracking periods on the
+    /// same day as time worked (`"gross"`), or not (`"net"`, the default).
+    pub counting: Counting,
+    /// Rules for deducting time for breaks from the total of each day worked for longer than a
+    /// number of hours, in `punch count` and `punch report`.
+    pub deductions: Vec<Deduction>,
+    /// Settings for telling time spent on call apart from time worked.
+    pub on_call: OnCall,
+    /// Settings for `punch invoice`.
+    pub invoices: InvoicesConfig,
+    /// Settings for `punch watch`.
+    pub watch: WatchConfig,
+    /// Settings for how the sheet is stored.
+    pub storage: StorageConfig,
+    /// Templates overriding the default output of some commands.
+    pub templates: TemplatesConfig,
+    /// Settings for submitting tracking periods for review.
+    pub review: ReviewConfig,
+    /// Settings for signing tracking periods.
+    pub signing: SigningConfig,
+    /// Settings for pushing tracking periods to other services.
+    pub push: PushConfig,
+    /// Settings for `punch export`.
+    pub export: ExportConfig,
+    /// Settings for showing the status in Home Assistant.
+    pub homeassistant: HomeAssistantConfig,
+    /// Commands of one's own, each standing for the arguments it's mapped to, such as
+    /// `w = "count week"`. Commands built into `punch` can't be replaced.
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Attempt to load the config from the file at the default location, as determined by
+    /// [`default_loc()`][default].
+    ///
+    /// [default]: #method.default_loc
+    pub fn load_default() -> Result<Config, ConfigError> {
+        Self::load(Self::default_loc()?)
+    }
+
+    /// Attempt to load the config from the file at the given path.
+    pub fn load<P>(path: P) -> Result<Config, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut config_toml = String::new();
+
+        {
+            let mut config_file = File::open(&path).map_err(ConfigError::OpenConfig)?;
+
+            config_file
+                .read_to_string(&mut config_toml)
+                .map_err(ConfigError::ReadConfig)?;
+        }
+
+        let mut config: Config = toml::from_str(&config_toml).map_err(ConfigError::ParseConfig)?;
+        config.apply_project_defaults();
+
+        Ok(config)
+    }
+
+    /// Use the rounding policy and rate in each project's defaults as the project's own, where the
+    /// rounding and rates configs don't already give it one.
+    fn apply_project_defaults(&mut self) {
+        for (project, defaults) in &self.projects.defaults {
+            if let Some(policy) = &defaults.rounding {
+                self.rounding
+                    .projects
+                    .entry(project.clone())
+                    .or_insert_with(|| policy.clone());
+            }
+
+            if let Some(hourly) = defaults.rate {
+                self.rates
+                    .projects
+                    .entry(project.clone())
+                    .or_insert_with(|| RateHistory {
+                        hourly: Some(hourly),
+                        ..RateHistory::default()
+                    });
+            }
+        }
+    }
+
+    /// Get the name of the user recorded on new tracking periods: the configured
+    /// [`user`][user], or if there isn't one, the [login name][login].
+    ///
+    /// [user]: #structfield.user
+    /// [login]: fn.login_name.html
+    pub fn user(&self) -> String {
+        self.user.clone().unwrap_or_else(login_name)
+    }
+
+    /// Get the default directory in which the config file is stored.
+    ///
+    /// This is the directory containing the file given by the `PUNCH_CONFIG` environment variable,
+    /// if it's set. Otherwise, the directory is determined using the [directories][directories]
+    /// crate by platform as follows:
+    ///
+    /// + Linux: `$XDG_CONFIG_HOME/punchclock`
+    /// + macOS: `$HOME/Library/Preferences/dev.neros.PunchClock`
+    /// + Windows: `%APPDATA%\Neros\PunchClock\config`
+    ///
+    /// [directories]: https://crates.io/crates/directories
+    pub fn default_dir() -> Result<PathBuf, ConfigError> {
+        if let Some(path) = env_path("PUNCH_CONFIG") {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            return Ok(dir.to_owned());
+        }
+
+        ProjectDirs::from("dev", "neros", "PunchClock")
+            .ok_or(ConfigError::FindConfig)
+            .map(|dirs| dirs.config_dir().to_owned())
+    }
+
+    /// Get the path to the default config file.
+    ///
+    /// This is the file given by the `PUNCH_CONFIG` environment variable, if it's set, or otherwise
+    /// the file `config.toml` inside the directory returned from [`default_dir()`][default].
+    ///
+    /// [default]: #method.default_dir
+    pub fn default_loc() -> Result<PathBuf, ConfigError> {
+        if let Some(path) = env_path("PUNCH_CONFIG") {
+            return Ok(path);
+        }
+
+        Self::default_dir().map(|mut dir| {
+            dir.push("config.toml");
+            dir
+        })
+    }
+
+    /// Whether any settings are given for the project with the given name, such as a budget or a
+    /// rate.
+    pub fn has_project_settings(&self, project: &str) -> bool {
+        self.projects.budgets.contains_key(project)
+            || self.projects.estimates.contains_key(project)
+            || self.rates.projects.contains_key(project)
+            || self.rounding.projects.contains_key(project)
+            || self.push.kimai.projects.contains_key(project)
+            || self.projects.defaults.contains_key(project)
+            || self.projects.is_archived(project)
+    }
+
+    /// Get the arguments that the alias with the given name stands for, if there is one, split
+    /// up as by [`split_args()`].
+    pub fn alias(&self, name: &str) -> Option<Vec<String>> {
+        self.aliases
+            .get(name)
+            .map(|expansion| split_args(expansion))
+    }
+}
+
+/// Split a command line into arguments, which are separated by whitespace except inside single or
+/// double quotes, so `in "client work"` is split into `in` and `client work`.
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(arg);
+    args
+}
+
+/// Join arguments into a command line that [`split_args()`] splits into the same arguments,
+/// quoting those that contain whitespace or quotes.
+///
+/// As quotes can't be escaped, an argument with both kinds of quote is split into single-quoted
+/// parts, with each single quote between them double-quoted, such as `'it'"'"'s "fine"'`.
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| match arg {
+            arg if arg.contains('"') && arg.contains('\'') => arg
+                .split('\'')
+                .map(|part| format!("'{}'", part))
+                .collect::<Vec<_>>()
+                .join("\"'\""),
+            arg if arg.contains('"') => format!("'{}'", arg),
+            arg if arg.is_empty() || arg.contains('\'') || arg.contains(char::is_whitespace) => {
+                format!("\"{}\"", arg)
+            }
+            arg => arg.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The tables in the config whose keys are names of projects.
+const PROJECT_KEYED: [&[&str]; 6] = [
+    &["projects", "budgets"],
+    &["projects", "defaults"],
+    &["projects", "estimates"],
+    &["rates", "projects"],
+    &["rounding", "projects"],
+    &["push", "kimai", "projects"],
+];
+/// The tables in the config whose values are names of projects.
+const PROJECT_VALUED: [&[&str]; 2] = [&["projects", "paths"], &["projects", "repos"]];
+
+/// Rename a project in the text of a config file, keeping its comments and layout, and get the
+/// new text, or `None` if the config doesn't refer to the project.
+///
+/// The project is renamed in the tables of settings for each project, such as budgets and rates,
+/// in the projects detected for directories and repositories, and in the arguments of aliases.
+/// Where settings are already given for `new`, they're kept, and those for `old` are dropped.
+pub fn rename_project(toml: &str, old: &str, new: &str) -> Result<Option<String>, ConfigError> {
+    let mut doc: toml_edit::DocumentMut = toml.parse().map_err(ConfigError::EditConfig)?;
+    let mut renamed = false;
+
+    for path in PROJECT_KEYED {
+        if let Some(table) = table_at(&mut doc, path) {
+            if let Some(settings) = table.remove(old) {
+                renamed = true;
+
+                if !table.contains_key(new) {
+                    table.insert(new, settings);
+                }
+            }
+        }
+    }
+
+    for path in PROJECT_VALUED {
+        if let Some(table) = table_at(&mut doc, path) {
+            for (_, item) in table.iter_mut() {
+                if let Some(value) = item
+                    .as_value_mut()
+                    .filter(|value| value.as_str() == Some(old))
+                {
+                    let decor = value.decor().clone();
+                    *value = new.into();
+                    *value.decor_mut() = decor;
+                    renamed = true;
+                }
+            }
+        }
+    }
+
+    if let Some(aliases) = table_at(&mut doc, &["aliases"]) {
+        for (_, item) in aliases.iter_mut() {
+            let Some(value) = item.as_value_mut() else {
+                continue;
+            };
+            let Some(args) = value.as_str().map(split_args) else {
+                continue;
+            };
+
+            if args.iter().any(|arg| arg == old) {
+                let args: Vec<_> = args
+                    .into_iter()
+                    .map(|arg| if arg == old { new.to_owned() } else { arg })
+                    .collect();
+                let decor = value.decor().clone();
+                *value = join_args(&args).into();
+                *value.decor_mut() = decor;
+                renamed = true;
+            }
+        }
+    }
+
+    if let Some(archived) = array_at(&mut doc, &["projects", "archived"]) {
+        if archived.iter().any(|value| value.as_str() == Some(old)) {
+            let has_new = archived.iter().any(|value| value.as_str() == Some(new));
+
+            archived.retain(|value| value.as_str() != Some(old));
+            if !has_new {
+                archived.push(new);
+            }
+
+            renamed = true;
+        }
+    }
+
+    Ok(renamed.then(|| doc.to_string()))
+}
+
+/// Archive or unarchive a project in the text of a config file, keeping its comments and layout,
+/// and get the new text, or `None` if the project is already archived or not.
+pub fn set_archived(
+    toml: &str,
+    project: &str,
+    archived: bool,
+) -> Result<Option<String>, ConfigError> {
+    let mut doc: toml_edit::DocumentMut = toml.parse().map_err(ConfigError::EditConfig)?;
+
+    if archived {
+        let projects = doc
+            .entry("projects")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or(ConfigError::NotTable("projects"))?;
+        let list = projects
+            .entry("archived")
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .ok_or(ConfigError::NotArray("projects.archived"))?;
+
+        if list.iter().any(|value| value.as_str() == Some(project)) {
+            return Ok(None);
+        }
+
+        list.push(project);
+    } else {
+        let Some(list) = array_at(&mut doc, &["projects", "archived"]) else {
+            return Ok(None);
+        };
+
+        if !list.iter().any(|value| value.as_str() == Some(project)) {
+            return Ok(None);
+        }
+
+        list.retain(|value| value.as_str() != Some(project));
+    }
+
+    Ok(Some(doc.to_string()))
+}
+
+/// Get the table at the given path of keys in a config file, if there is one.
+fn table_at<'a>(
+    doc: &'a mut toml_edit::DocumentMut,
+    path: &[&str],
+) -> Option<&'a mut dyn toml_edit::TableLike> {
+    let (first, rest) = path.split_first()?;
+    let mut item = doc.get_mut(first)?;
+
+    for key in rest {
+        item = item.get_mut(key)?;
+    }
+
+    item.as_table_like_mut()
+}
+
+/// Get the array at the given path of keys in a config file, if there is one.
+fn array_at<'a>(
+    doc: &'a mut toml_edit::DocumentMut,
+    path: &[&str],
+) -> Option<&'a mut toml_edit::Array> {
+    let (last, parents) = path.split_last()?;
+
+    table_at(doc, parents)?.get_mut(last)?.as_array_mut()
+}
+
+/// Get the path given by the environment variable with the given name, if it's set and not empty.
+pub fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Get the login name of the current user, as given by the `USER` (or on Windows, `USERNAME`)
+/// environment variable, or `"unknown"` if neither is set.
+pub fn login_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// Settings for tracking periods.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// The number of hours after which an ongoing tracking period is assumed to have been left
+    /// running by mistake.
+    pub stale_after: f64,
+    /// The number of hours beyond which `punch doctor` and `punch report` flag a finished tracking
+    /// period as suspiciously long.
+    pub max_length: f64,
+    /// The number of hours beyond which `punch doctor` and `punch report` flag the time tracked on
+    /// a day as suspiciously long.
+    pub max_day: f64,
+    /// Whether to split an ongoing tracking period at each midnight it has run past whenever the
+    /// sheet is loaded, as `punch watch` always does.
+    pub split_at_midnight: bool,
+    /// The tag given to the tracking periods starting at a midnight where an ongoing one was
+    /// split.
+    pub midnight_tag: String,
+    /// The number of hours after which an ongoing tracking period is punched out of automatically
+    /// whenever the sheet is loaded, if any.
+    pub auto_close_after: Option<f64>,
+}
+
+impl SessionConfig {
+    /// Get the length of time after which an ongoing tracking period is assumed to have been left
+    /// running by mistake.
+    pub fn stale_after(&self) -> Duration {
+        Duration::seconds((self.stale_after * 3600.0).round() as i64)
+    }
+
+    /// Get the length after which an ongoing tracking period is punched out of automatically, if
+    /// it is.
+    pub fn auto_close_after(&self) -> Option<Duration> {
+        self.auto_close_after
+            .map(|hours| Duration::seconds((hours * 3600.0).round() as i64))
+    }
+
+    /// Get the length beyond which a finished tracking period is suspiciously long.
+    pub fn max_length(&self) -> Duration {
+        Duration::seconds((self.max_length * 3600.0).round() as i64)
+    }
+
+    /// Get the amount of time tracked on a day beyond which it's suspiciously long.
+    pub fn max_day(&self) -> Duration {
+        Duration::seconds((self.max_day * 3600.0).round() as i64)
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            stale_after: 16.0,
+            max_length: 12.0,
+            max_day: 16.0,
+            split_at_midnight: false,
+            midnight_tag: "split-at-midnight".to_owned(),
+            auto_close_after: None,
+        }
+    }
+}
+
+/// Settings for projects.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectsConfig {
+    /// The project worked on in each directory (and its subdirectories). A leading `~` stands for
+    /// the home directory.
+    pub paths: BTreeMap<PathBuf, String>,
+    /// The project worked on in git repositories, by the name of the repository's directory.
+    pub repos: BTreeMap<String, String>,
+    /// The time budgeted for each project, by name.
+    pub budgets: BTreeMap<String, Budget>,
+    /// The hours each project is estimated to take, by name.
+    pub estimates: BTreeMap<String, f64>,
+    /// The hours each task is estimated to take, by the tag its tracking periods are given.
+    pub task_estimates: BTreeMap<String, f64>,
+    /// Settings applied to the tracking periods of each project, by name.
+    pub defaults: BTreeMap<String, ProjectDefaults>,
+    /// The projects that are no longer worked on, which are left out of completions, detection
+    /// and lists of projects, but not out of reports.
+    pub archived: BTreeSet<String>,
+}
+
+impl ProjectsConfig {
+    /// Detect the project being worked on in the given directory.
+    ///
+    /// The most specific directory in [`paths`][paths] containing `dir` is used if there is one.
+    /// Otherwise, if `dir` is inside a git repository whose name appears in [`repos`][repos], the
+    /// project for that repository is used.
+    ///
+    /// Archived projects aren't detected.
+    ///
+    /// [paths]: #structfield.paths
+    /// [repos]: #structfield.repos
+    pub fn detect(&self, dir: &Path) -> Option<String> {
+        let by_path = self
+            .paths
+            .iter()
+            .map(|(path, project)| (expand_home(path), project))
+            .filter(|(path, _)| dir.starts_with(path))
+            .max_by_key(|(path, _)| path.components().count())
+            .map(|(_, project)| project.clone());
+
+        by_path
+            .or_else(|| {
+                let repo = dir.ancestors().find(|dir| dir.join(".git").exists())?;
+                let name = repo.file_name()?.to_str()?;
+
+                self.repos.get(name).cloned()
+            })
+            .filter(|project| !self.is_archived(project))
+    }
+
+    /// Whether the project with the given name is archived.
+    pub fn is_archived(&self, project: &str) -> bool {
+        self.archived.contains(project)
+    }
+
+    /// Get the defaults for the given project, if it has any.
+    pub fn defaults_for(&self, project: Option<&str>) -> Option<&ProjectDefaults> {
+        project.and_then(|project| self.defaults.get(project))
+    }
+}
+
+/// Settings applied to the tracking periods of a project, so that they don't need to be given
+/// every time.
+///
+/// ```toml
+/// [projects.defaults.acme]
+/// tags = ["client"]
+/// billable = true
+/// rate = 90.0
+/// rounding = { minutes = 15, mode = "up" }
+/// jira_prefix = "ACME"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectDefaults {
+    /// Tags given to new tracking periods on the project, along with any given to `punch in`.
+    pub tags: Vec<String>,
+    /// Whether new tracking periods on the project are billable, even without `--billable`.
+    pub billable: bool,
+    /// How time on the project is rounded, unless `rounding.projects` gives it a policy.
+    pub rounding: Option<RoundingPolicy>,
+    /// The hourly rate charged for the project, unless `rates.projects` gives it one.
+    pub rate: Option<f64>,
+    /// The prefix of the keys of the project's Jira issues, such as `ACME` for `ACME-123`, for
+    /// finding the issue worked on in the tags or note of a tracking period.
+    pub jira_prefix: Option<String>,
+}
+
+impl ProjectDefaults {
+    /// Find the key of the Jira issue worked on in the given event: the first tag, or otherwise
+    /// word of the note, made up of the [`jira_prefix`][prefix], a hyphen and a number.
+    ///
+    /// [prefix]: #structfield.jira_prefix
+    pub fn issue_key(&self, event: &Event) -> Option<String> {
+        let prefix = self.jira_prefix.as_deref()?;
+        let words = event
+            .note
+            .iter()
+            .flat_map(|note| note.split(|c: char| !c.is_alphanumeric() && c != '-'));
+
+        event
+            .tags
+            .iter()
+            .map(String::as_str)
+            .chain(words)
+            .find(|word| {
+                word.strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_prefix('-'))
+                    .is_some_and(|number| {
+                        !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+                    })
+            })
+            .map(str::to_owned)
+    }
+}
+
+/// A limit on the time to be worked on a project in each period of time, such as 40 hours a
+/// month.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    /// The number of hours budgeted.
+    pub hours: f64,
+    /// The period of time the hours are budgeted for, such as `"month"` or `"week"`.
+    #[serde(default = "Budget::default_per")]
+    pub per: Period,
+}
+
+impl Budget {
+    fn default_per() -> Period {
+        Period::Month
+    }
+
+    /// Get the amount of time budgeted.
+    pub fn limit(&self) -> Duration {
+        Duration::seconds((self.hours * 3600.0).round() as i64)
+    }
+}
+
+/// Replace a leading `~` in the given path with the path to the home directory.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), BaseDirs::new()) {
+        (Ok(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// Settings for watch mode, in which the sheet is checked periodically in the background.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// How often to check the sheet, in seconds.
+    pub interval: u64,
+    /// Whether to send a desktop notification when the time worked today reaches the amount
+    /// expected by the schedule.
+    pub notify_target: bool,
+    /// A shell command to run when the time worked today reaches the amount expected by the
+    /// schedule.
+    pub target_hook: Option<String>,
+    /// Settings for punching in and out automatically when the screen is unlocked and locked.
+    pub presence: PresenceConfig,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            interval: 60,
+            notify_target: true,
+            target_hook: None,
+            presence: PresenceConfig::default(),
+        }
+    }
+}
+
+/// Settings for punching in and out automatically when the screen is unlocked and locked.
+///
+/// This is currently only supported on Linux when built with the `dbus` feature, by listening for
+/// the `ActiveChanged` signal of the `org.freedesktop.ScreenSaver` D-Bus interface and the `Lock`
+/// and `Unlock` signals of the logind session.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    /// Whether to punch out when the screen is locked and back in when it's unlocked.
+    pub enabled: bool,
+    /// The local time of day from which locking and unlocking is acted on (e.g. `"08:00:00"`).
+    pub from: Option<NaiveTime>,
+    /// The local time of day until which locking and unlocking is acted on (e.g. `"19:00:00"`).
+    pub to: Option<NaiveTime>,
+}
+
+impl PresenceConfig {
+    /// Whether locking and unlocking the screen should be acted on at the given local time of day.
+    pub fn active_at(&self, time: NaiveTime) -> bool {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if from > to => time >= from || time < to,
+            (from, to) => from.is_none_or(|from| time >= from) && to.is_none_or(|to| time < to),
+        }
+    }
+}
+
+/// Settings for how the sheet is stored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// The format to store the sheet in (default: the format of whichever sheet file already
+    /// exists, or JSON if none does).
+    pub format: Option<SheetFormat>,
+    /// How long tracking periods are kept in the sheet, such as `"3 years"`, after which
+    /// `punch compact` moves them to the archive next to it, unless they've been submitted for
+    /// review (default: forever).
+    pub retention: Option<Retention>,
+    /// Settings for keeping the sheet on a server, with the local sheet as a copy of it.
+    pub remote: RemoteConfig,
+}
+
+impl StorageConfig {
+    /// Get the path to the file the sheet is stored in.
+    ///
+    /// This is the file given by the `PUNCH_SHEET` environment variable, if it's set. Otherwise, if
+    /// a [format][format] is configured, it's the file for that format in
+    /// [`Sheet::default_dir()`][dir], and if not, it's [`Sheet::default_loc()`][loc].
+    ///
+    /// [format]: #structfield.format
+    /// [dir]: ../sheet/struct.Sheet.html#method.default_dir
+    /// [loc]: ../sheet/struct.Sheet.html#method.default_loc
+    pub fn sheet_loc(&self) -> Result<PathBuf, SheetError> {
+        if let Some(path) = env_path("PUNCH_SHEET") {
+            return Ok(path);
+        }
+
+        match self.format {
+            Some(format) => Sheet::default_dir().map(|dir| dir.join(format.file_name())),
+            None => Sheet::default_loc(),
+        }
+    }
+}
+
+/// Settings for keeping the sheet on a WebDAV server or in S3-compatible object storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// The kind of server the sheet is kept on.
+    pub kind: RemoteKind,
+    /// The URL of the sheet file on the server, such as `https://dav.example.com/punch/sheet.json`
+    /// or `https://bucket.s3.eu-west-1.amazonaws.com/sheet.json`. The sheet is only kept locally
+    /// unless this is set.
+    pub url: Option<String>,
+    /// The user name to log in to the server with, or the access key ID for S3.
+    pub username: Option<String>,
+    /// A shell command that prints the password for the server, or the secret access key for S3,
+    /// used if `PUNCH_REMOTE_PASSWORD` isn't set.
+    pub password_command: Option<String>,
+    /// The region the bucket is in, for S3 (default: `us-east-1`).
+    pub region: Option<String>,
+}
+
+/// A kind of server the sheet can be kept on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteKind {
+    /// A WebDAV server, such as Nextcloud, logging in with HTTP basic authentication.
+    #[default]
+    Webdav,
+    /// S3-compatible object storage, signing requests with AWS Signature Version 4.
+    S3,
+}
+
+/// A length of time for which tracking periods are kept, in days, months or years.
+///
+/// Retention is written in the config file as a number and a unit, such as `"90 days"`,
+/// `"18 months"` or `"3 years"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Retention {
+    Days(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Retention {
+    /// Get the instant before which tracking periods are no longer kept, as of the instant `now`:
+    /// the local midnight starting the day that's this long before today.
+    pub fn cutoff(&self, now: DateTime<Local>) -> DateTime<Utc> {
+        let today = now.date_naive();
+        let date = match *self {
+            Retention::Days(days) => today.checked_sub_days(Days::new(days.into())),
+            Retention::Months(months) => today.checked_sub_months(Months::new(months)),
+            Retention::Years(years) => today.checked_sub_months(Months::new(years * 12)),
+        };
+
+        date.map_or(DateTime::<Utc>::MIN_UTC, |date| midnight(date).into())
+    }
+}
+
+impl FromStr for Retention {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a length of time, such as \"3 years\"", raw);
+        let (amount, unit) = raw.trim().split_once(' ').ok_or_else(invalid)?;
+        let amount = amount.parse().map_err(|_| invalid())?;
+
+        match unit.trim().trim_end_matches('s') {
+            "day" => Ok(Retention::Days(amount)),
+            "month" => Ok(Retention::Months(amount)),
+            "year" => Ok(Retention::Years(amount)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Retention {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<Retention> for String {
+    fn from(retention: Retention) -> Self {
+        retention.to_string()
+    }
+}
+
+impl Display for Retention {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Retention::Days(1) => write!(f, "1 day"),
+            Retention::Days(days) => write!(f, "{} days", days),
+            Retention::Months(1) => write!(f, "1 month"),
+            Retention::Months(months) => write!(f, "{} months", months),
+            Retention::Years(1) => write!(f, "1 year"),
+            Retention::Years(years) => write!(f, "{} years", years),
+        }
+    }
+}
+
+/// Settings for submitting tracking periods for review.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReviewConfig {
+    /// The login names of the users who can approve and reject submitted tracking periods.
+    pub reviewers: Vec<String>,
+}
+
+/// Settings for signing tracking periods.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SigningConfig {
+    /// Whether finished tracking periods are signed with the key in `signing.key` next to the
+    /// config file, for `punch verify` to check.
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushConfig {
+    /// Settings for `punch push gsheets`.
+    pub gsheets: GsheetsConfig,
+    /// Settings for `punch push kimai`.
+    pub kimai: KimaiConfig,
+    /// Settings for `punch sync caldav`.
+    pub caldav: CaldavConfig,
+}
+
+/// Settings for pushing tracking periods to Google Sheets.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GsheetsConfig {
+    /// A shell command that prints an OAuth access token for the Sheets API, such as
+    /// `gcloud auth print-access-token`, used if `PUNCH_GSHEETS_TOKEN` isn't set.
+    pub token_command: Option<String>,
+}
+
+/// Settings for pushing tracking periods to a Kimai server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KimaiConfig {
+    /// The URL of the Kimai server, such as `https://kimai.example.com`.
+    pub url: Option<String>,
+    /// A shell command that prints an API token for the server, used if `PUNCH_KIMAI_TOKEN` isn't
+    /// set.
+    pub token_command: Option<String>,
+    /// The name of the activity to record timesheets under.
+    pub activity: String,
+    /// The name of the project on the server for each project whose name differs there.
+    pub projects: BTreeMap<String, String>,
+}
+
+impl Default for KimaiConfig {
+    fn default() -> Self {
+        KimaiConfig {
+            url: None,
+            token_command: None,
+            activity: "Work".to_owned(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+/// Settings for syncing tracking periods with calendars on a CalDAV server.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaldavConfig {
+    /// The URL of the calendar to push tracking periods to, such as
+    /// `https://dav.example.com/calendars/me/work/`.
+    pub url: Option<String>,
+    /// The URL of a calendar to pull events from into the sheet, if any.
+    pub pull_url: Option<String>,
+    /// The user name to log in to the server with.
+    pub username: Option<String>,
+    /// A shell command that prints the password for the server, used if `PUNCH_CALDAV_PASSWORD`
+    /// isn't set.
+    pub password_command: Option<String>,
+}
+
+/// Settings for publishing the status to an MQTT broker, where Home Assistant discovers it as a
+/// sensor.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HomeAssistantConfig {
+    /// The host name of the MQTT broker. Nothing is published unless this is set.
+    pub host: Option<String>,
+    /// The port of the MQTT broker.
+    pub port: u16,
+    /// The user name to log in to the broker with.
+    pub username: Option<String>,
+    /// A shell command that prints the password for the broker, used if `PUNCH_MQTT_PASSWORD`
+    /// isn't set.
+    pub password_command: Option<String>,
+    /// The prefix of the topics Home Assistant looks for sensors under.
+    pub discovery_prefix: String,
+    /// The ID of the sensor's device, which its topics start with.
+    pub node_id: String,
+}
+
+impl Default for HomeAssistantConfig {
+    fn default() -> Self {
+        HomeAssistantConfig {
+            host: None,
+            port: 1883,
+            username: None,
+            password_command: None,
+            discovery_prefix: "homeassistant".to_owned(),
+            node_id: "punch_clock".to_owned(),
+        }
+    }
+}
+
+/// Settings for `punch invoice`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvoicesConfig {
+    /// The pattern invoice numbers are made from, as described for
+    /// [`Invoices::next_number()`][next_number].
+    ///
+    /// [next_number]: ../invoices/struct.Invoices.html#method.next_number
+    pub number: String,
+    /// A file containing a [MiniJinja][minijinja] template to render invoices with, in place of
+    /// the default one. A leading `~` stands for the home directory. The variables available to
+    /// it are listed in the README.
+    ///
+    /// [minijinja]: https://docs.rs/minijinja
+    pub template: Option<PathBuf>,
+}
+
+impl InvoicesConfig {
+    /// Get the path to the template file, if there is one, with a leading `~` expanded.
+    pub fn template_path(&self) -> Option<PathBuf> {
+        self.template.as_deref().map(expand_home)
+    }
+}
+
+impl Default for InvoicesConfig {
+    fn default() -> Self {
+        InvoicesConfig {
+            number: "INV-{year}-{seq:04}".to_owned(),
+            template: None,
+        }
+    }
+}
+
+/// Settings for `punch export`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// CSV layouts that can be selected with `punch export --profile`, by name.
+    pub profiles: BTreeMap<String, ExportProfile>,
+    /// The accounts used by `punch export --format beancount` and `--format ledger`.
+    pub accounts: AccountsConfig,
+}
+
+/// The accounts that earnings are booked to when exporting to a plain-text accounting format.
+///
+/// In each account name, `{client}` stands for the project the time was worked on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountsConfig {
+    /// The account the amount owed by the client is added to.
+    pub receivable: String,
+    /// The account the amount earned is taken from.
+    pub income: String,
+    /// The currency code of amounts charged at rates without a currency.
+    pub currency: String,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> Self {
+        AccountsConfig {
+            receivable: "Assets:Receivable:{client}".to_owned(),
+            income: "Income:Consulting:{client}".to_owned(),
+            currency: "USD".to_owned(),
+        }
+    }
+}
+
+impl AccountsConfig {
+    /// Get the receivable and income accounts for the given project.
+    ///
+    /// The project's name is made into a valid account name component by capitalising each word
+    /// and joining the words with hyphens, and time without a project is booked to `Unassigned`.
+    pub fn for_client(&self, project: Option<&str>) -> (String, String) {
+        let client = project
+            .unwrap_or("unassigned")
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                let first = chars.next().unwrap().to_uppercase();
+                first.chain(chars).collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+        let client = if client.is_empty() {
+            "Unassigned".to_owned()
+        } else {
+            client
+        };
+
+        (
+            self.receivable.replace("{client}", &client),
+            self.income.replace("{client}", &client),
+        )
+    }
+}
+
+/// A fixed CSV layout to export tracking periods in, such as one expected by a payroll system.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportProfile {
+    /// The columns to write, in order.
+    pub columns: Vec<ProfileColumn>,
+    /// The header of each column (default: the name of each column). If this is shorter than
+    /// [`columns`][columns], the remaining columns use their names.
+    ///
+    /// [columns]: #structfield.columns
+    pub headers: Vec<String>,
+    /// Whether to write a header row.
+    pub header: bool,
+    /// The character separating columns.
+    pub delimiter: char,
+    /// The format of dates, in the syntax of [`strftime`][strftime].
+    ///
+    /// [strftime]: https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+    pub date_format: String,
+    /// The format of times of day, in the syntax of [`strftime`][strftime].
+    ///
+    /// [strftime]: https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+    pub time_format: String,
+    /// The character separating whole hours from fractions of an hour.
+    pub decimal_separator: char,
+    /// The number of decimal places to write hours with.
+    pub hours_precision: usize,
+    /// The employee ID written for tracking periods whose user isn't in
+    /// [`employee_ids`][ids].
+    ///
+    /// [ids]: #structfield.employee_ids
+    pub employee_id: Option<String>,
+    /// The employee ID of each user, by name.
+    pub employee_ids: BTreeMap<String, String>,
+}
+
+impl Default for ExportProfile {
+    fn default() -> Self {
+        ExportProfile {
+            columns: vec![
+                ProfileColumn::EmployeeId,
+                ProfileColumn::Date,
+                ProfileColumn::Hours,
+                ProfileColumn::Project,
+            ],
+            headers: Vec::new(),
+            header: true,
+            delimiter: ',',
+            date_format: "%Y-%m-%d".to_owned(),
+            time_format: "%H:%M".to_owned(),
+            decimal_separator: '.',
+            hours_precision: 2,
+            employee_id: None,
+            employee_ids: BTreeMap::new(),
+        }
+    }
+}
+
+impl ExportProfile {
+    /// Get the employee ID of the given user.
+    pub fn employee_id(&self, user: Option<&str>) -> Option<&str> {
+        user.and_then(|user| self.employee_ids.get(user))
+            .or(self.employee_id.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// A column that can be written by an [`ExportProfile`][profile].
+///
+/// [profile]: ./struct.ExportProfile.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileColumn {
+    /// The employee ID of the user of the tracking period.
+    EmployeeId,
+    /// The user of the tracking period.
+    User,
+    /// The local date the tracking period started on.
+    Date,
+    /// The local time of day the tracking period started at.
+    Start,
+    /// The local time of day the tracking period stopped at.
+    Stop,
+    /// The length of the tracking period, in decimal hours.
+    Hours,
+    /// The project worked on.
+    Project,
+    /// The tags of the tracking period, separated by semicolons.
+    Tags,
+    /// Whether the tracking period is billable.
+    Billable,
+    /// The note of the tracking period.
+    Note,
+    /// The key of the Jira issue worked on, found by the project's `jira_prefix`.
+    Issue,
+}
+
+impl ProfileColumn {
+    /// Get the name of the column, as written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfileColumn::EmployeeId => "employee_id",
+            ProfileColumn::User => "user",
+            ProfileColumn::Date => "date",
+            ProfileColumn::Start => "start",
+            ProfileColumn::Stop => "stop",
+            ProfileColumn::Hours => "hours",
+            ProfileColumn::Project => "project",
+            ProfileColumn::Tags => "tags",
+            ProfileColumn::Billable => "billable",
+            ProfileColumn::Note => "note",
+            ProfileColumn::Issue => "issue",
+        }
+    }
+}
+
+/// Templates overriding the default output of some commands, written in the syntax of
+/// [MiniJinja][minijinja].
+///
+/// The variables available to each template are listed in the README.
+///
+/// [minijinja]: https://docs.rs/minijinja
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplatesConfig {
+    /// The output of `punch status`.
+    pub status: Option<String>,
+    /// Each line of the output of `punch report`.
+    pub report: Option<String>,
+    /// The output of `punch count`.
+    pub count: Option<String>,
+}
+
+/// Errors arising through the use of [`Config`][config].
+///
+/// [config]: ./struct.Config.html
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("unable to find config file")]
+    FindConfig,
+    #[error("unable to open config file")]
+    OpenConfig(#[source] std::io::Error),
+    #[error("unable to read config file")]
+    ReadConfig(#[source] std::io::Error),
+    #[error("unable to parse config")]
+    ParseConfig(#[source] toml::de::Error),
+    #[error("unable to parse config")]
+    EditConfig(#[source] toml_edit::TomlError),
+    #[error("{0} in config isn't a table")]
+    NotTable(&'static str),
+    #[error("{0} in config isn't an array")]
+    NotArray(&'static str),
+    #[error("unable to write config file")]
+    WriteConfig(#[source] std::io::Error),
+}
@@ -0,0 +1,126 @@
+//! Serving a D-Bus interface on the session bus, so that desktop widgets and extensions can punch
+//! in and out and show the time worked without running the command-line tool.
+//!
+//! The interface `dev.neros.PunchClock1` is served at the path `/dev/neros/PunchClock` under the
+//! well-known name `dev.neros.PunchClock` while `punch watch` is running.
+
+use std::thread;
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use punch_clock::{
+    history::Operation,
+    sheet::{SheetError, SheetStatus},
+    Config, Event, Period, Sheet,
+};
+use tracing::{debug, warn};
+use zbus::{blocking::connection, fdo, interface};
+
+use crate::{change_sheet, error::CliError, load_sheet};
+
+/// The well-known name the service is registered under.
+const NAME: &str = "dev.neros.PunchClock";
+/// The path the service's object is served at.
+const PATH: &str = "/dev/neros/PunchClock";
+
+/// Start serving the D-Bus interface in a background thread.
+pub fn spawn(config: Config) {
+    thread::spawn(move || {
+        let connection = connection::Builder::session()
+            .and_then(|builder| builder.name(NAME))
+            .and_then(|builder| builder.serve_at(PATH, PunchClock { config }))
+            .and_then(|builder| builder.build());
+
+        match connection {
+            Ok(_connection) => {
+                debug!(name = NAME, path = PATH, "serving D-Bus interface");
+
+                // Requests are handled by the connection's own thread for as long as it's kept
+                // alive.
+                loop {
+                    thread::park();
+                }
+            }
+            Err(err) => warn!("Unable to serve D-Bus interface: {}", err),
+        }
+    });
+}
+
+/// The object served over D-Bus.
+struct PunchClock {
+    config: Config,
+}
+
+impl PunchClock {
+    /// Make a change to the sheet with the given function, then write the sheet and record the
+    /// change, returning the time the function returns.
+    fn change<F>(&self, operation: Operation, change: F) -> fdo::Result<String>
+    where
+        F: FnOnce(&mut Sheet) -> Result<DateTime<Utc>, SheetError>,
+    {
+        change_sheet(&self.config, operation, change)
+            .map(|time_utc| time_utc.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .map_err(fdo::Error::Failed)
+    }
+}
+
+#[interface(name = "dev.neros.PunchClock1")]
+impl PunchClock {
+    /// Punch in now on the given project (or none, if empty), returning the time punched in at.
+    fn punch_in(&self, project: String) -> fdo::Result<String> {
+        let project = Some(project).filter(|project| !project.is_empty());
+
+        self.change(Operation::PunchIn, |sheet| {
+            let event = Event::new(Utc::now())
+                .with_user(Some(self.config.user()))
+                .with_project(project);
+
+            sheet.punch_in_with(event)
+        })
+    }
+
+    /// Punch out now, returning the time punched out at.
+    fn punch_out(&self) -> fdo::Result<String> {
+        self.change(Operation::PunchOut, |sheet| sheet.punch_out_at(Utc::now()))
+    }
+
+    /// Get whether currently punched in, the time punched in at (or if punched out, the time last
+    /// punched out at, or an empty string if there's no tracking period yet), the project being
+    /// worked on, and the number of seconds worked today and this week.
+    fn status(&self) -> fdo::Result<(bool, String, String, i64, i64)> {
+        let summary = load_sheet(&self.config)
+            .map_err(failed)?
+            .summary(Local::now());
+        let format = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let (punched_in, time) = match summary.status {
+            SheetStatus::PunchedIn(start_utc) => (true, format(start_utc)),
+            SheetStatus::PunchedOut(end_utc) => (false, format(end_utc)),
+            SheetStatus::Empty => (false, String::new()),
+        };
+
+        Ok((
+            punched_in,
+            time,
+            summary.project.unwrap_or_default(),
+            summary.today.num_seconds(),
+            summary.week.num_seconds(),
+        ))
+    }
+
+    /// Count the number of seconds worked in the given period, which accepts the same values as
+    /// `punch count`.
+    fn count(&self, period: String) -> fdo::Result<i64> {
+        let period: Period = period.parse().map_err(fdo::Error::InvalidArgs)?;
+        let (start, end) = period.range_in(Local::now(), self.config.fiscal_year_start);
+
+        Ok(load_sheet(&self.config)
+            .map_err(failed)?
+            .count_range(start, end)
+            .num_seconds())
+    }
+}
+
+/// Turn an error that stopped the sheet from being loaded or written into a D-Bus error.
+fn failed(err: CliError) -> fdo::Error {
+    fdo::Error::Failed(err.describe())
+}
@@ -0,0 +1,76 @@
+//! Time deducted automatically for breaks, such as the 30 minutes some employers take off any day
+//! worked for longer than 6 hours, whether or not a break was recorded.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Days, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{period::midnight, totals, Sheet};
+
+/// A rule deducting time for a break from each day worked for longer than a number of hours.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Deduction {
+    /// The number of hours that have to be worked in a day for time to be deducted.
+    pub after: f64,
+    /// The number of minutes deducted.
+    pub minutes: i64,
+    /// Only deduct time on days on which no break was recorded between tracking periods.
+    #[serde(default)]
+    pub unless_break: bool,
+}
+
+impl Deduction {
+    /// Get the time deducted on a day on which the given amount of time was worked, with the given
+    /// amount of time taken as breaks.
+    fn apply(&self, worked: Duration, breaks: Duration) -> Duration {
+        let after = Duration::seconds((self.after * 3600.0).round() as i64);
+
+        if worked <= after || (self.unless_break && breaks > Duration::zero()) {
+            Duration::zero()
+        } else {
+            Duration::minutes(self.minutes).min(worked)
+        }
+    }
+}
+
+/// Work out the time deducted by the given rules on each local date between the two given
+/// instants. Where more than one rule applies to a day, only the one deducting the most does.
+/// Dates on which nothing is deducted are left out.
+pub fn by_date(
+    rules: &[Deduction],
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BTreeMap<NaiveDate, Duration> {
+    if rules.is_empty() {
+        return BTreeMap::new();
+    }
+
+    totals::totals_by_date(sheet, begin, end)
+        .into_iter()
+        .filter_map(|(date, worked)| {
+            let breaks =
+                sheet.count_breaks(midnight(date).into(), midnight(date + Days::new(1)).into());
+            let deducted = rules
+                .iter()
+                .map(|rule| rule.apply(worked, breaks))
+                .max()
+                .unwrap_or_else(Duration::zero);
+
+            (deducted > Duration::zero()).then_some((date, deducted))
+        })
+        .collect()
+}
+
+/// Total the time deducted by the given rules between the two given instants.
+pub fn total(
+    rules: &[Deduction],
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Duration {
+    by_date(rules, sheet, begin, end)
+        .values()
+        .fold(Duration::zero(), |acc, next| acc + *next)
+}
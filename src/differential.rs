@@ -0,0 +1,59 @@
+//! Charging time worked at certain times of day at more than the usual rate, such as 25% more for
+//! night shifts between 22:00 and 06:00.
+//!
+//! Differentials are written in the config file as follows:
+//!
+//! ```toml
+//! [[rates.differentials]]
+//! from = "22:00"
+//! to = "06:00"
+//! percent = 25
+//! ```
+//!
+//! A tracking period running into those hours has the part within them charged at the higher rate.
+//! Differentials add to any [overtime][overtime] multiplier, so an hour of overtime at 1.5 times
+//! the rate worked at night is charged at 1.75 times the rate.
+//!
+//! [overtime]: ../overtime/index.html
+
+use chrono::{DateTime, Days, Duration, Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::period::local_instant;
+
+/// A part of each day during which time is charged at a percentage more than the usual rate.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Differential {
+    /// The local time at which the higher rate starts.
+    pub from: NaiveTime,
+    /// The local time at which the higher rate ends, which is on the next day if it's no later
+    /// than `from`.
+    pub to: NaiveTime,
+    /// How much more is charged, as a percentage of the usual rate.
+    pub percent: f64,
+}
+
+impl Differential {
+    /// Get how much of the time between the two given instants falls within the differential's
+    /// hours.
+    pub fn overlap(&self, start: DateTime<Utc>, stop: DateTime<Utc>) -> Duration {
+        let first = start.with_timezone(&Local).date_naive() - Days::new(1);
+        let last = stop.with_timezone(&Local).date_naive();
+
+        first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .map(|date| {
+                let end_date = if self.to > self.from {
+                    date
+                } else {
+                    date + Days::new(1)
+                };
+                let from: DateTime<Utc> = local_instant(date, self.from).into();
+                let to: DateTime<Utc> = local_instant(end_date, self.to).into();
+
+                (to.min(stop) - from.max(start)).max(Duration::zero())
+            })
+            .fold(Duration::zero(), |acc, next| acc + next)
+    }
+}
@@ -0,0 +1,267 @@
+//! Editing events as text in the user's editor.
+//!
+//! Each event is written on its own line, as its start and stop times in local time, followed by
+//! its project, tags and note if it has them:
+//!
+//! ```text
+//! 2024-06-03 09:00:00 -> 2024-06-03 12:30:00 | acme #bugfix $ | Fixed the login bug
+//! 2024-06-03 13:15:00 -> ...
+//! ```
+//!
+//! An unfinished event has `...` in place of its stop time, and a billable event has `$` after its
+//! project and tags. Projects and tags containing whitespace are quoted, as in `"client work"`.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::Command,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use punch_clock::{
+    config::{join_args, split_args},
+    Event,
+};
+use thiserror::Error;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const UNFINISHED: &str = "...";
+
+const HEADER: &str = "\
+# Edit the tracking periods below, one per line, then save and quit.
+#
+# Format: <start> -> <stop> | <project> <#tags> <$> | <note>
+# Use ... as the stop time of an unfinished period. The project, tags and note are optional, and
+# $ marks a billable period. Quote projects and tags containing spaces, as in \"client work\". A
+# literal \\n in a note stands for a line break. Lines starting with # are ignored.
+";
+
+/// Errors arising while editing events as text.
+#[derive(Error, Debug)]
+pub enum EditError {
+    #[error("unable to write or read temporary file")]
+    TempFile(#[source] io::Error),
+    #[error("unable to run editor {0:?}")]
+    RunEditor(String, #[source] io::Error),
+    #[error("line {0}: {1}")]
+    Parse(usize, String),
+}
+
+/// Open the given events in the user's editor, and parse the edited text back into events.
+///
+/// The editor used is `editor` if given, otherwise the value of `$VISUAL` or `$EDITOR`, falling
+/// back to `vi`. If the edited text can't be parsed, `retry` is called with the error, and the
+/// edited text is reopened if it returns true.
+pub fn edit(
+    events: &[Event],
+    editor: Option<String>,
+    mut retry: impl FnMut(&EditError) -> bool,
+) -> Result<Vec<Event>, EditError> {
+    let editor = editor
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .unwrap_or_else(|| "vi".to_owned());
+
+    let path = create_temp()
+        .and_then(|(path, mut file)| {
+            file.write_all(to_text(events).as_bytes())?;
+            Ok(path)
+        })
+        .map_err(EditError::TempFile)?;
+
+    let result = loop {
+        let mut words = editor.split_whitespace();
+        let status = Command::new(words.next().unwrap_or("vi"))
+            .args(words)
+            .arg(&path)
+            .status()
+            .map_err(|err| EditError::RunEditor(editor.clone(), err));
+
+        if let Err(err) = status {
+            break Err(err);
+        }
+
+        let parsed = fs::read_to_string(&path)
+            .map_err(EditError::TempFile)
+            .and_then(|text| parse(&text));
+
+        match parsed {
+            Err(err @ EditError::Parse(..)) if retry(&err) => continue,
+            parsed => break parsed,
+        }
+    };
+
+    fs::remove_file(&path).ok();
+
+    // Times are only written to the nearest second, so events whose lines weren't changed are
+    // replaced with the originals to avoid losing precision.
+    result.map(|edited| {
+        edited
+            .into_iter()
+            .map(|event| {
+                let line = to_line(&event);
+
+                events
+                    .iter()
+                    .find(|original| to_line(original) == line)
+                    .cloned()
+                    .unwrap_or(event)
+            })
+            .collect()
+    })
+}
+
+/// Create a temporary file to edit events in, which only its owner can read on Unix, returning its
+/// path and the file opened for writing.
+///
+/// The file has a random name, and is only created if there's no file by that name already, so
+/// that another user can't guess the name and put a file of their own there first.
+fn create_temp() -> io::Result<(PathBuf, fs::File)> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    loop {
+        let mut bytes = [0; 8];
+        getrandom::getrandom(&mut bytes)?;
+
+        let name: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = env::temp_dir().join(format!("punch-edit-{}.txt", name));
+
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Write the given events as text, one per line.
+pub fn to_text(events: &[Event]) -> String {
+    let mut text = HEADER.to_owned();
+
+    for event in events {
+        text.push('\n');
+        text.push_str(&to_line(event));
+    }
+
+    text.push('\n');
+    text
+}
+
+/// Write a single event as a line of text.
+pub fn to_line(event: &Event) -> String {
+    let mut line = format!(
+        "{} -> {}",
+        format_local(event.start),
+        event
+            .stop
+            .map_or_else(|| UNFINISHED.to_owned(), format_local)
+    );
+
+    let mut labels: Vec<String> = event.project.iter().cloned().collect();
+    labels.extend(event.tags.iter().map(|tag| format!("#{}", tag)));
+
+    if event.billable {
+        labels.push("$".to_owned());
+    }
+
+    if !labels.is_empty() || event.note.is_some() {
+        line.push_str(" | ");
+        line.push_str(&join_args(&labels));
+    }
+
+    if let Some(note) = &event.note {
+        line.push_str(" | ");
+        line.push_str(&note.replace('\\', "\\\\").replace('\n', "\\n"));
+    }
+
+    line
+}
+
+/// Parse events written as text by [`to_text()`][to_text], in the order they appear.
+///
+/// [to_text]: fn.to_text.html
+pub fn parse(text: &str) -> Result<Vec<Event>, EditError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| parse_line(line).map_err(|err| EditError::Parse(index + 1, err)))
+        .collect()
+}
+
+/// Parse a single event from a line of text.
+fn parse_line(line: &str) -> Result<Event, String> {
+    let mut fields = line.splitn(3, '|').map(str::trim);
+    let times = fields.next().unwrap_or_default();
+    let labels = split_args(fields.next().unwrap_or_default());
+    let note = fields.next().filter(|note| !note.is_empty());
+
+    let (start, stop) = times
+        .split_once("->")
+        .ok_or_else(|| "expected <start> -> <stop>".to_owned())?;
+
+    let mut event = Event::new(parse_local(start.trim())?);
+
+    for label in labels {
+        match label.strip_prefix('#') {
+            Some(tag) => event.tags.push(tag.to_owned()),
+            None if label == "$" => event.billable = true,
+            None if event.project.is_none() => event.project = Some(label),
+            None => return Err(format!("more than one project given ({:?})", label)),
+        }
+    }
+
+    event.stop = match stop.trim() {
+        UNFINISHED => None,
+        stop => Some(parse_local(stop)?),
+    };
+    event.note = note.map(unescape);
+
+    Ok(event)
+}
+
+/// Format an instant as a local time.
+fn format_local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format(TIME_FORMAT).to_string()
+}
+
+/// Parse a local time into an instant.
+fn parse_local(raw: &str) -> Result<DateTime<Utc>, String> {
+    let naive = NaiveDateTime::parse_from_str(raw, TIME_FORMAT)
+        .map_err(|_| format!("{:?} is not a time in the format YYYY-MM-DD HH:MM:SS", raw))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| format!("{} doesn't exist in the local timezone", raw))
+}
+
+/// Undo the escaping of backslashes and line breaks in a note.
+fn unescape(note: &str) -> String {
+    let mut unescaped = String::with_capacity(note.len());
+    let mut chars = note.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            (c, _) => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
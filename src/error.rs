@@ -0,0 +1,79 @@
+//! Errors that stop a command from running at all, and the exit status for each kind of failure.
+//!
+//! A command that can't do what it was asked, such as punching out when not punched in, says why
+//! and exits with [`FAILED`]. The errors here are the ones that leave it unable to get that far,
+//! such as a sheet that can't be read, and each kind exits with a status of its own so that scripts
+//! can tell them apart.
+
+use std::{error::Error, io, path::PathBuf};
+
+use punch_clock::{config::ConfigError, sheet::SheetError};
+use thiserror::Error;
+
+/// The command couldn't do what was asked.
+pub const FAILED: i32 = 1;
+/// The command line couldn't be parsed.
+pub const USAGE: i32 = 2;
+/// The config couldn't be loaded.
+pub const CONFIG: i32 = 3;
+/// The sheet couldn't be found or read.
+pub const READ_SHEET: i32 = 4;
+/// The sheet couldn't be written.
+pub const WRITE_SHEET: i32 = 5;
+
+/// Errors that stop a command from running.
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("unable to load the config from {}", .0.display())]
+    LoadConfig(PathBuf, #[source] Box<ConfigError>),
+    #[error("unable to find the config file")]
+    FindConfig,
+    #[error("unable to find the sheet file")]
+    FindSheet,
+    #[error("unable to load the sheet from {}", .0.display())]
+    LoadSheet(PathBuf, #[source] SheetError),
+    #[error("unable to create the data directory {}", .0.display())]
+    CreateDataDir(PathBuf, #[source] io::Error),
+    #[error("unable to write the sheet to {}", .0.display())]
+    WriteSheet(PathBuf, #[source] SheetError),
+}
+
+impl CliError {
+    /// Get the status the process should exit with for the error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::LoadConfig(..) | CliError::FindConfig => CONFIG,
+            CliError::FindSheet | CliError::LoadSheet(..) => READ_SHEET,
+            CliError::CreateDataDir(..) | CliError::WriteSheet(..) => WRITE_SHEET,
+        }
+    }
+
+    /// Whether the error is due to the sheet file being there, but not being a sheet that can be
+    /// parsed, in which case `punch repair` may be able to salvage it.
+    pub fn is_unparseable_sheet(&self) -> bool {
+        matches!(
+            self,
+            CliError::LoadSheet(
+                _,
+                SheetError::ParseSheet { .. }
+                    | SheetError::ParseLine(..)
+                    | SheetError::ParseToml(_)
+            )
+        )
+    }
+
+    /// Describe the error along with everything that caused it, e.g. `unable to load the sheet
+    /// from sheet.json: unable to parse sheet: expected value at line 1 column 1`.
+    pub fn describe(&self) -> String {
+        let mut description = self.to_string();
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            description.push_str(": ");
+            description.push_str(&err.to_string());
+            source = err.source();
+        }
+
+        description
+    }
+}
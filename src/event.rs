@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -8,11 +10,143 @@ pub struct Event {
     pub start: DateTime<Utc>,
     /// The end of a time-tracking period.
     pub stop: Option<DateTime<Utc>>,
+    /// The name of the project that was worked on during a time-tracking period.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Labels describing the kind of work done during a time-tracking period.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether the time spent during a time-tracking period can be billed to a client.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub billable: bool,
+    /// Free-form notes about the work done during a time-tracking period.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The name of the user who did the work during a time-tracking period, on sheets shared by
+    /// more than one user. An event without a user belongs to every user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Where a time-tracking period is in the process of submitting timesheets for review.
+    #[serde(default, skip_serializing_if = "Review::is_draft")]
+    pub review: Review,
+    /// A signature of a finished time-tracking period made with the recording user's signing key,
+    /// in hexadecimal, showing that it hasn't been changed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// The UID of the calendar event a time-tracking period was pulled from, so that it isn't
+    /// pulled again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
 }
 
 impl Event {
     /// Create a new event starting at the given time.
     pub fn new(start: DateTime<Utc>) -> Self {
-        Event { start, stop: None }
+        Event {
+            start,
+            stop: None,
+            project: None,
+            tags: Vec::new(),
+            billable: false,
+            note: None,
+            user: None,
+            review: Review::Draft,
+            signature: None,
+            uid: None,
+        }
+    }
+
+    /// Set the project that was worked on during this event.
+    pub fn with_project(mut self, project: Option<String>) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Set the tags describing the work done during this event.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Set whether the time spent during this event can be billed to a client.
+    pub fn with_billable(mut self, billable: bool) -> Self {
+        self.billable = billable;
+        self
+    }
+
+    /// Set the user who did the work during this event.
+    pub fn with_user(mut self, user: Option<String>) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Whether this event belongs to the user with the given name, either by being theirs or by
+    /// not having a user.
+    pub fn belongs_to(&self, user: &str) -> bool {
+        self.user.as_deref().is_none_or(|owner| owner == user)
+    }
+
+    /// Whether this event is frozen by having been submitted for review, so that it can't be
+    /// changed until it's unlocked.
+    pub fn is_frozen(&self) -> bool {
+        matches!(self.review, Review::Submitted | Review::Approved)
+    }
+
+    /// Add a line of text to the end of this event's note.
+    pub fn append_note(&mut self, text: &str) {
+        match &mut self.note {
+            Some(note) if !note.is_empty() => {
+                note.push('\n');
+                note.push_str(text);
+            }
+            _ => self.note = Some(text.to_owned()),
+        }
+    }
+}
+
+/// Where an event is in the process of submitting timesheets for review.
+///
+/// Events start as drafts. Submitting them freezes them until a reviewer approves or rejects them;
+/// rejected events can be changed and submitted again. Submitted and approved events can be
+/// unlocked, turning them back into drafts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Review {
+    #[default]
+    Draft,
+    Submitted,
+    Approved,
+    Rejected,
+}
+
+impl Review {
+    /// Whether this is the [`Draft`][draft] state.
+    ///
+    /// [draft]: #variant.Draft
+    pub fn is_draft(&self) -> bool {
+        *self == Review::Draft
+    }
+
+    /// Whether an event in this state can be moved into the given state.
+    pub fn allows(self, next: Review) -> bool {
+        matches!(
+            (self, next),
+            (Review::Draft | Review::Rejected, Review::Submitted)
+                | (Review::Submitted, Review::Approved | Review::Rejected)
+                | (Review::Submitted | Review::Approved, Review::Draft)
+        )
+    }
+}
+
+impl Display for Review {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Review::Draft => "draft",
+            Review::Submitted => "submitted",
+            Review::Approved => "approved",
+            Review::Rejected => "rejected",
+        };
+
+        write!(f, "{}", name)
     }
 }
@@ -0,0 +1,609 @@
+//! Exporting events to other formats.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, SecondsFormat, Utc};
+use punch_clock::{
+    config::{ExportProfile, ProfileColumn, ProjectsConfig},
+    totals::split_days,
+    Config, Event, Sheet, TeamTotals,
+};
+use thiserror::Error;
+
+/// A format that events can be exported in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One row per event, with a header row.
+    Csv,
+    /// A JSON sheet containing the events, in the same format as the sheet file.
+    Json,
+    /// Beancount transactions booking the amount earned on each project on each day as income
+    /// owed by the client.
+    Beancount,
+    /// The same transactions as [`Beancount`][beancount], in Ledger's syntax.
+    ///
+    /// [beancount]: #variant.Beancount
+    Ledger,
+    /// An Excel workbook with a worksheet of events and a worksheet of the time worked on each
+    /// project on each day, with totals.
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "beancount" => Ok(Format::Beancount),
+            "ledger" => Ok(Format::Ledger),
+            #[cfg(feature = "xlsx")]
+            "xlsx" => Ok(Format::Xlsx),
+            _ => Err("Export format not recognised.".into()),
+        }
+    }
+}
+
+/// A period of time that the time worked can be totalled over in an export, instead of exporting
+/// each event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    /// Each local date, labelled like `2024-06-03`.
+    Day,
+    /// Each ISO 8601 week, labelled like `2024-W23`.
+    Week,
+    /// Each calendar month, labelled like `2024-06`.
+    Month,
+}
+
+impl Aggregate {
+    /// Get the first date of the period containing the given date.
+    fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Aggregate::Day => date,
+            Aggregate::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Aggregate::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// Get the label of the period starting on the given date.
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            Aggregate::Day => start.format("%Y-%m-%d").to_string(),
+            Aggregate::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Aggregate::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// Get the name of the column of labels in CSV exports.
+    fn column(self) -> &'static str {
+        match self {
+            Aggregate::Day => "date",
+            Aggregate::Week => "week",
+            Aggregate::Month => "month",
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "day" => Ok(Aggregate::Day),
+            "week" => Ok(Aggregate::Week),
+            "month" => Ok(Aggregate::Month),
+            _ => Err("Aggregate not recognised: use day, week or month.".into()),
+        }
+    }
+}
+
+/// Errors arising while exporting events.
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("unable to write CSV")]
+    WriteCsv(#[from] csv::Error),
+    #[error("unable to write JSON")]
+    WriteJson(#[from] serde_json::Error),
+    #[cfg(feature = "xlsx")]
+    #[error("unable to write workbook")]
+    WriteXlsx(#[from] rust_xlsxwriter::XlsxError),
+    #[error("the delimiter {0:?} is not an ASCII character")]
+    Delimiter(char),
+    #[error("unable to write export")]
+    Write(#[from] io::Error),
+}
+
+/// Write the given events to `out` in the given format.
+pub fn export(
+    events: &[Event],
+    format: Format,
+    config: &Config,
+    out: impl Write,
+) -> Result<(), ExportError> {
+    match format {
+        Format::Csv => export_csv(events, out),
+        Format::Json => export_json(events, out),
+        Format::Beancount | Format::Ledger => export_accounting(events, format, config, out),
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => export_xlsx(events, out),
+    }
+}
+
+fn export_csv(events: &[Event], out: impl Write) -> Result<(), ExportError> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    writer.write_record([
+        "start", "stop", "hours", "project", "tags", "billable", "note",
+    ])?;
+
+    for event in events {
+        let stop = event.stop.unwrap_or_else(Utc::now);
+
+        writer.write_record([
+            format_local(event.start),
+            event.stop.map(format_local).unwrap_or_default(),
+            hours(stop - event.start),
+            event.project.clone().unwrap_or_default(),
+            event.tags.join(";"),
+            event.billable.to_string(),
+            event.note.clone().unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write the time worked in each period by the given events to `out`, in CSV or JSON.
+///
+/// As CSV, there's a row for each period, in hours, with a column for the total and one for each
+/// project. As JSON, there's an object mapping each period's label to the number of seconds worked
+/// in it, or with `by_project`, to an object mapping each project to the seconds worked on it.
+pub fn export_aggregate(
+    events: &[Event],
+    format: Format,
+    aggregate: Aggregate,
+    by_project: bool,
+    out: impl Write,
+) -> Result<(), ExportError> {
+    match format {
+        Format::Json => export_aggregate_json(events, aggregate, by_project, out),
+        _ => export_aggregate_csv(events, aggregate, out),
+    }
+}
+
+fn export_aggregate_csv(
+    events: &[Event],
+    aggregate: Aggregate,
+    out: impl Write,
+) -> Result<(), ExportError> {
+    let mut writer = csv::Writer::from_writer(out);
+    let (by_period, projects) = by_period_and_project(events, aggregate);
+
+    let mut header = vec![aggregate.column().to_owned(), "total".to_owned()];
+    header.extend(
+        projects
+            .iter()
+            .map(|project| project.as_deref().unwrap_or("(no project)").to_owned()),
+    );
+    writer.write_record(&header)?;
+
+    for (start, times) in &by_period {
+        let total = times
+            .values()
+            .fold(Duration::zero(), |sum, time| sum + *time);
+
+        let mut row = vec![aggregate.label(*start), hours(total)];
+        row.extend(
+            projects
+                .iter()
+                .map(|project| hours(times.get(project).copied().unwrap_or_else(Duration::zero))),
+        );
+        writer.write_record(&row)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn export_aggregate_json(
+    events: &[Event],
+    aggregate: Aggregate,
+    by_project: bool,
+    mut out: impl Write,
+) -> Result<(), ExportError> {
+    let (by_period, _) = by_period_and_project(events, aggregate);
+
+    let periods: serde_json::Map<String, serde_json::Value> = by_period
+        .into_iter()
+        .map(|(start, times)| {
+            let value = if by_project {
+                times
+                    .into_iter()
+                    .map(|(project, time)| {
+                        let name = project.unwrap_or_else(|| "(no project)".to_owned());
+                        (name, time.num_seconds().into())
+                    })
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            } else {
+                times
+                    .values()
+                    .fold(Duration::zero(), |sum, time| sum + *time)
+                    .num_seconds()
+                    .into()
+            };
+
+            (aggregate.label(start), value)
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut out, &periods)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// The time worked on each project, with time without a project under `None`.
+type ProjectTimes = BTreeMap<Option<String>, Duration>;
+
+/// Total the time worked on each project in each period by the given events, returning the totals
+/// by the first date of each period and the projects worked on. Time without a project sorts first
+/// among the totals, but comes last among the projects, to be shown last.
+fn by_period_and_project(
+    events: &[Event],
+    aggregate: Aggregate,
+) -> (BTreeMap<NaiveDate, ProjectTimes>, Vec<Option<String>>) {
+    let mut by_period: BTreeMap<_, ProjectTimes> = BTreeMap::new();
+    let mut projects = BTreeSet::new();
+
+    for event in events {
+        for (day, time) in split_days(event.start, event.stop.unwrap_or_else(Utc::now)) {
+            *by_period
+                .entry(aggregate.start_of(day))
+                .or_default()
+                .entry(event.project.clone())
+                .or_insert_with(Duration::zero) += time;
+        }
+
+        projects.insert(event.project.clone());
+    }
+
+    let mut projects: Vec<Option<String>> = projects.into_iter().collect();
+    if projects.first() == Some(&None) {
+        projects.rotate_left(1);
+    }
+
+    (by_period, projects)
+}
+
+/// Write the finished events to `out` in the CSV layout given by an export profile, finding issue
+/// keys by the given projects' defaults.
+pub fn export_profile(
+    events: &[Event],
+    profile: &ExportProfile,
+    projects: &ProjectsConfig,
+    out: impl Write,
+) -> Result<(), ExportError> {
+    if !profile.delimiter.is_ascii() {
+        return Err(ExportError::Delimiter(profile.delimiter));
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(profile.delimiter as u8)
+        .from_writer(out);
+
+    if profile.header {
+        let headers = profile.columns.iter().enumerate().map(|(i, column)| {
+            profile
+                .headers
+                .get(i)
+                .map(String::as_str)
+                .unwrap_or(column.name())
+        });
+
+        writer.write_record(headers)?;
+    }
+
+    for event in events {
+        let Some(stop) = event.stop else {
+            continue;
+        };
+
+        let start = event.start.with_timezone(&Local);
+        let stop = stop.with_timezone(&Local);
+
+        let row = profile.columns.iter().map(|column| match column {
+            ProfileColumn::EmployeeId => profile
+                .employee_id(event.user.as_deref())
+                .unwrap_or_default()
+                .to_owned(),
+            ProfileColumn::User => event.user.clone().unwrap_or_default(),
+            ProfileColumn::Date => start.format(&profile.date_format).to_string(),
+            ProfileColumn::Start => start.format(&profile.time_format).to_string(),
+            ProfileColumn::Stop => stop.format(&profile.time_format).to_string(),
+            ProfileColumn::Hours => {
+                let hours = (stop - start).num_seconds() as f64 / 3600.0;
+                format!("{:.*}", profile.hours_precision, hours)
+                    .replace('.', &profile.decimal_separator.to_string())
+            }
+            ProfileColumn::Project => event.project.clone().unwrap_or_default(),
+            ProfileColumn::Tags => event.tags.join(";"),
+            ProfileColumn::Billable => event.billable.to_string(),
+            ProfileColumn::Note => event.note.clone().unwrap_or_default(),
+            ProfileColumn::Issue => projects
+                .defaults_for(event.project.as_deref())
+                .and_then(|defaults| defaults.issue_key(event))
+                .unwrap_or_default(),
+        });
+
+        writer.write_record(row.collect::<Vec<_>>())?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write the time worked by each user on each project to `out` as CSV, in hours, with a row and a
+/// column of totals.
+pub fn export_team(totals: &TeamTotals, out: impl Write) -> Result<(), ExportError> {
+    let mut writer = csv::Writer::from_writer(out);
+    let projects = totals.projects();
+
+    let mut header = vec!["user".to_owned()];
+    header.extend(
+        projects
+            .iter()
+            .map(|project| project.clone().unwrap_or_default()),
+    );
+    header.push("total".to_owned());
+    writer.write_record(&header)?;
+
+    for user in totals.users.keys() {
+        let mut row = vec![user.clone()];
+        row.extend(
+            projects
+                .iter()
+                .map(|project| hours(totals.get(user, project))),
+        );
+        row.push(hours(totals.user_total(user)));
+        writer.write_record(&row)?;
+    }
+
+    let mut row = vec!["total".to_owned()];
+    row.extend(
+        projects
+            .iter()
+            .map(|project| hours(totals.project_total(project))),
+    );
+    row.push(hours(totals.total()));
+    writer.write_record(&row)?;
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write a transaction to `out` for the amount earned on each project on each local date by the
+/// finished events, in Beancount's or Ledger's syntax. Each one adds the amount to the client's
+/// receivable account and takes it from their income account; time without a rate is left out.
+fn export_accounting(
+    events: &[Event],
+    format: Format,
+    config: &Config,
+    mut out: impl Write,
+) -> Result<(), ExportError> {
+    // The time worked on each project on each local date. The rate can only change between
+    // dates, so it is the same for all of it.
+    let mut by_day: BTreeMap<_, Duration> = BTreeMap::new();
+
+    for event in events {
+        let Some(stop) = event.stop else {
+            continue;
+        };
+
+        for (day, time) in split_days(event.start, stop) {
+            *by_day
+                .entry((day, event.project.as_deref()))
+                .or_insert_with(Duration::zero) += time;
+        }
+    }
+
+    let accounts = &config.export.accounts;
+
+    for ((day, project), time) in by_day {
+        let Some((hourly, currency)) = config.rates.hourly_on(project, day) else {
+            continue;
+        };
+
+        let currency = currency.unwrap_or(&accounts.currency);
+        let hours = time.num_seconds() as f64 / 3600.0;
+        let amount = format!("{:.2} {}", hourly * hours, currency);
+        let payee = project.unwrap_or("(no project)");
+        let narration = format!("{:.2} hours at {:.2} {}", hours, hourly, currency);
+        let (receivable, income) = accounts.for_client(project);
+
+        if format == Format::Beancount {
+            writeln!(
+                out,
+                "{} * \"{}\" \"{}\"",
+                day.format("%Y-%m-%d"),
+                quote(payee),
+                narration
+            )?;
+            writeln!(out, "  {}  {}", receivable, amount)?;
+            writeln!(out, "  {}", income)?;
+        } else {
+            writeln!(out, "{} {}", day.format("%Y/%m/%d"), payee)?;
+            writeln!(out, "    ; {}", narration)?;
+            writeln!(out, "    {}  {}", receivable, amount)?;
+            writeln!(out, "    {}", income)?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Escape a string for use inside double quotes in a Beancount file.
+fn quote(raw: &str) -> String {
+    raw.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn export_json(events: &[Event], mut out: impl Write) -> Result<(), ExportError> {
+    let sheet = Sheet::from_events(events.to_vec());
+
+    serde_json::to_writer_pretty(&mut out, &sheet)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "xlsx")]
+fn export_xlsx(events: &[Event], mut out: impl Write) -> Result<(), ExportError> {
+    use rust_xlsxwriter::{utility::row_col_to_cell, Format as CellFormat, Formula, Workbook};
+
+    // Durations are written as fractions of a day, which is how spreadsheets store them.
+    fn days(time: Duration) -> f64 {
+        time.num_seconds() as f64 / 86_400.0
+    }
+
+    let bold = CellFormat::new().set_bold();
+    let datetime = CellFormat::new().set_num_format("yyyy-mm-dd hh:mm");
+    let date = CellFormat::new().set_num_format("yyyy-mm-dd");
+    let duration = CellFormat::new().set_num_format("[h]:mm");
+    let decimal = CellFormat::new().set_num_format("0.00");
+
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet().set_name("Events")?;
+    let header = [
+        "Start", "Stop", "Duration", "Hours", "Project", "Tags", "Billable", "Note",
+    ];
+
+    for (col, title) in (0..).zip(header) {
+        sheet.write_string_with_format(0, col, title, &bold)?;
+    }
+
+    let mut row = 1;
+
+    for event in events {
+        let stop = event.stop.unwrap_or_else(Utc::now);
+
+        sheet.write_datetime_with_format(
+            row,
+            0,
+            event.start.with_timezone(&Local).naive_local(),
+            &datetime,
+        )?;
+        if let Some(stop) = event.stop {
+            sheet.write_datetime_with_format(
+                row,
+                1,
+                stop.with_timezone(&Local).naive_local(),
+                &datetime,
+            )?;
+        }
+        sheet.write_number_with_format(row, 2, days(stop - event.start), &duration)?;
+        sheet.write_formula_with_format(
+            row,
+            3,
+            Formula::new(format!("={}*24", row_col_to_cell(row, 2))),
+            &decimal,
+        )?;
+        sheet.write_string(row, 4, event.project.clone().unwrap_or_default())?;
+        sheet.write_string(row, 5, event.tags.join(";"))?;
+        sheet.write_boolean(row, 6, event.billable)?;
+        sheet.write_string(row, 7, event.note.clone().unwrap_or_default())?;
+
+        row += 1;
+    }
+
+    sheet.write_string_with_format(row, 0, "Total", &bold)?;
+    for (col, format) in [(2, &duration), (3, &decimal)] {
+        let sum = format!(
+            "=SUM({}:{})",
+            row_col_to_cell(1, col),
+            row_col_to_cell(row - 1, col)
+        );
+        sheet.write_formula_with_format(row, col, Formula::new(sum), format)?;
+    }
+
+    sheet.set_column_width(0, 18)?;
+    sheet.set_column_width(1, 18)?;
+    sheet.set_freeze_panes(1, 0)?;
+
+    // The time worked on each project on each local date.
+    let (by_day, projects) = by_period_and_project(events, Aggregate::Day);
+    let total_col = projects.len() as u16 + 1;
+
+    let sheet = workbook.add_worksheet().set_name("Summary")?;
+    sheet.write_string_with_format(0, 0, "Date", &bold)?;
+    for (col, project) in (1..).zip(&projects) {
+        let title = project.as_deref().unwrap_or("(no project)");
+        sheet.write_string_with_format(0, col, title, &bold)?;
+    }
+    sheet.write_string_with_format(0, total_col, "Total", &bold)?;
+
+    let mut row = 1;
+
+    for (day, times) in &by_day {
+        sheet.write_datetime_with_format(row, 0, day, &date)?;
+
+        for (col, project) in (1..).zip(&projects) {
+            let time = times.get(project).copied().unwrap_or_else(Duration::zero);
+            sheet.write_number_with_format(row, col, days(time), &duration)?;
+        }
+
+        let sum = format!(
+            "=SUM({}:{})",
+            row_col_to_cell(row, 1),
+            row_col_to_cell(row, total_col - 1)
+        );
+        sheet.write_formula_with_format(row, total_col, Formula::new(sum), &duration)?;
+
+        row += 1;
+    }
+
+    sheet.write_string_with_format(row, 0, "Total", &bold)?;
+    for col in 1..=total_col {
+        let sum = format!(
+            "=SUM({}:{})",
+            row_col_to_cell(1, col),
+            row_col_to_cell(row - 1, col)
+        );
+        sheet.write_formula_with_format(row, col, Formula::new(sum), &duration)?;
+    }
+
+    sheet.set_column_width(0, 12)?;
+    sheet.set_freeze_panes(1, 1)?;
+
+    out.write_all(&workbook.save_to_buffer()?)?;
+
+    Ok(())
+}
+
+/// Format a duration as a number of hours.
+pub fn hours(time: Duration) -> String {
+    format!("{:.2}", time.num_seconds() as f64 / 3600.0)
+}
+
+/// Format an instant as an RFC 3339 timestamp in local time.
+pub fn format_local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
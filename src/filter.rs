@@ -0,0 +1,77 @@
+use chrono::{DateTime, Days, NaiveDate, Utc};
+
+use crate::{period::midnight, Event};
+
+/// Criteria for selecting a subset of events, such as those belonging to a particular project.
+///
+/// Every criterion is optional, and the default filter selects every event.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Only select the parts of events that occurred at or after this instant.
+    pub from: Option<DateTime<Utc>>,
+    /// Only select the parts of events that occurred before this instant.
+    pub to: Option<DateTime<Utc>>,
+    /// Only select events for this project.
+    pub project: Option<String>,
+    /// Only select events with this tag.
+    pub tag: Option<String>,
+    /// Only select billable events.
+    pub billable_only: bool,
+}
+
+impl Filter {
+    /// Only select the parts of events that occurred on or after the local date `from` and on or
+    /// before the local date `to`, for whichever of them is given.
+    pub fn with_dates(mut self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        self.from = from.map(|from| midnight(from).into()).or(self.from);
+        self.to = to.map(|to| midnight(to + Days::new(1)).into()).or(self.to);
+        self
+    }
+
+    /// Whether the given event (or part of it) is selected by this filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        let after_from = self
+            .from
+            .is_none_or(|from| event.stop.is_none_or(|stop| stop > from));
+        let before_to = self.to.is_none_or(|to| event.start < to);
+        let project = self
+            .project
+            .as_ref()
+            .is_none_or(|project| event.project.as_ref() == Some(project));
+        let tag = self.tag.as_ref().is_none_or(|tag| event.tags.contains(tag));
+        let billable = !self.billable_only || event.billable;
+
+        after_from && before_to && project && tag && billable
+    }
+
+    /// Get copies of the events selected by this filter, cut short so that they don't extend
+    /// outside of the time between [`from`][from] and [`to`][to].
+    ///
+    /// An ongoing event is given a stop time if `to` is in the past.
+    ///
+    /// [from]: #structfield.from
+    /// [to]: #structfield.to
+    pub fn apply<'a>(&self, events: impl IntoIterator<Item = &'a Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .filter(|event| self.matches(event))
+            .map(|event| {
+                let mut event = event.clone();
+
+                if let Some(from) = self.from {
+                    event.start = std::cmp::max(event.start, from);
+                }
+
+                if let Some(to) = self.to {
+                    event.stop = match event.stop {
+                        Some(stop) => Some(std::cmp::min(stop, to)),
+                        None if to < Utc::now() => Some(to),
+                        None => None,
+                    };
+                }
+
+                event
+            })
+            .collect()
+    }
+}
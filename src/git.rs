@@ -0,0 +1,109 @@
+//! Integration with git repositories.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use thiserror::Error;
+
+/// The contents of the post-commit hook installed by [`install_hook()`][install].
+///
+/// [install]: fn.install_hook.html
+const POST_COMMIT_HOOK: &str = "#!/bin/sh
+# Installed by punch-clock: adds the subject of each commit to the note of the ongoing tracking
+# period, if there is one.
+punch note \"$(git log -1 --format=%s)\" >/dev/null 2>&1 || true
+";
+
+/// Errors arising while working with git repositories.
+#[derive(Error, Debug)]
+pub enum GitError {
+    #[error("unable to run git")]
+    RunGit(#[source] io::Error),
+    #[error("{0} is not a git repository")]
+    NotRepository(PathBuf),
+    #[error("a post-commit hook already exists at {0}")]
+    HookExists(PathBuf),
+    #[error("unable to write post-commit hook")]
+    WriteHook(#[source] io::Error),
+}
+
+/// Install a post-commit hook in the git repository at `repo`, returning the path it was written
+/// to.
+///
+/// An existing hook is only replaced if `force` is true.
+pub fn install_hook(repo: &Path, force: bool) -> Result<PathBuf, GitError> {
+    let hooks_dir = git(repo, &["rev-parse", "--git-path", "hooks"])?;
+    let hooks_dir = repo.join(hooks_dir.trim());
+    let hook = hooks_dir.join("post-commit");
+
+    if hook.exists() && !force {
+        return Err(GitError::HookExists(hook));
+    }
+
+    fs::create_dir_all(&hooks_dir).map_err(GitError::WriteHook)?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o755);
+    }
+
+    options
+        .open(&hook)
+        .and_then(|mut file| file.write_all(POST_COMMIT_HOOK.as_bytes()))
+        .map_err(GitError::WriteHook)?;
+
+    Ok(hook)
+}
+
+/// Get the abbreviated hash and subject of every commit in the repository at `repo` that was
+/// committed between the two given instants, oldest first.
+pub fn commits_between(
+    repo: &Path,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<String>, GitError> {
+    let since = format!(
+        "--since={}",
+        begin.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    let until = format!("--until={}", end.to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    let log = git(
+        repo,
+        &[
+            "log",
+            "--all",
+            "--reverse",
+            "--format=%h %s",
+            &since,
+            &until,
+        ],
+    )?;
+
+    Ok(log.lines().map(str::to_owned).collect())
+}
+
+/// Run git in the repository at `repo` with the given arguments, returning its standard output.
+fn git(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(GitError::RunGit)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(GitError::NotRepository(repo.to_owned()))
+    }
+}
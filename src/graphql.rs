@@ -0,0 +1,306 @@
+//! Serving a GraphQL API, so that dashboards can fetch exactly the tracking periods and totals they
+//! need in one request, and punch in and out or change a tracking period.
+//!
+//! Queries are answered with `POST` requests to `/graphql` while `punch serve --graphql` is
+//! running, and a `GET` request for it returns the schema. The sheet is read again for each query,
+//! and changes are written and recorded in the history as soon as they're made.
+
+use async_graphql::{
+    EmptySubscription, Error, MaybeUndefined, Object, Result, Schema, SimpleObject,
+};
+use chrono::{DateTime, Local, Utc};
+use futures_lite::future;
+use punch_clock::{
+    history::Operation,
+    sheet::{SheetError, SheetStatus},
+    Config, Event, Filter, Period, Review, Sheet,
+};
+
+use crate::{change_sheet, load_sheet};
+
+/// The schema of the API.
+pub type ApiSchema = Schema<Query, Mutation, EmptySubscription>;
+
+/// Build the schema of the API, with queries and mutations acting on the sheet given by the
+/// config.
+pub fn schema(config: &Config) -> ApiSchema {
+    Schema::build(
+        Query {
+            config: config.clone(),
+        },
+        Mutation {
+            config: config.clone(),
+        },
+        EmptySubscription,
+    )
+    .finish()
+}
+
+/// Run the GraphQL request in the given JSON body, returning the response as JSON, or an error if
+/// the body isn't a GraphQL request.
+pub fn execute(schema: &ApiSchema, body: &str) -> serde_json::Result<String> {
+    let request: async_graphql::Request = serde_json::from_str(body)?;
+    let response = future::block_on(schema.execute(request));
+
+    serde_json::to_string(&response)
+}
+
+/// The queries the API answers.
+pub struct Query {
+    config: Config,
+}
+
+#[Object]
+impl Query {
+    /// The tracking periods in a period of time, which accepts the same values as `punch count`,
+    /// optionally only those for a project or with a tag.
+    async fn events(
+        &self,
+        #[graphql(default = "today")] period: String,
+        project: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Vec<TrackingPeriod>> {
+        let filter = self.filter(&period, project, tag)?;
+
+        Ok(self
+            .sheet()?
+            .events()
+            .iter()
+            .filter(|event| filter.matches(event))
+            .map(TrackingPeriod::from)
+            .collect())
+    }
+
+    /// Whether time is being tracked, and the time worked today and this week.
+    async fn status(&self) -> Result<Status> {
+        let summary = self.sheet()?.summary(Local::now());
+
+        let (punched_in, since) = match summary.status {
+            SheetStatus::PunchedIn(start_utc) => (true, Some(start_utc)),
+            SheetStatus::PunchedOut(end_utc) => (false, Some(end_utc)),
+            SheetStatus::Empty => (false, None),
+        };
+
+        Ok(Status {
+            punched_in,
+            since,
+            project: summary.project,
+            session: summary.session.map(|session| session.num_seconds()),
+            today: summary.today.num_seconds(),
+            week: summary.week.num_seconds(),
+        })
+    }
+
+    /// The number of seconds worked in a period of time, which accepts the same values as `punch
+    /// count`, optionally only on a project or with a tag.
+    async fn count(
+        &self,
+        #[graphql(default = "today")] period: String,
+        project: Option<String>,
+        tag: Option<String>,
+    ) -> Result<i64> {
+        let filter = self.filter(&period, project, tag)?;
+        let (start, end) = (filter.from.unwrap(), filter.to.unwrap());
+
+        Ok(self
+            .sheet()?
+            .count_range_where(start, end, |event| filter.matches(event))
+            .num_seconds())
+    }
+
+    /// The number of seconds worked on each project in a period of time, which accepts the same
+    /// values as `punch count`.
+    async fn projects(
+        &self,
+        #[graphql(default = "today")] period: String,
+    ) -> Result<Vec<ProjectTotal>> {
+        let filter = self.filter(&period, None, None)?;
+        let (start, end) = (filter.from.unwrap(), filter.to.unwrap());
+        let sheet = self.sheet()?;
+
+        let mut projects: Vec<Option<String>> = sheet
+            .events_in_range(start, end)
+            .map(|event| event.project.clone())
+            .collect();
+        projects.sort();
+        projects.dedup();
+
+        Ok(projects
+            .into_iter()
+            .map(|project| {
+                let seconds = sheet
+                    .count_range_where(start, end, |event| event.project == project)
+                    .num_seconds();
+
+                ProjectTotal { project, seconds }
+            })
+            .filter(|total| total.seconds > 0)
+            .collect())
+    }
+}
+
+impl Query {
+    /// Load the configured user's view of the sheet.
+    fn sheet(&self) -> Result<Sheet> {
+        load_sheet(&self.config).map_err(|err| Error::new(err.describe()))
+    }
+
+    /// Get a filter selecting the events in the given period of time, and on the given project and
+    /// with the given tag, if any.
+    fn filter(&self, period: &str, project: Option<String>, tag: Option<String>) -> Result<Filter> {
+        let period: Period = period.parse().map_err(Error::new)?;
+        let (start, end) = period.range_in(Local::now(), self.config.fiscal_year_start);
+
+        Ok(Filter {
+            from: Some(start),
+            to: Some(end),
+            project,
+            tag,
+            ..Filter::default()
+        })
+    }
+}
+
+/// The changes the API can make to the sheet.
+pub struct Mutation {
+    config: Config,
+}
+
+#[Object]
+impl Mutation {
+    /// Punch in now on the given project with the given tags, returning the time punched in at.
+    async fn punch_in(
+        &self,
+        project: Option<String>,
+        #[graphql(default)] tags: Vec<String>,
+    ) -> Result<DateTime<Utc>> {
+        self.change(Operation::PunchIn, |sheet| {
+            let event = Event::new(Utc::now())
+                .with_user(Some(self.config.user()))
+                .with_project(project)
+                .with_tags(tags);
+
+            sheet.punch_in_with(event)
+        })
+    }
+
+    /// Punch out now, returning the time punched out at.
+    async fn punch_out(&self) -> Result<DateTime<Utc>> {
+        self.change(Operation::PunchOut, |sheet| sheet.punch_out_at(Utc::now()))
+    }
+
+    /// Change the tracking period starting at `start`, returning it as changed. Fields that aren't
+    /// given are left as they are, and a project or note given as `null` is removed.
+    #[allow(clippy::too_many_arguments)]
+    async fn edit_event(
+        &self,
+        start: DateTime<Utc>,
+        new_start: Option<DateTime<Utc>>,
+        stop: Option<DateTime<Utc>>,
+        project: MaybeUndefined<String>,
+        tags: Option<Vec<String>>,
+        billable: Option<bool>,
+        note: MaybeUndefined<String>,
+    ) -> Result<TrackingPeriod> {
+        self.change(Operation::Edit, |sheet| {
+            let mut edited = sheet.clone();
+            let mut event = edited.remove_event(start)?;
+
+            event.start = new_start.unwrap_or(event.start);
+            event.stop = stop.or(event.stop);
+            project.update_to(&mut event.project);
+            event.tags = tags.unwrap_or(event.tags);
+            event.billable = billable.unwrap_or(event.billable);
+            note.update_to(&mut event.note);
+
+            // As with `punch edit`, a changed tracking period is recorded as the current user's
+            // unsigned draft.
+            event.user = Some(self.config.user());
+            event.review = Review::Draft;
+            event.signature = None;
+
+            edited.insert_event(event.clone())?;
+            *sheet = edited;
+
+            Ok(TrackingPeriod::from(&event))
+        })
+    }
+}
+
+impl Mutation {
+    /// Make a change to the sheet with the given function, then write the sheet and record the
+    /// change.
+    fn change<T, F>(&self, operation: Operation, change: F) -> Result<T>
+    where
+        F: FnOnce(&mut Sheet) -> Result<T, SheetError>,
+    {
+        change_sheet(&self.config, operation, change).map_err(Error::new)
+    }
+}
+
+/// A tracking period.
+#[derive(SimpleObject)]
+#[graphql(name = "Event")]
+pub struct TrackingPeriod {
+    /// When the tracking period started.
+    start: DateTime<Utc>,
+    /// When the tracking period stopped, or `null` if it's ongoing.
+    stop: Option<DateTime<Utc>>,
+    /// The project worked on.
+    project: Option<String>,
+    /// The tags describing the work done.
+    tags: Vec<String>,
+    /// Whether the time can be billed to a client.
+    billable: bool,
+    /// Notes about the work done.
+    note: Option<String>,
+    /// The user who did the work, on sheets shared by more than one user.
+    user: Option<String>,
+    /// Where the tracking period is in the review process: `draft`, `submitted`, `approved` or
+    /// `rejected`.
+    review: String,
+    /// The number of seconds the tracking period lasted, or has lasted so far if it's ongoing.
+    seconds: i64,
+}
+
+impl From<&Event> for TrackingPeriod {
+    fn from(event: &Event) -> Self {
+        TrackingPeriod {
+            start: event.start,
+            stop: event.stop,
+            project: event.project.clone(),
+            tags: event.tags.clone(),
+            billable: event.billable,
+            note: event.note.clone(),
+            user: event.user.clone(),
+            review: event.review.to_string(),
+            seconds: (event.stop.unwrap_or_else(Utc::now) - event.start).num_seconds(),
+        }
+    }
+}
+
+/// Whether time is being tracked, and the time worked today and this week.
+#[derive(SimpleObject)]
+pub struct Status {
+    /// Whether time is currently being tracked.
+    punched_in: bool,
+    /// When the ongoing tracking period started, or if punched out, when the last one stopped.
+    since: Option<DateTime<Utc>>,
+    /// The project of the ongoing tracking period.
+    project: Option<String>,
+    /// The number of seconds the ongoing tracking period has lasted so far.
+    session: Option<i64>,
+    /// The number of seconds worked today.
+    today: i64,
+    /// The number of seconds worked this week.
+    week: i64,
+}
+
+/// The time worked on a project.
+#[derive(SimpleObject)]
+pub struct ProjectTotal {
+    /// The project, or `null` for time worked on no project.
+    project: Option<String>,
+    /// The number of seconds worked on the project.
+    seconds: i64,
+}
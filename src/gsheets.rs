@@ -0,0 +1,193 @@
+//! Pushing tracking periods to a Google Sheet.
+//!
+//! Requests are made to the Sheets API with `curl`, authenticated with an OAuth access token
+//! taken from the `PUNCH_GSHEETS_TOKEN` environment variable or printed by a configured command.
+//! Each row starts with an ID, and rows whose ID is already in the first column of the sheet are
+//! skipped, so the same period can be pushed repeatedly.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, SecondsFormat, Utc};
+use punch_clock::{config::GsheetsConfig, totals::split_days, Event};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{
+    export::{format_local, hours},
+    http::{self, encode, request, HttpError},
+};
+
+const API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+/// Errors arising while pushing to a Google Sheet.
+#[derive(Error, Debug)]
+pub enum PushError {
+    #[error("unable to run token command")]
+    RunTokenCommand(#[source] io::Error),
+    #[error("no access token: set PUNCH_GSHEETS_TOKEN or push.gsheets.token_command")]
+    NoToken,
+    #[error("{0}")]
+    Http(#[from] HttpError),
+}
+
+/// What to push for each tracking period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rows {
+    /// One row per finished tracking period.
+    Events,
+    /// One row per date, with the total time worked on it.
+    DailyTotals,
+}
+
+/// Append rows for the given events to the sheet named `sheet` in the spreadsheet with the given
+/// ID, skipping rows that are already there. Returns the number of rows appended and skipped.
+///
+/// Ongoing tracking periods, and the daily total for today, aren't pushed, as they're not final
+/// and pushing them would stop them being updated later.
+pub fn push(
+    config: &GsheetsConfig,
+    events: &[Event],
+    rows: Rows,
+    spreadsheet: &str,
+    sheet: &str,
+) -> Result<(usize, usize), PushError> {
+    let rows = build_rows(events, rows);
+
+    let token = token(config)?;
+    let existing = existing_ids(&token, spreadsheet, sheet)?;
+
+    let (skipped, new): (Vec<_>, Vec<_>) = rows
+        .into_iter()
+        .partition(|row| row[0].as_str().is_some_and(|id| existing.contains(id)));
+
+    if !new.is_empty() {
+        let url = format!(
+            "{}/{}/values/{}:append?valueInputOption=USER_ENTERED&insertDataOption=INSERT_ROWS",
+            API,
+            spreadsheet,
+            encode(&format!("{}!A1", sheet))
+        );
+
+        request(&token, &url, Some(&json!({ "values": new })))?;
+    }
+
+    Ok((new.len(), skipped.len()))
+}
+
+/// Get the number of rows that would be pushed for the given events, including any that are
+/// already in the sheet.
+pub fn count(events: &[Event], rows: Rows) -> usize {
+    build_rows(events, rows).len()
+}
+
+/// Build the rows of the given kind for the given events.
+fn build_rows(events: &[Event], rows: Rows) -> Vec<Vec<Value>> {
+    match rows {
+        Rows::Events => event_rows(events),
+        Rows::DailyTotals => daily_rows(events),
+    }
+}
+
+/// Create a row for each finished event: its ID, start, stop, hours, project, tags, whether it's
+/// billable, note and user.
+fn event_rows(events: &[Event]) -> Vec<Vec<Value>> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let stop = event.stop?;
+
+            Some(vec![
+                json!(event_id(event)),
+                json!(format_local(event.start)),
+                json!(format_local(stop)),
+                json!(hours(stop - event.start)),
+                json!(event.project.clone().unwrap_or_default()),
+                json!(event.tags.join(";")),
+                json!(event.billable),
+                json!(event.note.clone().unwrap_or_default()),
+                json!(event.user.clone().unwrap_or_default()),
+            ])
+        })
+        .collect()
+}
+
+/// Create a row for each date before today with time worked on it: its ID, the date and hours.
+fn daily_rows(events: &[Event]) -> Vec<Vec<Value>> {
+    let today = Local::now().date_naive();
+    let mut days: BTreeMap<(Option<&str>, NaiveDate), Duration> = BTreeMap::new();
+
+    for event in events {
+        let Some(stop) = event.stop else {
+            continue;
+        };
+
+        for (date, time) in split_days(event.start, stop) {
+            *days
+                .entry((event.user.as_deref(), date))
+                .or_insert_with(Duration::zero) += time;
+        }
+    }
+
+    days.into_iter()
+        .filter(|((_, date), _)| *date < today)
+        .map(|((user, date), time)| {
+            let date = date.format("%Y-%m-%d").to_string();
+            let id = match user {
+                Some(user) => format!("{}/{}", user, date),
+                None => date.clone(),
+            };
+
+            vec![
+                json!(id),
+                json!(date),
+                json!(hours(time)),
+                json!(user.unwrap_or_default()),
+            ]
+        })
+        .collect()
+}
+
+/// Identify an event by its start, and its user if it has one, which no other event can share.
+fn event_id(event: &Event) -> String {
+    let start = rfc3339(event.start);
+
+    match &event.user {
+        Some(user) => format!("{}/{}", user, start),
+        None => start,
+    }
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Get an access token from the environment, or by running the configured command.
+fn token(config: &GsheetsConfig) -> Result<String, PushError> {
+    http::token("PUNCH_GSHEETS_TOKEN", config.token_command.as_deref())
+        .map_err(PushError::RunTokenCommand)?
+        .ok_or(PushError::NoToken)
+}
+
+/// Get the IDs in the first column of the given sheet.
+fn existing_ids(token: &str, spreadsheet: &str, sheet: &str) -> Result<HashSet<String>, PushError> {
+    let url = format!(
+        "{}/{}/values/{}",
+        API,
+        spreadsheet,
+        encode(&format!("{}!A:A", sheet))
+    );
+    let response = request(token, &url, None)?;
+
+    let ids = response["values"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|row| row.get(0)?.as_str())
+        .map(str::to_owned)
+        .collect();
+
+    Ok(ids)
+}
@@ -0,0 +1,384 @@
+//! An append-only log of the changes made to a sheet.
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::login_name, sheet::SheetError, Event, Sheet};
+
+/// The changes made to a sheet, oldest first.
+///
+/// The history is stored next to the sheet in a file with one change per line, and changes are
+/// only ever appended to it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    pub changes: Vec<Change>,
+}
+
+impl History {
+    /// Get the path to the history file for the sheet at the given path.
+    ///
+    /// This is the file `history.jsonl` in the same directory as the sheet.
+    pub fn loc_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        sheet_path.as_ref().with_file_name("history.jsonl")
+    }
+
+    /// Attempt to load the history from the file at the given path.
+    pub fn load<P>(path: P) -> Result<History, HistoryError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path).map_err(HistoryError::OpenHistory)?;
+        let mut changes = Vec::new();
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(HistoryError::ReadHistory)?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let change = serde_json::from_str(&line)
+                .map_err(|err| HistoryError::ParseHistory(i + 1, err))?;
+            changes.push(change);
+        }
+
+        Ok(History { changes })
+    }
+
+    /// Get the change that [`punch undo`][undo] would revert next, if there is one.
+    ///
+    /// Changes are undone from the most recent backwards, skipping over changes that were
+    /// themselves made by undoing or redoing.
+    ///
+    /// [undo]: enum.Operation.html#variant.Undo
+    pub fn next_undo(&self) -> Option<&Change> {
+        self.stacks().0.last().map(|&i| &self.changes[i])
+    }
+
+    /// Get the change that [`punch redo`][redo] would make again next, if there is one.
+    ///
+    /// Undone changes can be redone until a change is made by anything other than undoing or
+    /// redoing.
+    ///
+    /// [redo]: enum.Operation.html#variant.Redo
+    pub fn next_redo(&self) -> Option<&Change> {
+        self.stacks().1.last().map(|&i| &self.changes[i])
+    }
+
+    /// Work out what the given sheet looked like at the given time, by reverting every change
+    /// recorded after it, from the most recent backwards.
+    ///
+    /// The sheet may be shared by more than one user, so the result isn't [checked][check] for
+    /// overlapping events. If an event added by one of the changes is no longer in the sheet, such
+    /// as after editing the sheet file by hand, an error is returned.
+    ///
+    /// [check]: ../sheet/struct.Sheet.html#method.check
+    pub fn sheet_as_of(&self, sheet: &Sheet, time: DateTime<Utc>) -> Result<Sheet, HistoryError> {
+        let mut events = sheet.events().to_vec();
+
+        for change in self.changes.iter().rev().take_while(|c| c.time > time) {
+            replace(&mut events, &change.added, &change.removed)?;
+        }
+
+        Ok(Sheet::from_events(events).with_revision(sheet.revision))
+    }
+
+    /// Replay the history to find the indices of the changes that can be undone and redone, each
+    /// with the next one last.
+    fn stacks(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut done = Vec::new();
+        let mut undone = Vec::new();
+
+        for (i, change) in self.changes.iter().enumerate() {
+            match change.operation {
+                Operation::Undo => undone.extend(done.pop()),
+                Operation::Redo => done.extend(undone.pop()),
+                _ => {
+                    done.push(i);
+                    undone.clear();
+                }
+            }
+        }
+
+        (done, undone)
+    }
+
+    /// Attempt to append the given change to the history file at the given path, creating the
+    /// file if it doesn't exist.
+    pub fn append<P>(path: P, change: &Change) -> Result<(), HistoryError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut line = serde_json::to_vec(change).unwrap();
+        line.push(b'\n');
+
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(HistoryError::WriteHistory)
+    }
+}
+
+/// A single change made to a sheet: the events that were removed from it and the events that
+/// were added to it.
+///
+/// Modifying an event is recorded as removing the old version and adding the new one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    /// When the change was made.
+    pub time: DateTime<Utc>,
+    /// The name of the user who made the change.
+    pub user: String,
+    /// The operation that made the change.
+    pub operation: Operation,
+    /// The events that were removed from the sheet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<Event>,
+    /// The events that were added to the sheet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<Event>,
+}
+
+impl Change {
+    /// Work out the change made by the given operation from the sheet `before` to the sheet
+    /// `after`, returning `None` if they contain the same events.
+    ///
+    /// The change is attributed to the current user, as given by the `USER` (or on Windows,
+    /// `USERNAME`) environment variable, at the current time.
+    pub fn between(operation: Operation, before: &Sheet, after: &Sheet) -> Option<Change> {
+        let (removed, added) = diff(before.events(), after.events());
+
+        if removed.is_empty() && added.is_empty() {
+            return None;
+        }
+
+        Some(Change {
+            time: Utc::now(),
+            user: login_name(),
+            operation,
+            removed,
+            added,
+        })
+    }
+
+    /// Revert the change in the given sheet, removing the events that were added and restoring
+    /// the events that were removed.
+    ///
+    /// If the events that were added are no longer in the sheet, or the result isn't
+    /// [valid][check], the sheet is left unchanged and an error is returned.
+    ///
+    /// [check]: ../sheet/struct.Sheet.html#method.check
+    pub fn undo(&self, sheet: &mut Sheet) -> Result<(), HistoryError> {
+        swap(sheet, &self.added, &self.removed)
+    }
+
+    /// Make the change again in the given sheet, after it has been [undone][undo].
+    ///
+    /// [undo]: #method.undo
+    pub fn redo(&self, sheet: &mut Sheet) -> Result<(), HistoryError> {
+        swap(sheet, &self.removed, &self.added)
+    }
+}
+
+/// The differences between two sheets, with events that were changed between them paired up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// The events only in the second sheet.
+    pub added: Vec<Event>,
+    /// The events only in the first sheet.
+    pub removed: Vec<Event>,
+    /// The events in the first sheet that were changed, each with its version in the second.
+    pub changed: Vec<(Event, Event)>,
+}
+
+impl Diff {
+    /// Compare the sheet `before` with the sheet `after`.
+    ///
+    /// An event only in `before` is taken to have been changed into an event only in `after` if
+    /// both belong to the same user and they start at the same time or overlap.
+    pub fn between(before: &Sheet, after: &Sheet) -> Diff {
+        let (removed, mut added) = diff(before.events(), after.events());
+        let mut result = Diff::default();
+
+        for old in removed {
+            let same = |new: &Event| {
+                new.user == old.user
+                    && (new.start == old.start
+                        || (new.stop.is_none_or(|stop| old.start < stop)
+                            && old.stop.is_none_or(|stop| new.start < stop)))
+            };
+
+            match added.iter().position(same) {
+                Some(i) => result.changed.push((old, added.remove(i))),
+                None => result.removed.push(old),
+            }
+        }
+
+        result.added = added;
+        result
+    }
+
+    /// Whether the sheets contain the same events.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Get how much more time is tracked in the second sheet than in the first, counting
+    /// unfinished events up to the given time.
+    pub fn tracked(&self, now: DateTime<Utc>) -> Duration {
+        let length = |event: &Event| event.stop.unwrap_or(now).max(event.start) - event.start;
+
+        self.added.iter().map(length).sum::<Duration>()
+            - self.removed.iter().map(length).sum::<Duration>()
+            + self
+                .changed
+                .iter()
+                .map(|(old, new)| length(new) - length(old))
+                .sum::<Duration>()
+    }
+}
+
+/// Replace the events `remove` in the given sheet with the events `add`.
+fn swap(sheet: &mut Sheet, remove: &[Event], add: &[Event]) -> Result<(), HistoryError> {
+    let mut events = sheet.events().to_vec();
+    replace(&mut events, remove, add)?;
+
+    let new = Sheet::from_events(events);
+    new.check().map_err(HistoryError::Conflict)?;
+    sheet.set_events(new.into_events());
+
+    Ok(())
+}
+
+/// Replace the events `remove` in the given list of events with the events `add`, leaving them
+/// unsorted.
+fn replace(events: &mut Vec<Event>, remove: &[Event], add: &[Event]) -> Result<(), HistoryError> {
+    for event in remove {
+        match events.iter().position(|e| e == event) {
+            Some(i) => {
+                events.remove(i);
+            }
+            None => return Err(HistoryError::Diverged(event.start)),
+        }
+    }
+
+    events.extend_from_slice(add);
+
+    Ok(())
+}
+
+/// Find the events only in `before` and the events only in `after`, given that both are sorted.
+fn diff(before: &[Event], after: &[Event]) -> (Vec<Event>, Vec<Event>) {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < before.len() && j < after.len() {
+        match before[i].cmp(&after[j]) {
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+            std::cmp::Ordering::Less => {
+                removed.push(before[i].clone());
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                added.push(after[j].clone());
+                j += 1;
+            }
+        }
+    }
+
+    removed.extend_from_slice(&before[i..]);
+    added.extend_from_slice(&after[j..]);
+
+    (removed, added)
+}
+
+/// An operation that changes a sheet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    PunchIn,
+    PunchOut,
+    Note,
+    Edit,
+    Import,
+    Undo,
+    Redo,
+    Submit,
+    Approve,
+    Reject,
+    Unlock,
+    Delete,
+    Retag,
+    Shift,
+    MigrateTz,
+    RenameProject,
+    Purge,
+    RestoreSnapshot,
+    SplitAtMidnight,
+    AutoClose,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Operation::PunchIn => "punch in",
+            Operation::PunchOut => "punch out",
+            Operation::Note => "note",
+            Operation::Edit => "edit",
+            Operation::Import => "import",
+            Operation::Undo => "undo",
+            Operation::Redo => "redo",
+            Operation::Submit => "submit",
+            Operation::Approve => "approve",
+            Operation::Reject => "reject",
+            Operation::Unlock => "unlock",
+            Operation::Delete => "delete",
+            Operation::Retag => "retag",
+            Operation::Shift => "shift",
+            Operation::MigrateTz => "migrate-tz",
+            Operation::RenameProject => "rename project",
+            Operation::Purge => "purge",
+            Operation::RestoreSnapshot => "restore snapshot",
+            Operation::SplitAtMidnight => "split at midnight",
+            Operation::AutoClose => "auto close",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Errors arising through the use of [`History`][history].
+///
+/// [history]: ./struct.History.html
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("unable to open history file")]
+    OpenHistory(#[source] std::io::Error),
+    #[error("unable to read history file")]
+    ReadHistory(#[source] std::io::Error),
+    #[error("unable to parse line {0} of history")]
+    ParseHistory(usize, #[source] serde_json::Error),
+    #[error("the event starting at {0} has changed since")]
+    Diverged(DateTime<Utc>),
+    #[error("{0}")]
+    Conflict(#[source] SheetError),
+    #[error("unable to write to history file")]
+    WriteHistory(#[source] std::io::Error),
+}
@@ -0,0 +1,84 @@
+//! Showing the status in [Home Assistant](https://www.home-assistant.io) as a sensor, through MQTT
+//! discovery.
+//!
+//! The sensor's config is published under the discovery prefix, so that Home Assistant adds the
+//! sensor by itself. Its state is `working` or `off`, with the project, the length of the ongoing
+//! tracking period and the time worked today (both in seconds) as attributes. Every message is
+//! retained, so the sensor shows the last status published when Home Assistant restarts.
+
+use std::io;
+
+use punch_clock::{config::HomeAssistantConfig, sheet::StatusSummary};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    http,
+    mqtt::{Client, MqttError},
+};
+
+/// Errors arising while publishing the status.
+#[derive(Error, Debug)]
+pub enum PublishError {
+    #[error("unable to run password command")]
+    RunPasswordCommand(#[source] io::Error),
+    #[error("no password: set PUNCH_MQTT_PASSWORD or homeassistant.password_command")]
+    NoPassword,
+    #[error("{0}")]
+    Mqtt(#[from] MqttError),
+}
+
+/// Publish the sensor's config and the given status to the broker at the given host.
+pub fn publish(
+    config: &HomeAssistantConfig,
+    host: &str,
+    summary: &StatusSummary,
+) -> Result<(), PublishError> {
+    let password = match config.username {
+        Some(_) => Some(
+            http::token("PUNCH_MQTT_PASSWORD", config.password_command.as_deref())
+                .map_err(PublishError::RunPasswordCommand)?
+                .ok_or(PublishError::NoPassword)?,
+        ),
+        None => None,
+    };
+    let login = config.username.as_deref().zip(password.as_deref());
+
+    let node = &config.node_id;
+    let state_topic = format!("{}/status/state", node);
+    let attributes_topic = format!("{}/status/attributes", node);
+
+    let discovery = json!({
+        "name": "Status",
+        "unique_id": format!("{}_status", node),
+        "state_topic": state_topic,
+        "json_attributes_topic": attributes_topic,
+        "icon": "mdi:briefcase-clock",
+        "device": {
+            "identifiers": [node],
+            "name": "Punch Clock",
+            "model": "punch-clock",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    let state = match summary.session {
+        Some(_) => "working",
+        None => "off",
+    };
+    let attributes = json!({
+        "project": summary.project,
+        "session_duration": summary.session.map(|session| session.num_seconds()),
+        "today_total": summary.today.num_seconds(),
+    });
+
+    let mut client = Client::connect(&format!("{}:{}", host, config.port), node, login)?;
+    client.publish_retained(
+        &format!("{}/sensor/{}/status/config", config.discovery_prefix, node),
+        discovery.to_string().as_bytes(),
+    )?;
+    client.publish_retained(&attributes_topic, attributes.to_string().as_bytes())?;
+    client.publish_retained(&state_topic, state.as_bytes())?;
+    client.disconnect()?;
+
+    Ok(())
+}
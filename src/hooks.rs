@@ -0,0 +1,83 @@
+//! Running the user's scripts when the sheet changes.
+//!
+//! After each change to the sheet, the executable named after the operation that made it (e.g.
+//! `on-punch-in`, `on-punch-out` or `on-edit`) is run from the `hooks` directory next to the config
+//! file, if it exists. The last event the change added (or if it added none, the last it removed)
+//! is described in `PUNCH_*` environment variables, and the whole change is written to the script's
+//! standard input as JSON, in the same form as a line of the history.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use chrono::SecondsFormat;
+use punch_clock::{history::Change, Config};
+use tracing::{debug, warn};
+
+/// Get the directory hook scripts are kept in.
+pub fn dir() -> Option<PathBuf> {
+    Config::default_dir().ok().map(|dir| dir.join("hooks"))
+}
+
+/// Run the hook script for the given change, if there is one.
+pub fn run(change: &Change) {
+    let Some(dir) = dir() else {
+        return;
+    };
+
+    let name = format!("on-{}", change.operation.to_string().replace(' ', "-"));
+    let path = dir.join(&name);
+
+    if !path.is_file() {
+        return;
+    }
+
+    debug!(hook = %path.display(), "running hook");
+
+    match run_script(&path, change) {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("Hook {} failed with {}", name, status),
+        Err(err) => warn!("Unable to run hook {}: {}", name, err),
+    }
+}
+
+/// Run the script at the given path for the given change, waiting for it to finish.
+fn run_script(path: &Path, change: &Change) -> io::Result<ExitStatus> {
+    let mut env = vec![
+        ("PUNCH_OPERATION", change.operation.to_string()),
+        ("PUNCH_USER", change.user.clone()),
+        (
+            "PUNCH_TIME",
+            change.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+    ];
+
+    if let Some(event) = change.added.last().or(change.removed.last()) {
+        env.push((
+            "PUNCH_START",
+            event.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+        env.push((
+            "PUNCH_STOP",
+            event
+                .stop
+                .map(|stop| stop.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_default(),
+        ));
+        env.push(("PUNCH_PROJECT", event.project.clone().unwrap_or_default()));
+        env.push(("PUNCH_TAGS", event.tags.join(",")));
+        env.push(("PUNCH_NOTE", event.note.clone().unwrap_or_default()));
+    }
+
+    let mut child = Command::new(path).envs(env).stdin(Stdio::piped()).spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The script may not read its input, in which case the pipe is closed early.
+        let json = serde_json::to_vec(change).unwrap();
+        stdin.write_all(&json).ok();
+    }
+
+    child.wait()
+}
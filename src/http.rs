@@ -0,0 +1,259 @@
+//! Making requests to web APIs with `curl`.
+
+use std::{
+    io::{self, Write},
+    process::{Command, Output, Stdio},
+};
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors arising while making requests.
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("unable to run curl")]
+    RunCurl(#[source] io::Error),
+    #[error("request failed: {0}")]
+    Request(String),
+    #[error("unable to parse response")]
+    ParseResponse(#[source] serde_json::Error),
+}
+
+/// Get an access token from the given environment variable, or by running the given shell
+/// command, returning `None` if neither gives one.
+pub fn token(env_var: &str, command: Option<&str>) -> io::Result<Option<String>> {
+    if let Ok(token) = std::env::var(env_var) {
+        return Ok(Some(token));
+    }
+
+    let Some(command) = command else {
+        return Ok(None);
+    };
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    Ok(Some(token).filter(|token| output.status.success() && !token.is_empty()))
+}
+
+/// Make a request authenticated with the given bearer token, posting `body` as JSON if there is
+/// one, and parse the JSON response.
+///
+/// The options are passed to `curl` as a config file on standard input, so that the access token
+/// doesn't appear in the list of running processes.
+pub fn request(token: &str, url: &str, body: Option<&Value>) -> Result<Value, HttpError> {
+    let mut options = format!(
+        "url = \"{}\"\nheader = \"Authorization: Bearer {}\"\nheader = \"Accept: \
+         application/json\"\n",
+        quote(url),
+        quote(token)
+    );
+
+    if let Some(body) = body {
+        options.push_str("header = \"Content-Type: application/json\"\n");
+        options.push_str(&format!("data-binary = \"{}\"\n", quote(&body.to_string())));
+    }
+
+    let output = curl(&options, true)?;
+
+    if !output.status.success() {
+        // APIs describe errors in different places in the response, if at all.
+        let message = serde_json::from_slice::<Value>(&output.stdout)
+            .ok()
+            .and_then(|body| {
+                body["error"]["message"]
+                    .as_str()
+                    .or(body["message"].as_str())
+                    .map(str::to_owned)
+            })
+            .unwrap_or_else(|| String::from_utf8_lossy(&output.stderr).trim().to_owned());
+
+        return Err(HttpError::Request(message));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(HttpError::ParseResponse)
+}
+
+/// Make a request with the given method, authenticated with the given user name and password if
+/// there are any, sending the given headers and body, and return the body of the response.
+///
+/// As with [`request()`], the options are passed to `curl` on standard input.
+pub fn send(
+    method: &str,
+    url: &str,
+    login: Option<(&str, &str)>,
+    headers: &[&str],
+    body: Option<&str>,
+) -> Result<String, HttpError> {
+    let mut options = format!(
+        "url = \"{}\"\nrequest = \"{}\"\n",
+        quote(url),
+        quote(method)
+    );
+
+    if let Some((username, password)) = login {
+        options.push_str(&format!(
+            "user = \"{}:{}\"\n",
+            quote(username),
+            quote(password)
+        ));
+    }
+
+    for header in headers {
+        options.push_str(&format!("header = \"{}\"\n", quote(header)));
+    }
+
+    if let Some(body) = body {
+        options.push_str(&format!("data-binary = \"{}\"\n", quote(body)));
+    }
+
+    let output = curl(&options, true)?;
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(HttpError::Request(message));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The status and headers of a response to a request made with [`transfer()`].
+#[derive(Clone, Debug, Default)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The headers, with their names in lowercase.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Response {
+    /// Get the value of the header with the given name, in lowercase, if there is one.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Make a request with the given method, sending the given headers and passing the given extra
+/// lines to `curl`'s config file, such as to log in or to upload or download files, and return the
+/// status and headers of the response, whatever the status is.
+///
+/// As with [`request()`], the options are passed to `curl` on standard input.
+pub fn transfer(
+    method: &str,
+    url: &str,
+    options: &[(&str, &str)],
+    headers: &[&str],
+) -> Result<Response, HttpError> {
+    let mut config = format!(
+        "url = \"{}\"\nrequest = \"{}\"\ndump-header = \"-\"\n",
+        quote(url),
+        quote(method)
+    );
+
+    for (name, value) in options {
+        config.push_str(&format!("{} = \"{}\"\n", name, quote(value)));
+    }
+
+    for header in headers {
+        config.push_str(&format!("header = \"{}\"\n", quote(header)));
+    }
+
+    let output = curl(&config, false)?;
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(HttpError::Request(message));
+    }
+
+    // Responses such as `100 Continue` come first, each with its own headers, so only the last
+    // response counts.
+    let mut response = Response::default();
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if line.starts_with("HTTP/") {
+            response = Response {
+                status: line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|status| status.parse().ok())
+                    .unwrap_or_default(),
+                headers: Vec::new(),
+            };
+        } else if let Some((name, value)) = line.split_once(':') {
+            response
+                .headers
+                .push((name.trim().to_lowercase(), value.trim().to_owned()));
+        }
+    }
+
+    Ok(response)
+}
+
+/// How long `curl` waits to connect to a server, in seconds, so that an unreachable server doesn't
+/// hold up a command.
+const CONNECT_TIMEOUT: &str = "10";
+
+/// How long `curl` waits for a whole request, in seconds.
+const MAX_TIME: &str = "60";
+
+/// Run `curl` with the given config file on standard input, returning its output, and making it
+/// fail on error responses if `fail` is set.
+fn curl(options: &str, fail: bool) -> Result<Output, HttpError> {
+    let mut args = vec![
+        "--silent",
+        "--show-error",
+        "--connect-timeout",
+        CONNECT_TIMEOUT,
+        "--max-time",
+        MAX_TIME,
+    ];
+
+    if fail {
+        args.push("--fail-with-body");
+    }
+
+    let mut curl = Command::new("curl")
+        .args(args)
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(HttpError::RunCurl)?;
+
+    curl.stdin
+        .take()
+        .unwrap()
+        .write_all(options.as_bytes())
+        .map_err(HttpError::RunCurl)?;
+
+    curl.wait_with_output().map_err(HttpError::RunCurl)
+}
+
+/// Escape a string for use inside double quotes in a `curl` config file, which can't span lines.
+fn quote(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// Percent-encode a string for use in a URL path or query, leaving `!` and `:` as they are.
+pub fn encode(raw: &str) -> String {
+    raw.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'!' | b':' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
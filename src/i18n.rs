@@ -0,0 +1,118 @@
+//! Translating messages for the user.
+//!
+//! Messages are written in English in the code, and looked up by their English text in the
+//! catalog for the user's language, much like gettext. Messages missing from the catalog are left
+//! in English. Catalogs are TOML files in the `locales` directory mapping each English message to
+//! its translation, in which `{}` stands for the next argument and `{0}`, `{1}` and so on for the
+//! arguments in the order the English message uses them.
+
+use std::{collections::HashMap, env, fmt::Display, sync::OnceLock};
+
+use tracing::{debug, warn};
+
+/// The catalogs for each supported language, by language code.
+const CATALOGS: &[(&str, &str)] = &[("de", include_str!("../locales/de.toml"))];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Select the catalog to translate messages with.
+///
+/// The given language is used if there is one. Otherwise, the language is taken from the
+/// `LC_ALL`, `LC_MESSAGES` or `LANG` environment variable. A language with a region (e.g. `de_AT`)
+/// falls back to the catalog for the language alone (e.g. `de`).
+pub fn init(language: Option<&str>) {
+    let language = language.map(str::to_owned).or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+    });
+
+    let Some(language) = language else {
+        return;
+    };
+
+    // Strip any encoding or modifier, as in `de_DE.UTF-8@euro`.
+    let language = language
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('-', "_");
+    let primary = language.split('_').next().unwrap_or_default();
+
+    let Some((code, source)) = CATALOGS
+        .iter()
+        .find(|(code, _)| *code == language)
+        .or_else(|| CATALOGS.iter().find(|(code, _)| *code == primary))
+    else {
+        return;
+    };
+
+    match toml::from_str(source) {
+        Ok(catalog) => {
+            debug!(language = code, "loaded message catalog");
+            CATALOG.set(catalog).ok();
+        }
+        Err(err) => warn!("Unable to parse message catalog for {}: {}", code, err),
+    }
+}
+
+/// Get the translation of the given message, or the message itself if it has none.
+pub fn translate(message: &str) -> &str {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(message))
+        .map_or(message, String::as_str)
+}
+
+/// Translate the given message and fill in its arguments.
+pub fn format(message: &str, args: &[&dyn Display]) -> String {
+    let template = translate(message);
+    let mut output = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        output.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            output.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let Some(end) = rest.find('}').filter(|_| rest.starts_with('{')) else {
+            output.push_str(&rest[..1]);
+            rest = &rest[1..];
+            continue;
+        };
+
+        let index = match rest[1..end].parse() {
+            Ok(index) => index,
+            Err(_) => {
+                next += 1;
+                next - 1
+            }
+        };
+
+        if let Some(arg) = args.get(index) {
+            output.push_str(&arg.to_string());
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Translate a message, filling in its arguments like `format!()`.
+///
+/// Only `{}` placeholders are supported in the message, which must be a string literal so that it
+/// can be found in the catalogs.
+macro_rules! tr {
+    ($message:literal $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::format($message, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
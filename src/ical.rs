@@ -0,0 +1,187 @@
+//! Writing tracking periods as iCalendar events ([RFC 5545]), for calendar applications, and
+//! reading events written by them.
+//!
+//! [RFC 5545]: https://www.rfc-editor.org/rfc/rfc5545
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use punch_clock::Event;
+
+/// The format of times in iCalendar, in UTC.
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Write the given events as an iCalendar calendar, with an ongoing event shown as lasting until
+/// now.
+pub fn calendar(events: &[Event]) -> String {
+    let now = Utc::now();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//punch-clock//punch-clock//EN".to_owned(),
+        "CALSCALE:GREGORIAN".to_owned(),
+        "X-WR-CALNAME:Tracked time".to_owned(),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{}", uid(event)));
+        lines.push(format!(
+            "DTSTAMP:{}",
+            format_time(event.stop.unwrap_or(now))
+        ));
+        lines.push(format!("DTSTART:{}", format_time(event.start)));
+        lines.push(format!("DTEND:{}", format_time(event.stop.unwrap_or(now))));
+
+        let summary = event.project.as_deref().unwrap_or("(no project)");
+        lines.push(format!("SUMMARY:{}", escape(summary)));
+
+        if let Some(note) = &event.note {
+            lines.push(format!("DESCRIPTION:{}", escape(note)));
+        }
+
+        if !event.tags.is_empty() {
+            let tags: Vec<_> = event.tags.iter().map(|tag| escape(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+
+        lines.push("END:VEVENT".to_owned());
+    }
+
+    lines.push("END:VCALENDAR".to_owned());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Get a unique identifier for an event that stays the same as long as its start and user do,
+/// which no other event can share.
+pub fn uid(event: &Event) -> String {
+    match &event.user {
+        Some(user) => format!("{}-{}@punch-clock", format_time(event.start), user),
+        None => format!("{}@punch-clock", format_time(event.start)),
+    }
+}
+
+/// Format an instant as an iCalendar time in UTC.
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+/// Read the events with a start and an end from an iCalendar calendar, with their UIDs.
+///
+/// Each event's summary is taken as its project, its description as its note and its categories as
+/// its tags. Times with a time zone other than UTC are taken as local times, and all-day events
+/// are skipped, as are any repetitions of an event.
+pub fn parse(text: &str) -> Vec<(String, Event)> {
+    let unfolded = text
+        .replace("\r\n ", "")
+        .replace("\n ", "")
+        .replace("\r\n\t", "");
+    let mut events = Vec::new();
+    let mut current: Option<(Option<String>, Event, bool)> = None;
+
+    for line in unfolded.lines() {
+        let line = line.trim_end_matches('\r');
+
+        match line {
+            "BEGIN:VEVENT" => {
+                current = Some((None, Event::new(DateTime::<Utc>::MIN_UTC), false));
+                continue;
+            }
+            "END:VEVENT" => {
+                if let Some((Some(uid), event, true)) = current.take() {
+                    if event.stop.is_some() && event.start != DateTime::<Utc>::MIN_UTC {
+                        events.push((uid, event));
+                    }
+                }
+
+                continue;
+            }
+            _ => {}
+        }
+
+        let (Some((uid, event, timed)), Some((property, value))) =
+            (current.as_mut(), line.split_once(':'))
+        else {
+            continue;
+        };
+        let (name, params) = property.split_once(';').unwrap_or((property, ""));
+
+        match name {
+            "UID" => *uid = Some(value.to_owned()),
+            "DTSTART" | "DTEND" => {
+                let time = parse_time(value);
+                *timed = !params.contains("VALUE=DATE") && time.is_some();
+
+                if name == "DTSTART" {
+                    event.start = time.unwrap_or(DateTime::<Utc>::MIN_UTC);
+                } else {
+                    event.stop = time;
+                }
+            }
+            "SUMMARY" => event.project = Some(unescape(value)).filter(|s| !s.is_empty()),
+            "DESCRIPTION" => event.note = Some(unescape(value)).filter(|s| !s.is_empty()),
+            "CATEGORIES" => event.tags = value.split(',').map(unescape).collect(),
+            _ => {}
+        }
+    }
+
+    events
+}
+
+/// Parse an iCalendar time, in UTC if it ends with `Z` and otherwise as a local time.
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    match value.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|naive| Utc.from_utc_datetime(&naive)),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(Into::into),
+    }
+}
+
+/// Escape text for use as the value of an iCalendar property.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Undo the escaping of the value of an iCalendar property.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => {}
+            },
+            _ => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+/// Fold a content line longer than 75 octets onto continuation lines starting with a space,
+/// without splitting characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded
+}
@@ -0,0 +1,558 @@
+//! Importing events from CSV files.
+//!
+//! The meaning of each column in the file is guessed from its header and from a sample of its
+//! values, and can then be confirmed or corrected interactively.
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::Path,
+};
+
+use chrono::{DateTime, Days, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use punch_clock::Event;
+use thiserror::Error;
+
+use crate::{
+    edit, format_duration,
+    prompt::{ask, confirm},
+};
+
+/// The number of rows used to guess the meaning of each column, and to preview the result.
+const SAMPLE_ROWS: usize = 5;
+
+/// A special datetime format, standing for RFC 3339 (e.g. `2024-06-03T09:00:00+01:00`).
+const RFC3339: &str = "rfc3339";
+
+const DATETIME_FORMATS: &[&str] = &[
+    RFC3339,
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+];
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y", "%d.%m.%Y"];
+const TIME_FORMATS: &[&str] = &["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"];
+
+/// Errors arising while importing events.
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("unable to read CSV file")]
+    ReadCsv(#[source] csv::Error),
+}
+
+/// The meaning of a column in a CSV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The date and time at which an event started.
+    Start,
+    /// The date and time at which an event stopped.
+    Stop,
+    /// The date on which an event started.
+    StartDate,
+    /// The time of day at which an event started.
+    StartTime,
+    /// The date on which an event stopped (default: the date it started).
+    StopDate,
+    /// The time of day at which an event stopped.
+    StopTime,
+    /// The length of an event, as `H:MM`, `H:MM:SS` or a decimal number of hours.
+    Duration,
+    /// The project worked on during an event.
+    Project,
+    /// Notes about the work done during an event.
+    Note,
+    /// Tags describing an event, separated by commas or semicolons.
+    Tags,
+    /// A column that isn't imported.
+    Ignore,
+}
+
+impl Role {
+    const ALL: [Role; 11] = [
+        Role::Start,
+        Role::Stop,
+        Role::StartDate,
+        Role::StartTime,
+        Role::StopDate,
+        Role::StopTime,
+        Role::Duration,
+        Role::Project,
+        Role::Note,
+        Role::Tags,
+        Role::Ignore,
+    ];
+
+    /// Get the formats that values in a column with this role might be in.
+    fn formats(self) -> &'static [&'static str] {
+        match self {
+            Role::Start | Role::Stop => DATETIME_FORMATS,
+            Role::StartDate | Role::StopDate => DATE_FORMATS,
+            Role::StartTime | Role::StopTime => TIME_FORMATS,
+            _ => &[],
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Role::Start => "start",
+            Role::Stop => "stop",
+            Role::StartDate => "start-date",
+            Role::StartTime => "start-time",
+            Role::StopDate => "stop-date",
+            Role::StopTime => "stop-time",
+            Role::Duration => "duration",
+            Role::Project => "project",
+            Role::Note => "note",
+            Role::Tags => "tags",
+            Role::Ignore => "ignore",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// The contents of a CSV file.
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Read a CSV file with a header row.
+    pub fn read(path: &Path) -> Result<Table, ImportError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .map_err(ImportError::ReadCsv)?;
+
+        let headers = reader
+            .headers()
+            .map_err(ImportError::ReadCsv)?
+            .iter()
+            .map(str::to_owned)
+            .collect();
+
+        let rows = reader
+            .records()
+            .map(|record| record.map(|r| r.iter().map(str::to_owned).collect()))
+            .collect::<Result<_, _>>()
+            .map_err(ImportError::ReadCsv)?;
+
+        Ok(Table { headers, rows })
+    }
+
+    /// Get a sample of the non-empty values in the given column.
+    fn sample(&self, column: usize) -> impl Iterator<Item = &str> {
+        self.rows
+            .iter()
+            .filter_map(move |row| row.get(column))
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+            .take(SAMPLE_ROWS)
+    }
+}
+
+/// The meaning of each column in a CSV file, and the format of its values.
+#[derive(Clone, Debug)]
+pub struct Mapping {
+    pub columns: Vec<(Role, Option<String>)>,
+}
+
+impl Mapping {
+    /// Guess the meaning of each column in the given table from its header and a sample of its
+    /// values.
+    ///
+    /// Tables exported from [Kimai][kimai] are recognised by their headers, and mapped by
+    /// [`kimai()`][kimai_mapping].
+    ///
+    /// [kimai]: https://www.kimai.org
+    /// [kimai_mapping]: #method.kimai
+    pub fn guess(table: &Table) -> Mapping {
+        if let Some(mapping) = Self::kimai(table) {
+            return mapping;
+        }
+
+        let mut columns: Vec<(Role, Option<String>)> = Vec::new();
+
+        for (index, header) in table.headers.iter().enumerate() {
+            let sample: Vec<&str> = table.sample(index).collect();
+            let taken = |role| columns.iter().any(|(r, _)| *r == role);
+            let fits = |role: Role| guess_format(role, &sample);
+
+            let role = guess_from_header(header, &fits)
+                .filter(|role| !taken(*role))
+                .or_else(|| {
+                    [
+                        Role::Start,
+                        Role::Stop,
+                        Role::StartDate,
+                        Role::StartTime,
+                        Role::StopTime,
+                    ]
+                    .into_iter()
+                    .find(|role| !taken(*role) && !sample.is_empty() && fits(*role).is_some())
+                })
+                .unwrap_or(Role::Ignore);
+
+            columns.push((role, fits(role)));
+        }
+
+        Mapping { columns }
+    }
+
+    /// Map the columns of a table exported from Kimai as CSV, if it is one: the `Date`, `From`
+    /// and `To` columns give the start and stop of each event, and the `Project`, `Description`
+    /// and `Tags` columns are kept. Kimai's `Duration` column is ignored, as its format depends on
+    /// Kimai's settings.
+    pub fn kimai(table: &Table) -> Option<Mapping> {
+        let has = |header: &str| table.headers.iter().any(|h| h == header);
+
+        if !["Date", "From", "To", "Customer", "Project", "Activity"]
+            .into_iter()
+            .all(has)
+        {
+            return None;
+        }
+
+        let columns = table
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                let role = match header.as_str() {
+                    "Date" => Role::StartDate,
+                    "From" => Role::StartTime,
+                    "To" => Role::StopTime,
+                    "Project" => Role::Project,
+                    "Description" => Role::Note,
+                    "Tags" => Role::Tags,
+                    _ => Role::Ignore,
+                };
+                let sample: Vec<&str> = table.sample(index).collect();
+
+                (role, guess_format(role, &sample))
+            })
+            .collect();
+
+        Some(Mapping { columns })
+    }
+
+    /// Check that the mapping contains enough information to create events, returning a
+    /// description of what's missing if not.
+    pub fn check(&self) -> Result<(), String> {
+        let has = |role| self.columns.iter().any(|(r, _)| *r == role);
+
+        let has_start = has(Role::Start) || (has(Role::StartDate) && has(Role::StartTime));
+
+        if !has_start {
+            return Err("no column for the start of each event".to_owned());
+        }
+
+        if !has(Role::Stop) && !has(Role::StopTime) && !has(Role::Duration) {
+            return Err("no column for the stop time or duration of each event".to_owned());
+        }
+
+        for (role, format) in &self.columns {
+            if !role.formats().is_empty() && format.is_none() {
+                return Err(format!("no format for the {} column", role));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create an event from each row of the given table, returning the events and a description
+    /// of the problem with each row that couldn't be converted, by row number.
+    pub fn to_events(&self, table: &Table) -> (Vec<Event>, Vec<(usize, String)>) {
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, row) in table.rows.iter().enumerate() {
+            match self.to_event(row) {
+                Ok(event) => events.push(event),
+                // Rows are numbered from 2, counting the header row.
+                Err(err) => errors.push((index + 2, err)),
+            }
+        }
+
+        (events, errors)
+    }
+
+    /// Create an event from a row of a table.
+    fn to_event(&self, row: &[String]) -> Result<Event, String> {
+        let value = |role| {
+            self.columns
+                .iter()
+                .position(|(r, _)| *r == role)
+                .and_then(|index| {
+                    Some((row.get(index)?.as_str(), self.columns[index].1.as_deref()))
+                })
+                .filter(|(value, _)| !value.is_empty())
+        };
+
+        let start = match (
+            value(Role::Start),
+            value(Role::StartDate),
+            value(Role::StartTime),
+        ) {
+            (Some((raw, Some(format))), _, _) => parse_datetime(raw, format)?,
+            (_, Some((date, Some(date_format))), Some((time, Some(time_format)))) => {
+                let date = parse_date(date, date_format)?;
+                local(date.and_time(parse_time(time, time_format)?))?
+            }
+            _ => return Err("missing start".to_owned()),
+        };
+
+        let stop = match (
+            value(Role::Stop),
+            value(Role::StopTime),
+            value(Role::Duration),
+        ) {
+            (Some((raw, Some(format))), _, _) => parse_datetime(raw, format)?,
+            (_, Some((time, Some(time_format))), _) => {
+                let start_date = start.with_timezone(&Local).date_naive();
+                let time = parse_time(time, time_format)?;
+
+                let date = match value(Role::StopDate) {
+                    Some((date, Some(date_format))) => parse_date(date, date_format)?,
+                    // An event that stops at an earlier time of day than it started is assumed
+                    // to have continued past midnight.
+                    _ if time < start.with_timezone(&Local).time() => start_date + Days::new(1),
+                    _ => start_date,
+                };
+
+                local(date.and_time(time))?
+            }
+            (_, _, Some((raw, _))) => start + parse_duration(raw)?,
+            _ => return Err("missing stop time or duration".to_owned()),
+        };
+
+        let mut event = Event::new(start).with_project(value(Role::Project).map(|(p, _)| p.into()));
+        event.stop = Some(stop);
+        event.note = value(Role::Note).map(|(note, _)| note.to_owned());
+        event.tags = value(Role::Tags)
+            .map(|(tags, _)| {
+                tags.split([',', ';'])
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(event)
+    }
+}
+
+/// Guess the meaning of a column from its header, using `fits` to find out whether its values
+/// fit a certain role.
+fn guess_from_header(header: &str, fits: &impl Fn(Role) -> Option<String>) -> Option<Role> {
+    let header = header.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| header.contains(word));
+
+    let (datetime, date, time) = if has(&["start", "begin", "from"]) {
+        (Role::Start, Role::StartDate, Role::StartTime)
+    } else if has(&["stop", "end", "finish", "until"]) {
+        (Role::Stop, Role::StopDate, Role::StopTime)
+    } else if has(&["duration", "hours", "length", "time spent"]) {
+        return Some(Role::Duration);
+    } else if has(&["tag"]) {
+        return Some(Role::Tags);
+    } else if has(&["project", "client", "customer"]) {
+        return Some(Role::Project);
+    } else if has(&["note", "description", "comment", "task", "summary"]) {
+        return Some(Role::Note);
+    } else if has(&["date", "day"]) {
+        return Some(Role::StartDate);
+    } else {
+        return None;
+    };
+
+    if has(&["date"]) {
+        Some(date)
+    } else if has(&["time"]) && fits(time).is_some() {
+        Some(time)
+    } else {
+        [datetime, date, time]
+            .into_iter()
+            .find(|role| fits(*role).is_some())
+            .or(Some(datetime))
+    }
+}
+
+/// Find the format for the given role that the most values in the sample can be parsed with,
+/// preferring formats earlier in the list if there's a tie.
+fn guess_format(role: Role, sample: &[&str]) -> Option<String> {
+    role.formats()
+        .iter()
+        .rev()
+        .map(|format| {
+            let parsed = sample
+                .iter()
+                .filter(|value| match role {
+                    Role::Start | Role::Stop => parse_datetime(value, format).is_ok(),
+                    Role::StartDate | Role::StopDate => parse_date(value, format).is_ok(),
+                    _ => parse_time(value, format).is_ok(),
+                })
+                .count();
+
+            (format, parsed)
+        })
+        .filter(|(_, parsed)| *parsed > 0)
+        .max_by_key(|(_, parsed)| *parsed)
+        .map(|(format, _)| format.to_string())
+}
+
+fn parse_datetime(raw: &str, format: &str) -> Result<DateTime<Utc>, String> {
+    if format == RFC3339 {
+        DateTime::parse_from_rfc3339(raw)
+            .map(Into::into)
+            .map_err(|_| format!("{:?} is not an RFC 3339 time", raw))
+    } else {
+        NaiveDateTime::parse_from_str(raw, format)
+            .map_err(|_| format!("{:?} doesn't match the format {:?}", raw, format))
+            .and_then(local)
+    }
+}
+
+fn parse_date(raw: &str, format: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(raw, format)
+        .map_err(|_| format!("{:?} doesn't match the format {:?}", raw, format))
+}
+
+fn parse_time(raw: &str, format: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(raw, format)
+        .map_err(|_| format!("{:?} doesn't match the format {:?}", raw, format))
+}
+
+fn parse_duration(raw: &str) -> Result<Duration, String> {
+    let invalid = || format!("{:?} is not a duration", raw);
+
+    if raw.contains(':') {
+        let mut parts = raw
+            .split(':')
+            .map(|part| part.parse::<i64>().map_err(|_| invalid()));
+        let hours = parts.next().ok_or_else(invalid)??;
+        let minutes = parts.next().ok_or_else(invalid)??;
+        let seconds = parts.next().transpose()?.unwrap_or(0);
+
+        Ok(Duration::seconds(hours * 3600 + minutes * 60 + seconds))
+    } else {
+        let hours: f64 = raw.replace(',', ".").parse().map_err(|_| invalid())?;
+
+        Ok(Duration::seconds((hours * 3600.0).round() as i64))
+    }
+}
+
+/// Interpret a local date and time as an instant.
+fn local(naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| format!("{} doesn't exist in the local timezone", naive))
+}
+
+/// Walk the user through confirming or correcting a guessed mapping, returning the final mapping,
+/// or `None` if the user stops answering.
+pub fn confirm_mapping(table: &Table, mut mapping: Mapping) -> Option<Mapping> {
+    loop {
+        println!("{}", tr!("Columns:"));
+
+        for (index, (header, (role, format))) in
+            table.headers.iter().zip(&mapping.columns).enumerate()
+        {
+            let example = table.sample(index).next().unwrap_or_default();
+            let format = format
+                .as_ref()
+                .map(|format| format!(" ({})", format))
+                .unwrap_or_default();
+
+            println!(
+                "{}",
+                tr!(
+                    "  {}. {} → {}{}, e.g. {}",
+                    index + 1,
+                    format!("{:?}", header),
+                    role,
+                    format,
+                    format!("{:?}", example)
+                )
+            );
+        }
+
+        match mapping.check() {
+            Ok(()) if confirm(&tr!("Use these columns?")) => return Some(mapping),
+            Ok(()) => {}
+            Err(problem) => println!("{}", tr!("These columns can't be imported: {}.", problem)),
+        }
+
+        let roles: Vec<String> = Role::ALL.iter().map(Role::to_string).collect();
+        println!("{}", tr!("Column meanings: {}.", roles.join(", ")));
+
+        for (index, header) in table.headers.iter().enumerate() {
+            let (role, format) = &mut mapping.columns[index];
+
+            let answer = ask(&tr!(
+                "Meaning of column {} {} [{}]:",
+                index + 1,
+                format!("{:?}", header),
+                role
+            ))?;
+
+            if let Some(new) = Role::ALL.iter().find(|r| r.to_string() == answer) {
+                *role = *new;
+                let sample: Vec<&str> = table.sample(index).collect();
+                *format = guess_format(*role, &sample);
+            }
+
+            if !role.formats().is_empty() {
+                let current = format.clone().unwrap_or_default();
+                let answer = ask(&tr!("Format of column {} [{}]:", index + 1, current))?;
+
+                if !answer.is_empty() {
+                    *format = Some(answer);
+                }
+            }
+        }
+    }
+}
+
+/// Show a preview of the events created from a table, and any rows that couldn't be converted.
+pub fn preview(events: &[Event], errors: &[(usize, String)]) {
+    println!("{}", tr!("Preview:"));
+
+    for event in events.iter().take(SAMPLE_ROWS) {
+        println!("  {}", edit::to_line(event));
+    }
+
+    if events.len() > SAMPLE_ROWS {
+        println!("{}", tr!("  ... and {} more", events.len() - SAMPLE_ROWS));
+    }
+
+    let total = events
+        .iter()
+        .filter_map(|e| Some(e.stop? - e.start))
+        .fold(Duration::zero(), |acc, next| acc + next);
+
+    println!(
+        "{}",
+        tr!(
+            "{} events totalling {}.",
+            events.len(),
+            format_duration(total)
+        )
+    );
+
+    for (row, err) in errors {
+        println!("{}", tr!("Skipping row {}: {}.", row, err));
+    }
+}
@@ -0,0 +1,299 @@
+//! Creating invoices for the time worked on a project, and keeping track of them in the invoice
+//! registry.
+
+use std::{fs, io, path::Path};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use minijinja::context;
+use punch_clock::{
+    invoices::{Invoice, InvoiceError, InvoiceStatus, Invoices},
+    split_tiers, Amounts, Config, Sheet, Tier,
+};
+
+use crate::{format_amount, template};
+
+/// The template used to render invoices if none is configured.
+const DEFAULT_TEMPLATE: &str = "\
+Invoice {{ number }}
+Date: {{ created }}
+Client: {{ client }}
+Period: {{ from }} to {{ to }}
+
+{% for line in lines -%}
+{{ line.date }}  {{ line.start }}-{{ line.stop }}  {{ line.hours }} h  {{ line.amount }}{% if \
+                                line.note %}  {{ line.note }}{% endif %}
+{% endfor %}
+{% for tier in tiers -%}
+{{ tier.hours }} h at {{ tier.rate }}  {{ tier.amount }}
+{% endfor %}{% if tiers %}
+{% endif %}Total: {{ hours }} h, {{ amount }}";
+
+/// Load the invoice registry for the configured sheet, which is empty if the file doesn't exist
+/// yet.
+fn load(config: &Config) -> Result<Invoices, InvoiceError> {
+    let path = Invoices::loc_for(config.storage.sheet_loc().unwrap());
+
+    match Invoices::load(&path) {
+        Err(InvoiceError::ReadInvoices(err)) if err.kind() == io::ErrorKind::NotFound => {
+            Ok(Invoices::default())
+        }
+        result => result,
+    }
+}
+
+/// Write the invoice registry for the configured sheet.
+fn write(config: &Config, invoices: &Invoices) -> Result<(), InvoiceError> {
+    invoices.write(Invoices::loc_for(config.storage.sheet_loc().unwrap()))
+}
+
+/// Create an invoice for the finished tracking periods on the given project between the two given
+/// instants, render it to standard output or a file, and add it to the registry.
+pub fn create(
+    config: &Config,
+    sheet: &Sheet,
+    client: &str,
+    (begin, end): (DateTime<Utc>, DateTime<Utc>),
+    billable: bool,
+    output: Option<&Path>,
+    dry_run: bool,
+) {
+    let events: Vec<_> = sheet
+        .events_in_range(begin, end)
+        .filter(|event| event.stop.is_some() && event.project.as_deref() == Some(client))
+        .filter(|event| !billable || event.billable)
+        .collect();
+
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        fail!("No finished tracking periods on {} to invoice.", client);
+        return;
+    };
+
+    let mut total = Amounts::default();
+    let mut time = Duration::zero();
+    let mut lines = Vec::new();
+    let policy = config.rounding.policy_for(Some(client));
+    let rounded = policy.round_each(events.iter().copied(), begin, end);
+    let tiers = config.rates.tiers_for(Some(client));
+    let mut charged = 0.0;
+
+    // Each line shows its share of the rounded time, and what that earns at the rates the time was
+    // actually worked at, so that the lines add up to the total.
+    for (event, length) in events.iter().zip(rounded) {
+        let start = event.start.max(begin);
+        let stop = event.stop.unwrap().min(end);
+        let mut earned = config
+            .rates
+            .earned(sheet, event, begin, end)
+            .scaled(ratio(length, stop - start));
+        let (start_local, stop_local) = (start.with_timezone(&Local), stop.with_timezone(&Local));
+
+        // With tiers, each line's rounded hours are charged at the rates of the tiers they fall in,
+        // counting the rounded hours of the lines before it, so the lines, the tiers and the total
+        // all charge the same hours.
+        if let Some((tiers, currency)) = tiers {
+            let hours = length.num_seconds() as f64 / 3600.0;
+            earned = tiered(tiers, currency, charged, charged + hours);
+            charged += hours;
+        }
+
+        total.add_all(&earned);
+        time += length;
+
+        lines.push(context! {
+            date => start_local.format("%Y-%m-%d").to_string(),
+            start => start_local.format("%H:%M").to_string(),
+            stop => stop_local.format("%H:%M").to_string(),
+            hours => format!("{:.2}", length.num_seconds() as f64 / 3600.0),
+            amount => earned.by_currency.iter().map(|(currency, amount)| {
+                format_amount(*amount, currency.as_deref())
+            }).collect::<Vec<_>>().join(" + "),
+            note => event.note.clone(),
+            tags => event.tags.clone(),
+        });
+    }
+
+    let mut tier_lines = Vec::new();
+
+    if let Some((tiers, currency)) = tiers {
+        for (hourly, hours) in split_tiers(tiers, charged) {
+            tier_lines.push(context! {
+                hours => format!("{:.2}", hours),
+                rate => format_amount(hourly, currency),
+                amount => format_amount(hourly * hours, currency),
+            });
+        }
+    }
+
+    if total.is_mixed() {
+        fail!(
+            "Can't invoice {}: its rates are in more than one currency.",
+            client
+        );
+        return;
+    }
+
+    let (currency, amount) = total.by_currency.into_iter().next().unwrap_or((None, 0.0));
+
+    let mut invoices = match load(config) {
+        Ok(invoices) => invoices,
+        Err(err) => {
+            fail!("Can't load invoices: {}.", err);
+            return;
+        }
+    };
+
+    let today = Local::now().date_naive();
+    let (number, seq) = invoices.next_number(&config.invoices.number, today);
+    let invoice = Invoice {
+        number,
+        seq,
+        client: client.to_owned(),
+        from: first.start.max(begin).with_timezone(&Local).date_naive(),
+        to: last
+            .stop
+            .unwrap()
+            .min(end)
+            .with_timezone(&Local)
+            .date_naive(),
+        created: today,
+        hours: time.num_seconds() as f64 / 3600.0,
+        amount,
+        currency,
+        status: InvoiceStatus::Draft,
+    };
+
+    let template = match config.invoices.template_path() {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(template) => template,
+            Err(err) => {
+                fail!("Can't read invoice template {}: {}.", path.display(), err);
+                return;
+            }
+        },
+        None => DEFAULT_TEMPLATE.to_owned(),
+    };
+
+    let variables = context! {
+        number => &invoice.number,
+        client => &invoice.client,
+        from => invoice.from.to_string(),
+        to => invoice.to.to_string(),
+        created => invoice.created.to_string(),
+        status => invoice.status.to_string(),
+        hours => format!("{:.2}", invoice.hours),
+        amount => format_amount(invoice.amount, invoice.currency.as_deref()),
+        currency => &invoice.currency,
+        lines => lines,
+        tiers => tier_lines,
+    };
+
+    let body = match template::render(&template, variables) {
+        Ok(body) => body,
+        Err(err) => {
+            fail!("Can't render invoice template: {}.", err);
+            return;
+        }
+    };
+
+    if dry_run {
+        say!("Would create invoice {}:", invoice.number);
+        say!("{}", body);
+        return;
+    }
+
+    match output {
+        Some(path) => {
+            if let Err(err) = fs::write(path, body + "\n") {
+                fail!("Can't write invoice to {}: {}.", path.display(), err);
+                return;
+            }
+
+            say!("Wrote invoice {} to {}.", invoice.number, path.display());
+        }
+        // The invoice is the output of the command, so it's printed even with `--quiet`.
+        None => println!("{}", body),
+    }
+
+    invoices.invoices.push(invoice);
+
+    if let Err(err) = write(config, &invoices) {
+        fail!("Can't save invoice: {}.", err);
+    }
+}
+
+/// Get the amount charged by the given tiers, in the given currency, for the hours of an invoice
+/// from `from` hours into it to `to` hours into it.
+fn tiered(tiers: &[Tier], currency: Option<&str>, from: f64, to: f64) -> Amounts {
+    let charge = |hours| {
+        split_tiers(tiers, hours)
+            .into_iter()
+            .map(|(hourly, hours)| hourly * hours)
+            .sum::<f64>()
+    };
+
+    let mut amounts = Amounts::default();
+    amounts.add(currency, charge(to) - charge(from));
+    amounts
+}
+
+/// Get the ratio of a rounded length of time to the length it was rounded from.
+fn ratio(rounded: Duration, time: Duration) -> f64 {
+    if time > Duration::zero() {
+        rounded.num_seconds() as f64 / time.num_seconds() as f64
+    } else {
+        1.0
+    }
+}
+
+/// Print the invoices in the registry.
+pub fn list(config: &Config) {
+    let invoices = match load(config) {
+        Ok(invoices) => invoices,
+        Err(err) => {
+            fail!("Can't load invoices: {}.", err);
+            return;
+        }
+    };
+
+    for invoice in &invoices.invoices {
+        say!(
+            "{}  {}  {} to {}  {}  {}",
+            invoice.number,
+            invoice.client,
+            invoice.from,
+            invoice.to,
+            format_amount(invoice.amount, invoice.currency.as_deref()),
+            crate::i18n::translate(&invoice.status.to_string())
+        );
+    }
+}
+
+/// Change the status of the invoice with the given number.
+pub fn mark(config: &Config, number: &str, status: InvoiceStatus, dry_run: bool) {
+    let mut invoices = match load(config) {
+        Ok(invoices) => invoices,
+        Err(err) => {
+            fail!("Can't load invoices: {}.", err);
+            return;
+        }
+    };
+
+    let Some(invoice) = invoices.get_mut(number) else {
+        fail!("No invoice numbered {}.", number);
+        return;
+    };
+
+    let name = crate::i18n::translate(&status.to_string()).to_owned();
+
+    if dry_run {
+        say!("Would mark invoice {} as {}.", number, name);
+        return;
+    }
+
+    invoice.status = status;
+
+    match write(config, &invoices) {
+        Ok(()) => say!("Marked invoice {} as {}.", number, name),
+        Err(err) => fail!("Can't save invoice: {}.", err),
+    }
+}
@@ -0,0 +1,200 @@
+//! A registry of the invoices generated from a sheet, kept in a file next to the sheet.
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The invoices that have been generated, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Invoices {
+    pub invoices: Vec<Invoice>,
+}
+
+impl Invoices {
+    /// Get the path to the registry file for the sheet at the given path.
+    ///
+    /// This is the file `invoices.json` in the same directory as the sheet.
+    pub fn loc_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        sheet_path.as_ref().with_file_name("invoices.json")
+    }
+
+    /// Attempt to load the registry from the file at the given path.
+    pub fn load<P>(path: P) -> Result<Invoices, InvoiceError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path).map_err(InvoiceError::ReadInvoices)?;
+
+        serde_json::from_reader(BufReader::new(file)).map_err(InvoiceError::ParseInvoices)
+    }
+
+    /// Attempt to write the registry to the file at the given path.
+    pub fn write<P>(&self, path: P) -> Result<(), InvoiceError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).map_err(InvoiceError::WriteInvoices)?;
+
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|err| InvoiceError::WriteInvoices(err.into()))
+    }
+
+    /// Get the invoice with the given number.
+    pub fn get_mut(&mut self, number: &str) -> Option<&mut Invoice> {
+        self.invoices
+            .iter_mut()
+            .find(|invoice| invoice.number == number)
+    }
+
+    /// Work out the number and sequence number of the next invoice created on the given date,
+    /// following the given pattern.
+    ///
+    /// The pattern is text in which `{year}`, `{month}` and `{day}` stand for the parts of the
+    /// date and `{seq}` for the sequence number, optionally zero-padded to a number of digits
+    /// with `{seq:N}`. Sequence numbers count up from 1 among the invoices whose numbers only
+    /// differ in their sequence number, so a pattern such as `INV-{year}-{seq:04}` starts again
+    /// from `INV-2025-0001` each year.
+    pub fn next_number(&self, pattern: &str, date: NaiveDate) -> (String, u32) {
+        let seq = self
+            .invoices
+            .iter()
+            .filter(|invoice| {
+                format_number(pattern, invoice.created, invoice.seq)
+                    == format_number(pattern, date, invoice.seq)
+            })
+            .map(|invoice| invoice.seq)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        (format_number(pattern, date, seq), seq)
+    }
+}
+
+/// Fill in an invoice number pattern, as described for
+/// [`Invoices::next_number()`][next_number].
+///
+/// [next_number]: struct.Invoices.html#method.next_number
+fn format_number(pattern: &str, date: NaiveDate, seq: u32) -> String {
+    let mut number = String::new();
+    let mut rest = pattern;
+
+    while let Some(open) = rest.find('{') {
+        number.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let Some(close) = rest.find('}') else {
+            break;
+        };
+
+        let (name, width) = match rest[1..close].split_once(':') {
+            Some((name, width)) => (name, width.parse().unwrap_or(0)),
+            None => (&rest[1..close], 0),
+        };
+
+        match name {
+            "year" => number.push_str(&date.year().to_string()),
+            "month" => number.push_str(&format!("{:02}", date.month())),
+            "day" => number.push_str(&format!("{:02}", date.day())),
+            "seq" => number.push_str(&format!("{:0width$}", seq, width = width)),
+            _ => number.push_str(&rest[..=close]),
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    number.push_str(rest);
+    number
+}
+
+/// An invoice for the time worked on a client's project in a period of time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Invoice {
+    /// The invoice number.
+    pub number: String,
+    /// The sequence number the invoice number was made from.
+    pub seq: u32,
+    /// The project the invoice is for.
+    pub client: String,
+    /// The first date of the period the invoice covers.
+    pub from: NaiveDate,
+    /// The last date of the period the invoice covers.
+    pub to: NaiveDate,
+    /// The date the invoice was created on.
+    pub created: NaiveDate,
+    /// The number of hours invoiced.
+    pub hours: f64,
+    /// The amount invoiced.
+    pub amount: f64,
+    /// The code of the currency the amount is in, if the rates have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// How far the invoice has got.
+    pub status: InvoiceStatus,
+}
+
+/// How far an invoice has got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    /// The invoice has been created, but not sent.
+    #[default]
+    Draft,
+    /// The invoice has been sent to the client.
+    Sent,
+    /// The invoice has been paid.
+    Paid,
+    /// The invoice has been cancelled.
+    Void,
+}
+
+impl FromStr for InvoiceStatus {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "draft" => Ok(InvoiceStatus::Draft),
+            "sent" => Ok(InvoiceStatus::Sent),
+            "paid" => Ok(InvoiceStatus::Paid),
+            "void" => Ok(InvoiceStatus::Void),
+            _ => Err("Invoice status not recognised.".into()),
+        }
+    }
+}
+
+impl Display for InvoiceStatus {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            InvoiceStatus::Draft => "draft",
+            InvoiceStatus::Sent => "sent",
+            InvoiceStatus::Paid => "paid",
+            InvoiceStatus::Void => "void",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Errors arising through the use of [`Invoices`][invoices].
+///
+/// [invoices]: ./struct.Invoices.html
+#[derive(Error, Debug)]
+pub enum InvoiceError {
+    #[error("unable to read invoice registry")]
+    ReadInvoices(#[source] std::io::Error),
+    #[error("unable to parse invoice registry")]
+    ParseInvoices(#[source] serde_json::Error),
+    #[error("unable to write invoice registry")]
+    WriteInvoices(#[source] std::io::Error),
+}
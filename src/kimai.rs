@@ -0,0 +1,160 @@
+//! Pushing tracking periods to a [Kimai](https://www.kimai.org) server.
+//!
+//! Requests are made to Kimai's API with `curl`, authenticated with an API token taken from the
+//! `PUNCH_KIMAI_TOKEN` environment variable or printed by a configured command. Tracking periods
+//! that start at the same time as a timesheet already on the server are skipped, so the same
+//! period can be pushed repeatedly.
+
+use std::{collections::HashSet, io};
+
+use chrono::{DateTime, Local, Utc};
+use punch_clock::{config::KimaiConfig, Event};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::http::{self, encode, request, HttpError};
+
+/// The format of the times sent to and returned by Kimai's API.
+const LOCAL_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Errors arising while pushing to a Kimai server.
+#[derive(Error, Debug)]
+pub enum PushError {
+    #[error("no server: set push.kimai.url")]
+    NoUrl,
+    #[error("unable to run token command")]
+    RunTokenCommand(#[source] io::Error),
+    #[error("no API token: set PUNCH_KIMAI_TOKEN or push.kimai.token_command")]
+    NoToken,
+    #[error("no activity named {0:?} on the server")]
+    NoActivity(String),
+    #[error("{0}")]
+    Http(#[from] HttpError),
+}
+
+/// The result of pushing tracking periods to a Kimai server.
+#[derive(Debug, Default)]
+pub struct Pushed {
+    /// The number of tracking periods added as timesheets.
+    pub added: usize,
+    /// The number of tracking periods that were already on the server.
+    pub skipped: usize,
+    /// The projects of the tracking periods that couldn't be pushed, as there is no project on
+    /// the server with the same name.
+    pub unknown_projects: Vec<String>,
+}
+
+/// Add a timesheet for each of the given events that has finished, between the two given instants,
+/// unless one starting at the same time is already on the server.
+pub fn push(
+    config: &KimaiConfig,
+    events: &[Event],
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Pushed, PushError> {
+    let url = config.url.as_deref().ok_or(PushError::NoUrl)?;
+    let url = url.trim_end_matches('/');
+    let token = http::token("PUNCH_KIMAI_TOKEN", config.token_command.as_deref())
+        .map_err(PushError::RunTokenCommand)?
+        .ok_or(PushError::NoToken)?;
+
+    let projects = request(&token, &format!("{}/api/projects", url), None)?;
+    let activities = request(&token, &format!("{}/api/activities", url), None)?;
+    // Tracking periods overlapping the start or end of the period are pushed whole, so the
+    // timesheets they were pushed as before can lie partly outside it.
+    let (first, last) = events.iter().fold((begin, end), |(first, last), event| {
+        (first.min(event.start), last.max(event.stop.unwrap_or(last)))
+    });
+    let existing = existing_starts(&token, url, first, last)?;
+
+    let mut pushed = Pushed::default();
+
+    for event in events {
+        let Some(stop) = event.stop else {
+            continue;
+        };
+
+        if existing.contains(&event.start) {
+            pushed.skipped += 1;
+            continue;
+        }
+
+        let name = event.project.as_deref().unwrap_or_default();
+        let name = config.projects.get(name).map_or(name, String::as_str);
+
+        let Some(project) = find(&projects, |project| project["name"] == name) else {
+            if !pushed
+                .unknown_projects
+                .iter()
+                .any(|unknown| unknown == name)
+            {
+                pushed.unknown_projects.push(name.to_owned());
+            }
+            continue;
+        };
+
+        // Activities can belong to a project or be shared by all of them, and one belonging to
+        // the project is preferred.
+        let activity = find(&activities, |activity| {
+            activity["name"] == config.activity.as_str() && activity["project"] == project
+        })
+        .or_else(|| {
+            find(&activities, |activity| {
+                activity["name"] == config.activity.as_str() && activity["project"].is_null()
+            })
+        })
+        .ok_or_else(|| PushError::NoActivity(config.activity.clone()))?;
+
+        let timesheet = json!({
+            "begin": event.start.with_timezone(&Local).format(LOCAL_FORMAT).to_string(),
+            "end": stop.with_timezone(&Local).format(LOCAL_FORMAT).to_string(),
+            "project": project,
+            "activity": activity,
+            "description": event.note.clone().unwrap_or_default(),
+            "tags": event.tags.join(","),
+        });
+
+        request(&token, &format!("{}/api/timesheets", url), Some(&timesheet))?;
+        pushed.added += 1;
+    }
+
+    Ok(pushed)
+}
+
+/// Find the ID of the first item in a list returned by the API that matches the given predicate.
+fn find(list: &Value, predicate: impl Fn(&Value) -> bool) -> Option<i64> {
+    list.as_array()?
+        .iter()
+        .find(|item| predicate(item))
+        .and_then(|item| item["id"].as_i64())
+}
+
+/// Get the start times of the current user's timesheets on the server between the two given
+/// instants.
+fn existing_starts(
+    token: &str,
+    url: &str,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<HashSet<DateTime<Utc>>, PushError> {
+    // Kimai can't represent the earliest instant chrono can, which `punch push kimai all` uses.
+    let begin = begin.max(DateTime::UNIX_EPOCH);
+    let url = format!(
+        "{}/api/timesheets?size=10000&begin={}&end={}",
+        url,
+        encode(&begin.with_timezone(&Local).format(LOCAL_FORMAT).to_string()),
+        encode(&end.with_timezone(&Local).format(LOCAL_FORMAT).to_string())
+    );
+    let timesheets = request(token, &url, None)?;
+
+    let starts = timesheets
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|timesheet| timesheet["begin"].as_str())
+        .filter_map(|begin| DateTime::parse_from_str(begin, "%Y-%m-%dT%H:%M:%S%z").ok())
+        .map(Into::into)
+        .collect();
+
+    Ok(starts)
+}
@@ -3,10 +3,38 @@
 //! This library exposes an API for performing all the same tasks as through the command-line
 //! interface (e.g. punching in or out, checking time tracking status, counting totals).
 
+pub mod anomaly;
+pub mod backup;
+pub mod checksum;
+pub mod config;
+pub mod deductions;
+pub mod differential;
 mod event;
+mod filter;
+pub mod history;
+pub mod invoices;
+pub mod migrate;
+pub mod on_call;
+pub mod overtime;
 mod period;
+mod rates;
+pub mod repair;
+mod rounding;
+mod schedule;
+pub mod search;
 pub mod sheet;
+pub mod signing;
+pub mod snapshot;
+pub mod team;
+pub mod totals;
 
-pub use event::Event;
-pub use period::Period;
+pub use config::Config;
+pub use event::{Event, Review};
+pub use filter::Filter;
+pub use period::{local_instant, midnight, Period, YearStart};
+pub use rates::{split_tiers, Amounts, RateChange, RateHistory, Rates, Tier};
+pub use rounding::{Rounding, RoundingMode, RoundingPolicy, RoundingScope};
+pub use schedule::{Schedule, ScheduleChange, WeekHours, WorkingHours};
 pub use sheet::Sheet;
+pub use team::TeamTotals;
+pub use totals::{Counting, DailyTotals};
@@ -1,235 +1,3743 @@
+#[macro_use]
+mod i18n;
+#[macro_use]
+mod output;
+
+mod caldav;
+mod calendar;
+mod completions;
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+mod dbus;
+mod edit;
+mod error;
+mod export;
+mod git;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod gsheets;
+mod homeassistant;
+mod hooks;
+mod http;
+mod ical;
+mod import;
+mod invoice;
+mod kimai;
+mod mqtt;
+mod notify;
 mod opt;
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+mod presence;
+mod prompt;
+mod remote;
+#[cfg(feature = "scripting")]
+mod script;
+mod serve;
+mod service;
+mod shell;
+mod template;
+mod timeline;
+mod watch;
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ffi::OsString,
+    fs::File,
+    io::{self, IsTerminal},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use chrono::{prelude::*, Duration};
-use directories::ProjectDirs;
-use opt::Opt;
+use error::CliError;
+use minijinja::context;
+use opt::{
+    Command, Grouping, Hook, Invoice, Opt, Project, Push, Service, Snapshot, StatusFormat, Sync,
+    Tag,
+};
 use punch_clock::{
-    sheet::{SheetError, SheetStatus},
-    Period, Sheet,
+    anomaly::{self, Anomaly},
+    backup::{self, BackupError, Locations},
+    checksum::{self, Checksum},
+    config::{self, login_name, ConfigError},
+    deductions,
+    history::{Change, Diff, History, HistoryError, Operation},
+    migrate,
+    repair::{self, Salvaged},
+    search::{self, Query},
+    sheet::{SheetError, SheetFormat, SheetStatus, StatusSummary},
+    signing::{self, Verification},
+    snapshot::{self, SnapshotError},
+    totals, Amounts, Config, Counting, DailyTotals, Event, Filter, Period, Review, RoundingScope,
+    Sheet, TeamTotals, WorkingHours,
 };
+use remote::{Pulled, RemoteError};
 use structopt::StructOpt;
+use tracing::{debug, trace, warn};
 
 const SAME_DAY_FORMAT: &str = "%H:%M:%S";
 const DIFF_DAY_FORMAT: &str = "%H:%M:%S on %e %b";
 
 fn main() {
-    let opt = Opt::from_args();
+    let args: Vec<OsString> = std::env::args_os().collect();
 
-    // Try to load the sheet from the default location. If loading fails due to a missing file,
-    // create a new empty sheet.
-    let mut sheet = Sheet::load_default()
-        .or_else(|err| match err {
-            SheetError::OpenSheet(io_err) if io_err.raw_os_error() == Some(2) => {
-                Ok(Sheet::default())
+    // Commands that aren't built in may be aliases from the config, which are only looked up after
+    // parsing fails so that the config isn't read for every command.
+    let opt = Opt::from_iter_safe(&args).unwrap_or_else(|err| match expand_alias(&args) {
+        Some(expanded) => Opt::from_iter_safe(expanded).unwrap_or_else(|err| exit_usage(err)),
+        None => exit_usage(err),
+    });
+
+    // A service has no terminal to write to, so it writes to the system's log instead.
+    match opt.command {
+        Command::Service(Service::Run) => service::init_logging(),
+        _ => output::init(opt.quiet, opt.verbose),
+    }
+
+    match migrate::migrate() {
+        Ok(moved) => {
+            for (from, to) in moved {
+                warn!("Moved {} to {}", from.display(), to.display());
             }
-            _ => Err(err),
-        })
-        .unwrap();
+        }
+        Err(err) => warn!("Unable to move files to their new locations: {}", err),
+    }
+
+    // The shell runs each command it's given as if it were given on the command line, so it can't
+    // be run as a command itself.
+    match opt.command {
+        Command::Shell => shell::repl(),
+        _ => {
+            if let Err(err) = run(opt) {
+                report(&err);
+                std::process::exit(err.exit_code());
+            }
+        }
+    }
+
+    if output::failed() {
+        std::process::exit(error::FAILED);
+    }
+}
+
+/// Print an error that stopped a command from running, with a hint at what to do about it if there
+/// is one.
+fn report(err: &CliError) {
+    fail!("Error: {}.", err.describe());
+
+    if err.is_unparseable_sheet() {
+        say!("Run `punch repair` to salvage the tracking periods that can still be read.");
+    }
+}
+
+/// Print the given error from parsing the command line and exit with [`error::USAGE`], unless
+/// it's only printing help or the version.
+fn exit_usage(err: structopt::clap::Error) -> ! {
+    if !err.use_stderr() {
+        err.exit();
+    }
 
-    match opt {
-        Opt::In { .. } => match sheet.punch_in() {
-            Ok(time_utc) => {
-                let time_local: DateTime<Local> = time_utc.into();
+    eprintln!("{}", err.message);
+    std::process::exit(error::USAGE);
+}
+
+/// Replace the command in the given command-line arguments with the arguments it stands for, if
+/// it's an alias in the config.
+fn expand_alias(args: &[OsString]) -> Option<Vec<OsString>> {
+    let mut words = args.iter().enumerate().skip(1);
 
-                println!("Punching in at {}.", time_local.format("%H:%M:%S"));
+    // The command is the first argument that isn't an option, or the value of one.
+    let (index, name) = loop {
+        let (index, arg) = words.next()?;
+
+        match arg.to_str() {
+            Some(arg) if opt::VALUED_GLOBAL_OPTIONS.contains(&arg) => {
+                words.next();
             }
-            Err(SheetError::PunchedIn(start_utc)) => {
-                let start_local: DateTime<Local> = start_utc.into();
+            Some(arg) if arg.starts_with('-') => {}
+            Some(arg) => break (index, arg),
+            None => return None,
+        }
+    };
 
-                let format = if start_local.date() == Local::today() {
-                    SAME_DAY_FORMAT
-                } else {
-                    DIFF_DAY_FORMAT
-                };
+    let expansion = Config::load_default().ok()?.alias(name)?;
+
+    Some(
+        args[..index]
+            .iter()
+            .cloned()
+            .chain(expansion.into_iter().map(OsString::from))
+            .chain(args[index + 1..].iter().cloned())
+            .collect(),
+    )
+}
 
-                println!(
-                    "Can't punch in: already punched in at {}.",
-                    start_local.format(format)
+/// Carry out the command given on the command line, returning an error if it couldn't be run at
+/// all, such as because the sheet couldn't be read.
+fn run(opt: Opt) -> Result<(), CliError> {
+    let Opt {
+        dry_run,
+        yes,
+        user,
+        as_of,
+        command,
+        ..
+    } = opt;
+
+    // Try to load the config from the default location. If loading fails due to a missing file,
+    // use the default config.
+    let config_path = Config::default_loc().map_err(|_| CliError::FindConfig)?;
+    debug!(path = %config_path.display(), "loading config");
+    let mut config = match Config::load(&config_path) {
+        Ok(config) => config,
+        Err(ConfigError::OpenConfig(io_err)) if io_err.raw_os_error() == Some(2) => {
+            Config::default()
+        }
+        Err(err) => return Err(CliError::LoadConfig(config_path, Box::new(err))),
+    };
+
+    if user.is_some() {
+        config.user = user;
+    }
+
+    i18n::init(config.language.as_deref());
+
+    // The sheet's location is looked up wherever it's needed, so it's only checked here.
+    config
+        .storage
+        .sheet_loc()
+        .map_err(|_| CliError::FindSheet)?;
+
+    // Restoring a backup replaces the sheet, so the one there is neither loaded nor written back,
+    // which also lets a sheet that can't be loaded be restored.
+    if let Command::Restore { backup } = &command {
+        restore_backup(&config, backup, dry_run, yes);
+        return Ok(());
+    }
+
+    // Likewise, the sheet being repaired can't be loaded.
+    if let Command::Repair = &command {
+        return repair_sheet(&config, dry_run, yes);
+    }
+
+    if as_of.is_some() && command.changes_sheet() {
+        fail!("--as-of only works with commands that don't change the sheet.");
+        return Ok(());
+    }
+
+    // The status for a prompt is printed on every prompt, so it goes by the local copy rather than
+    // waiting on the server.
+    let prompt_only = command.only_touches_last() && !command.changes_sheet();
+
+    if config.storage.remote.url.is_some() && !dry_run && !prompt_only {
+        pull_remote(&config);
+    }
+
+    // Punching in and out, adding notes and printing the status for a prompt only touch the last
+    // tracking period, so if the sheet is stored as JSON Lines, only that period is read and the
+    // file is appended to instead of being rewritten. On a shared sheet, the last period may be
+    // another user's, in which case the whole sheet has to be read to find the user's own.
+    let tail = (as_of.is_none()
+        && command.only_touches_last()
+        && config
+            .storage
+            .sheet_loc()
+            .is_ok_and(|path| SheetFormat::from_path(path) == SheetFormat::JsonLines))
+    .then(|| load_sheet_with(&config, Sheet::load_tail))
+    .transpose()?
+    .filter(|loaded| {
+        loaded
+            .events()
+            .iter()
+            .all(|event| event.belongs_to(&config.user()))
+    });
+    let changes_sheet = command.changes_sheet();
+    let operation = command.operation();
+
+    let mut sheet = match &tail {
+        Some(loaded) => loaded.clone(),
+        None => load_sheet(&config)?,
+    };
+
+    // Accepting the sheet as it is would only repeat the warning that it doesn't match, and
+    // checking it otherwise reads the whole sheet.
+    if !matches!(command, Command::Doctor { .. }) {
+        check_sheet(&config, false);
+    }
+
+    if !matches!(command, Command::MergeConflicts) {
+        check_conflicts(&config);
+    }
+
+    // The history holds the changes made to every user's tracking periods, so they're undone on
+    // the whole sheet.
+    if let Some(time) = as_of {
+        let shared = load_sheet_with(&config, Sheet::load)?;
+
+        match load_history(&config).and_then(|history| history.sheet_as_of(&shared, time)) {
+            Ok(then) => sheet = then.user_view(&config.user()),
+            Err(err) => {
+                fail!(
+                    "Can't work out what the sheet looked like at {}: {}.",
+                    format_local(time, "%Y-%m-%d %H:%M"),
+                    err
                 );
+                return Ok(());
             }
-            Err(err) => {
-                panic!("Unexpected error while punching in: {}", err);
+        }
+    }
+
+    let mut changes = Vec::new();
+
+    // The sheet as it was is only looked at, so it's left as it was. `punch nag` checks the sheet
+    // as it was left, so that it reports a tracking period left open rather than closing it.
+    if as_of.is_none() && !matches!(command, Command::Nag) {
+        changes.extend(auto_close(&config, &mut sheet));
+
+        if config.sessions.split_at_midnight {
+            changes.extend(split_at_midnights(&config, &mut sheet));
+        }
+    }
+
+    // Keep the sheet as it was loaded, so that the change made to it can be recorded in the
+    // history.
+    let before = operation.map(|_| sheet.clone());
+
+    // An edit of the config that's only written once the sheet has been, with the message to print
+    // then, so that the two can't disagree if writing the sheet is refused.
+    let mut config_edit = None;
+
+    match command {
+        Command::In {
+            project,
+            mut tags,
+            mut billable,
+            time,
+            force,
+        } => {
+            let time_utc = time.map_or_else(Utc::now, Into::into);
+            let project = project.or_else(|| {
+                let dir = std::env::current_dir().ok()?;
+                config.projects.detect(&dir)
+            });
+
+            if let Some(start_utc) = sheet.stale_since(config.sessions.stale_after(), time_utc) {
+                offer_stale_punch_out(&mut sheet, &config, start_utc, time_utc, yes);
             }
-        },
-        Opt::Out { .. } => match sheet.punch_out() {
-            Ok(time_utc) => {
-                let time_local: DateTime<Local> = time_utc.into();
 
-                println!("Punching out at {}.", time_local.format("%H:%M:%S"));
+            if let Some(defaults) = config.projects.defaults_for(project.as_deref()) {
+                for tag in &defaults.tags {
+                    if !tags.contains(tag) {
+                        tags.push(tag.clone());
+                    }
+                }
+
+                billable |= defaults.billable;
             }
-            Err(SheetError::PunchedOut(end_utc)) => {
-                let end_local: DateTime<Local> = end_utc.into();
 
-                let format = if end_local.date() == Local::today() {
-                    SAME_DAY_FORMAT
-                } else {
-                    DIFF_DAY_FORMAT
-                };
+            let event = Event::new(time_utc)
+                .with_user(Some(config.user()))
+                .with_project(project.clone())
+                .with_tags(tags)
+                .with_billable(billable);
+
+            let punched_in = if force {
+                sheet.force_punch_in_with(event)
+            } else {
+                sheet.punch_in_with(event)
+            };
+
+            match punched_in {
+                Ok(time_utc) => {
+                    let time_local: DateTime<Local> = time_utc.into();
+
+                    match &project {
+                        Some(project) => say!(
+                            "Punching in on {} at {}.",
+                            project,
+                            time_local.format("%H:%M:%S")
+                        ),
+                        None => say!("Punching in at {}.", time_local.format("%H:%M:%S")),
+                    }
+
+                    if let Some(project) = &project {
+                        if config.projects.is_archived(project) {
+                            say!("Warning: {} is archived.", project);
+                        }
+
+                        let whole = match &tail {
+                            Some(loaded) if config.projects.budgets.contains_key(project) => {
+                                whole_sheet(&config, &sheet, loaded)
+                            }
+                            _ => None,
+                        };
+
+                        warn_budget(&config, whole.as_ref().unwrap_or(&sheet), project);
+                    }
+                }
+                Err(SheetError::PunchedIn(start_utc)) => {
+                    fail!(
+                        "Can't punch in: already punched in at {}.",
+                        format_time(start_utc)
+                    );
+                }
+                Err(SheetError::PunchInBeforeStop { event, .. }) if force => {
+                    fail!(
+                        "Can't punch in: the last tracking period starts later, at {}.",
+                        format_time(event.start)
+                    );
+                }
+                Err(SheetError::PunchInBeforeStop { event, .. }) => {
+                    fail!(
+                        "Can't punch in: the last tracking period ends later, at {}. Use --force \
+                         to cut it short.",
+                        format_time(event.stop.unwrap())
+                    );
+                }
+                Err(err @ SheetError::Frozen(..)) => {
+                    fail!("Can't punch in: {}.", err);
+                }
+                Err(SheetError::InFuture(time_utc)) => {
+                    fail!(
+                        "Can't punch in: {} is in the future.",
+                        format_time(time_utc)
+                    );
+                }
+                Err(err) => {
+                    fail!("Can't punch in: {}.", err);
+                }
+            }
+        }
+        Command::Out { time, force } => {
+            let time_utc = time.map_or_else(Utc::now, Into::into);
+            let punched_out = if force {
+                sheet.force_punch_out_at(time_utc)
+            } else {
+                sheet.punch_out_at(time_utc)
+            };
+
+            match punched_out {
+                Ok(time_utc) => {
+                    let time_local: DateTime<Local> = time_utc.into();
+
+                    say!("Punching out at {}.", time_local.format("%H:%M:%S"));
+                }
+                Err(SheetError::PunchedOut(end_utc)) => {
+                    fail!(
+                        "Can't punch out: already punched out at {}.",
+                        format_time(end_utc)
+                    );
+                }
+                Err(SheetError::NoPunches) => {
+                    fail!("Can't punch out; no punch-in recorded.");
+                }
+                Err(SheetError::InFuture(time_utc)) => {
+                    fail!(
+                        "Can't punch out: {} is in the future.",
+                        format_time(time_utc)
+                    );
+                }
+                Err(SheetError::PunchOutBeforePunchIn { event, .. }) => {
+                    fail!(
+                        "Can't punch out: that's before punching in at {}. Use --force to record \
+                         the time between them.",
+                        format_time(event.start)
+                    );
+                }
+                Err(SheetError::Overlap(..)) => {
+                    fail!("Can't punch out: the time would overlap the tracking period before.");
+                }
+                Err(err) => {
+                    fail!("Can't punch out: {}.", err);
+                }
+            }
+        }
+        Command::Count {
+            compare, counting, ..
+        } if !compare.is_empty() => {
+            let (period, baseline) = (&compare[0], &compare[1]);
+            let counting = counting.counting(config.counting);
+            let worked_sheet = config.on_call.worked(&sheet);
+            let count = |period: &Period| {
+                let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+                let worked = count_time(&config, &sheet, start, end, as_of.is_none());
+                let deducted = deductions::total(&config.deductions, &worked_sheet, start, end);
+
+                (
+                    counting.total(&worked_sheet, worked, start, end) - deducted,
+                    deducted,
+                )
+            };
+            let ((time, time_deducted), (baseline_time, baseline_deducted)) =
+                (count(period), count(baseline));
+            let comparison = totals::Comparison {
+                time,
+                baseline: baseline_time,
+            };
+
+            for (period, time, deducted) in [
+                (period, time, time_deducted),
+                (baseline, baseline_time, baseline_deducted),
+            ] {
+                say!(
+                    "Time worked {}: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(time),
+                );
+                print_deducted(deducted);
+            }
+
+            let difference = comparison.difference();
+            let sign = if difference > Duration::zero() {
+                "+"
+            } else {
+                ""
+            };
+
+            match comparison.percent_change() {
+                Some(percent) => say!(
+                    "Difference: {}{} ({}).",
+                    sign,
+                    format_duration(difference),
+                    format!("{:+.0}%", percent)
+                ),
+                None => say!("Difference: {}{}.", sign, format_duration(difference)),
+            }
+        }
+        Command::Count {
+            period, counting, ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let counting = counting.counting(config.counting);
+            let worked_sheet = config.on_call.worked(&sheet);
+            let worked = count_time(&config, &sheet, start, end, as_of.is_none());
+            let deducted = deductions::total(&config.deductions, &worked_sheet, start, end);
+            let total = counting.total(&worked_sheet, worked, start, end) - deducted;
+            let (on_call, _) = config.on_call.count_range(&sheet, start, end);
+
+            match &config.templates.count {
+                Some(template) => template::print(
+                    template,
+                    context! {
+                        period => period.to_string().to_lowercase(),
+                        total => format_duration(total),
+                        total_seconds => total.num_seconds(),
+                        deducted => format_duration(deducted),
+                        deducted_seconds => deducted.num_seconds(),
+                        on_call => format_duration(on_call),
+                        on_call_seconds => on_call.num_seconds(),
+                    },
+                ),
+                None if counting == Counting::Gross => say!(
+                    "Time worked {}, including breaks: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(total),
+                ),
+                None => say!(
+                    "Time worked {}: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(total),
+                ),
+            }
+
+            if config.templates.count.is_none() {
+                print_deducted(deducted);
+                print_on_call(&config, &sheet, &period, start, end);
+            }
+        }
+        Command::Balance { period } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let worked = worked_time(&config, &sheet, start, end, as_of.is_none());
+
+            // Expectations only begin from the first tracked event, so that counting over all
+            // time doesn't start at the beginning of the calendar.
+            let expected = match sheet.events().first() {
+                Some(first) => config
+                    .schedule
+                    .expected_range(std::cmp::max(start, first.start), end),
+                None => Duration::zero(),
+            };
+
+            say!(
+                "Time worked {}: {}.",
+                i18n::translate(&period.to_string().to_lowercase()),
+                format_duration(worked),
+            );
+            say!(
+                "Time expected {}: {}.",
+                i18n::translate(&period.to_string().to_lowercase()),
+                format_duration(expected),
+            );
+            say!("Balance: {}.", format_duration(worked - expected));
+            print_on_call(&config, &sheet, &period, start, end);
+        }
+        Command::Gaps {
+            period,
+            working_hours,
+            min,
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let hours = working_hours.unwrap_or(config.schedule.working_hours);
+
+            // As with `punch balance`, nothing is expected before the first tracked event.
+            let start = match sheet.events().first() {
+                Some(first) => start.max(first.start),
+                None => end,
+            };
+            let name = i18n::translate(&period.to_string().to_lowercase()).to_owned();
+
+            print_gaps(
+                &config,
+                &sheet,
+                (start, end.min(Utc::now())),
+                hours,
+                Duration::minutes(min),
+                &name,
+            );
+        }
+        Command::Earnings { period, billable } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let mut projects: BTreeMap<Option<String>, (Duration, Amounts)> = BTreeMap::new();
+
+            for event in sheet.events_in_range(start, end) {
+                if billable && !event.billable {
+                    continue;
+                }
 
-                println!(
-                    "Can't punch out: already punched out at {}.",
-                    end_local.format(format)
+                let stop = event.stop.unwrap_or_else(Utc::now).min(end);
+                let (time, earned) = projects.entry(event.project.clone()).or_default();
+                *time += stop - event.start.max(start);
+                earned.add_all(&config.rates.earned(&sheet, event, start, end));
+            }
+
+            let mut total = Amounts::default();
+
+            for (project, (time, earned)) in &projects {
+                let project = project.clone().unwrap_or_else(|| tr!("(no project)"));
+
+                say!(
+                    "{}: {}, earning {}.",
+                    project,
+                    format_duration(*time),
+                    format_amounts(earned)
+                );
+                total.add_all(earned);
+            }
+
+            let name = i18n::translate(&period.to_string().to_lowercase()).to_owned();
+
+            // Amounts in different currencies can't be added up, so each gets its own total.
+            if total.is_mixed() {
+                for (currency, amount) in &total.by_currency {
+                    say!(
+                        "Total earned {} in {}: {}.",
+                        name,
+                        currency.clone().unwrap_or_else(|| tr!("no currency")),
+                        format_amount(*amount, currency.as_deref())
+                    );
+                }
+            } else {
+                say!("Total earned {}: {}.", name, format_amounts(&total));
+            }
+        }
+        Command::Invoice(Invoice::Create {
+            client,
+            period,
+            billable,
+            output,
+        }) => invoice::create(
+            &config,
+            &sheet,
+            &client,
+            period.range_in(Local::now(), config.fiscal_year_start),
+            billable,
+            output.as_deref(),
+            dry_run,
+        ),
+        Command::Invoice(Invoice::List) => invoice::list(&config),
+        Command::Invoice(Invoice::Mark { number, status }) => {
+            invoice::mark(&config, &number, status, dry_run)
+        }
+        Command::Note { text } => match sheet.append_note(&text) {
+            Ok(()) => {
+                say!("Added note to the ongoing tracking period.");
+            }
+            Err(SheetError::PunchedOut(end_utc)) => {
+                fail!(
+                    "Can't add note: not punched in, last punched out at {}.",
+                    format_time(end_utc)
                 );
             }
             Err(SheetError::NoPunches) => {
-                println!("Can't punch out; no punch-in recorded.");
+                fail!("Can't add note; no punch-in recorded.");
             }
             Err(err) => {
-                panic!("Unexpected error while punching out: {}", err);
+                fail!("Can't add note: {}.", err);
             }
         },
-        Opt::Status => match sheet.status() {
-            SheetStatus::PunchedIn(start_utc) => {
-                let start_local: DateTime<Local> = start_utc.into();
+        Command::Report {
+            period,
+            team: true,
+            sheets,
+            csv,
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let mut totals = TeamTotals::default();
 
-                let format = if start_local.date() == Local::today() {
-                    SAME_DAY_FORMAT
-                } else {
-                    DIFF_DAY_FORMAT
+            match sheets {
+                Some(dir) => add_sheets_in(&mut totals, &dir, start, end),
+                None => totals.add_sheet(
+                    &load_sheet_with(&config, Sheet::load)?,
+                    &config.user(),
+                    start,
+                    end,
+                ),
+            }
+
+            if csv {
+                if let Err(err) = export::export_team(&totals, io::stdout().lock()) {
+                    fail!("Can't export team totals: {}.", err);
+                }
+            } else {
+                print_team(&totals);
+            }
+        }
+        Command::Report {
+            budgets: true,
+            include_archived,
+            ..
+        } => print_budgets(&config, &sheet, include_archived),
+        Command::Report {
+            period,
+            estimates: true,
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            print_estimates(&config, &sheet, start, end);
+        }
+        Command::Report {
+            period,
+            by: Some(Grouping::Week),
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let mut previous = None;
+
+            for (week, total) in totals::totals_by_week(&sheet, start, end) {
+                let delta = previous
+                    .map(|previous| {
+                        let delta = total - previous;
+                        let sign = if delta > Duration::zero() { "+" } else { "" };
+                        format!(" ({}{})", sign, format_duration(delta))
+                    })
+                    .unwrap_or_default();
+
+                say!(
+                    "{}  {}{}",
+                    format!("{}-W{:02}", week.year(), week.week()),
+                    format_duration(total),
+                    delta
+                );
+                previous = Some(total);
+            }
+        }
+        Command::Report {
+            trend: Some(period),
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let months = totals::totals_by_month(&sheet, start, end);
+
+            if months.is_empty() {
+                say!(
+                    "No time worked {}.",
+                    i18n::translate(&period.to_string().to_lowercase())
+                );
+            } else {
+                let times: Vec<_> = months.iter().map(|(_, time)| *time).collect();
+                say!("{}", sparkline(&times));
+            }
+
+            let mut previous: Option<Duration> = None;
+
+            for (month, time) in months {
+                let change = previous
+                    .filter(|previous| *previous > Duration::zero())
+                    .map(|previous| {
+                        let percent = (time - previous).num_seconds() as f64
+                            / previous.num_seconds() as f64
+                            * 100.0;
+                        format!(" ({:+.0}%)", percent)
+                    })
+                    .unwrap_or_default();
+
+                say!(
+                    "{}  {}{}",
+                    month.format("%Y-%m").to_string(),
+                    format_duration(time),
+                    change
+                );
+                previous = Some(time);
+            }
+        }
+        Command::Report {
+            period,
+            by: Some(Grouping::Weekday),
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+
+            for (weekday, average, days) in totals::average_by_weekday(&sheet, start, end) {
+                let name = match weekday {
+                    Weekday::Mon => "Mondays",
+                    Weekday::Tue => "Tuesdays",
+                    Weekday::Wed => "Wednesdays",
+                    Weekday::Thu => "Thursdays",
+                    Weekday::Fri => "Fridays",
+                    Weekday::Sat => "Saturdays",
+                    Weekday::Sun => "Sundays",
                 };
 
-                println!("Punched in since {}.", start_local.format(format));
+                say!(
+                    "{}: {} on average over {} days.",
+                    i18n::translate(name),
+                    format_duration(average),
+                    days
+                );
             }
-            SheetStatus::PunchedOut(end_utc) => {
-                let end_local: DateTime<Local> = end_utc.into();
+        }
+        Command::Report {
+            period,
+            with_commits,
+            counting,
+            ..
+        } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
 
-                let format = if end_local.date() == Local::today() {
-                    SAME_DAY_FORMAT
-                } else {
-                    DIFF_DAY_FORMAT
+            // The time of each tracking period is the part of it in the period reported on, rounded
+            // the same way as the rounded total if each tracking period is rounded.
+            let rounded = config
+                .rounding
+                .round_each(sheet.events_in_range(start, end), start, end);
+
+            for (event, rounded) in sheet.events_in_range(start, end).zip(rounded) {
+                let stop = event.stop.unwrap_or_else(Utc::now);
+                let policy = config.rounding.policy_for(event.project.as_deref());
+                let length = match policy.scope {
+                    RoundingScope::Event => rounded,
+                    _ => stop.min(end) - event.start.max(start),
+                };
+                let start_local: DateTime<Local> = event.start.into();
+                let stop_local = match event.stop {
+                    Some(stop) => DateTime::<Local>::from(stop).format("%H:%M").to_string(),
+                    None => tr!("now"),
                 };
 
-                println!(
-                    "Not punched in; last punched out at {}.",
-                    end_local.format(format)
+                if let Some(template) = &config.templates.report {
+                    template::print(
+                        template,
+                        context! {
+                            date => start_local.format("%Y-%m-%d").to_string(),
+                            start => start_local.format("%H:%M").to_string(),
+                            stop => stop_local,
+                            duration => format_duration(length),
+                            duration_seconds => length.num_seconds(),
+                            project => event.project,
+                            tags => event.tags,
+                            billable => event.billable,
+                            review => event.review.to_string(),
+                            note => event.note,
+                        },
+                    );
+                } else {
+                    say!(
+                        "{}–{} ({}){}",
+                        start_local.format("%a %e %b %H:%M"),
+                        stop_local,
+                        format_duration(length),
+                        format_labels(event),
+                    );
+
+                    for line in event.note.iter().flat_map(|note| note.lines()) {
+                        say!("    {}", line);
+                    }
+                }
+
+                if let Some(repo) = &with_commits {
+                    match git::commits_between(repo, event.start, stop) {
+                        Ok(commits) => {
+                            for commit in commits {
+                                say!("    * {}", commit);
+                            }
+                        }
+                        Err(err) => {
+                            say!("    Unable to list commits: {}.", err);
+                        }
+                    }
+                }
+            }
+
+            let worked_sheet = config.on_call.worked(&sheet);
+            let deducted = deductions::by_date(&config.deductions, &worked_sheet, start, end);
+
+            for (date, time) in &deducted {
+                say!(
+                    "{}: deducted {} for a break.",
+                    date.format("%a %e %b"),
+                    format_duration(*time)
                 );
             }
-            SheetStatus::Empty => {
-                println!("Not punched in; no punch-ins recorded.");
+
+            let deducted = deducted
+                .values()
+                .fold(Duration::zero(), |acc, next| acc + *next);
+
+            if !config.rounding.is_none() {
+                let total =
+                    config
+                        .rounding
+                        .total(worked_sheet.events_in_range(start, end), start, end)
+                        - deducted;
+
+                say!(
+                    "Rounded total {}: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(total)
+                );
             }
-        },
-        Opt::Count { period } => {
-            if sheet.status() == SheetStatus::Empty {
-                println!(
-                    "Time worked {}: 0 hours, 0 minutes.",
-                    period.to_string().to_lowercase()
+
+            if counting.counting(config.counting) == Counting::Gross {
+                let worked = count_time(&config, &sheet, start, end, as_of.is_none());
+
+                say!(
+                    "Time worked {}, including breaks: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(
+                        Counting::Gross.total(&worked_sheet, worked, start, end) - deducted
+                    )
+                );
+            } else if deducted > Duration::zero() && config.rounding.is_none() {
+                // Otherwise the total after the deductions isn't shown anywhere.
+                say!(
+                    "Time worked {}: {}.",
+                    i18n::translate(&period.to_string().to_lowercase()),
+                    format_duration(
+                        count_time(&config, &sheet, start, end, as_of.is_none()) - deducted
+                    )
                 );
+            }
+
+            print_on_call(&config, &sheet, &period, start, end);
+            print_anomalies(&config, &sheet, start, end);
+        }
+        Command::Cal { month } => {
+            let first = month.unwrap_or_else(|| Local::now().date_naive().with_day(1).unwrap());
+            calendar::print(&sheet, first);
+        }
+        Command::Timeline { period } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            timeline::print(&sheet, start, end);
+        }
+        Command::Doctor {
+            accept_checksum: true,
+            ..
+        } => {
+            let path = config.storage.sheet_loc().unwrap();
+            let events = load_sheet_with(&config, Sheet::load)?.events().len();
+
+            if dry_run {
+                say!("Would record the checksum of {}.", path.display());
             } else {
-                let (start, end) = match period {
-                    Period::All => (sheet.events[0].start, Utc::now()),
-                    Period::Today => {
-                        let end_local = Local::now();
-                        let end_utc: DateTime<Utc> = end_local.into();
-                        let start_local = Local::today().and_hms(0, 0, 0);
+                match Checksum::record(&path, events) {
+                    Ok(_) => say!(
+                        "Recorded the checksum of {}, with {} tracking periods.",
+                        path.display(),
+                        events
+                    ),
+                    Err(err) => fail!("Can't record the checksum of the sheet: {}.", err),
+                }
+            }
+        }
+        Command::Doctor { period, .. } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let name = i18n::translate(&period.to_string().to_lowercase()).to_owned();
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+            let matches_checksum = check_sheet(&config, true);
 
-                        (start_utc, end_utc)
-                    }
-                    Period::Yesterday => {
-                        let end_local = Local::today().and_hms(0, 0, 0);
-                        let end_utc: DateTime<Utc> = end_local.into();
-                        let start_local = Local::today().pred().and_hms(0, 0, 0);
+            if print_anomalies(&config, &sheet, start, end) == 0 && matches_checksum {
+                say!("No problems found {}.", name);
+            }
+        }
+        Command::Edit { period, editor } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let original: Vec<Event> = sheet.events_in_range(start, end).cloned().collect();
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+            let edited = edit::edit(&original, editor, |err| {
+                say!("Can't read edited tracking periods: {}.", err);
+                prompt::confirm(&tr!("Edit again?"))
+            });
 
-                        (start_utc, end_utc)
-                    }
-                    Period::Week => {
-                        let mut last_monday = Local::today();
-                        while last_monday.weekday() != Weekday::Mon {
-                            last_monday = last_monday.pred();
-                        }
+            // The text format leaves out the user, review state, signature and calendar UID, so
+            // unchanged tracking periods keep theirs, and changed or new ones are recorded as the
+            // current user's unsigned drafts.
+            let edited = edited.map(|edited| {
+                edited
+                    .into_iter()
+                    .map(|event| {
+                        let unchanged = original.iter().find(|o| {
+                            Event {
+                                user: None,
+                                review: Review::Draft,
+                                signature: None,
+                                uid: None,
+                                ..(*o).clone()
+                            } == event
+                        });
 
-                        let start_local = last_monday.and_hms(0, 0, 0);
-                        let end_local = Local::now();
-                        let end_utc: DateTime<Utc> = end_local.into();
+                        match unchanged {
+                            Some(original) => original.clone(),
+                            None => event.with_user(Some(config.user())),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            });
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+            match edited {
+                Ok(edited) if edited == original => {
+                    say!("No changes made.");
+                }
+                Ok(edited) => {
+                    let count = edited.len();
 
-                        (start_utc, end_utc)
+                    match sheet.replace_range(start, end, edited) {
+                        Ok(replaced) => {
+                            say!(
+                                "Replaced {} tracking periods with {}.",
+                                replaced.len(),
+                                count
+                            );
+                        }
+                        Err(err) => {
+                            fail!("Can't apply changes: {}.", err);
+                        }
                     }
-                    Period::LastWeek => {
-                        let mut last_monday = Local::today();
-                        while last_monday.weekday() != Weekday::Mon {
-                            last_monday = last_monday.pred();
+                }
+                Err(err) => {
+                    fail!("Can't edit tracking periods: {}.", err);
+                }
+            }
+        }
+        Command::Submit { period } => review_period(&config, &mut sheet, period, Review::Submitted),
+        Command::Approve { period } => review_period(&config, &mut sheet, period, Review::Approved),
+        Command::Reject { period } => review_period(&config, &mut sheet, period, Review::Rejected),
+        Command::Unlock { period } => review_period(&config, &mut sheet, period, Review::Draft),
+        Command::Import { file, interactive } => match import::Table::read(&file) {
+            Ok(table) => {
+                let mut mapping = import::Mapping::guess(&table);
+
+                if interactive {
+                    match import::confirm_mapping(&table, mapping) {
+                        Some(confirmed) => mapping = confirmed,
+                        None => {
+                            say!("Import cancelled.");
+                            return Ok(());
                         }
+                    }
+                }
 
-                        let mut monday_before = last_monday.pred();
-                        while monday_before.weekday() != Weekday::Mon {
-                            monday_before = monday_before.pred();
+                match mapping.check() {
+                    Ok(()) => {
+                        let (mut events, errors) = mapping.to_events(&table);
+
+                        for event in &mut events {
+                            event.user.get_or_insert_with(|| config.user());
                         }
 
-                        let start_local = monday_before.and_hms(0, 0, 0);
-                        let end_local = last_monday.and_hms(0, 0, 0);
-                        let end_utc: DateTime<Utc> = end_local.into();
+                        if interactive {
+                            import::preview(&events, &errors);
+                        } else {
+                            for (row, err) in &errors {
+                                say!("Skipping row {}: {}.", row, err);
+                            }
+                        }
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+                        if !interactive || prompt::confirm(&tr!("Import {} events?", events.len()))
+                        {
+                            match sheet.merge(events) {
+                                Ok(added) => say!("Imported {} events.", added.len()),
+                                Err(SheetError::Overlapping { event, overlapping }) => {
+                                    fail!(
+                                        "Can't import events: {} overlaps:",
+                                        edit::to_line(&event)
+                                    );
 
-                        (start_utc, end_utc)
+                                    for event in &overlapping {
+                                        fail!("  {}", edit::to_line(event));
+                                    }
+                                }
+                                Err(err) => fail!("Can't import events: {}.", err),
+                            }
+                        }
+                    }
+                    Err(problem) => {
+                        fail!(
+                            "Can't import {}: {}. Try again with --interactive.",
+                            file.display(),
+                            problem
+                        );
                     }
-                    Period::Month => {
-                        let now = Local::now();
-                        let month_first = Local.ymd(now.year(), now.month(), 1);
+                }
+            }
+            Err(err) => {
+                fail!("Can't import {}: {}.", file.display(), err);
+            }
+        },
+        Command::MergeConflicts => {
+            let copies = Sheet::conflicted_copies(config.storage.sheet_loc().unwrap());
 
-                        let start_local = month_first.and_hms(0, 0, 0);
-                        let end_local = now;
-                        let end_utc: DateTime<Utc> = end_local.into();
+            if copies.is_empty() {
+                say!("No conflicted copies of the sheet found.");
+            }
+
+            for copy in copies {
+                match Sheet::load(&copy)
+                    .and_then(|conflicted| sheet.merge(conflicted.into_events()))
+                {
+                    Ok(added) if dry_run => say!(
+                        "Would merge {} tracking periods from {}.",
+                        added.len(),
+                        copy.display()
+                    ),
+                    Ok(added) => {
+                        // The copy is kept under another name rather than deleted, in case it
+                        // holds changes to tracking periods that the merge left out.
+                        let mut merged = copy.clone().into_os_string();
+                        merged.push(".merged");
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+                        match std::fs::rename(&copy, &merged) {
+                            Ok(()) => say!(
+                                "Merged {} tracking periods from {}.",
+                                added.len(),
+                                copy.display()
+                            ),
+                            Err(err) => fail!(
+                                "Merged {} tracking periods from {}, but can't rename it: {}.",
+                                added.len(),
+                                copy.display(),
+                                err
+                            ),
+                        }
+                    }
+                    Err(SheetError::Overlapping { event, overlapping }) => {
+                        fail!(
+                            "Can't merge {}: {} overlaps:",
+                            copy.display(),
+                            edit::to_line(&event)
+                        );
 
-                        (start_utc, end_utc)
+                        for event in &overlapping {
+                            fail!("  {}", edit::to_line(event));
+                        }
                     }
-                    Period::LastMonth => {
-                        let today = Local::today();
-                        let month_first = Local.ymd(today.year(), today.month(), 1);
+                    Err(err) => fail!("Can't merge {}: {}.", copy.display(), err),
+                }
+            }
+        }
+        Command::Delete { filter, all } => {
+            let filter = filter.to_filter();
+
+            if filter == Filter::default() && !all {
+                fail!(
+                    "Can't delete tracking periods: no filter given. Use --all to delete all of \
+                     them."
+                );
+                return Ok(());
+            }
+
+            if !confirm_bulk(&sheet, &filter, &tr!("Delete them?"), dry_run || yes) {
+                return Ok(());
+            }
 
-                        let day_before = month_first - Duration::days(1);
-                        let last_month_first = Local.ymd(day_before.year(), day_before.month(), 1);
+            match sheet.remove_matching(&filter) {
+                Ok(removed) if !dry_run => say!("Deleted {} tracking periods.", removed.len()),
+                Ok(_) => {}
+                Err(err) => fail!("Can't delete tracking periods: {}.", err),
+            }
+        }
+        Command::Retag {
+            add_tag,
+            remove_tag,
+            filter,
+        } => {
+            let filter = filter.to_filter();
 
-                        let start_local = last_month_first.and_hms(0, 0, 0);
-                        let end_local = month_first.and_hms(0, 0, 0);
-                        let end_utc: DateTime<Utc> = end_local.into();
+            if !confirm_bulk(&sheet, &filter, &tr!("Retag them?"), dry_run || yes) {
+                return Ok(());
+            }
 
-                        let span = end_local - start_local;
-                        let start_utc = end_utc - span;
+            let retagged = sheet.modify_matching(&filter, |event| {
+                event.tags.retain(|tag| !remove_tag.contains(tag));
 
-                        (start_utc, end_utc)
+                for tag in &add_tag {
+                    if !event.tags.contains(tag) {
+                        event.tags.push(tag.clone());
                     }
-                };
+                }
+            });
+
+            match retagged {
+                Ok(retagged) if !dry_run => say!("Retagged {} tracking periods.", retagged),
+                Ok(_) => {}
+                Err(err) => fail!("Can't retag tracking periods: {}.", err),
+            }
+        }
+        Command::Tag(Tag::Rename { old, new })
+            if sheet.events().iter().any(|event| event.tags.contains(&new)) =>
+        {
+            fail!(
+                "Can't rename #{} to #{}, as #{} is already used. Use `punch tag merge` instead.",
+                old,
+                new,
+                new
+            );
+        }
+        Command::Tag(
+            Tag::Rename { old, new }
+            | Tag::Merge {
+                from: old,
+                into: new,
+            },
+        ) => {
+            let filter = Filter {
+                tag: Some(old.clone()),
+                ..Filter::default()
+            };
+
+            let replaced = sheet.modify_matching(&filter, |event| {
+                event.tags.retain(|tag| *tag != old);
+
+                if !event.tags.contains(&new) {
+                    event.tags.push(new.clone());
+                }
+            });
+
+            match replaced {
+                Ok(0) => say!("No tracking periods are tagged #{}.", old),
+                Ok(replaced) if !dry_run => say!(
+                    "Replaced #{} with #{} on {} tracking periods.",
+                    old,
+                    new,
+                    replaced
+                ),
+                Ok(_) => {}
+                Err(err) => fail!("Can't retag tracking periods: {}.", err),
+            }
+        }
+        Command::Project(Project::Rename {
+            old,
+            new,
+            merge: false,
+        }) if sheet
+            .events()
+            .iter()
+            .any(|event| event.project.as_ref() == Some(&new))
+            || config.has_project_settings(&new) =>
+        {
+            fail!(
+                "Can't rename {} to {}, as {} already exists. Use --merge to merge them.",
+                old,
+                new,
+                new
+            );
+        }
+        Command::Project(Project::Rename { old, new, .. }) => {
+            let filter = Filter {
+                project: Some(old.clone()),
+                ..Filter::default()
+            };
 
-                let total = sheet.count_range(start, end);
+            // The tracking periods are renamed first, so that the config is left alone if any of
+            // them can't be. The config is only written after the sheet.
+            let renamed = match sheet.modify_matching(&filter, |event| {
+                event.project = Some(new.clone());
+            }) {
+                Ok(renamed) => renamed,
+                Err(err) => {
+                    fail!("Can't rename the project: {}.", err);
+                    return Ok(());
+                }
+            };
+
+            match edited_config(|toml| config::rename_project(toml, &old, &new)) {
+                Ok(None) if renamed == 0 => say!("Nothing refers to {}.", old),
+                Ok(None) => {}
+                Ok(Some(_)) if dry_run => say!("Would rename {} to {} in the config.", old, new),
+                Ok(Some((path, edited))) => {
+                    let message = tr!("Renamed {} to {} in the config.", old, new);
+                    config_edit = Some((path, edited, message));
+                }
+                Err(err) => {
+                    fail!("Can't rename the project in the config: {}.", err);
+                    return Ok(());
+                }
+            }
 
-                println!(
-                    "Time worked {}: {} hours, {} minutes.",
-                    period.to_string().to_lowercase(),
-                    total.num_hours(),
-                    total.num_minutes() - total.num_hours() * 60,
+            if renamed > 0 && !dry_run {
+                say!(
+                    "Renamed {} to {} on {} tracking periods.",
+                    old,
+                    new,
+                    renamed
                 );
             }
         }
-    }
+        Command::Project(Project::Archive { project }) => {
+            match edit_config(dry_run, |toml| config::set_archived(toml, &project, true)) {
+                Ok(false) => say!("{} is already archived.", project),
+                Ok(true) if dry_run => say!("Would archive {}.", project),
+                Ok(true) => say!("Archived {}.", project),
+                Err(err) => fail!("Can't archive the project: {}.", err),
+            }
+        }
+        Command::Project(Project::Unarchive { project }) => {
+            match edit_config(dry_run, |toml| config::set_archived(toml, &project, false)) {
+                Ok(false) => say!("{} isn't archived.", project),
+                Ok(true) if dry_run => say!("Would unarchive {}.", project),
+                Ok(true) => say!("Unarchived {}.", project),
+                Err(err) => fail!("Can't unarchive the project: {}.", err),
+            }
+        }
+        Command::Project(Project::List { include_archived }) => {
+            let projects: BTreeSet<&str> = sheet
+                .events()
+                .iter()
+                .filter_map(|event| event.project.as_deref())
+                .collect();
 
-    // Try to write the sheet to the default location. If loading fails due to a missing directory,
-    // create the directory.
-    sheet
-        .write_default()
-        .or_else(|err| match err {
-            SheetError::WriteSheet(io_err) if io_err.raw_os_error() == Some(2) => {
-                let dd = ProjectDirs::from("dev", "neros", "PunchClock")
-                    .expect("Unable to locate data directory for punch-clock.")
-                    .data_dir()
-                    .to_owned();
+            for project in projects {
+                if !config.projects.is_archived(project) {
+                    println!("{}", project);
+                } else if include_archived {
+                    println!("{} ({})", project, tr!("archived"));
+                }
+            }
+        }
+        Command::Shift { by, filter } => {
+            let filter = filter.to_filter();
 
-                std::fs::create_dir(dd).expect("Unable to create data directory for punch-clock.");
-                sheet.write_default()
+            if !confirm_bulk(&sheet, &filter, &tr!("Shift them?"), dry_run || yes) {
+                return Ok(());
             }
-            _ => Err(err),
-        })
-        .unwrap();
+
+            let shifted = sheet.modify_matching(&filter, |event| {
+                event.start += by;
+                event.stop = event.stop.map(|stop| stop + by);
+            });
+
+            match shifted {
+                Ok(shifted) if !dry_run => say!("Shifted {} tracking periods.", shifted),
+                Ok(_) => {}
+                Err(err) => fail!("Can't shift tracking periods: {}.", err),
+            }
+        }
+        Command::MigrateTz {
+            assume_recorded_in: zone,
+            filter,
+        } => {
+            let filter = filter.to_filter();
+            let zone_name = i18n::translate(&zone.to_string()).to_owned();
+            let question = tr!("Reinterpret their times as recorded in {}?", zone_name);
+
+            if !confirm_bulk(&sheet, &filter, &question, dry_run || yes) {
+                return Ok(());
+            }
+
+            let original = sheet.clone();
+
+            // The times were stored as UTC, so their UTC date and time is what the clock showed.
+            let migrated = sheet.modify_matching(&filter, |event| {
+                event.start = zone.to_utc(event.start.naive_utc());
+                event.stop = event.stop.map(|stop| zone.to_utc(stop.naive_utc()));
+            });
+
+            match migrated {
+                Ok(_) if dry_run => {}
+                Ok(migrated) => {
+                    let path = config.storage.sheet_loc().unwrap();
+                    let mut backup = path.clone().into_os_string();
+                    backup.push(".bak");
+                    let backup = PathBuf::from(backup);
+
+                    if let Err(err) = std::fs::copy(&path, &backup) {
+                        fail!("Can't back up the sheet to {}: {}.", backup.display(), err);
+                        return Ok(());
+                    }
+
+                    say!("Backed up the sheet to {}.", backup.display());
+
+                    if let Some(change) = Change::between(Operation::MigrateTz, &original, &sheet) {
+                        print_events_changed(&change);
+                    }
+
+                    say!("Migrated {} tracking periods.", migrated);
+                }
+                Err(err) => fail!("Can't migrate tracking periods: {}.", err),
+            }
+        }
+        Command::Export {
+            profile: Some(name),
+            ..
+        } if !config.export.profiles.contains_key(&name) => {
+            fail!("No export profile named {} in the config.", name);
+        }
+        Command::Export {
+            format,
+            aggregate: Some(_),
+            ..
+        } if !matches!(format, export::Format::Csv | export::Format::Json) => {
+            fail!("Can't export totals: they can only be exported as CSV or JSON.");
+        }
+        Command::Export {
+            format,
+            profile,
+            aggregate,
+            by,
+            output,
+            filter,
+        } => {
+            let events = filter.to_filter().apply(sheet.events());
+            let profile = profile.map(|name| &config.export.profiles[&name]);
+
+            let write = |out: &mut dyn io::Write| match (profile, aggregate) {
+                (Some(profile), _) => {
+                    export::export_profile(&events, profile, &config.projects, out)
+                }
+                (None, Some(aggregate)) => {
+                    export::export_aggregate(&events, format, aggregate, by.is_some(), out)
+                }
+                (None, None) => export::export(&events, format, &config, out),
+            };
+
+            let result = match &output {
+                Some(path) => File::create(path)
+                    .map_err(Into::into)
+                    .and_then(|mut file| write(&mut file)),
+                None => write(&mut io::stdout().lock()),
+            };
+
+            if let Err(err) = result {
+                fail!("Can't export tracking periods: {}.", err);
+            }
+        }
+        Command::Verify {
+            print_key: true, ..
+        } => match signing::key_loc().and_then(signing::load) {
+            Ok(key) => println!("{}", signing::public_key(&key)),
+            Err(err) => fail!("Can't load the signing key: {}.", err),
+        },
+        Command::Verify {
+            period, public_key, ..
+        } => {
+            let key = match public_key {
+                Some(hex) => signing::parse_public_key(&hex),
+                None => signing::key_loc()
+                    .and_then(signing::load)
+                    .map(|key| key.verifying_key()),
+            };
+
+            let key = match key {
+                Ok(key) => key,
+                Err(err) => {
+                    fail!("Can't load the key to check signatures with: {}.", err);
+                    return Ok(());
+                }
+            };
+
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let (mut valid, mut invalid, mut unsigned) = (0, 0, 0);
+
+            for event in sheet.events_in_range(start, end) {
+                match signing::verify(event, &key) {
+                    Verification::Valid => valid += 1,
+                    Verification::Invalid => {
+                        say!("Changed since it was signed: {}", edit::to_line(event));
+                        invalid += 1;
+                    }
+                    // Ongoing tracking periods are only signed once they're finished.
+                    Verification::Unsigned if event.stop.is_none() => {}
+                    Verification::Unsigned => unsigned += 1,
+                }
+            }
+
+            say!("{} tracking periods have valid signatures.", valid);
+
+            if unsigned > 0 {
+                say!("{} tracking periods aren't signed.", unsigned);
+            }
+
+            if invalid > 0 {
+                fail!(
+                    "{} tracking periods have been changed since they were signed.",
+                    invalid
+                );
+            }
+        }
+        Command::Dump { output } => {
+            let locations = backup_locations(&config);
+            let result = match &output {
+                _ if dry_run => backup::dump(&locations, io::sink()),
+                Some(path) => File::create(path)
+                    .map_err(BackupError::WriteBackup)
+                    .and_then(|file| backup::dump(&locations, file)),
+                None => backup::dump(&locations, io::stdout().lock()),
+            };
+
+            match (result, &output) {
+                (Ok(included), _) if dry_run => {
+                    for path in included {
+                        say!("Would back up {}.", path.display());
+                    }
+                }
+                (Ok(included), Some(path)) => {
+                    say!("Backed up {} files to {}.", included.len(), path.display())
+                }
+                (Ok(_), None) => {}
+                (Err(err), _) => fail!("Can't write the backup: {}.", err),
+            }
+        }
+        Command::Restore { .. } => unreachable!("restored before loading the sheet"),
+        Command::Repair => unreachable!("repaired before loading the sheet"),
+        Command::Serve {
+            ical: false,
+            graphql: false,
+            ..
+        } => {
+            fail!(
+                "Nothing to serve: use --ical to serve an iCalendar feed, or --graphql to serve a \
+                 GraphQL API."
+            );
+        }
+        #[cfg(not(feature = "graphql"))]
+        Command::Serve { graphql: true, .. } => {
+            fail!("Can't serve a GraphQL API: punch was built without the graphql feature.");
+        }
+        Command::Serve {
+            ical,
+            graphql,
+            allow_origin,
+            address,
+        } => {
+            let services = serve::Services {
+                ical,
+                graphql,
+                allow_origin,
+            };
+
+            if let Err(err) = serve::serve(&config, &address, &services) {
+                fail!("Can't serve on {}: {}.", address, err);
+            }
+        }
+        Command::Schema => {
+            print!("{}", punch_clock::sheet::SCHEMA);
+        }
+        Command::Shell => {
+            say!("Already in the shell.");
+        }
+        Command::Search {
+            query,
+            regex,
+            filter,
+        } => {
+            let query = match regex {
+                true => Query::regex(&query),
+                false => Ok(Query::words(&query)),
+            };
+
+            match query {
+                Ok(query) => {
+                    let events = filter.to_filter().apply(sheet.events());
+                    let found = search::search(&events, &query);
+                    let color =
+                        io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+                    for &index in &found {
+                        print_search_hit(&events[index], &query, color);
+                    }
+
+                    say!("Found {} tracking periods.", found.len());
+                }
+                Err(err) => fail!("Can't search: {}.", err),
+            }
+        }
+        Command::Completions { shell } => {
+            completions::print(shell);
+        }
+        Command::Complete { names } => {
+            for name in completions::names(&config, &sheet, &names) {
+                println!("{}", name);
+            }
+        }
+        Command::Hook(Hook::InstallGit { repo, .. }) if dry_run => {
+            say!("Would install a post-commit hook in {}.", repo.display());
+        }
+        Command::Hook(Hook::InstallGit { repo, force }) => match git::install_hook(&repo, force) {
+            Ok(hook) => {
+                say!("Installed post-commit hook at {}.", hook.display());
+            }
+            Err(err) => {
+                fail!("Can't install post-commit hook: {}.", err);
+            }
+        },
+        Command::Push(Push::Gsheets {
+            spreadsheet,
+            sheet: name,
+            daily,
+            period,
+        }) => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let mut events: Vec<Event> = sheet.events_in_range(start, end).cloned().collect();
+            let rows = if daily {
+                // Only count the time within the period towards each day's total.
+                for event in &mut events {
+                    event.start = event.start.max(start);
+                    event.stop = event.stop.map(|stop| stop.min(end));
+                }
+
+                gsheets::Rows::DailyTotals
+            } else {
+                gsheets::Rows::Events
+            };
+
+            if dry_run {
+                say!(
+                    "Would push {} rows to Google Sheets, skipping any already there.",
+                    gsheets::count(&events, rows)
+                );
+            } else {
+                match gsheets::push(&config.push.gsheets, &events, rows, &spreadsheet, &name) {
+                    Ok((pushed, skipped)) => {
+                        say!(
+                            "Pushed {} rows to Google Sheets, skipping {} already there.",
+                            pushed,
+                            skipped
+                        );
+                    }
+                    Err(err) => {
+                        fail!("Can't push to Google Sheets: {}.", err);
+                    }
+                }
+            }
+        }
+        Command::Push(Push::Kimai { period }) => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let events: Vec<Event> = sheet.events_in_range(start, end).cloned().collect();
+
+            if dry_run {
+                say!(
+                    "Would push {} tracking periods to Kimai, skipping any already there.",
+                    events.iter().filter(|event| event.stop.is_some()).count()
+                );
+            } else {
+                match kimai::push(&config.push.kimai, &events, start, end) {
+                    Ok(pushed) => {
+                        say!(
+                            "Pushed {} tracking periods to Kimai, skipping {} already there.",
+                            pushed.added,
+                            pushed.skipped
+                        );
+
+                        for project in pushed.unknown_projects {
+                            let project = match project.as_str() {
+                                "" => tr!("(no project)"),
+                                _ => project,
+                            };
+
+                            fail!(
+                                "Can't push tracking periods on {} to Kimai: no project by that \
+                                 name.",
+                                project
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        fail!("Can't push to Kimai: {}.", err);
+                    }
+                }
+            }
+        }
+        Command::Sync(Sync::Caldav { period }) => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+            let events: Vec<Event> = sheet.events_in_range(start, end).cloned().collect();
+
+            if dry_run {
+                say!(
+                    "Would push {} tracking periods to the calendar.",
+                    events.iter().filter(|event| event.stop.is_some()).count()
+                );
+            } else {
+                match caldav::push(&config.push.caldav, &events) {
+                    Ok(pushed) => say!("Pushed {} tracking periods to the calendar.", pushed),
+                    Err(err) => fail!("Can't push to the calendar: {}.", err),
+                }
+            }
+
+            match caldav::pull(&config.push.caldav, start, end) {
+                Ok(Some(pulled)) => {
+                    // Events pushed from this sheet, or from another user's, are already tracked,
+                    // as are those pulled before, even if they've been changed since.
+                    let known: HashSet<String> = sheet
+                        .events()
+                        .iter()
+                        .flat_map(|event| [Some(ical::uid(event)), event.uid.clone()])
+                        .flatten()
+                        .collect();
+                    let now = Utc::now();
+                    let events: Vec<Event> = pulled
+                        .into_iter()
+                        .filter(|(uid, _)| !known.contains(uid))
+                        .map(|(uid, mut event)| {
+                            event.user.get_or_insert_with(|| config.user());
+                            event.uid = Some(uid);
+                            event
+                        })
+                        .filter(|event| {
+                            event.start >= start
+                                && event.stop.is_some_and(|stop| stop <= end.min(now))
+                        })
+                        .collect();
+
+                    match sheet.merge(events) {
+                        Ok(added) => say!("Pulled {} events from the calendar.", added.len()),
+                        Err(err) => fail!("Can't pull events from the calendar: {}.", err),
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => fail!("Can't pull from the calendar: {}.", err),
+            }
+        }
+        Command::Status {
+            format: Some(StatusFormat::Waybar),
+        } => {
+            let summary = sheet.summary(Local::now());
+            println!("{}", waybar_status(&summary));
+        }
+        Command::Status {
+            format: Some(StatusFormat::Tmux),
+        } => {
+            let summary = sheet.summary(Local::now());
+            println!("{}", tmux_status(&summary));
+        }
+        Command::Status {
+            format: Some(StatusFormat::Starship),
+        } => match prompt_status(&sheet) {
+            Some(status) => println!("{}", status),
+            None => output::set_failed(),
+        },
+        Command::Status { format: None } => {
+            let summary = sheet.summary(Local::now());
+
+            match &config.templates.status {
+                Some(template) => template::print(template, status_variables(&summary)),
+                None => print_status(&summary),
+            }
+
+            for project in config.projects.budgets.keys() {
+                if !config.projects.is_archived(project) {
+                    warn_budget(&config, &sheet, project);
+                }
+            }
+        }
+        Command::Convert { format } => {
+            // With `storage.format` set, the sheet may not have been converted to that format
+            // yet, in which case it's converted from the file it's still in.
+            let configured = config.storage.sheet_loc().unwrap();
+            let old_path = if configured.exists() {
+                configured
+            } else {
+                Sheet::default_loc().unwrap_or(configured)
+            };
+            let new_path = old_path.with_file_name(format.file_name());
+
+            if new_path == old_path {
+                say!("The sheet is already stored as {}.", old_path.display());
+                return Ok(());
+            }
+
+            if dry_run {
+                say!("Would convert the sheet to {}.", new_path.display());
+                return Ok(());
+            }
+
+            say!(
+                "This will write the sheet to {} and remove {}.",
+                new_path.display(),
+                old_path.display()
+            );
+
+            if !prompt::confirm_destructive(&tr!("Convert the sheet?"), yes) {
+                say!("Conversion cancelled.");
+                return Ok(());
+            }
+
+            // The sheet loaded above is only the current user's view of it, from the configured
+            // file.
+            let whole = match Sheet::load(&old_path) {
+                Ok(sheet) => sheet,
+                Err(SheetError::OpenSheet(err)) if err.kind() == io::ErrorKind::NotFound => {
+                    Sheet::default()
+                }
+                Err(err) => return Err(CliError::LoadSheet(old_path, err)),
+            };
+            write_sheet_to(&new_path, &whole)?;
+
+            if let Err(err) = std::fs::remove_file(&old_path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    fail!("Can't remove {}: {}.", old_path.display(), err);
+                }
+            }
+
+            if let Err(err) = Checksum::remove(&old_path) {
+                warn!("Unable to remove the checksum of the old sheet: {}", err);
+            }
+
+            say!("Converted the sheet to {}.", new_path.display());
+
+            if config
+                .storage
+                .format
+                .is_some_and(|configured| configured != format)
+            {
+                say!("Note: the config sets `storage.format`, which should be updated to match.");
+            }
+
+            return Ok(());
+        }
+        Command::History { period } => {
+            let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+
+            match load_history(&config) {
+                Ok(history) => {
+                    let changes: Vec<_> = history
+                        .changes
+                        .iter()
+                        .filter(|change| change.time >= start && change.time <= end)
+                        .collect();
+
+                    if changes.is_empty() {
+                        say!("No changes recorded.");
+                    }
+
+                    for change in changes {
+                        let time_local: DateTime<Local> = change.time.into();
+
+                        say!(
+                            "{} by {}: {}",
+                            time_local.format("%Y-%m-%d %H:%M:%S"),
+                            change.user,
+                            i18n::translate(&change.operation.to_string())
+                        );
+                        print_events_changed(change);
+                    }
+                }
+                Err(err) => {
+                    fail!("Can't read history: {}.", err);
+                }
+            }
+        }
+        Command::Diff { file, reverse } => {
+            let other = match backup::is_backup(&file) {
+                true => backup::read_sheet(&backup_locations(&config), &file)
+                    .map_err(|err| err.to_string()),
+                false => Sheet::load(&file).map_err(|err| err.to_string()),
+            };
+
+            match other {
+                Ok(other) if reverse => print_diff(&Diff::between(&sheet, &other)),
+                Ok(other) => print_diff(&Diff::between(&other, &sheet)),
+                Err(err) => fail!("Can't read {}: {}.", file.display(), err),
+            }
+        }
+        Command::Snapshot(Snapshot::Create { name, force }) => {
+            let sheet_path = config.storage.sheet_loc().unwrap();
+
+            if dry_run {
+                say!("Would take snapshot {}.", name);
+            } else {
+                match snapshot::Snapshot::create(&sheet_path, &name, force) {
+                    Ok(_) => say!("Took snapshot {}.", name),
+                    Err(SnapshotError::Exists(_)) => fail!(
+                        "A snapshot named {} already exists. Use --force to replace it.",
+                        name
+                    ),
+                    Err(err) => fail!("Can't take snapshot {}: {}.", name, err),
+                }
+            }
+        }
+        Command::Snapshot(Snapshot::List) => {
+            match snapshot::Snapshot::list(config.storage.sheet_loc().unwrap()) {
+                Ok(snapshots) if snapshots.is_empty() => say!("No snapshots taken."),
+                Ok(snapshots) => {
+                    for snapshot in snapshots {
+                        let taken = format_local(snapshot.created, "%Y-%m-%d %H:%M");
+
+                        match snapshot.load() {
+                            Ok(loaded) => say!(
+                                "{}: taken {}, {} tracking periods",
+                                snapshot.name,
+                                taken,
+                                loaded.events().len()
+                            ),
+                            Err(err) => say!("{}: taken {}, {}", snapshot.name, taken, err),
+                        }
+                    }
+                }
+                Err(err) => fail!("Can't list snapshots: {}.", err),
+            }
+        }
+        Command::Snapshot(Snapshot::Restore { name }) => {
+            let found = snapshot::Snapshot::find(config.storage.sheet_loc().unwrap(), &name)
+                .and_then(|snapshot| snapshot.load().map(|loaded| (snapshot, loaded)));
+
+            match found {
+                Ok((snapshot, loaded)) => {
+                    say!(
+                        "Restoring the sheet as it was when snapshot {} was taken, {}.",
+                        name,
+                        format_local(snapshot.created, "%Y-%m-%d %H:%M")
+                    );
+                    sheet.set_events(loaded.into_events());
+                }
+                Err(err) => fail!("Can't restore snapshot {}: {}.", name, err),
+            }
+        }
+        Command::Snapshot(Snapshot::Delete { name }) => {
+            match snapshot::Snapshot::find(config.storage.sheet_loc().unwrap(), &name) {
+                Ok(_) if dry_run => say!("Would delete snapshot {}.", name),
+                Ok(snapshot) => match snapshot.delete() {
+                    Ok(()) => say!("Deleted snapshot {}.", name),
+                    Err(err) => fail!("Can't delete snapshot {}: {}.", name, err),
+                },
+                Err(err) => fail!("Can't delete snapshot {}: {}.", name, err),
+            }
+        }
+        Command::Undo { steps } => changes = step_history(&config, &mut sheet, steps, false),
+        Command::Redo { steps } => changes = step_history(&config, &mut sheet, steps, true),
+        Command::Compact => {
+            let sheet_path = config.storage.sheet_loc().unwrap();
+            let path = DailyTotals::loc_for(&sheet_path);
+            let archive_path = sheet_path.with_file_name("archive.json");
+            let cutoff = config
+                .storage
+                .retention
+                .as_ref()
+                .map(|retention| retention.cutoff(Local::now()));
+            let purged = match cutoff {
+                Some(cutoff) => sheet.remove_finished_before(cutoff),
+                None => Vec::new(),
+            };
+            // Submitted and approved tracking periods stay on the sheet however old they are.
+            let kept: Vec<Event> = sheet
+                .events()
+                .iter()
+                .filter(|event| {
+                    event
+                        .stop
+                        .zip(cutoff)
+                        .is_some_and(|(stop, cutoff)| stop <= cutoff)
+                })
+                .cloned()
+                .collect();
+            let print_kept = || {
+                say!(
+                    "Keeping {} submitted or approved tracking periods older than {}, as they \
+                     can't be changed until they're unlocked:",
+                    kept.len(),
+                    config.storage.retention.as_ref().unwrap()
+                );
+
+                for event in &kept {
+                    say!(
+                        "{}{}",
+                        format_local(event.start, "%Y-%m-%d %H:%M"),
+                        format_labels(event)
+                    );
+                }
+            };
+
+            if dry_run {
+                if !purged.is_empty() {
+                    say!(
+                        "Would move {} tracking periods to {}.",
+                        purged.len(),
+                        archive_path.display()
+                    );
+                }
+
+                if !kept.is_empty() {
+                    print_kept();
+                }
+
+                say!("Would rewrite the sheet and rebuild the daily totals cache.");
+            } else {
+                say!(
+                    "This will rewrite {} ({} tracking periods) and rebuild {}.",
+                    sheet_path.display(),
+                    sheet.events().len(),
+                    path.display()
+                );
+
+                if !purged.is_empty() {
+                    say!(
+                        "{} tracking periods older than {} will be moved to {}.",
+                        purged.len(),
+                        config.storage.retention.as_ref().unwrap(),
+                        archive_path.display()
+                    );
+                }
+
+                if !kept.is_empty() {
+                    print_kept();
+                }
+
+                if !prompt::confirm_destructive(&tr!("Compact the sheet?"), yes) {
+                    say!("Compaction cancelled.");
+                    return Ok(());
+                }
+
+                if !purged.is_empty() {
+                    let mut archive = if archive_path.exists() {
+                        match Sheet::load(&archive_path) {
+                            Ok(archive) => archive,
+                            Err(err) => {
+                                fail!("Can't read the archive: {}.", err);
+                                return Ok(());
+                            }
+                        }
+                    } else {
+                        Sheet::default()
+                    };
+
+                    if let Err(err) = archive
+                        .merge(purged.clone())
+                        .and_then(|_| archive.write(&archive_path))
+                    {
+                        fail!("Can't write the archive: {}.", err);
+                        return Ok(());
+                    }
+
+                    let first = purged.iter().map(|event| event.start).min().unwrap();
+                    let last = purged.iter().filter_map(|event| event.stop).max().unwrap();
+                    let mut projects: BTreeMap<Option<String>, Duration> = BTreeMap::new();
+
+                    for event in &purged {
+                        *projects.entry(event.project.clone()).or_default() +=
+                            event.stop.unwrap() - event.start;
+                    }
+
+                    say!(
+                        "Moved {} tracking periods, from {} through {}, to {}.",
+                        purged.len(),
+                        first.with_timezone(&Local).format("%Y-%m-%d"),
+                        last.with_timezone(&Local).format("%Y-%m-%d"),
+                        archive_path.display()
+                    );
+
+                    for (project, time) in &projects {
+                        let project = project.clone().unwrap_or_else(|| tr!("(no project)"));
+                        say!("{}: {}.", project, format_duration(*time));
+                    }
+                }
+
+                let mut totals = DailyTotals::default();
+                totals.update(&sheet);
+
+                match totals.write(&path) {
+                    Ok(()) => say!("Rewrote the sheet and rebuilt the daily totals cache."),
+                    Err(err) => fail!("Can't write daily totals cache: {}.", err),
+                }
+            }
+        }
+        #[cfg(feature = "scripting")]
+        Command::Script { path, args } => {
+            if let Err(err) = script::run(&path, args, &config, &sheet) {
+                fail!("Can't run script {}: {}.", path.display(), err);
+            }
+        }
+        Command::Nag => {
+            let now = Local::now();
+            let today = now.date_naive();
+            let (work_start, work_end) = config.schedule.working_hours.on(today);
+            let working = config.schedule.expected_on(today) > Duration::zero()
+                && (work_start..work_end).contains(&now.with_timezone(&Utc));
+
+            match sheet.status() {
+                SheetStatus::PunchedIn(start) if start < Period::Today.range(now).0 => {
+                    fail!(
+                        "Still punched in since {}. Did you forget to punch out?",
+                        format_time(start)
+                    );
+                }
+                SheetStatus::PunchedIn(start)
+                    if now.with_timezone(&Utc) - start > config.sessions.stale_after() =>
+                {
+                    fail!(
+                        "Punched in for {}, since {}. Did you forget to punch out?",
+                        format_duration(now.with_timezone(&Utc) - start),
+                        format_time(start)
+                    );
+                }
+                SheetStatus::PunchedIn(_) => {}
+                _ if working => fail!("Not punched in, though it's working hours."),
+                _ => {}
+            }
+        }
+        Command::Watch => watch::watch(&config),
+        Command::Service(Service::Install) => service::install(&config),
+        Command::Service(Service::Uninstall) => service::uninstall(),
+        Command::Service(Service::Run) => service::run(config.clone()),
+    }
+
+    // Signing comes before the change is worked out, so that the history has the signatures too.
+    if config.signing.enabled && !dry_run {
+        sign_changed(&mut sheet, before.as_ref(), &mut changes);
+    }
+
+    if let (Some(operation), Some(before)) = (operation, before) {
+        changes.extend(Change::between(operation, &before, &sheet));
+    }
+
+    if dry_run {
+        if changes_sheet && changes.is_empty() {
+            say!("Dry run: the sheet would be left unchanged.");
+        }
+
+        for change in &changes {
+            say!(
+                "Dry run: {} would make these changes:",
+                i18n::translate(&change.operation.to_string())
+            );
+            print_events_changed(change);
+        }
+
+        return Ok(());
+    }
+
+    let tailed = tail.is_some();
+
+    if changes_sheet || !changes.is_empty() {
+        let written = match tail {
+            Some(loaded) => write_sheet_tail(&config, &sheet, &loaded)?,
+            None => write_sheet(&config, &sheet)?,
+        };
+
+        if !written {
+            return Ok(());
+        }
+    }
+
+    if let Some((path, edited, message)) = config_edit {
+        match write_config(&path, edited) {
+            Ok(()) => say!("{}", message),
+            Err(err) => fail!("Can't write the config: {}.", err),
+        }
+    }
+
+    for change in &changes {
+        record_change(&config, change);
+    }
+
+    if !changes.is_empty() && config.homeassistant.host.is_some() {
+        // Only the last tracking period is read from the tail of the sheet, which isn't enough to
+        // count the time worked today.
+        let sheet = if tailed { load_sheet(&config)? } else { sheet };
+
+        publish_status(&config, &sheet);
+    }
+
+    Ok(())
+}
+
+/// Publish the status to Home Assistant, if a broker is configured.
+fn publish_status(config: &Config, sheet: &Sheet) {
+    let Some(host) = &config.homeassistant.host else {
+        return;
+    };
+
+    let summary = sheet.summary(Local::now());
+
+    if let Err(err) = homeassistant::publish(&config.homeassistant, host, &summary) {
+        warn!("Unable to publish status to Home Assistant: {}", err);
+    }
+}
+
+/// Punch out of the ongoing tracking period if it has lasted longer than
+/// `sessions.auto_close_after` hours, returning the change made to the sheet, if any.
+fn auto_close(config: &Config, sheet: &mut Sheet) -> Option<Change> {
+    let hours = config.sessions.auto_close_after?;
+    let cap = config.sessions.auto_close_after()?;
+    let open = sheet.clone();
+    let note = format!("auto-closed after {}h cap", hours);
+    let stop_utc = sheet.auto_close(cap, Utc::now(), &note)?;
+
+    say!(
+        "Punched out automatically at {}, after {} punched in.",
+        format_time(stop_utc),
+        format_duration(cap)
+    );
+
+    Change::between(Operation::AutoClose, &open, sheet)
+}
+
+/// Split the ongoing tracking period at each local midnight it has run past, returning the change
+/// made to the sheet, if any.
+fn split_at_midnights(config: &Config, sheet: &mut Sheet) -> Option<Change> {
+    let unsplit = sheet.clone();
+    let splits = sheet.split_at_midnights(Utc::now(), &config.sessions.midnight_tag);
+
+    if splits == 0 {
+        return None;
+    }
+
+    say!(
+        "Split the ongoing tracking period at midnight into {} days.",
+        splits + 1
+    );
+
+    Change::between(Operation::SplitAtMidnight, &unsplit, sheet)
+}
+
+/// Draw a sparkline of the given lengths of time, with one bar for each, scaled to the longest.
+fn sparkline(times: &[Duration]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = times.iter().max().copied().unwrap_or_else(Duration::zero);
+
+    times
+        .iter()
+        .map(|time| match max.num_seconds() {
+            0 => BARS[0],
+            max => BARS[(time.num_seconds() * 7 / max) as usize],
+        })
+        .collect()
+}
+
+/// Count the time worked between the two given instants, rounded as configured, using the cache
+/// of daily totals if `cached` is set.
+fn count_time(
+    config: &Config,
+    sheet: &Sheet,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    cached: bool,
+) -> Duration {
+    if config.rounding.is_none() {
+        worked_time(config, sheet, start, end, cached)
+    } else {
+        config.rounding.total(
+            config.on_call.worked(sheet).events_in_range(start, end),
+            start,
+            end,
+        )
+    }
+}
+
+/// Count the time worked between the two given instants, leaving out time spent on call without
+/// responding to an incident.
+///
+/// The cache of daily totals is only used if `cached` is set and there's no such time, since it
+/// counts every tracking period of the sheet as it is now. It's left alone when looking at the
+/// sheet as it was in the past.
+fn worked_time(
+    config: &Config,
+    sheet: &Sheet,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    cached: bool,
+) -> Duration {
+    match config.on_call.worked(sheet) {
+        Cow::Borrowed(sheet) if cached => {
+            daily_totals(config, sheet).count_range(sheet, start, end)
+        }
+        worked => worked.count_range(start, end),
+    }
+}
+
+/// Print how much time was spent on call in the given period, if any was, and how much of it was
+/// spent responding to incidents.
+fn print_on_call(
+    config: &Config,
+    sheet: &Sheet,
+    period: &Period,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) {
+    let (on_call, incidents) = config.on_call.count_range(sheet, start, end);
+    let name = i18n::translate(&period.to_string().to_lowercase()).to_owned();
+
+    if incidents > Duration::zero() {
+        say!(
+            "Time on call {}: {}, of which {} was spent on incidents and counted as worked.",
+            name,
+            format_duration(on_call),
+            format_duration(incidents)
+        );
+    } else if on_call > Duration::zero() {
+        say!("Time on call {}: {}.", name, format_duration(on_call));
+    }
+}
+
+/// Print how much time was deducted for breaks from a total just printed, if any was.
+fn print_deducted(deducted: Duration) {
+    if deducted > Duration::zero() {
+        say!(
+            "Deducted {} for breaks under the rules in the config.",
+            format_duration(deducted)
+        );
+    }
+}
+
+/// Print the events removed and added by a change, one per line.
+fn print_events_changed(change: &Change) {
+    for event in &change.removed {
+        say!("  - {}", edit::to_line(event));
+    }
+
+    for event in &change.added {
+        say!("  + {}", edit::to_line(event));
+    }
+}
+
+/// Print the tracking periods added, removed and changed between two sheets, with how the times of
+/// the changed ones moved, and how much the time tracked changed in all.
+fn print_diff(diff: &Diff) {
+    if diff.is_empty() {
+        say!("No differences.");
+        return;
+    }
+
+    for event in &diff.removed {
+        say!("  - {}", edit::to_line(event));
+    }
+
+    for event in &diff.added {
+        say!("  + {}", edit::to_line(event));
+    }
+
+    for (old, new) in &diff.changed {
+        let mut shifts = Vec::new();
+
+        if new.start != old.start {
+            shifts.push(tr!("start {}", format_shift(new.start - old.start)));
+        }
+
+        if let (Some(old_stop), Some(new_stop)) = (old.stop, new.stop) {
+            if new_stop != old_stop {
+                shifts.push(tr!("stop {}", format_shift(new_stop - old_stop)));
+            }
+        }
+
+        say!("  ~ {}", edit::to_line(old));
+
+        if shifts.is_empty() {
+            say!("    {}", edit::to_line(new));
+        } else {
+            say!("    {} ({})", edit::to_line(new), shifts.join(", "));
+        }
+    }
+
+    say!(
+        "{} added, {} removed and {} changed tracking periods, changing the time tracked by {}.",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        format_duration(diff.tracked(Utc::now()))
+    );
+}
+
+/// Print whether currently punched in, and if so, since when, together with the time worked today
+/// and this week.
+fn print_status(summary: &StatusSummary) {
+    say!("{}", status_message(summary));
+}
+
+/// Print a tracking period found by `punch search`, with the parts matching the query highlighted
+/// if `color` is set, and a long note cut down to the part around the first match.
+fn print_search_hit(event: &Event, query: &Query, color: bool) {
+    const SNIPPET: usize = 60;
+
+    let mark = |text: &str| highlight(text, &query.find_in(text), color);
+
+    let mut line = format!(
+        "{} {} {}",
+        format_local(event.start, "%Y-%m-%d %H:%M"),
+        event
+            .stop
+            .map_or_else(|| "...".to_owned(), |stop| format_local(stop, "%H:%M")),
+        format_duration(event.stop.unwrap_or_else(Utc::now) - event.start),
+    );
+
+    if let Some(project) = &event.project {
+        line.push_str(&format!(" | {}", mark(project)));
+    }
+
+    for tag in &event.tags {
+        line.push_str(&format!(" #{}", mark(tag)));
+    }
+
+    if let Some(note) = &event.note {
+        let note = note.replace('\n', " ");
+        let chars: Vec<(usize, char)> = note.char_indices().collect();
+        let offset = |index: usize| chars.get(index).map_or(note.len(), |(offset, _)| *offset);
+
+        // Start a little before the first match, but not so late that the end is left short.
+        let first = query.find_in(&note).first().map_or(0, |range| range.start);
+        let first = chars.partition_point(|(offset, _)| *offset < first);
+        let start = first
+            .saturating_sub(SNIPPET / 3)
+            .min(chars.len().saturating_sub(SNIPPET));
+        let end = (start + SNIPPET).min(chars.len());
+
+        line.push_str(&format!(
+            " | {}{}{}",
+            if start > 0 { "…" } else { "" },
+            mark(&note[offset(start)..offset(end)]),
+            if end < chars.len() { "…" } else { "" }
+        ));
+    }
+
+    say!("{}", line);
+}
+
+/// Format an instant in the local timezone.
+fn format_local(time: DateTime<Utc>, format: &str) -> String {
+    time.with_timezone(&Local).format(format).to_string()
+}
+
+/// Highlight the given byte ranges of some text in bold yellow if `color` is set.
+fn highlight(text: &str, ranges: &[Range<usize>], color: bool) -> String {
+    if !color {
+        return text.to_owned();
+    }
+
+    let mut highlighted = String::new();
+    let mut from = 0;
+
+    for range in ranges {
+        highlighted.push_str(&text[from..range.start]);
+        highlighted.push_str(&format!("\x1b[1;33m{}\x1b[0m", &text[range.clone()]));
+        from = range.end;
+    }
+
+    highlighted.push_str(&text[from..]);
+    highlighted
+}
+
+/// Describe whether currently punched in, and if so, since when, together with the time worked
+/// today and this week.
+fn status_message(summary: &StatusSummary) -> String {
+    let totals = tr!(
+        "today {}; this week {}",
+        format_duration(summary.today),
+        format_duration(summary.week)
+    );
+
+    match (&summary.status, summary.session) {
+        (SheetStatus::PunchedIn(start_utc), Some(session)) => {
+            let on = summary
+                .project
+                .as_ref()
+                .map(|project| tr!(" on {}", project))
+                .unwrap_or_default();
+
+            tr!(
+                "Punched in{} since {} ({}); {}.",
+                on,
+                format_time(*start_utc),
+                format_duration(session),
+                totals
+            )
+        }
+        (SheetStatus::PunchedOut(end_utc), _) => tr!(
+            "Not punched in; last punched out at {}; {}.",
+            format_time(*end_utc),
+            totals
+        ),
+        _ => tr!("Not punched in; no punch-ins recorded."),
+    }
+}
+
+/// Get the status as the JSON read by Waybar's custom modules: the project and length of the
+/// ongoing tracking period, or the time worked today, with the full status as the tooltip and a
+/// class of `in` or `out` for styling.
+fn waybar_status(summary: &StatusSummary) -> serde_json::Value {
+    let (text, class) = match summary.session {
+        Some(session) => {
+            let time = format_clock(session);
+
+            match &summary.project {
+                Some(project) => (format!("{} {}", project, time), "in"),
+                None => (time, "in"),
+            }
+        }
+        None => (format_clock(summary.today), "out"),
+    };
+
+    serde_json::json!({
+        "text": text,
+        "tooltip": status_message(summary),
+        "class": class,
+        "alt": class,
+    })
+}
+
+/// Get the time worked on the given project over the period of its budget, and the budget, if
+/// it has one.
+fn budget_usage(config: &Config, sheet: &Sheet, project: &str) -> Option<(Duration, Duration)> {
+    let budget = config.projects.budgets.get(project)?;
+    let (start, end) = budget.per.range_in(Local::now(), config.fiscal_year_start);
+
+    Some((
+        sheet.count_project_range(project, start, end),
+        budget.limit(),
+    ))
+}
+
+/// Load the whole sheet for a command that only loaded its last tracking periods as `loaded`, and
+/// changed them into `sheet`, with the changes in place, or `None` if it can't be loaded.
+fn whole_sheet(config: &Config, sheet: &Sheet, loaded: &Sheet) -> Option<Sheet> {
+    let mut whole = load_sheet(config).ok()?;
+    let kept = whole.events().len().saturating_sub(loaded.events().len());
+    let mut events = whole.events()[..kept].to_vec();
+
+    events.extend(sheet.events().iter().cloned());
+    whole.set_events(events);
+
+    Some(whole)
+}
+
+/// Warn if the time worked on the given project has reached 80% or all of its budget.
+fn warn_budget(config: &Config, sheet: &Sheet, project: &str) {
+    let Some((used, limit)) = budget_usage(config, sheet, project) else {
+        return;
+    };
+
+    if used >= limit {
+        say!(
+            "Warning: {} is over budget, with {} worked of {}.",
+            project,
+            format_duration(used),
+            format_duration(limit)
+        );
+    } else if used * 5 >= limit * 4 {
+        say!(
+            "Warning: {} has used {}% of its budget, with {} worked of {}.",
+            project,
+            used.num_seconds() * 100 / limit.num_seconds().max(1),
+            format_duration(used),
+            format_duration(limit)
+        );
+    }
+}
+
+/// Print the time worked on each project that has a budget, compared with the budget, leaving out
+/// archived projects unless they're included.
+fn print_budgets(config: &Config, sheet: &Sheet, include_archived: bool) {
+    for (project, budget) in &config.projects.budgets {
+        if config.projects.is_archived(project) && !include_archived {
+            continue;
+        }
+
+        let Some((used, limit)) = budget_usage(config, sheet, project) else {
+            continue;
+        };
+
+        say!(
+            "{}: {} of {} {} ({}%), {} remaining.",
+            project,
+            format_duration(used),
+            format_duration(limit),
+            i18n::translate(&budget.per.to_string().to_lowercase()),
+            used.num_seconds() * 100 / limit.num_seconds().max(1),
+            // Whole minutes are printed, so count the remaining time from those.
+            format_duration((limit - Duration::minutes(used.num_minutes())).max(Duration::zero()))
+        );
+    }
+}
+
+/// Print the time worked between the two given instants on each project and task that has an
+/// estimate, compared with the estimate.
+fn print_estimates(config: &Config, sheet: &Sheet, start: DateTime<Utc>, end: DateTime<Utc>) {
+    let projects = config.projects.estimates.iter().map(|(project, hours)| {
+        let actual = sheet.count_project_range(project, start, end);
+        (project.clone(), *hours, actual)
+    });
+    let tasks = config.projects.task_estimates.iter().map(|(tag, hours)| {
+        let actual = sheet.count_range_where(start, end, |e| e.tags.contains(tag));
+        (format!("#{}", tag), *hours, actual)
+    });
+
+    for (name, hours, actual) in projects.chain(tasks) {
+        let estimate = Duration::seconds((hours * 3600.0).round() as i64);
+        // Whole minutes are printed, so count the variance from those.
+        let variance = Duration::minutes(actual.num_minutes()) - estimate;
+        let sign = if variance > Duration::zero() { "+" } else { "" };
+
+        say!(
+            "{}: estimated {}, actual {}, variance {}{} ({}{}%).",
+            name,
+            format_duration(estimate),
+            format_duration(actual),
+            sign,
+            format_duration(variance),
+            sign,
+            variance.num_seconds() * 100 / estimate.num_seconds().max(1)
+        );
+    }
+}
+
+/// Move the tracking periods in the given period into the given review state, checking that the
+/// current user is a reviewer if approving or rejecting them, or unlocking approved ones.
+fn review_period(config: &Config, sheet: &mut Sheet, period: Period, review: Review) {
+    let reviewer = login_name();
+    let is_reviewer = config.review.reviewers.contains(&reviewer);
+    let (start, end) = period.range_in(Local::now(), config.fiscal_year_start);
+
+    if matches!(review, Review::Approved | Review::Rejected) && !is_reviewer {
+        fail!(
+            "Can't review tracking periods: {} isn't listed in `review.reviewers` in the config.",
+            reviewer
+        );
+        return;
+    }
+
+    // Submitted tracking periods can be taken back by anyone, but approved ones stay approved
+    // unless a reviewer unlocks them.
+    if review == Review::Draft
+        && !is_reviewer
+        && sheet
+            .events_in_range(start, end)
+            .any(|event| event.review == Review::Approved)
+    {
+        fail!(
+            "Can't unlock approved tracking periods: {} isn't listed in `review.reviewers` in the \
+             config.",
+            reviewer
+        );
+        return;
+    }
+
+    let name = i18n::translate(&period.to_string().to_lowercase()).to_owned();
+    let moved = sheet.review_range(start, end, review);
+
+    match (review, moved) {
+        (Review::Submitted, 0) => say!("No tracking periods to submit {}.", name),
+        (Review::Submitted, _) => say!("Submitted {} tracking periods {}.", moved, name),
+        (Review::Approved, 0) => say!("No submitted tracking periods to approve {}.", name),
+        (Review::Approved, _) => say!("Approved {} tracking periods {}.", moved, name),
+        (Review::Rejected, 0) => say!("No submitted tracking periods to reject {}.", name),
+        (Review::Rejected, _) => say!("Rejected {} tracking periods {}.", moved, name),
+        (Review::Draft, 0) => say!(
+            "No submitted or approved tracking periods to unlock {}.",
+            name
+        ),
+        (Review::Draft, _) => say!("Unlocked {} tracking periods {}.", moved, name),
+    }
+}
+
+/// List the tracking periods selected by the given filter and ask for confirmation before changing
+/// them in bulk, unless `yes` is set. Returns whether to go ahead, which is never the case if no
+/// tracking periods are selected.
+fn confirm_bulk(sheet: &Sheet, filter: &Filter, question: &str, yes: bool) -> bool {
+    let selected: Vec<_> = sheet
+        .events()
+        .iter()
+        .filter(|e| filter.matches(e))
+        .collect();
+
+    if selected.is_empty() {
+        say!("No tracking periods match.");
+        return false;
+    }
+
+    if !yes {
+        say!("This will change {} tracking periods:", selected.len());
+
+        for event in &selected {
+            say!("  {}", edit::to_line(event));
+        }
+    }
+
+    if !prompt::confirm_destructive(question, yes) {
+        say!("Nothing changed.");
+        return false;
+    }
+
+    true
+}
+
+/// Print a warning about each anomaly among the tracking periods between the two given instants,
+/// returning the number found.
+fn print_anomalies(
+    config: &Config,
+    sheet: &Sheet,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> usize {
+    let anomalies = anomaly::find(
+        sheet,
+        start,
+        end,
+        config.sessions.max_length(),
+        config.sessions.max_day(),
+    );
+
+    for found in &anomalies {
+        let event = found
+            .index()
+            .map(|index| edit::to_line(&sheet.events()[index]))
+            .unwrap_or_default();
+
+        match found {
+            Anomaly::LongSession { index, length, .. } => say!(
+                "Warning: tracking period {} lasted {}: {}",
+                index,
+                format_duration(*length),
+                event
+            ),
+            Anomaly::ShortSession { index, length, .. } => say!(
+                "Warning: tracking period {} lasted only {} seconds: {}",
+                index,
+                length.num_seconds(),
+                event
+            ),
+            Anomaly::LongDay { date, total } => say!(
+                "Warning: {} tracked on {}.",
+                format_duration(*total),
+                date.format("%a %-d %b %Y")
+            ),
+            Anomaly::Duplicate { index, of, .. } => say!(
+                "Warning: tracking period {} duplicates tracking period {}: {}",
+                index,
+                of,
+                event
+            ),
+        }
+    }
+
+    anomalies.len()
+}
+
+/// List the stretches of the given working hours between the two given instants that no tracking
+/// period covers and that are at least `min` long, on the dates on which the schedule expects
+/// work, followed by their total.
+fn print_gaps(
+    config: &Config,
+    sheet: &Sheet,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    hours: WorkingHours,
+    min: Duration,
+    period_name: &str,
+) {
+    let mut date = start.with_timezone(&Local).date_naive();
+    let mut total = Duration::zero();
+
+    while hours.on(date).0 < end {
+        let (day_start, day_end) = hours.on(date);
+        let (day_start, day_end) = (day_start.max(start), day_end.min(end));
+
+        if day_start < day_end && config.schedule.expected_on(date) > Duration::zero() {
+            for (gap_start, gap_end) in sheet.untracked(day_start, day_end) {
+                if gap_end - gap_start < min {
+                    continue;
+                }
+
+                say!(
+                    "{} {}\u{2013}{} untracked, {}.",
+                    date.format("%a %-d %b"),
+                    gap_start.with_timezone(&Local).format("%H:%M"),
+                    gap_end.with_timezone(&Local).format("%H:%M"),
+                    format_duration(gap_end - gap_start)
+                );
+
+                total += gap_end - gap_start;
+            }
+        }
+
+        date = date.succ_opt().unwrap();
+    }
+
+    if total.is_zero() {
+        say!("No untracked working hours {}.", period_name);
+    } else {
+        say!(
+            "Untracked working hours {}: {}.",
+            period_name,
+            format_duration(total)
+        );
+    }
+}
+
+/// Add the time worked between the two given instants in each sheet in the given directory to the
+/// team totals, counting tracking periods without a user as belonging to the user named by the
+/// file.
+fn add_sheets_in(totals: &mut TeamTotals, dir: &Path, start: DateTime<Utc>, end: DateTime<Utc>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            fail!("Can't read sheets in {}: {}.", dir.display(), err);
+            return;
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("json" | "jsonl" | "toml" | "msgpack")
+            )
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let user = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        match Sheet::load(&path) {
+            Ok(sheet) => totals.add_sheet(&sheet, &user, start, end),
+            Err(err) => fail!("Can't load {}: {}.", path.display(), err),
+        }
+    }
+}
+
+/// Print the time worked by each user on each project as a table, in hours, with a row and a
+/// column of totals.
+fn print_team(totals: &TeamTotals) {
+    let projects = totals.projects();
+
+    let mut rows = vec![{
+        let mut header = vec![String::new()];
+        header.extend(
+            projects
+                .iter()
+                .map(|project| project.clone().unwrap_or_else(|| tr!("(no project)"))),
+        );
+        header.push(tr!("total"));
+        header
+    }];
+
+    for user in totals.users.keys() {
+        let mut row = vec![user.clone()];
+        row.extend(
+            projects
+                .iter()
+                .map(|project| export::hours(totals.get(user, project))),
+        );
+        row.push(export::hours(totals.user_total(user)));
+        rows.push(row);
+    }
+
+    let mut row = vec![tr!("total")];
+    row.extend(
+        projects
+            .iter()
+            .map(|project| export::hours(totals.project_total(project))),
+    );
+    row.push(export::hours(totals.total()));
+    rows.push(row);
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for row in rows {
+        let mut line = format!("{:<width$}", row[0], width = widths[0]);
+
+        for (cell, width) in row.iter().zip(&widths).skip(1) {
+            line.push_str(&format!("  {:>width$}", cell, width = width));
+        }
+
+        say!("{}", line);
+    }
+}
+
+/// Get the variables available to a status template.
+fn status_variables(summary: &StatusSummary) -> minijinja::Value {
+    let (since, last_out) = match summary.status {
+        SheetStatus::PunchedIn(start_utc) => (Some(format_time(start_utc)), None),
+        SheetStatus::PunchedOut(end_utc) => (None, Some(format_time(end_utc))),
+        SheetStatus::Empty => (None, None),
+    };
+
+    context! {
+        punched_in => since.is_some(),
+        since,
+        last_out,
+        project => summary.project,
+        session => summary.session.map(format_duration),
+        session_seconds => summary.session.map(|session| session.num_seconds()),
+        today => format_duration(summary.today),
+        today_seconds => summary.today.num_seconds(),
+        week => format_duration(summary.week),
+        week_seconds => summary.week.num_seconds(),
+    }
+}
+
+/// Warn that the ongoing tracking period has probably been left running by mistake, and offer to
+/// end it at a more plausible time.
+///
+/// The suggested time is the start of the period plus the hours expected on that day by the
+/// schedule, or 8 hours if none are expected. With `yes` (from `--yes`), it's ended then without
+/// asking.
+fn offer_stale_punch_out(
+    sheet: &mut Sheet,
+    config: &Config,
+    start_utc: DateTime<Utc>,
+    now_utc: DateTime<Utc>,
+    yes: bool,
+) {
+    let start_local: DateTime<Local> = start_utc.into();
+
+    let expected = config.schedule.expected_on(start_local.date_naive());
+    let length = if expected > Duration::zero() {
+        expected
+    } else {
+        Duration::hours(8)
+    };
+    let suggested_utc = std::cmp::min(start_utc + length, now_utc);
+
+    say!(
+        "Punched in since {} ({}); did you forget to punch out?",
+        format_time(start_utc),
+        format_duration(now_utc - start_utc),
+    );
+
+    if prompt::confirm_destructive(
+        &tr!("Punch out at {} first?", format_time(suggested_utc)),
+        yes,
+    ) {
+        match sheet.punch_out_at(suggested_utc) {
+            Ok(time_utc) => say!("Punching out at {}.", format_time(time_utc)),
+            Err(err) => fail!("Can't punch out: {}.", err),
+        }
+    }
+}
+
+/// Load the configured user's [view][view] of the sheet from the location determined by the
+/// storage config.
+///
+/// [view]: ../punch_clock/sheet/struct.Sheet.html#method.user_view
+fn load_sheet(config: &Config) -> Result<Sheet, CliError> {
+    load_sheet_with(config, shell::load).map(|sheet| sheet.user_view(&config.user()))
+}
+
+/// Load the sheet from the location determined by the storage config using the given function.
+fn load_sheet_with<F>(config: &Config, load: F) -> Result<Sheet, CliError>
+where
+    F: FnOnce(PathBuf) -> Result<Sheet, SheetError>,
+{
+    let started = Instant::now();
+    let path = config
+        .storage
+        .sheet_loc()
+        .map_err(|_| CliError::FindSheet)?;
+
+    // Try to load the sheet from the configured location. If loading fails due to a missing file,
+    // create a new empty sheet.
+    debug!(path = %path.display(), "loading sheet");
+    let sheet = match load(path.clone()) {
+        Ok(sheet) => sheet,
+        Err(SheetError::OpenSheet(io_err)) if io_err.raw_os_error() == Some(2) => Sheet::default(),
+        Err(err) => return Err(CliError::LoadSheet(path, err)),
+    };
+
+    debug!(events = sheet.events().len(), elapsed = ?started.elapsed(), "loaded sheet");
+
+    Ok(sheet)
+}
+
+/// Record a change made to the sheet in the history, and run the hook script for it.
+fn record_change(config: &Config, change: &Change) {
+    let path = History::loc_for(config.storage.sheet_loc().unwrap());
+
+    debug!(
+        operation = %change.operation,
+        removed = change.removed.len(),
+        added = change.added.len(),
+        history = %path.display(),
+        "recording change"
+    );
+
+    for event in &change.removed {
+        trace!("removed {}", edit::to_line(event));
+    }
+
+    for event in &change.added {
+        trace!("added {}", edit::to_line(event));
+    }
+
+    if let Err(err) = History::append(path, change) {
+        warn!("Unable to record change in history: {}", err);
+    }
+
+    // The daily totals cache can't tell when a finished tracking period has been changed, so it
+    // has to be rebuilt, unless purging has just rebuilt it.
+    if change.operation != Operation::Purge && change.removed.iter().any(|e| e.stop.is_some()) {
+        clear_totals(config);
+    }
+
+    hooks::run(change);
+}
+
+/// Load the history of changes to the sheet. If there's no history file yet, the history is empty.
+fn load_history(config: &Config) -> Result<History, HistoryError> {
+    let path = History::loc_for(config.storage.sheet_loc().unwrap());
+
+    History::load(path).or_else(|err| match err {
+        HistoryError::OpenHistory(io_err) if io_err.raw_os_error() == Some(2) => {
+            Ok(History::default())
+        }
+        _ => Err(err),
+    })
+}
+
+/// Undo (or if `redo` is set, redo) up to the given number of changes to the sheet, returning
+/// the change made by each step so that it can be recorded in the history.
+fn step_history(config: &Config, sheet: &mut Sheet, steps: usize, redo: bool) -> Vec<Change> {
+    let operation = if redo {
+        Operation::Redo
+    } else {
+        Operation::Undo
+    };
+
+    let mut history = match load_history(config) {
+        Ok(history) => history,
+        Err(err) => {
+            fail!("Can't read history: {}.", err);
+            return Vec::new();
+        }
+    };
+    let first_step = history.changes.len();
+
+    for _ in 0..steps {
+        let next = if redo {
+            history.next_redo()
+        } else {
+            history.next_undo()
+        };
+
+        let Some(change) = next.cloned() else {
+            if redo {
+                say!("Nothing to redo.");
+            } else {
+                say!("Nothing to undo.");
+            }
+            break;
+        };
+
+        let before = sheet.clone();
+        let result = if redo {
+            change.redo(sheet)
+        } else {
+            change.undo(sheet)
+        };
+
+        let name = i18n::translate(&change.operation.to_string()).to_owned();
+        let time = format_time(change.time);
+
+        if let Err(err) = result {
+            if redo {
+                fail!("Can't redo {} from {}: {}.", name, time, err);
+            } else {
+                fail!("Can't undo {} from {}: {}.", name, time, err);
+            }
+            break;
+        }
+
+        if redo {
+            say!("Redid {} from {}.", name, time);
+        } else {
+            say!("Undid {} from {}.", name, time);
+        }
+
+        // Each step has to be in the history before the next one, so that the following
+        // change is undone or redone next.
+        history
+            .changes
+            .extend(Change::between(operation, &before, sheet));
+    }
+
+    history.changes.split_off(first_step)
+}
+
+/// Sign the finished tracking periods changed by the command, and by the given changes made
+/// before it, with the signing key, generating the key if there isn't one yet.
+///
+/// Tracking periods that weren't changed keep their signatures, even if those don't match, so
+/// that changes made behind Punch Clock's back still show up.
+fn sign_changed(sheet: &mut Sheet, before: Option<&Sheet>, changes: &mut [Change]) {
+    let key = match signing::key_loc().and_then(signing::load_or_generate) {
+        Ok(key) => key,
+        Err(err) => {
+            warn!("Unable to load the signing key: {}", err);
+            return;
+        }
+    };
+
+    let public_key = key.verifying_key();
+    let needs_signature = |event: &Event| {
+        event.stop.is_some() && signing::verify(event, &public_key) != Verification::Valid
+    };
+
+    let unchanged: BTreeSet<&Event> = before
+        .map(|b| b.events().iter().collect())
+        .unwrap_or_default();
+    let changed_before: BTreeSet<Event> = changes
+        .iter()
+        .flat_map(|change| change.added.iter().cloned())
+        .collect();
+
+    sheet.update_events(|event| {
+        let changed =
+            (before.is_some() && !unchanged.contains(&*event)) || changed_before.contains(event);
+
+        if changed && needs_signature(event) {
+            event.signature = Some(signing::sign(event, &key));
+        }
+    });
+
+    for event in changes
+        .iter_mut()
+        .flat_map(|change| change.added.iter_mut())
+    {
+        if needs_signature(event) {
+            event.signature = Some(signing::sign(event, &key));
+        }
+    }
+}
+
+/// Get where the files that are backed up are kept.
+fn backup_locations(config: &Config) -> Locations {
+    Locations {
+        config: Config::default_loc().unwrap(),
+        sheet: config.storage.sheet_loc().unwrap(),
+    }
+}
+
+/// Restore the files in the backup at the given path, after confirming that the files already
+/// there should be replaced.
+fn restore_backup(config: &Config, path: &Path, dry_run: bool, yes: bool) {
+    let locations = backup_locations(config);
+
+    let targets = match backup::contents(&locations, path) {
+        Ok(targets) => targets,
+        Err(err) => {
+            fail!("Can't read the backup: {}.", err);
+            return;
+        }
+    };
+
+    if dry_run {
+        for target in targets {
+            say!("Would restore {}.", target.display());
+        }
+
+        return;
+    }
+
+    let existing: Vec<_> = targets.iter().filter(|target| target.exists()).collect();
+
+    if !existing.is_empty() {
+        for target in existing {
+            say!("This will replace {}.", target.display());
+        }
+
+        if !prompt::confirm_destructive(&tr!("Restore the backup?"), yes) {
+            say!("Restore cancelled.");
+            return;
+        }
+    }
+
+    match backup::restore(&locations, path) {
+        Ok(restored) => {
+            for target in &restored {
+                say!("Restored {}.", target.display());
+            }
+
+            // A checksum left from before would no longer match a restored sheet.
+            if !restored.contains(&Checksum::loc_for(&locations.sheet)) {
+                if let Err(err) = Checksum::remove(&locations.sheet) {
+                    warn!("Unable to remove the checksum of the sheet: {}", err);
+                }
+            }
+        }
+        Err(err) => fail!("Can't restore the backup: {}.", err),
+    }
+}
+
+/// Salvage the tracking periods that can be parsed from a sheet that can't be loaded, writing them
+/// to a fresh sheet and setting the rest of the file aside.
+fn repair_sheet(config: &Config, dry_run: bool, yes: bool) -> Result<(), CliError> {
+    let path = config
+        .storage
+        .sheet_loc()
+        .map_err(|_| CliError::FindSheet)?;
+    let contents = std::fs::read(&path)
+        .map_err(|err| CliError::LoadSheet(path.clone(), SheetError::ReadSheet(err)))?;
+    let format = SheetFormat::from_path(&path);
+
+    if Sheet::read(contents.as_slice(), format).is_ok() {
+        say!("The sheet can be loaded, so there's nothing to repair.");
+        return Ok(());
+    }
+
+    let Some(salvaged) = repair::salvage(&contents, format) else {
+        fail!(
+            "Can't repair the sheet: only sheets stored as JSON, JSON Lines or TOML can be \
+             repaired."
+        );
+        return Ok(());
+    };
+    let broken_path = Salvaged::loc_for(&path);
+
+    if dry_run {
+        say!(
+            "Would keep {} tracking periods and set aside {} broken parts in {}.",
+            salvaged.sheet.events().len(),
+            salvaged.broken.len(),
+            broken_path.display()
+        );
+        return Ok(());
+    }
+
+    say!(
+        "This will keep {} tracking periods and set aside {} broken parts in {}.",
+        salvaged.sheet.events().len(),
+        salvaged.broken.len(),
+        broken_path.display()
+    );
+
+    if !prompt::confirm_destructive(&tr!("Repair the sheet?"), yes) {
+        say!("Repair cancelled.");
+        return Ok(());
+    }
+
+    if let Err(err) = salvaged.set_aside(&path) {
+        fail!(
+            "Can't set aside the broken parts of the sheet in {}: {}.",
+            broken_path.display(),
+            err
+        );
+        return Ok(());
+    }
+
+    let revision = salvaged.sheet.revision + 1;
+    let sheet = salvaged.sheet.with_revision(revision);
+
+    // The file no longer matches its checksum, which is why it's being repaired, so it isn't
+    // checked before writing.
+    debug!(path = %path.display(), events = sheet.events().len(), "writing repaired sheet");
+    with_data_dir(&path, || sheet.write(&path))?;
+    record_checksum(&path, sheet.events().len());
+    clear_totals(config);
+
+    say!(
+        "Repaired the sheet, keeping {} tracking periods.",
+        sheet.events().len()
+    );
+
+    Ok(())
+}
+
+/// Load the cache of daily totals for the sheet, bringing it up to date with the sheet first.
+fn daily_totals(config: &Config, sheet: &Sheet) -> DailyTotals {
+    let path = DailyTotals::loc_for(config.storage.sheet_loc().unwrap());
+
+    // The cache can always be rebuilt from the sheet, so a missing or unreadable cache file is
+    // treated as an empty cache.
+    let mut totals = DailyTotals::load(&path).unwrap_or_default();
+
+    if totals.update(sheet) {
+        debug!(path = %path.display(), "updating daily totals cache");
+
+        if let Err(err) = totals.write(&path) {
+            warn!("Unable to write daily totals cache: {}", err);
+        }
+    }
+
+    totals
+}
+
+/// Remove the cache of daily totals, after past tracking periods have been changed.
+fn clear_totals(config: &Config) {
+    let path = DailyTotals::loc_for(config.storage.sheet_loc().unwrap());
+
+    if let Err(err) = std::fs::remove_file(path) {
+        if err.kind() != io::ErrorKind::NotFound {
+            warn!("Unable to remove daily totals cache: {}", err);
+        }
+    }
+}
+
+/// Edit the text of the config file, unless it's a dry run, returning whether it was changed.
+///
+/// The edit gets the text of the config file and returns its new text, or `None` if there's nothing
+/// to change. The new config is written to a temporary file first, which then replaces the config
+/// file, so that the config is never left half written.
+fn edit_config<F>(dry_run: bool, edit: F) -> Result<bool, ConfigError>
+where
+    F: FnOnce(&str) -> Result<Option<String>, ConfigError>,
+{
+    let Some((path, edited)) = edited_config(edit)? else {
+        return Ok(false);
+    };
+
+    if !dry_run {
+        write_config(&path, edited)?;
+    }
+
+    Ok(true)
+}
+
+/// Get the path of the config file and its text after the given edit, or `None` if there's
+/// nothing to change, without writing it.
+fn edited_config<F>(edit: F) -> Result<Option<(PathBuf, String)>, ConfigError>
+where
+    F: FnOnce(&str) -> Result<Option<String>, ConfigError>,
+{
+    let path = Config::default_loc()?;
+
+    let toml = match std::fs::read_to_string(&path) {
+        Ok(toml) => toml,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(ConfigError::ReadConfig(err)),
+    };
+
+    Ok(edit(&toml)?.map(|edited| (path, edited)))
+}
+
+/// Write the given text to the config file at the given path, through a temporary file.
+fn write_config(path: &Path, edited: String) -> Result<(), ConfigError> {
+    let mut temporary = path.to_owned().into_os_string();
+    temporary.push(".new");
+
+    // The config file may not exist yet, nor its directory.
+    path.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&temporary, edited))
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .map_err(ConfigError::WriteConfig)
+}
+
+/// Check that the sheet matches the checksum recorded when it was last written, and warn with ways
+/// to recover if it doesn't, returning whether it matches. Unless `full` is set, a sheet with the
+/// size and modification time recorded is taken to match without reading it.
+fn check_sheet(config: &Config, full: bool) -> bool {
+    let path = config.storage.sheet_loc().unwrap();
+    let verified = if full {
+        checksum::verify_full(&path)
+    } else {
+        checksum::verify(&path)
+    };
+
+    match verified {
+        Ok(_) => true,
+        Err(err) if err.is_mismatch() => {
+            warn!(
+                "{} doesn't match its checksum: {}. It may be corrupted or only partly synced, so \
+                 it won't be written to. Restore it from a backup with `punch restore`, or run \
+                 `punch doctor --accept-checksum` if it's fine as it is.",
+                path.display(),
+                err
+            );
+            false
+        }
+        Err(err) => {
+            warn!("Unable to check the checksum of the sheet: {}", err);
+            true
+        }
+    }
+}
+
+/// Warn about conflicted copies of the sheet left by sync tools, which hold changes that aren't in
+/// the sheet.
+fn check_conflicts(config: &Config) {
+    let copies = Sheet::conflicted_copies(config.storage.sheet_loc().unwrap());
+
+    if !copies.is_empty() {
+        let names: Vec<String> = copies
+            .iter()
+            .map(|copy| copy.display().to_string())
+            .collect();
+
+        warn!(
+            "Found conflicted copies of the sheet, left by a sync tool: {}. Merge them into the \
+             sheet with `punch merge-conflicts`.",
+            names.join(", ")
+        );
+    }
+}
+
+/// Check that the sheet at the given path can be written over, failing if it doesn't match its
+/// checksum, and returning the checksum if one has been recorded.
+fn check_before_writing(path: &Path) -> Result<Option<Checksum>, ()> {
+    match checksum::verify_full(path) {
+        Ok(checksum) => Ok(checksum),
+        Err(err) if err.is_mismatch() => {
+            fail!(
+                "Not writing the sheet, as it doesn't match its checksum: {}.",
+                err
+            );
+            Err(())
+        }
+        Err(err) => {
+            warn!("Unable to check the checksum of the sheet: {}", err);
+            Ok(None)
+        }
+    }
+}
+
+/// Record the checksum of the sheet just written to the given path, which holds the given number
+/// of tracking periods.
+fn record_checksum(path: &Path, events: usize) {
+    if let Err(err) = Checksum::record(path, events) {
+        warn!("Unable to record the checksum of the sheet: {}", err);
+    }
+}
+
+/// Load the sheet, make a change to it with the given function, then write the sheet and record
+/// the change, for the interfaces through which other programs change the sheet.
+#[cfg(any(all(target_os = "linux", feature = "dbus"), feature = "graphql"))]
+fn change_sheet<T, F>(config: &Config, operation: Operation, change: F) -> Result<T, String>
+where
+    F: FnOnce(&mut Sheet) -> Result<T, SheetError>,
+{
+    let mut sheet = load_sheet(config).map_err(|err| err.describe())?;
+    let before = sheet.clone();
+
+    let result = change(&mut sheet).map_err(|err| err.to_string())?;
+
+    if !write_sheet(config, &sheet).map_err(|err| err.describe())? {
+        return Err("the sheet doesn't match its checksum".to_owned());
+    }
+
+    if let Some(change) = Change::between(operation, &before, &sheet) {
+        record_change(config, &change);
+    }
+
+    Ok(result)
+}
+
+/// Write the configured user's view of the sheet to the location determined by the storage config,
+/// keeping the tracking periods of other users, returning whether it was written.
+///
+/// The sheet isn't written if another process has written the file since the sheet was loaded.
+fn write_sheet(config: &Config, sheet: &Sheet) -> Result<bool, CliError> {
+    // Put the user's view back among the other users' tracking periods, as they are now.
+    let mut shared = load_sheet_with(config, Sheet::load)?;
+
+    if !check_revision(sheet.revision, shared.revision) {
+        return Ok(false);
+    }
+
+    shared.replace_user_view(&config.user(), sheet.clone());
+    shared.revision += 1;
+
+    Ok(write_sheet_to(&config.storage.sheet_loc().unwrap(), &shared)? && push_remote(config))
+}
+
+/// Write the sheet to the given path, unless the file there doesn't match its checksum, returning
+/// whether it was written.
+fn write_sheet_to(path: &Path, sheet: &Sheet) -> Result<bool, CliError> {
+    if check_before_writing(path).is_err() {
+        return Ok(false);
+    }
+
+    debug!(path = %path.display(), events = sheet.events().len(), "writing sheet");
+    with_data_dir(path, || sheet.write(path))?;
+    record_checksum(path, sheet.events().len());
+
+    Ok(true)
+}
+
+/// Save changes to a sheet loaded with [`Sheet::load_tail`] to the location determined by the
+/// storage config, unless the file doesn't match its checksum, returning whether they were saved.
+fn write_sheet_tail(config: &Config, sheet: &Sheet, loaded: &Sheet) -> Result<bool, CliError> {
+    let path = config.storage.sheet_loc().unwrap();
+
+    let Ok(checksum) = check_before_writing(&path) else {
+        return Ok(false);
+    };
+
+    match Sheet::load_revision(&path) {
+        Ok(revision) if !check_revision(loaded.revision, revision) => return Ok(false),
+        Ok(_) => {}
+        Err(err) => warn!("Unable to check the revision of the sheet: {}", err),
+    }
+
+    let sheet = sheet.clone().with_revision(loaded.revision + 1);
+
+    // Only the end of the file is rewritten, so the number of tracking periods in the rest of it
+    // comes from the checksum, or from reading the whole file if none has been recorded yet.
+    let before = checksum.map_or_else(
+        || Sheet::load(&path).map_or(loaded.events().len(), |sheet| sheet.events().len()),
+        |checksum| checksum.events,
+    );
+
+    debug!(path = %path.display(), "appending to sheet");
+    with_data_dir(&path, || sheet.write_tail(&path, loaded))?;
+    record_checksum(
+        &path,
+        (before + sheet.events().len()).saturating_sub(loaded.events().len()),
+    );
+
+    Ok(push_remote(config))
+}
+
+/// Check that the sheet file is still at the revision the sheet was loaded at, failing if another
+/// process has written it since.
+fn check_revision(loaded: u64, current: u64) -> bool {
+    if loaded != current {
+        fail!(
+            "Not writing the sheet, as another process wrote it while this one was running \
+             (revision {} was loaded, but it's now at revision {}). Run the command again to make \
+             the change to the sheet as it is now.",
+            loaded,
+            current
+        );
+    }
+
+    loaded == current
+}
+
+/// Bring the local copy of the sheet up to date with the one on the server, if there is one,
+/// carrying on with the local copy if that fails.
+fn pull_remote(config: &Config) {
+    let path = config.storage.sheet_loc().unwrap();
+    let url = config.storage.remote.url.as_deref().unwrap_or_default();
+
+    match remote::pull(&config.storage.remote, &path) {
+        Ok(Pulled::Merged(added)) => warn!(
+            "Merged {} tracking periods recorded here into the sheet on {}, which had changed",
+            added, url
+        ),
+        Ok(pulled) => debug!(?pulled, url, "brought sheet up to date"),
+        Err(err) => warn!(
+            "Unable to bring the sheet up to date with {}, so using the copy here: {}",
+            url, err
+        ),
+    }
+}
+
+/// Store the sheet on the server, if there is one, returning `true` even if that fails, since the
+/// changes are kept locally and stored later.
+fn push_remote(config: &Config) -> bool {
+    let Some(url) = config.storage.remote.url.as_deref() else {
+        return true;
+    };
+
+    match remote::push(&config.storage.remote, &config.storage.sheet_loc().unwrap()) {
+        Ok(()) => debug!(url, "stored sheet"),
+        Err(RemoteError::Changed) => warn!(
+            "The sheet on {} has changed, so the changes made here will be merged into it next \
+             time",
+            url
+        ),
+        Err(err) => warn!(
+            "Unable to store the sheet on {}, so it will be stored next time: {}",
+            url, err
+        ),
+    }
+
+    true
+}
+
+/// Run the given function that writes the sheet to the given path, creating the directory it's in
+/// and trying again if it fails due to the directory missing.
+fn with_data_dir<F>(path: &Path, write: F) -> Result<(), CliError>
+where
+    F: Fn() -> Result<(), SheetError>,
+{
+    let started = Instant::now();
+
+    let written = match write() {
+        Err(SheetError::WriteSheet(io_err)) if io_err.raw_os_error() == Some(2) => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .map_err(|err| CliError::CreateDataDir(dir.to_owned(), err))?;
+            }
+
+            write()
+        }
+        written => written,
+    };
+
+    written.map_err(|err| CliError::WriteSheet(path.to_owned(), err))?;
+    debug!(elapsed = ?started.elapsed(), "wrote sheet");
+
+    Ok(())
+}
+
+/// Format an instant in local time, including the date if it isn't today.
+fn format_time(time_utc: DateTime<Utc>) -> String {
+    let time_local: DateTime<Local> = time_utc.into();
+
+    let format = if time_local.date_naive() == Local::now().date_naive() {
+        SAME_DAY_FORMAT
+    } else {
+        DIFF_DAY_FORMAT
+    };
+
+    time_local.format(format).to_string()
+}
+
+/// Format the project, tags and review state of an event, with a leading space if there are any.
+fn format_labels(event: &Event) -> String {
+    let mut labels = String::new();
+
+    if let Some(project) = &event.project {
+        labels.push_str(&format!(" [{}]", project));
+    }
+
+    for tag in &event.tags {
+        labels.push_str(&format!(" #{}", tag));
+    }
+
+    if !event.review.is_draft() {
+        labels.push_str(&format!(
+            " ({})",
+            i18n::translate(&event.review.to_string())
+        ));
+    }
+
+    labels
+}
+
+/// Format a duration as a number of hours and minutes.
+fn format_duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let minutes = duration.num_minutes().abs();
+
+    tr!("{}{} hours, {} minutes", sign, minutes / 60, minutes % 60)
+}
+
+/// Get the status as a segment of tmux's status line: the project and length of the ongoing
+/// tracking period in green, or the time worked today dimmed if not punched in.
+///
+/// Long project names are cut short, so that the segment doesn't crowd out the rest of the status
+/// line in a narrow terminal.
+fn tmux_status(summary: &StatusSummary) -> String {
+    const MAX_PROJECT: usize = 16;
+
+    match summary.session {
+        Some(session) => {
+            let project = summary
+                .project
+                .as_deref()
+                .map(|project| match project.chars().count() > MAX_PROJECT {
+                    true => {
+                        let short: String = project.chars().take(MAX_PROJECT - 1).collect();
+                        format!("{}… ", short)
+                    }
+                    false => format!("{} ", project),
+                })
+                .unwrap_or_default();
+
+            // Any `#` in the project name would start a style or format in tmux.
+            format!(
+                "#[fg=green]● {}{}#[default]",
+                project.replace('#', "##"),
+                format_clock(session)
+            )
+        }
+        None => format!("#[dim]○ {}#[default]", format_clock(summary.today)),
+    }
+}
+
+/// Get the project and length of the ongoing tracking period, such as `acme 1:05`, for a prompt,
+/// or `None` if not punched in. Unlike the other formats, this only looks at the last tracking
+/// period, so that it's quick to print however long the sheet is.
+fn prompt_status(sheet: &Sheet) -> Option<String> {
+    let last = sheet.events().last().filter(|event| event.stop.is_none())?;
+    let time = format_clock(Utc::now() - last.start);
+
+    Some(match &last.project {
+        Some(project) => format!("{} {}", project, time),
+        None => time,
+    })
+}
+
+/// Format a length of time compactly as hours and minutes, such as `1:05`.
+fn format_clock(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Format a change in time compactly as hours and minutes with a sign, such as `+1:05` or `-0:30`.
+fn format_shift(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() {
+        "-"
+    } else {
+        "+"
+    };
+
+    format!("{}{}", sign, format_clock(duration.abs()))
+}
+
+/// Format amounts of money in one or more currencies, separated by plus signs.
+fn format_amounts(amounts: &Amounts) -> String {
+    if amounts.by_currency.is_empty() {
+        return format_amount(0.0, None);
+    }
+
+    amounts
+        .by_currency
+        .iter()
+        .map(|(currency, amount)| format_amount(*amount, currency.as_deref()))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Format an amount of money in the given currency, with the number of decimal places the
+/// currency uses, separators between thousands, and the currency's symbol or code.
+fn format_amount(amount: f64, currency: Option<&str>) -> String {
+    let decimals = match currency {
+        Some("JPY" | "KRW" | "ISK" | "CLP" | "VND" | "UGX" | "PYG") => 0,
+        Some("BHD" | "KWD" | "OMR" | "JOD" | "TND" | "LYD" | "IQD") => 3,
+        _ => 2,
+    };
+
+    let formatted = format!("{:.*}", decimals, amount.abs());
+    let (whole, fraction) = formatted.split_at(formatted.find('.').unwrap_or(formatted.len()));
+
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    let sign = if amount < 0.0 { "-" } else { "" };
+    let number = format!("{}{}", grouped, fraction);
+
+    match currency {
+        Some("USD") => format!("{}${}", sign, number),
+        Some("EUR") => format!("{}€{}", sign, number),
+        Some("GBP") => format!("{}£{}", sign, number),
+        Some("JPY") => format!("{}¥{}", sign, number),
+        Some(code) => format!("{}{} {}", sign, number, code),
+        None => format!("{}{}", sign, number),
+    }
 }
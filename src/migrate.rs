@@ -0,0 +1,86 @@
+//! Moving files out of the directories older versions kept them in.
+//!
+//! The config file belongs in the config directory, and the sheet (in any format), its backup and
+//! checksum, the history, the cache of daily totals and the invoice registry belong in the data
+//! directory. On platforms where the two differ, files found in the other one are moved where they
+//! belong, unless a file is already there, so each is only moved once. Directories given by
+//! environment variables are left alone.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use directories::ProjectDirs;
+use thiserror::Error;
+
+use crate::{config::env_path, sheet::SheetFormat};
+
+/// The files kept in the data directory, besides the sheet and its backup.
+const DATA_FILES: [&str; 3] = ["history.jsonl", "totals.json", "invoices.json"];
+
+/// Errors arising while moving files.
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("unable to move {} to {}", .0.display(), .1.display())]
+    Move(PathBuf, PathBuf, #[source] io::Error),
+}
+
+/// Move the files in the wrong directory to the right one, returning where each was moved from
+/// and to.
+pub fn migrate() -> Result<Vec<(PathBuf, PathBuf)>, MigrateError> {
+    let Some(dirs) = ProjectDirs::from("dev", "neros", "PunchClock") else {
+        return Ok(Vec::new());
+    };
+    let (config_dir, data_dir) = (dirs.config_dir(), dirs.data_dir());
+
+    if config_dir == data_dir {
+        return Ok(Vec::new());
+    }
+
+    let mut moves = Vec::new();
+
+    if env_path("PUNCH_CONFIG").is_none() {
+        moves.push((data_dir.join("config.toml"), config_dir.join("config.toml")));
+    }
+
+    if env_path("PUNCH_DATA_DIR").is_none() && env_path("PUNCH_SHEET").is_none() {
+        let sheets = SheetFormat::ALL.iter().flat_map(|format| {
+            let name = format.file_name();
+            [
+                name.to_owned(),
+                format!("{}.bak", name),
+                format!("{}.sum", name),
+            ]
+        });
+
+        for name in sheets.chain(DATA_FILES.map(str::to_owned)) {
+            moves.push((config_dir.join(&name), data_dir.join(&name)));
+        }
+    }
+
+    let mut moved = Vec::new();
+
+    for (from, to) in moves {
+        if from.is_file() && !to.exists() {
+            move_file(&from, &to)
+                .map_err(|err| MigrateError::Move(from.clone(), to.clone(), err))?;
+            moved.push((from, to));
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Move a file, creating the directory it's moved into, and copying it if it can't simply be
+/// renamed, such as when the directories are on different filesystems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::rename(from, to).or_else(|_| {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    })
+}
@@ -0,0 +1,133 @@
+//! Publishing messages to an MQTT broker, with just enough of MQTT 3.1.1 to connect, publish
+//! retained messages at most once and disconnect.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+/// How long to wait for the broker before giving up.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors arising while publishing to an MQTT broker.
+#[derive(Error, Debug)]
+pub enum MqttError {
+    #[error("unable to connect to broker")]
+    Connect(#[source] io::Error),
+    #[error("connection refused by broker (code {0})")]
+    Refused(u8),
+    #[error("unable to send to broker")]
+    Send(#[source] io::Error),
+}
+
+/// Open a connection to the given address, trying each address it resolves to in turn and giving
+/// up on each after the timeout.
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to");
+
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+/// A connection to an MQTT broker.
+pub struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    /// Connect to the broker at the given address as the given client, logging in with the given
+    /// user name and password if there are any.
+    pub fn connect(
+        address: &str,
+        client_id: &str,
+        login: Option<(&str, &str)>,
+    ) -> Result<Self, MqttError> {
+        let mut stream = connect(address).map_err(MqttError::Connect)?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .map_err(MqttError::Connect)?;
+        stream
+            .set_write_timeout(Some(TIMEOUT))
+            .map_err(MqttError::Connect)?;
+
+        // The protocol name and level, then the flags: a clean session, and a user name and
+        // password if logging in.
+        let mut body = string(b"MQTT");
+        body.push(4);
+        body.push(if login.is_some() { 0xc2 } else { 0x02 });
+        body.extend_from_slice(&60u16.to_be_bytes());
+        body.extend(string(client_id.as_bytes()));
+
+        if let Some((username, password)) = login {
+            body.extend(string(username.as_bytes()));
+            body.extend(string(password.as_bytes()));
+        }
+
+        stream
+            .write_all(&packet(0x10, &body))
+            .map_err(MqttError::Send)?;
+
+        let mut ack = [0; 4];
+        stream.read_exact(&mut ack).map_err(MqttError::Connect)?;
+
+        match ack[3] {
+            0 => Ok(Client { stream }),
+            code => Err(MqttError::Refused(code)),
+        }
+    }
+
+    /// Publish a message on the given topic, to be kept by the broker for later subscribers.
+    pub fn publish_retained(&mut self, topic: &str, payload: &[u8]) -> Result<(), MqttError> {
+        let mut body = string(topic.as_bytes());
+        body.extend_from_slice(payload);
+
+        self.stream
+            .write_all(&packet(0x31, &body))
+            .map_err(MqttError::Send)
+    }
+
+    /// Disconnect from the broker.
+    pub fn disconnect(mut self) -> Result<(), MqttError> {
+        self.stream
+            .write_all(&packet(0xe0, &[]))
+            .map_err(MqttError::Send)
+    }
+}
+
+/// Make a packet of the given type from its body, preceded by the body's length.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut length = body.len();
+
+    // The length is written 7 bits at a time, with the top bit set on all but the last byte.
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+
+        if length == 0 {
+            packet.push(byte);
+            break;
+        }
+
+        packet.push(byte | 0x80);
+    }
+
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Write a string preceded by its length, as strings are written in packets.
+fn string(bytes: &[u8]) -> Vec<u8> {
+    let mut string = (bytes.len() as u16).to_be_bytes().to_vec();
+    string.extend_from_slice(bytes);
+    string
+}
@@ -0,0 +1,48 @@
+//! Notifying the user of things that happen while they aren't looking at the terminal.
+
+use std::{
+    io,
+    process::{Command, ExitStatus},
+};
+
+/// Show a desktop notification with the given summary and body text.
+///
+/// This uses `notify-send` on Linux and other Unix-like platforms, and `osascript` on macOS. On any
+/// other platform, the notification is printed to standard output instead, or logged when running
+/// as a service.
+pub fn desktop(summary: &str, body: &str) -> io::Result<()> {
+    if crate::service::is_running() {
+        tracing::info!("{}: {}", summary, body);
+    } else if cfg!(target_os = "macos") {
+        let script = format!("display notification {:?} with title {:?}", body, summary);
+
+        Command::new("osascript").arg("-e").arg(script).status()?;
+    } else if cfg!(unix) {
+        Command::new("notify-send")
+            .arg(summary)
+            .arg(body)
+            .status()?;
+    } else {
+        println!("{}: {}", summary, body);
+    }
+
+    Ok(())
+}
+
+/// Run a user-provided command through the shell, with the given environment variables set.
+pub fn run_hook(command: &str, env: &[(&str, String)]) -> io::Result<ExitStatus> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    shell
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .status()
+}
@@ -0,0 +1,90 @@
+//! Tracking time spent on call, such as standing by for pages outside working hours, separately
+//! from time worked.
+//!
+//! Tracking periods with the on-call tag (`on-call` by default) are left out of the time worked
+//! counted by `punch count`, `punch report` and `punch balance`, which show the time on call as a
+//! total of its own. A period on call that also has one of the incident tags (`incident` by
+//! default) was spent responding to an incident, and counts as time worked after all:
+//!
+//! ```toml
+//! [on_call]
+//! tag = "pager"
+//! incident_tags = ["incident", "outage"]
+//! ```
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Event, Sheet};
+
+/// Settings for telling time on call apart from time worked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnCall {
+    /// The tag marking tracking periods spent on call (default: `"on-call"`).
+    pub tag: String,
+    /// The tags marking tracking periods on call during which an incident was responded to, which
+    /// count as time worked (default: `["incident"]`).
+    pub incident_tags: Vec<String>,
+}
+
+impl Default for OnCall {
+    fn default() -> Self {
+        OnCall {
+            tag: "on-call".to_owned(),
+            incident_tags: vec!["incident".to_owned()],
+        }
+    }
+}
+
+impl OnCall {
+    /// Whether the given event was spent on call.
+    pub fn is_on_call(&self, event: &Event) -> bool {
+        event.tags.contains(&self.tag)
+    }
+
+    /// Whether the given event was spent on call without responding to an incident, and so
+    /// doesn't count as time worked.
+    pub fn is_standby(&self, event: &Event) -> bool {
+        self.is_on_call(event)
+            && !event
+                .tags
+                .iter()
+                .any(|tag| self.incident_tags.contains(tag))
+    }
+
+    /// Get the given sheet without the events spent on call without responding to an incident,
+    /// borrowing it if there are none.
+    pub fn worked<'a>(&self, sheet: &'a Sheet) -> Cow<'a, Sheet> {
+        if !sheet.events().iter().any(|event| self.is_standby(event)) {
+            return Cow::Borrowed(sheet);
+        }
+
+        let events = sheet
+            .events()
+            .iter()
+            .filter(|event| !self.is_standby(event))
+            .cloned()
+            .collect();
+
+        Cow::Owned(Sheet::from_events(events).with_revision(sheet.revision))
+    }
+
+    /// Count the time spent on call between the two given instants, returning the time spent on
+    /// call in total and the part of it spent responding to incidents.
+    pub fn count_range(
+        &self,
+        sheet: &Sheet,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> (Duration, Duration) {
+        (
+            sheet.count_range_where(begin, end, |event| self.is_on_call(event)),
+            sheet.count_range_where(begin, end, |event| {
+                self.is_on_call(event) && !self.is_standby(event)
+            }),
+        )
+    }
+}
@@ -1,32 +1,994 @@
-use chrono::{DateTime, Local};
-use punch_clock::Period;
-use structopt::StructOpt;
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use punch_clock::{
+    history::Operation, invoices::InvoiceStatus, sheet::SheetFormat, Counting, Filter, Period,
+    WorkingHours,
+};
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+
+use crate::export::{Aggregate, Format};
+
+/// The global options of [`Opt`] that take a value, which has to be skipped over when looking for
+/// the command on the command line.
+pub const VALUED_GLOBAL_OPTIONS: &[&str] = &["--user", "--as-of"];
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "punch", about = "Lightweight time-tracking utility.")]
-pub enum Opt {
+pub struct Opt {
+    /// Print the changes that would be made to the sheet, without writing anything.
+    #[structopt(long = "dry-run", global = true)]
+    pub dry_run: bool,
+    /// Answer yes to confirmation prompts before destructive changes, for use in scripts.
+    #[structopt(short = "y", long = "yes", global = true)]
+    pub yes: bool,
+    /// Print nothing but the output of commands such as `export`, reporting failure only through
+    /// the exit status.
+    #[structopt(short = "q", long = "quiet", global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Log diagnostic information, such as the files used and the tracking periods changed, to
+    /// standard error. Give twice for more detail.
+    #[structopt(short = "v", long = "verbose", global = true, parse(from_occurrences))]
+    pub verbose: u8,
+    /// Act on the tracking periods of this user, on a sheet shared by more than one user
+    /// (default: `user` in the config, or the current login name).
+    #[structopt(long = "user", global = true)]
+    pub user: Option<String>,
+    /// Look at the sheet as it was at this time, by undoing the changes recorded in the history
+    /// since, such as to check a report sent before the sheet was edited. Accepts a date (meaning
+    /// midnight at its start) or an RFC 3339 time. Only for commands that don't change the sheet.
+    #[structopt(long = "as-of", global = true, parse(try_from_str = parse_moment))]
+    pub as_of: Option<DateTime<Utc>>,
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
     /// Start tracking time.
     In {
-        /// The time to start the tracking period from (default: now). Currently unimplemented;
-        /// always defaults to now.
+        /// The project to track time for (default: the project configured for the current
+        /// directory, if there is one).
+        project: Option<String>,
+        /// A tag describing the work being done. Can be given more than once.
+        #[structopt(long = "tag")]
+        tags: Vec<String>,
+        /// Mark the time as billable to a client.
+        #[structopt(short = "b", long = "billable")]
+        billable: bool,
+        /// The time to start the tracking period from (default: now).
         #[structopt(short = "t", long = "time")]
         time: Option<DateTime<Local>>,
+        /// If the last tracking period ends after the time, or is ongoing, cut it short to end at
+        /// the time instead of refusing.
+        #[structopt(short = "f", long = "force")]
+        force: bool,
     },
     /// Stop tracking time.
     Out {
-        /// The time to end the tracking period at (default: now). Currently unimplemented; always
-        /// defaults to now.
+        /// The time to end the tracking period at (default: now).
         #[structopt(short = "t", long = "time")]
         time: Option<DateTime<Local>>,
+        /// If the time is before the start of the ongoing tracking period, record the time between
+        /// the two instead of refusing.
+        #[structopt(short = "f", long = "force")]
+        force: bool,
+    },
+    /// Check whether currently punched in, and if so, since when, together with the time worked
+    /// today and this week.
+    Status {
+        /// Print the status for a status bar instead: waybar prints the JSON read by Waybar's
+        /// custom modules, tmux a coloured segment for tmux's status line, and starship just the
+        /// project and length of the ongoing tracking period, exiting with a non-zero status if
+        /// not punched in.
+        #[structopt(long = "format")]
+        format: Option<StatusFormat>,
     },
-    /// Check whether currently punched in, and if so, since when.
-    Status,
     /// Count the amount of time worked over a certain period of time.
     Count {
         /// Period of time to count from. Values for <period> include: all, today, yesterday, week,
-        /// month, last week, last month. Shortened versions of these values are also available,
-        /// such as "t" for "today".
+        /// month, last week, last month, fiscal year (or year), last fiscal year (or last year),
+        /// fiscal quarter (or quarter), last fiscal quarter (or last quarter). Shortened versions
+        /// of these values are also available, such as "t" for "today".
+        #[structopt(default_value = "today")]
+        period: Period,
+        /// Instead of counting one period, compare the time worked in the first of these periods
+        /// with the time worked in the second, such as `--compare week "last week"`.
+        #[structopt(
+            long = "compare",
+            number_of_values = 2,
+            value_names = &["period", "baseline"],
+            conflicts_with = "period"
+        )]
+        compare: Vec<Period>,
+        #[structopt(flatten)]
+        counting: CountingOpt,
+    },
+    /// Compare the amount of time worked over a certain period of time with the amount expected by
+    /// the configured schedule.
+    Balance {
+        /// Period of time to compare over. Accepts the same values as for `count`.
+        #[structopt(default_value = "week")]
+        period: Period,
+    },
+    /// List the stretches of working hours on working days in a certain period of time that no
+    /// tracking period covers, so that forgotten time can be filled in.
+    Gaps {
+        /// Period of time to look for gaps in. Accepts the same values as for `count`.
+        #[structopt(default_value = "week")]
+        period: Period,
+        /// The working hours to look for gaps in, such as 09:00-17:00 (default:
+        /// `schedule.working_hours` in the config).
+        #[structopt(long = "working-hours")]
+        working_hours: Option<WorkingHours>,
+        /// Only list gaps at least this many minutes long.
+        #[structopt(long = "min", default_value = "1")]
+        min: i64,
+    },
+    /// Draw the tracking periods on each day in a certain period of time as bars on a 24-hour
+    /// axis, showing how the days were structured.
+    Timeline {
+        /// Period of time to draw tracking periods from. Accepts the same values as for `count`.
         #[structopt(default_value = "today")]
         period: Period,
     },
+    /// Show a month as a calendar, with the hours worked on each day.
+    Cal {
+        /// The month to show, such as 2024-06 (default: the current month).
+        #[structopt(parse(try_from_str = parse_month))]
+        month: Option<NaiveDate>,
+    },
+    /// Total the amount earned in a certain period of time on each project, at the hourly rates
+    /// in the config.
+    Earnings {
+        /// Period of time to count earnings from. Accepts the same values as for `count`.
+        #[structopt(default_value = "month")]
+        period: Period,
+        /// Only count billable tracking periods.
+        #[structopt(short = "b", long = "billable")]
+        billable: bool,
+    },
+    /// Create invoices for the time worked on a project, and keep track of them.
+    Invoice(Invoice),
+    /// Add a line of text to the note of the ongoing tracking period.
+    Note {
+        /// The text to add.
+        text: String,
+    },
+    /// List the tracking periods in a certain period of time.
+    Report {
+        /// Period of time to list tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "today")]
+        period: Period,
+        /// Also list the commits made in the git repository at this path during each tracking
+        /// period.
+        #[structopt(long = "with-commits")]
+        with_commits: Option<PathBuf>,
+        /// Instead of listing tracking periods, total the time worked by each user of a shared
+        /// sheet on each project.
+        #[structopt(long = "team")]
+        team: bool,
+        /// With --team, read a sheet for each user from the files in this directory, counting
+        /// tracking periods without a user as belonging to the user named by the file.
+        #[structopt(long = "sheets", requires = "team")]
+        sheets: Option<PathBuf>,
+        /// With --team, print the totals as CSV, in hours.
+        #[structopt(long = "csv", requires = "team")]
+        csv: bool,
+        /// Instead of listing tracking periods, compare the time worked on each project that has
+        /// a budget with the budget, over the period the budget is for.
+        #[structopt(long = "budgets", conflicts_with = "team")]
+        budgets: bool,
+        /// Include archived projects in the comparison with budgets.
+        #[structopt(long = "include-archived", requires = "budgets")]
+        include_archived: bool,
+        /// Instead of listing tracking periods, compare the time worked on each project and task
+        /// that has an estimate with the estimate.
+        #[structopt(long = "estimates", conflicts_with_all = &["team", "budgets"])]
+        estimates: bool,
+        /// Instead of listing tracking periods, total the time worked in each week (by ISO week
+        /// number), with the change from the week before, or average the time worked on each day
+        /// of the week (weekday).
+        #[structopt(long = "by", conflicts_with_all = &["team", "budgets", "estimates"])]
+        by: Option<Grouping>,
+        /// Instead of listing tracking periods, total the time worked in each month of this period,
+        /// with a sparkline and the change from the month before.
+        #[structopt(
+            long = "trend",
+            conflicts_with_all = &["period", "team", "budgets", "estimates", "by"]
+        )]
+        trend: Option<Period>,
+        #[structopt(flatten)]
+        counting: CountingOpt,
+    },
+    /// Search the projects, tags and notes of the tracking periods for words, listing the tracking
+    /// periods in which all of them appear, ignoring case.
+    Search {
+        /// The words to search for, or with --regex, a regular expression.
+        query: String,
+        /// Search for a regular expression instead of words.
+        #[structopt(long = "regex")]
+        regex: bool,
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+    /// Check the tracking periods in a certain period of time for suspicious data: tracking
+    /// periods that are very long or shorter than a minute, days with too much time tracked, and
+    /// duplicated tracking periods.
+    Doctor {
+        /// Period of time to check tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "all")]
+        period: Period,
+        /// Record the checksum of the sheet as it is now, instead of checking it, after making sure
+        /// that a sheet which doesn't match its checksum is fine as it is.
+        #[structopt(long = "accept-checksum")]
+        accept_checksum: bool,
+    },
+    /// Edit the tracking periods in a certain period of time as text in an editor.
+    Edit {
+        /// Period of time to edit tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "today")]
+        period: Period,
+        /// The editor to use (default: $VISUAL or $EDITOR).
+        #[structopt(long = "editor")]
+        editor: Option<String>,
+    },
+    /// Submit the tracking periods in a certain period of time for review, freezing them until
+    /// they're approved, rejected or unlocked.
+    Submit {
+        /// Period of time to submit tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "last week")]
+        period: Period,
+    },
+    /// Approve the submitted tracking periods in a certain period of time. Only reviewers listed
+    /// in the config can approve tracking periods.
+    Approve {
+        /// Period of time to approve tracking periods from. Accepts the same values as for
+        /// `count`.
+        #[structopt(default_value = "last week")]
+        period: Period,
+    },
+    /// Reject the submitted tracking periods in a certain period of time, so that they can be
+    /// changed and submitted again. Only reviewers listed in the config can reject tracking
+    /// periods.
+    Reject {
+        /// Period of time to reject tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "last week")]
+        period: Period,
+    },
+    /// Unlock the submitted or approved tracking periods in a certain period of time, so that
+    /// they can be changed again. Only reviewers listed in the config can unlock approved tracking
+    /// periods.
+    Unlock {
+        /// Period of time to unlock tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "last week")]
+        period: Period,
+    },
+    /// Import tracking periods from a CSV file.
+    Import {
+        /// The CSV file to import from. The first row must contain the name of each column.
+        file: PathBuf,
+        /// Confirm or correct the meaning of each column, and preview the result before
+        /// importing.
+        #[structopt(short = "i", long = "interactive")]
+        interactive: bool,
+    },
+    /// Merge the conflicted copies of the sheet that sync tools such as Dropbox and Syncthing left
+    /// next to it into the sheet, adding `.merged` to the name of each copy merged.
+    MergeConflicts,
+    /// Delete every tracking period selected by the filter options, after asking for
+    /// confirmation.
+    Delete {
+        #[structopt(flatten)]
+        filter: FilterOpt,
+        /// Delete every tracking period when no filter options are given.
+        #[structopt(long = "all")]
+        all: bool,
+    },
+    /// Add tags to or remove tags from every tracking period selected by the filter options,
+    /// after asking for confirmation.
+    Retag {
+        /// A tag to add. Can be given more than once.
+        #[structopt(long = "add-tag", required_unless = "remove-tag")]
+        add_tag: Vec<String>,
+        /// A tag to remove. Can be given more than once.
+        #[structopt(long = "remove-tag")]
+        remove_tag: Vec<String>,
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+    /// Rename or merge tags on every tracking period that has them.
+    Tag(Tag),
+    /// Rename, merge, archive or list projects.
+    Project(Project),
+    /// Move the start and stop of every tracking period selected by the filter options by the
+    /// same amount of time, such as after tracking a day in the wrong timezone.
+    Shift {
+        /// The amount of time to move the tracking periods by, such as `-2h`, `+30m` or `1h15m`.
+        #[structopt(long = "by", allow_hyphen_values = true, parse(try_from_str = parse_offset))]
+        by: Duration,
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+    /// Repair tracking periods whose local times were stored as if they were UTC, such as by an
+    /// import that ignored timezones, by reinterpreting their times as recorded in a timezone.
+    /// The sheet file is backed up first.
+    MigrateTz {
+        /// The timezone the times were really recorded in: `local`, `UTC` or an offset from UTC
+        /// such as `+02:00`.
+        #[structopt(long = "assume-recorded-in", allow_hyphen_values = true)]
+        assume_recorded_in: Zone,
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+    /// Export tracking periods, to standard output or a file.
+    Export {
+        /// The format to export in: csv, json, beancount, ledger or (if built with the xlsx
+        /// feature) xlsx.
+        #[structopt(short = "f", long = "format", default_value = "csv")]
+        format: Format,
+        /// Export as CSV in the layout of this export profile from the config instead, such as
+        /// one expected by a payroll system.
+        #[structopt(short = "p", long = "profile", conflicts_with = "format")]
+        profile: Option<String>,
+        /// Export the total time worked in each day, week or month instead of each tracking
+        /// period: as CSV, in hours with a column for each project, or as JSON, in seconds.
+        #[structopt(long = "aggregate", conflicts_with = "profile")]
+        aggregate: Option<Aggregate>,
+        /// With --aggregate and JSON, break each period's total down by project (project).
+        #[structopt(long = "by", possible_values = &["project"], requires = "aggregate")]
+        by: Option<String>,
+        /// The file to write to (default: standard output).
+        #[structopt(short = "o", long = "output")]
+        output: Option<PathBuf>,
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+    /// Push tracking periods to other services.
+    Push(Push),
+    /// Push tracking periods to other services and pull the events there into the sheet.
+    Sync(Sync),
+    /// Serve the tracking periods over HTTP until killed, as an iCalendar feed that calendar
+    /// applications can subscribe to, or as a GraphQL API for dashboards.
+    Serve {
+        /// Serve an iCalendar feed of the tracking periods.
+        #[structopt(long = "ical")]
+        ical: bool,
+        /// Serve a GraphQL API at /graphql, which can query the tracking periods and totals, punch
+        /// in and out, and change tracking periods (if built with the graphql feature).
+        #[structopt(long = "graphql")]
+        graphql: bool,
+        /// Let web pages from this origin (e.g. `http://localhost:3000`) send requests to the
+        /// GraphQL API from a browser.
+        #[structopt(long = "allow-origin")]
+        allow_origin: Option<String>,
+        /// The address to listen on.
+        #[structopt(long = "address", default_value = "127.0.0.1:8737")]
+        address: String,
+    },
+    /// Print the JSON Schema describing the format of the sheet file and JSON exports.
+    Schema,
+    /// Run commands one after another in an interactive shell, keeping the sheet in memory between
+    /// them.
+    Shell,
+    /// Print a script that completes commands in a shell, including the names of projects and tags
+    /// in the sheet.
+    Completions {
+        /// The shell to complete commands in.
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
+    /// Print the names of the projects or tags in the sheet, one per line, for completion scripts.
+    #[structopt(setting = AppSettings::Hidden)]
+    Complete {
+        /// What to print the names of: projects or tags.
+        #[structopt(possible_values = &["projects", "tags"])]
+        names: String,
+    },
+    /// Run a Rhai script with access to the tracking periods in the sheet, for custom reports and
+    /// checks.
+    #[cfg(feature = "scripting")]
+    Script {
+        /// The script to run.
+        path: PathBuf,
+        /// Arguments to pass to the script, as `ARGS`.
+        args: Vec<String>,
+    },
+    /// Convert the sheet to a different storage format, replacing the old sheet file.
+    Convert {
+        /// The format to convert to: json, jsonl, toml or msgpack.
+        format: SheetFormat,
+    },
+    /// List the changes made to the sheet, with when and by whom they were made.
+    History {
+        /// Period of time to list changes made in. Accepts the same values as for `count`.
+        #[structopt(default_value = "all")]
+        period: Period,
+    },
+    /// Compare another sheet file, or the sheet in a backup written by `dump`, with the sheet,
+    /// listing the tracking periods added, removed and changed since the other was written.
+    Diff {
+        /// The sheet file or backup to compare with.
+        file: PathBuf,
+        /// List the changes from the sheet to the other instead, such as what restoring a backup
+        /// would change.
+        #[structopt(short = "r", long = "reverse")]
+        reverse: bool,
+    },
+    /// Take, list, restore or delete named copies of the sheet, such as from before a big
+    /// cleanup.
+    Snapshot(Snapshot),
+    /// Undo the most recent changes made to the sheet.
+    Undo {
+        /// The number of changes to undo.
+        #[structopt(default_value = "1")]
+        steps: usize,
+    },
+    /// Make changes again after undoing them.
+    Redo {
+        /// The number of changes to redo.
+        #[structopt(default_value = "1")]
+        steps: usize,
+    },
+    /// Rewrite the sheet file in full and rebuild the cache of daily totals kept next to it,
+    /// moving tracking periods older than `storage.retention` in the config to the archive.
+    Compact,
+    /// Check the signatures of the tracking periods in a certain period of time, listing the ones
+    /// that have been changed since they were signed.
+    Verify {
+        /// Period of time to check tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "all")]
+        period: Period,
+        /// The public key to check the signatures with, in hexadecimal (default: the public key for
+        /// the signing key next to the config file).
+        #[structopt(long = "public-key")]
+        public_key: Option<String>,
+        /// Print the public key for the signing key, to give to whoever checks the signatures.
+        #[structopt(long = "print-key", conflicts_with = "public-key")]
+        print_key: bool,
+    },
+    /// Write a backup of the config, hooks, sheet, archive, history and invoices as a gzipped tar
+    /// archive, such as for moving to another machine.
+    Dump {
+        /// The file to write the backup to (default: standard output).
+        #[structopt(short = "o", long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Restore the files in a backup written by `dump`, replacing any already there.
+    Restore {
+        /// The backup to restore.
+        backup: PathBuf,
+    },
+    /// Salvage the tracking periods that can still be read from a sheet that can't be loaded,
+    /// setting the rest of the file aside in a `.broken` file next to it.
+    Repair,
+    /// Install integrations with other tools.
+    Hook(Hook),
+    /// Check once whether anything needs attention, such as from cron: still being punched in
+    /// since before today or for too long, or not being punched in during working hours. Prints
+    /// nothing and exits successfully if not, or prints what needs attention and exits with an
+    /// error.
+    Nag,
+    /// Keep running in the background, checking the sheet periodically and sending a notification
+    /// when the time worked today reaches the amount expected by the schedule.
+    Watch,
+    /// Install or uninstall a service of the operating system that runs `punch watch` in the
+    /// background. Only supported on Windows and macOS.
+    Service(Service),
+}
+
+impl Command {
+    /// Whether the command only reads or changes the last tracking period.
+    pub fn only_touches_last(&self) -> bool {
+        matches!(
+            self,
+            Command::In { .. }
+                | Command::Out { .. }
+                | Command::Note { .. }
+                | Command::Status {
+                    format: Some(StatusFormat::Starship)
+                }
+                | Command::Nag
+        )
+    }
+
+    /// The operation recorded in the history for changes made by the command, if it can change
+    /// the sheet.
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Command::In { .. } => Some(Operation::PunchIn),
+            Command::Out { .. } => Some(Operation::PunchOut),
+            Command::Note { .. } => Some(Operation::Note),
+            Command::Edit { .. } => Some(Operation::Edit),
+            Command::Import { .. } | Command::MergeConflicts => Some(Operation::Import),
+            Command::Submit { .. } => Some(Operation::Submit),
+            Command::Approve { .. } => Some(Operation::Approve),
+            Command::Reject { .. } => Some(Operation::Reject),
+            Command::Unlock { .. } => Some(Operation::Unlock),
+            Command::Delete { .. } => Some(Operation::Delete),
+            Command::Retag { .. } | Command::Tag(_) => Some(Operation::Retag),
+            Command::Shift { .. } => Some(Operation::Shift),
+            Command::MigrateTz { .. } => Some(Operation::MigrateTz),
+            Command::Project(Project::Rename { .. }) => Some(Operation::RenameProject),
+            Command::Compact => Some(Operation::Purge),
+            Command::Snapshot(Snapshot::Restore { .. }) => Some(Operation::RestoreSnapshot),
+            Command::Sync(_) => Some(Operation::Import),
+            _ => None,
+        }
+    }
+
+    /// Whether the command can change the sheet, so that it needs to be written afterwards.
+    pub fn changes_sheet(&self) -> bool {
+        #[cfg(feature = "scripting")]
+        if let Command::Script { .. } = self {
+            return false;
+        }
+
+        !matches!(
+            self,
+            Command::Status { .. }
+                | Command::Count { .. }
+                | Command::Balance { .. }
+                | Command::Gaps { .. }
+                | Command::Timeline { .. }
+                | Command::Cal { .. }
+                | Command::Serve { .. }
+                | Command::Search { .. }
+                | Command::Doctor { .. }
+                | Command::Earnings { .. }
+                | Command::Report { .. }
+                | Command::Export { .. }
+                | Command::Schema
+                | Command::Shell
+                | Command::Completions { .. }
+                | Command::Complete { .. }
+                | Command::History { .. }
+                | Command::Diff { .. }
+                | Command::Snapshot(
+                    Snapshot::Create { .. } | Snapshot::List | Snapshot::Delete { .. }
+                )
+                | Command::Project(
+                    Project::Archive { .. } | Project::Unarchive { .. } | Project::List { .. }
+                )
+                | Command::Verify { .. }
+                | Command::Dump { .. }
+                | Command::Restore { .. }
+                | Command::Repair
+                | Command::Hook(_)
+                | Command::Service(_)
+                | Command::Nag
+                | Command::Push(_)
+                | Command::Invoice(_)
+        )
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Hook {
+    /// Install a git post-commit hook that adds the subject of each commit to the note of the
+    /// ongoing tracking period.
+    InstallGit {
+        /// The git repository to install the hook in (default: the current directory).
+        #[structopt(default_value = ".")]
+        repo: PathBuf,
+        /// Replace an existing post-commit hook.
+        #[structopt(short = "f", long = "force")]
+        force: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Snapshot {
+    /// Take a snapshot of the sheet as it is now.
+    Create {
+        /// The name of the snapshot, such as `before-cleanup`.
+        name: String,
+        /// Replace a snapshot that already has the name.
+        #[structopt(short = "f", long = "force")]
+        force: bool,
+    },
+    /// List the snapshots, with when they were taken and how many tracking periods they hold.
+    List,
+    /// Put the sheet back as it was when a snapshot was taken. This can be undone.
+    Restore {
+        /// The name of the snapshot to restore.
+        name: String,
+    },
+    /// Delete a snapshot.
+    Delete {
+        /// The name of the snapshot to delete.
+        name: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Invoice {
+    /// Create an invoice for the finished tracking periods on a project in a certain period of
+    /// time, at the hourly rates in the config, and add it to the invoice registry.
+    Create {
+        /// The project to invoice.
+        client: String,
+        /// Period of time to invoice tracking periods from. Accepts the same values as for
+        /// `count`.
+        #[structopt(default_value = "last month")]
+        period: Period,
+        /// Only invoice billable tracking periods.
+        #[structopt(short = "b", long = "billable")]
+        billable: bool,
+        /// The file to write the invoice to (default: standard output).
+        #[structopt(short = "o", long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// List the invoices in the registry.
+    List,
+    /// Change the status of an invoice: draft, sent, paid or void.
+    Mark {
+        /// The number of the invoice.
+        number: String,
+        /// The new status of the invoice.
+        status: InvoiceStatus,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Push {
+    /// Append the finished tracking periods in a certain period of time to a Google Sheet,
+    /// skipping any that were pushed before.
+    Gsheets {
+        /// The ID of the spreadsheet, as found in its URL.
+        #[structopt(long = "spreadsheet")]
+        spreadsheet: String,
+        /// The name of the sheet within the spreadsheet to append to.
+        #[structopt(long = "sheet", default_value = "Sheet1")]
+        sheet: String,
+        /// Push the total time worked on each day instead of each tracking period.
+        #[structopt(long = "daily")]
+        daily: bool,
+        /// Period of time to push tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "yesterday")]
+        period: Period,
+    },
+    /// Add the finished tracking periods in a certain period of time to a Kimai server as
+    /// timesheets, skipping any that are already there.
+    Kimai {
+        /// Period of time to push tracking periods from. Accepts the same values as for `count`.
+        #[structopt(default_value = "yesterday")]
+        period: Period,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Service {
+    /// Install the service and start it, checking the sheet of the current user.
+    Install,
+    /// Stop the service and remove it.
+    Uninstall,
+    /// Run as the service, as started by the operating system.
+    #[structopt(setting = AppSettings::Hidden)]
+    Run,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Sync {
+    /// Store the finished tracking periods in a certain period of time as events in a calendar on a
+    /// CalDAV server, replacing any pushed before, and add the events in that period from a second
+    /// calendar to the sheet.
+    Caldav {
+        /// Period of time to sync. Accepts the same values as for `count`.
+        #[structopt(default_value = "this week")]
+        period: Period,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Tag {
+    /// Give a tag a new name that isn't used yet.
+    Rename {
+        /// The tag to rename.
+        old: String,
+        /// The new name of the tag.
+        new: String,
+    },
+    /// Replace a tag with another one that's already used, such as a misspelling of it.
+    Merge {
+        /// The tag to replace.
+        from: String,
+        /// The tag to replace it with.
+        into: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Project {
+    /// Give a project a new name on every tracking period and in the config, including its
+    /// budget, estimate, rate, Kimai name, directories, repositories and aliases.
+    Rename {
+        /// The project to rename.
+        old: String,
+        /// The new name of the project.
+        new: String,
+        /// Merge the project into another that already exists, keeping that project's settings.
+        #[structopt(long = "merge")]
+        merge: bool,
+    },
+    /// Archive a project that's no longer worked on, leaving it out of completions, detection
+    /// and lists of projects, but not out of reports.
+    Archive {
+        /// The project to archive.
+        project: String,
+    },
+    /// Stop a project being archived.
+    Unarchive {
+        /// The project to unarchive.
+        project: String,
+    },
+    /// List the projects in the sheet.
+    List {
+        /// Include archived projects.
+        #[structopt(long = "include-archived")]
+        include_archived: bool,
+    },
+}
+
+// Options for selecting a subset of tracking periods. This isn't a doc comment, as structopt would
+// use it as the description of every command that flattens the struct.
+#[derive(Debug, StructOpt)]
+pub struct FilterOpt {
+    /// Only include time on or after this date (YYYY-MM-DD).
+    #[structopt(long = "from")]
+    pub from: Option<NaiveDate>,
+    /// Only include time on or before this date (YYYY-MM-DD).
+    #[structopt(long = "to")]
+    pub to: Option<NaiveDate>,
+    /// Only include tracking periods for this project.
+    #[structopt(long = "project")]
+    pub project: Option<String>,
+    /// Only include tracking periods with this tag.
+    #[structopt(long = "tag")]
+    pub tag: Option<String>,
+    /// Only include billable tracking periods.
+    #[structopt(long = "billable-only")]
+    pub billable_only: bool,
+}
+
+// Options for whether breaks are counted as time worked. As with `FilterOpt`, this isn't a doc
+// comment.
+#[derive(Debug, StructOpt)]
+pub struct CountingOpt {
+    /// Count the breaks between tracking periods on the same day as time worked (default:
+    /// `counting` in the config).
+    #[structopt(long = "gross", conflicts_with = "net")]
+    pub gross: bool,
+    /// Don't count the breaks between tracking periods as time worked.
+    #[structopt(long = "net")]
+    pub net: bool,
+}
+
+impl CountingOpt {
+    /// Get how to count time, given the default from the config.
+    pub fn counting(&self, default: Counting) -> Counting {
+        match (self.gross, self.net) {
+            (true, _) => Counting::Gross,
+            (_, true) => Counting::Net,
+            _ => default,
+        }
+    }
+}
+
+impl FilterOpt {
+    /// Convert these options into a filter.
+    pub fn to_filter(&self) -> Filter {
+        Filter {
+            project: self.project.clone(),
+            tag: self.tag.clone(),
+            billable_only: self.billable_only,
+            ..Filter::default()
+        }
+        .with_dates(self.from, self.to)
+    }
+}
+
+/// How `punch report --by` groups the time worked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grouping {
+    /// By ISO 8601 week.
+    Week,
+    /// By day of the week, averaged.
+    Weekday,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "week" | "w" => Ok(Grouping::Week),
+            "weekday" | "wd" => Ok(Grouping::Weekday),
+            _ => Err("Grouping not recognised: use week or weekday.".into()),
+        }
+    }
+}
+
+/// A format for `punch status --format`, for status bars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusFormat {
+    /// JSON with the text, tooltip and class of a Waybar custom module.
+    Waybar,
+    /// A short segment of tmux's status line, coloured with tmux's style syntax.
+    Tmux,
+    /// The project and length of the ongoing tracking period, for prompts such as Starship's
+    /// custom modules, which only needs the last tracking period to be read.
+    Starship,
+}
+
+impl FromStr for StatusFormat {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "waybar" => Ok(StatusFormat::Waybar),
+            "tmux" => Ok(StatusFormat::Tmux),
+            "starship" | "porcelain" => Ok(StatusFormat::Starship),
+            _ => Err("Status format not recognised: use waybar, tmux or starship.".into()),
+        }
+    }
+}
+
+/// A timezone that times may have been recorded in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    /// The system's local timezone, including any daylight saving time.
+    Local,
+    /// A fixed offset from UTC.
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    /// Get the instant at which a clock in this timezone showed the given time.
+    ///
+    /// Times that are skipped by a change to daylight saving time are taken to be in the offset
+    /// from before the change.
+    pub fn to_utc(self, naive: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Zone::Local => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(Into::into)
+                .unwrap_or_else(|| {
+                    let offset = Local.offset_from_utc_datetime(&naive).fix();
+                    Utc.from_utc_datetime(&(naive - offset))
+                }),
+            Zone::Fixed(offset) => Utc.from_utc_datetime(&(naive - offset)),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || "Timezone not recognised: use local, UTC or an offset such as +02:00.";
+
+        if raw.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+
+        let rest = match raw.get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("utc") => &raw[3..],
+            _ => raw,
+        };
+
+        if rest.is_empty() || rest == "Z" {
+            return Ok(Zone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+
+        let (sign, rest) = if let Some(rest) = rest.strip_prefix('+') {
+            (1, rest)
+        } else if let Some(rest) = rest.strip_prefix('-') {
+            (-1, rest)
+        } else {
+            return Err(invalid().into());
+        };
+
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((hours, minutes)) => (hours, minutes),
+            None if rest.len() == 4 && rest.is_ascii() => rest.split_at(2),
+            None => (rest, "0"),
+        };
+
+        // Only digits are allowed, so that neither part can have a sign of its own.
+        let number = |digits: &str| {
+            digits
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| digits.parse::<u32>().ok())
+                .flatten()
+        };
+        let (hours, minutes) = match (number(hours), number(minutes)) {
+            (Some(hours), Some(minutes)) if hours <= 23 && minutes < 60 => (hours, minutes),
+            _ => return Err(invalid().into()),
+        };
+
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60) as i32)
+            .map(Zone::Fixed)
+            .ok_or_else(|| invalid().into())
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Zone::Local => write!(f, "local time"),
+            Zone::Fixed(offset) => write!(f, "UTC{}", offset),
+        }
+    }
+}
+
+/// Parse a moment written as an RFC 3339 time, or as a date standing for midnight at its start in
+/// local time.
+fn parse_moment(raw: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(time.into());
+    }
+
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .map(|date| Zone::Local.to_utc(date.and_time(Default::default())))
+        .map_err(|_| {
+            format!(
+                "{:?} is not a date or time, such as 2024-05-01 or 2024-05-01T17:30:00+02:00",
+                raw
+            )
+        })
+}
+
+/// Parse a month written as `YYYY-MM`, returning its first day.
+fn parse_month(raw: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", raw), "%Y-%m-%d")
+        .map_err(|_| format!("{:?} is not a month, such as 2024-06", raw))
+}
+
+/// Parse an amount of time to move tracking periods by, made of whole numbers of hours, minutes
+/// and seconds such as `1h30m`, optionally preceded by a sign.
+fn parse_offset(raw: &str) -> Result<Duration, String> {
+    let invalid = || format!("{:?} is not an amount of time, such as -2h or 1h30m", raw);
+
+    let (sign, rest) = match raw.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+
+    let mut offset = Duration::zero();
+    let mut number = String::new();
+
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+
+        offset += match c {
+            'h' => Duration::hours(amount),
+            'm' => Duration::minutes(amount),
+            's' => Duration::seconds(amount),
+            _ => return Err(invalid()),
+        };
+    }
+
+    if !number.is_empty() || rest.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(offset * sign)
 }
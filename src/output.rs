@@ -0,0 +1,80 @@
+//! Printing messages for the user, and recording whether a command failed.
+//!
+//! Messages are printed with [`say!`] and [`fail!`] rather than `println!`, so that `--quiet` can
+//! suppress them while still exiting with a non-zero status when something went wrong.
+
+use std::{
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tracing::Level;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Set up output for the given `--quiet` flag and number of `-v` flags.
+///
+/// Diagnostic logging goes to standard error: by default only warnings are logged, `-v` adds
+/// debugging information such as the paths used, the events touched and how long things took, and
+/// `-vv` adds more detail still. `--quiet` turns logging off entirely.
+pub fn init(quiet: bool, verbose: u8) {
+    QUIET.store(quiet, Ordering::Relaxed);
+
+    if quiet {
+        return;
+    }
+
+    let level = match verbose {
+        0 => Level::WARN,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(io::stderr)
+        .init();
+}
+
+/// Whether messages for the user should be suppressed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Record that the command failed, so that the process exits with a non-zero status.
+pub fn set_failed() {
+    FAILED.store(true, Ordering::Relaxed);
+}
+
+/// Forget that a command failed, so that the process doesn't exit with a non-zero status for it.
+pub fn clear_failed() {
+    FAILED.store(false, Ordering::Relaxed);
+}
+
+/// Whether the command failed.
+pub fn failed() -> bool {
+    FAILED.load(Ordering::Relaxed)
+}
+
+/// Print a message for the user, [translated][tr] into their language, unless `--quiet` was given.
+///
+/// [tr]: ../i18n/index.html
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!("{}", tr!($($arg)*));
+        }
+    };
+}
+
+/// Print a message saying why something couldn't be done, unless `--quiet` was given, and make the
+/// process exit with a non-zero status.
+macro_rules! fail {
+    ($($arg:tt)*) => {{
+        $crate::output::set_failed();
+        say!($($arg)*);
+    }};
+}
@@ -0,0 +1,129 @@
+//! Paying some time at a multiple of the usual rate, such as time beyond 40 hours in a week, or
+//! all time worked on Sundays and holidays.
+//!
+//! Rules are written in the config file as follows:
+//!
+//! ```toml
+//! [rates]
+//! holidays = ["2024-12-25", "2024-12-26"]
+//!
+//! [[rates.overtime]]
+//! after_weekly = 40
+//! multiplier = 1.5
+//!
+//! [[rates.overtime]]
+//! weekdays = ["sun"]
+//! holidays = true
+//! multiplier = 2
+//! ```
+//!
+//! Where more than one rule applies to the same time, it's paid at the highest multiplier.
+
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{period::midnight, Sheet};
+
+/// A rule paying some time at a multiple of the usual rate.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OvertimeRule {
+    /// The number the usual rate is multiplied by.
+    pub multiplier: f64,
+    /// Apply to the time worked in an ISO week beyond this many hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_weekly: Option<f64>,
+    /// Apply to the time worked in a day beyond this many hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_daily: Option<f64>,
+    /// Apply to all the time worked on these days of the week.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<Weekday>,
+    /// Apply to all the time worked on the holidays listed with the rates.
+    #[serde(default)]
+    pub holidays: bool,
+}
+
+impl OvertimeRule {
+    /// Get how much of a stretch of time worked on the given date, of the given length, the rule
+    /// applies to, given the time worked earlier that day and earlier that week.
+    ///
+    /// Thresholds are crossed partway through a stretch, so the time a rule applies to is always
+    /// the end of it.
+    fn applies_to(
+        &self,
+        date: NaiveDate,
+        holiday: bool,
+        length: Duration,
+        (day, week): (Duration, Duration),
+    ) -> Duration {
+        let beyond = |worked: Duration, hours: f64| {
+            let threshold = Duration::seconds((hours * 3600.0).round() as i64);
+            (worked + length - threshold.max(worked)).clamp(Duration::zero(), length)
+        };
+
+        let on_day = self.weekdays.contains(&date.weekday()) || (self.holidays && holiday);
+
+        [
+            on_day.then_some(length),
+            self.after_daily.map(|hours| beyond(day, hours)),
+            self.after_weekly.map(|hours| beyond(week, hours)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_else(Duration::zero)
+    }
+}
+
+/// Split a stretch of time worked on a single local date, starting at `start` and lasting
+/// `length`, into parts paid at different multiples of the usual rate according to the given
+/// rules, counting the time worked before it from the given sheet. Time no rule applies to is paid
+/// at a multiple of 1.
+pub fn split(
+    rules: &[OvertimeRule],
+    holidays: &[NaiveDate],
+    sheet: &Sheet,
+    start: DateTime<Utc>,
+    length: Duration,
+) -> Vec<(f64, Duration)> {
+    if rules.is_empty() {
+        return vec![(1.0, length)];
+    }
+
+    let date = start.with_timezone(&chrono::Local).date_naive();
+    let monday = date - Days::new(date.weekday().num_days_from_monday().into());
+    let worked = (
+        sheet.count_range(midnight(date).into(), start),
+        sheet.count_range(midnight(monday).into(), start),
+    );
+    let holiday = holidays.contains(&date);
+
+    let mut applying: Vec<_> = rules
+        .iter()
+        .map(|rule| {
+            (
+                rule.multiplier,
+                rule.applies_to(date, holiday, length, worked),
+            )
+        })
+        .collect();
+    applying.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    // Each rule applies to the end of the stretch, so what's left to a rule is the part of its end
+    // that rules with higher multipliers haven't already taken.
+    let mut parts = Vec::new();
+    let mut taken = Duration::zero();
+
+    for (multiplier, time) in applying {
+        if time > taken {
+            parts.push((multiplier, time - taken));
+            taken = time;
+        }
+    }
+
+    if taken < length {
+        parts.push((1.0, length - taken));
+    }
+
+    parts
+}
@@ -3,8 +3,15 @@ use std::{
     str::FromStr,
 };
 
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
 /// Represents a period of time relative to now.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// In the config file, a period is written as any of the values accepted on the command line,
+/// such as `"month"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum Period {
     /// The period of time that began at the start of the first tracked event.
     All,
@@ -25,6 +32,190 @@ pub enum Period {
     /// The period of time between the midnights at the beginning of the last two occurrences of
     /// days whose numbers were 1 (including the current day).
     LastMonth,
+    /// The period of time that began at midnight at the start of the last day that occurred on
+    /// which a fiscal year started (including the current day).
+    FiscalYear,
+    /// The fiscal year before the current one.
+    LastFiscalYear,
+    /// The period of time that began at midnight at the start of the last day that occurred on
+    /// which a quarter of a fiscal year started (including the current day).
+    FiscalQuarter,
+    /// The quarter of a fiscal year before the current one.
+    LastFiscalQuarter,
+}
+
+impl Period {
+    /// Get the instants at which this period begins and ends, relative to the given current time.
+    ///
+    /// Since [`Period::All`][all] has no fixed beginning, it is considered to begin at the
+    /// earliest representable instant.
+    ///
+    /// [all]: #variant.All
+    pub fn range(&self, now: DateTime<Local>) -> (DateTime<Utc>, DateTime<Utc>) {
+        self.range_in(now, YearStart::default())
+    }
+
+    /// Get the instants at which this period begins and ends, like [`range()`][range], with
+    /// fiscal years starting on the given day.
+    ///
+    /// [range]: #method.range
+    pub fn range_in(
+        &self,
+        now: DateTime<Local>,
+        year_start: YearStart,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        let today = now.date_naive();
+
+        let (start, end) = match self {
+            Period::All => return (DateTime::<Utc>::MIN_UTC, now.into()),
+            Period::Today => (midnight(today), now),
+            Period::Yesterday => (midnight(today - Days::new(1)), midnight(today)),
+            Period::Week => (midnight(last_monday(today)), now),
+            Period::LastWeek => {
+                let last_monday = last_monday(today);
+                (midnight(last_monday - Days::new(7)), midnight(last_monday))
+            }
+            Period::Month => (midnight(month_first(today)), now),
+            Period::LastMonth => {
+                let month_first = month_first(today);
+                let last_month_first = self::month_first(month_first - Days::new(1));
+                (midnight(last_month_first), midnight(month_first))
+            }
+            Period::FiscalYear => (midnight(year_start.last(today)), now),
+            Period::LastFiscalYear => {
+                let first = year_start.last(today);
+                (
+                    midnight(year_start.last(first - Days::new(1))),
+                    midnight(first),
+                )
+            }
+            Period::FiscalQuarter => (midnight(year_start.last_quarter(today)), now),
+            Period::LastFiscalQuarter => {
+                let first = year_start.last_quarter(today);
+                (
+                    midnight(year_start.last_quarter(first - Days::new(1))),
+                    midnight(first),
+                )
+            }
+        };
+
+        (start.into(), end.into())
+    }
+}
+
+/// Get the instant of local midnight at the start of the given date.
+///
+/// If midnight doesn't exist on that date in the local timezone (because of a daylight saving
+/// transition), the equivalent UTC midnight is used instead.
+pub fn midnight(date: NaiveDate) -> DateTime<Local> {
+    local_instant(date, NaiveTime::MIN)
+}
+
+/// Get the instant at the given local time on the given local date.
+///
+/// Times that don't exist on that date in the local timezone (because of a daylight saving
+/// transition) are taken as UTC instead.
+pub fn local_instant(date: NaiveDate, time: NaiveTime) -> DateTime<Local> {
+    let naive = date.and_time(time);
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&naive))
+}
+
+/// Get the last Monday that occurred, including the given date.
+fn last_monday(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+/// Get the first day of the month that the given date is in.
+fn month_first(date: NaiveDate) -> NaiveDate {
+    date - Days::new((date.day() - 1).into())
+}
+
+/// The month and day on which each fiscal year starts, written as `MM-DD` (e.g. `"04-06"` for
+/// the 6th of April).
+///
+/// If the day doesn't exist in a year (the 29th of February), the last day of the month is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct YearStart {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl YearStart {
+    /// Get the date on which the fiscal year starting in the given calendar year begins.
+    fn in_year(self, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, self.month, self.day)
+            .or_else(|| NaiveDate::from_ymd_opt(year, self.month, self.day - 1))
+            .unwrap()
+    }
+
+    /// Get the last date on which a fiscal year started, including the given date.
+    fn last(self, date: NaiveDate) -> NaiveDate {
+        let start = self.in_year(date.year());
+
+        if start <= date {
+            start
+        } else {
+            self.in_year(date.year() - 1)
+        }
+    }
+
+    /// Get the last date on which a quarter of a fiscal year started, including the given date.
+    fn last_quarter(self, date: NaiveDate) -> NaiveDate {
+        let year = self.last(date);
+
+        (0..4)
+            .map(|quarter| year + Months::new(quarter * 3))
+            .take_while(|start| *start <= date)
+            .last()
+            .unwrap_or(year)
+    }
+}
+
+impl Default for YearStart {
+    fn default() -> Self {
+        YearStart { month: 1, day: 1 }
+    }
+}
+
+impl FromStr for YearStart {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a month and day, such as \"04-06\"", raw);
+        let (month, day) = raw.split_once('-').ok_or_else(invalid)?;
+        let month = month.parse().map_err(|_| invalid())?;
+        let day = day.parse().map_err(|_| invalid())?;
+
+        // 2000 is a leap year, so every valid month and day exists in it.
+        NaiveDate::from_ymd_opt(2000, month, day).ok_or_else(invalid)?;
+
+        Ok(YearStart { month, day })
+    }
+}
+
+impl TryFrom<String> for YearStart {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<YearStart> for String {
+    fn from(start: YearStart) -> Self {
+        start.to_string()
+    }
+}
+
+impl Display for YearStart {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
 }
 
 impl FromStr for Period {
@@ -32,18 +223,44 @@ impl FromStr for Period {
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         match raw {
-            "all" | "a" => Ok(Period::All),
+            "all" | "all-time" | "a" => Ok(Period::All),
             "today" | "t" => Ok(Period::Today),
             "yesterday" | "y" => Ok(Period::Yesterday),
             "week" | "this week" | "w" | "tw" => Ok(Period::Week),
             "last week" | "lastweek" | "lw" => Ok(Period::LastWeek),
             "month" | "this month" | "m" | "tm" => Ok(Period::Month),
             "last month" | "lastmonth" | "lm" => Ok(Period::LastMonth),
+            "fiscal year" | "this fiscal year" | "fy" | "tfy" | "year" | "this year" => {
+                Ok(Period::FiscalYear)
+            }
+            "last fiscal year" | "lfy" | "last year" => Ok(Period::LastFiscalYear),
+            "fiscal quarter"
+            | "this fiscal quarter"
+            | "fq"
+            | "tfq"
+            | "quarter"
+            | "this quarter"
+            | "q" => Ok(Period::FiscalQuarter),
+            "last fiscal quarter" | "lfq" | "last quarter" | "lq" => Ok(Period::LastFiscalQuarter),
             _ => Err("Time period not recognised.".into()),
         }
     }
 }
 
+impl TryFrom<String> for Period {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<Period> for String {
+    fn from(period: Period) -> Self {
+        period.to_string().to_lowercase()
+    }
+}
+
 impl Display for Period {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
@@ -54,6 +271,10 @@ impl Display for Period {
             Period::LastWeek => write!(f, "Last Week"),
             Period::Month => write!(f, "This Month"),
             Period::LastMonth => write!(f, "Last Month"),
+            Period::FiscalYear => write!(f, "This Fiscal Year"),
+            Period::LastFiscalYear => write!(f, "Last Fiscal Year"),
+            Period::FiscalQuarter => write!(f, "This Fiscal Quarter"),
+            Period::LastFiscalQuarter => write!(f, "Last Fiscal Quarter"),
         }
     }
 }
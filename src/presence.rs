@@ -0,0 +1,186 @@
+//! Punching in and out automatically when the screen is unlocked and locked.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use chrono::{Local, Utc};
+use punch_clock::{
+    history::{Change, Operation},
+    sheet::SheetStatus,
+    Config, Event,
+};
+use tracing::{debug, warn};
+use zbus::{
+    blocking::{Connection, MessageIterator},
+    zvariant::OwnedObjectPath,
+};
+
+use crate::{format_time, load_sheet, record_change, write_sheet};
+
+/// The D-Bus interfaces of screen savers on the session bus. GNOME uses its own interface in place
+/// of the freedesktop one.
+const SCREEN_SAVERS: [&str; 2] = ["org.freedesktop.ScreenSaver", "org.gnome.ScreenSaver"];
+
+/// The well-known name of logind on the system bus.
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Start listening for the screen being locked and unlocked in background threads.
+///
+/// Screen savers announce locking with their `ActiveChanged` signal on the session bus, and logind
+/// with the `Lock` and `Unlock` signals of the session on the system bus, which are all that some
+/// desktops (and `loginctl lock-session`) send.
+pub fn spawn(config: Config) {
+    let (sender, receiver) = mpsc::channel();
+
+    let screen_saver = sender.clone();
+    thread::spawn(move || {
+        if let Err(err) = listen_screen_saver(&screen_saver) {
+            warn!("Unable to listen for screen saver signals: {}", err);
+        }
+    });
+
+    thread::spawn(move || {
+        if let Err(err) = listen_logind(&sender) {
+            warn!("Unable to listen for logind lock signals: {}", err);
+        }
+    });
+
+    thread::spawn(move || act_on_locks(&config, receiver));
+}
+
+/// Send whether the screen is locked each time a screen saver is activated or deactivated.
+fn listen_screen_saver(locks: &Sender<bool>) -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let rule = "type='signal',member='ActiveChanged'";
+
+    for message in MessageIterator::for_match_rule(rule, &connection, None)? {
+        let message = message?;
+        let header = message.header();
+
+        if !header
+            .interface()
+            .is_some_and(|name| SCREEN_SAVERS.contains(&name.as_str()))
+        {
+            continue;
+        }
+
+        if locks.send(message.body().deserialize()?).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Send whether the screen is locked each time logind asks the current session to lock or unlock.
+fn listen_logind(locks: &Sender<bool>) -> zbus::Result<()> {
+    let connection = Connection::system()?;
+
+    // The special session ID `auto` stands for the caller's session, or if it isn't in one (such
+    // as when running as a service), the user's graphical session.
+    let session: OwnedObjectPath = connection
+        .call_method(
+            Some(LOGIND),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "GetSession",
+            &("auto",),
+        )?
+        .body()
+        .deserialize()?;
+    debug!(session = %session.as_str(), "listening for logind lock signals");
+
+    let rule = format!(
+        "type='signal',sender='{}',interface='org.freedesktop.login1.Session',path='{}'",
+        LOGIND,
+        session.as_str()
+    );
+
+    for message in MessageIterator::for_match_rule(rule.as_str(), &connection, None)? {
+        let message = message?;
+        let locked = match message.header().member().map(|member| member.as_str()) {
+            Some("Lock") => true,
+            Some("Unlock") => false,
+            _ => continue,
+        };
+
+        if locks.send(locked).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Punch out when the screen is locked, and back in when it's unlocked.
+///
+/// Only punch-outs made because of locking cause a punch-in on unlocking, so unlocking the screen
+/// never starts tracking time that wasn't being tracked before it was locked. The tracking period
+/// started on unlocking has the project, tags and billable flag of the one stopped on locking.
+fn act_on_locks(config: &Config, locks: Receiver<bool>) {
+    let mut closed_on_lock: Option<Event> = None;
+
+    for locked in locks {
+        if !config.watch.presence.active_at(Local::now().time()) {
+            continue;
+        }
+
+        let mut sheet = match load_sheet(config) {
+            Ok(sheet) => sheet,
+            Err(err) => {
+                warn!("Unable to check whether punched in: {}", err.describe());
+                continue;
+            }
+        };
+        let before = sheet.clone();
+
+        match (locked, sheet.status()) {
+            (true, SheetStatus::PunchedIn(_)) => {
+                if let Ok(time_utc) = sheet.punch_out_at(Utc::now()) {
+                    say!("Screen locked; punching out at {}.", format_time(time_utc));
+                    match write_sheet(config, &sheet) {
+                        Ok(true) => {
+                            if let Some(change) =
+                                Change::between(Operation::PunchOut, &before, &sheet)
+                            {
+                                record_change(config, &change);
+                            }
+                            closed_on_lock = sheet.events().last().cloned();
+                        }
+                        Ok(false) => {}
+                        Err(err) => warn!("Unable to punch out: {}", err.describe()),
+                    }
+                }
+            }
+            (false, SheetStatus::PunchedOut(_)) => {
+                let Some(closed) = closed_on_lock.take() else {
+                    continue;
+                };
+                let event = Event::new(Utc::now())
+                    .with_user(Some(config.user()))
+                    .with_project(closed.project)
+                    .with_tags(closed.tags)
+                    .with_billable(closed.billable);
+
+                if let Ok(time_utc) = sheet.punch_in_with(event) {
+                    say!("Screen unlocked; punching in at {}.", format_time(time_utc));
+                    match write_sheet(config, &sheet) {
+                        Ok(true) => {
+                            if let Some(change) =
+                                Change::between(Operation::PunchIn, &before, &sheet)
+                            {
+                                record_change(config, &change);
+                            }
+                        }
+                        Ok(false) => {}
+                        Err(err) => warn!("Unable to punch in: {}", err.describe()),
+                    }
+                }
+            }
+            (false, _) => closed_on_lock = None,
+            _ => {}
+        }
+    }
+}
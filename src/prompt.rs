@@ -0,0 +1,40 @@
+//! Asking the user questions in the terminal.
+
+use std::io::{self, Write};
+
+use crate::i18n;
+
+/// Ask the user a question, returning their answer with surrounding whitespace removed, or `None`
+/// if there's no more input.
+pub fn ask(question: &str) -> Option<String> {
+    print!("{} ", question);
+    io::stdout().flush().ok();
+
+    let mut answer = String::new();
+
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_owned()),
+    }
+}
+
+/// Ask the user a yes-or-no question, returning `true` if they answer yes, either in English or
+/// in their language.
+pub fn confirm(question: &str) -> bool {
+    let answer = ask(&tr!("{} [y/N]", question))
+        .unwrap_or_default()
+        .to_lowercase();
+
+    ["y", "yes"]
+        .iter()
+        .any(|yes| answer == *yes || answer == i18n::translate(yes))
+}
+
+/// Ask the user to confirm a destructive action, unless `yes` is set (by `--yes`), in which case
+/// the action is confirmed without asking.
+///
+/// Without `--yes`, running out of input counts as declining, so scripts never make destructive
+/// changes by accident.
+pub fn confirm_destructive(question: &str, yes: bool) -> bool {
+    yes || confirm(question)
+}
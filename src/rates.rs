@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    differential::Differential,
+    overtime::{self, OvertimeRule},
+    period::midnight,
+    totals::split_days,
+    Event, Sheet,
+};
+
+/// The hourly rates charged for work, by default and on particular projects, together with any
+/// changes to those rates that took effect on later dates.
+///
+/// Rates are written in the config file as follows:
+///
+/// ```toml
+/// [rates]
+/// hourly = 80
+/// currency = "EUR"
+///
+/// [rates.projects.acme]
+/// hourly = 95
+/// currency = "USD"
+///
+/// [[rates.projects.acme.changes]]
+/// from = "2024-07-01"
+/// hourly = 110
+/// ```
+///
+/// Projects without a rate of their own on a date are charged at the default rate, if there is
+/// one, in the default currency. Some of the time may be charged at a multiple of the rate by
+/// [overtime rules][overtime], or at more than the rate at certain times of day by
+/// [differentials][differential]. Invoices for projects with [tiers][tiers] are charged by them
+/// instead.
+///
+/// [overtime]: ../overtime/index.html
+/// [differential]: ../differential/index.html
+/// [tiers]: struct.RateHistory.html#structfield.tiers
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rates {
+    /// The rate charged for projects that aren't listed in [`projects`][projects].
+    ///
+    /// [projects]: #structfield.projects
+    #[serde(flatten)]
+    pub default: RateHistory,
+    /// The rate charged for each project, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, RateHistory>,
+    /// Rules charging some time at a multiple of the rate, such as time beyond 40 hours a week.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overtime: Vec<OvertimeRule>,
+    /// The dates of holidays, for overtime rules that apply on them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub holidays: Vec<NaiveDate>,
+    /// The times of day at which time is charged at more than the rate, such as night shifts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub differentials: Vec<Differential>,
+}
+
+impl Rates {
+    /// Get the hourly rate charged for the given project on the given date, and the currency it's
+    /// in, if there is one: the project's own rate, or if it has none on that date, the default
+    /// rate.
+    pub fn hourly_on(&self, project: Option<&str>, date: NaiveDate) -> Option<(f64, Option<&str>)> {
+        let own = project
+            .and_then(|project| self.projects.get(project))
+            .and_then(|history| {
+                let hourly = history.hourly_on(date)?;
+                let currency = history
+                    .currency
+                    .as_deref()
+                    .or(self.default.currency.as_deref());
+                Some((hourly, currency))
+            });
+
+        own.or_else(|| {
+            Some((
+                self.default.hourly_on(date)?,
+                self.default.currency.as_deref(),
+            ))
+        })
+    }
+
+    /// Get the tiers invoices for the given project are charged by, and the currency they're in,
+    /// if there is one: the project's own tiers, or if it has none, the default tiers. Returns
+    /// `None` if neither has tiers.
+    pub fn tiers_for(&self, project: Option<&str>) -> Option<(&[Tier], Option<&str>)> {
+        let own = project
+            .and_then(|project| self.projects.get(project))
+            .filter(|history| !history.tiers.is_empty())
+            .map(|history| {
+                let currency = history
+                    .currency
+                    .as_deref()
+                    .or(self.default.currency.as_deref());
+                (&history.tiers[..], currency)
+            });
+
+        own.or_else(|| {
+            (!self.default.tiers.is_empty())
+                .then(|| (&self.default.tiers[..], self.default.currency.as_deref()))
+        })
+    }
+
+    /// Get the amount earned by the part of the given event on the given sheet between the two
+    /// given instants, charging the time on each local date at the rate that applied on it, any
+    /// overtime at a multiple of it, and the time within any differential's hours at the extra
+    /// percentage on top. Time on dates without a rate earns nothing.
+    ///
+    /// The time worked before the event is counted from the sheet, for overtime rules that apply
+    /// beyond a number of hours.
+    pub fn earned(
+        &self,
+        sheet: &Sheet,
+        event: &Event,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Amounts {
+        let start = event.start.max(begin);
+        let stop = event.stop.unwrap_or_else(Utc::now).min(end);
+        let mut amounts = Amounts::default();
+
+        for (date, time) in split_days(start, stop) {
+            if let Some((hourly, currency)) = self.hourly_on(event.project.as_deref(), date) {
+                let day_start = start.max(midnight(date).into());
+                let overtime: f64 =
+                    overtime::split(&self.overtime, &self.holidays, sheet, day_start, time)
+                        .into_iter()
+                        .map(|(multiplier, time)| multiplier * time.num_seconds() as f64 / 3600.0)
+                        .sum();
+                let differentials: f64 = self
+                    .differentials
+                    .iter()
+                    .map(|differential| {
+                        let time = differential.overlap(day_start, day_start + time);
+                        differential.percent / 100.0 * time.num_seconds() as f64 / 3600.0
+                    })
+                    .sum();
+                let hours = overtime + differentials;
+
+                amounts.add(currency, hourly * hours);
+            }
+        }
+
+        amounts
+    }
+}
+
+/// Amounts of money, kept separate by currency so that amounts in different currencies are never
+/// added together.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Amounts {
+    /// The amount in each currency, by its code. Amounts charged at rates without a currency are
+    /// kept under `None`.
+    pub by_currency: BTreeMap<Option<String>, f64>,
+}
+
+impl Amounts {
+    /// Add an amount in the given currency.
+    pub fn add(&mut self, currency: Option<&str>, amount: f64) {
+        *self
+            .by_currency
+            .entry(currency.map(str::to_owned))
+            .or_insert(0.0) += amount;
+    }
+
+    /// Add every amount in `other`.
+    pub fn add_all(&mut self, other: &Amounts) {
+        for (currency, amount) in &other.by_currency {
+            self.add(currency.as_deref(), *amount);
+        }
+    }
+
+    /// Get the amounts multiplied by the given factor.
+    pub fn scaled(&self, factor: f64) -> Amounts {
+        Amounts {
+            by_currency: self
+                .by_currency
+                .iter()
+                .map(|(currency, amount)| (currency.clone(), amount * factor))
+                .collect(),
+        }
+    }
+
+    /// Whether the amounts are in more than one currency.
+    pub fn is_mixed(&self) -> bool {
+        self.by_currency.len() > 1
+    }
+}
+
+/// An hourly rate, together with any changes to it that took effect on later dates.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateHistory {
+    /// The rate that applies before the first change (or always, if there are none).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hourly: Option<f64>,
+    /// The code of the currency the rate is in, such as `EUR` (default: the currency of the
+    /// default rate, if it has one).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Changes to the rate, each of which applies from its date onwards.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<RateChange>,
+    /// Bands of hours charged at rates of their own in each invoice, such as the first 20 hours at
+    /// one rate and the rest at another, in place of the hourly rate and any overtime and
+    /// differentials.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<Tier>,
+}
+
+impl RateHistory {
+    /// Get the hourly rate that applies on the given date, if there is one.
+    pub fn hourly_on(&self, date: NaiveDate) -> Option<f64> {
+        self.changes
+            .iter()
+            .filter(|change| change.from <= date)
+            .max_by_key(|change| change.from)
+            .map(|change| change.hourly)
+            .or(self.hourly)
+    }
+}
+
+/// A band of the hours in an invoice charged at a rate of its own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tier {
+    /// The number of hours into the invoice at which the band ends, or `None` for a band covering
+    /// the rest of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up_to: Option<f64>,
+    /// The hourly rate charged for the hours in the band.
+    pub hourly: f64,
+}
+
+/// Split a number of hours among the given tiers, in order of where they end, returning the
+/// hourly rate and number of hours charged at it for each tier some of the hours fall in. Hours
+/// beyond the end of the last tier are charged at its rate.
+pub fn split_tiers(tiers: &[Tier], hours: f64) -> Vec<(f64, f64)> {
+    let mut sorted: Vec<_> = tiers.iter().collect();
+    sorted.sort_by(|a, b| {
+        let end = |tier: &Tier| tier.up_to.unwrap_or(f64::INFINITY);
+        end(a).total_cmp(&end(b))
+    });
+
+    let mut parts = Vec::new();
+    let mut charged = 0.0;
+
+    for (i, tier) in sorted.iter().enumerate() {
+        let end = match tier.up_to {
+            Some(up_to) if i + 1 < sorted.len() => up_to.min(hours),
+            _ => hours,
+        };
+
+        if end > charged {
+            parts.push((tier.hourly, end - charged));
+            charged = end;
+        }
+    }
+
+    parts
+}
+
+/// A change to an hourly rate that takes effect from a certain date.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateChange {
+    /// The first date on which the new rate applies.
+    pub from: NaiveDate,
+    /// The new hourly rate.
+    pub hourly: f64,
+}
@@ -0,0 +1,358 @@
+//! Keeping the sheet on a WebDAV server or in S3-compatible object storage.
+//!
+//! The local sheet is a copy of the one on the server. Before each command, the server's sheet is
+//! fetched if it has changed, and after the local sheet is written, it's stored on the server, but
+//! only if the server's sheet hasn't changed in the meantime, which is checked with the entity tag
+//! it had when it was last fetched or stored. The tag is kept in `remote.json` next to the sheet,
+//! along with the checksum of the local copy at the time, so that changes that couldn't be stored,
+//! such as while offline, are stored later. If both have changed, the local tracking periods are
+//! merged into the server's sheet, which keeps its own version of any changed on both sides.
+//!
+//! Requests are made with `curl`, logging in with the configured user name and the password in
+//! `PUNCH_REMOTE_PASSWORD` or printed by a configured command.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use punch_clock::{
+    checksum::{self, Checksum},
+    config::{RemoteConfig, RemoteKind},
+    sheet::SheetError,
+    Sheet,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::http::{self, HttpError};
+
+/// Where a sheet is stored other than in a local file.
+pub trait SheetStore {
+    /// Download the stored sheet to the file at the given path, unless its entity tag is `etag`.
+    fn fetch(&self, to: &Path, etag: Option<&str>) -> Result<Fetched, RemoteError>;
+
+    /// Upload the file at the given path as the stored sheet, but only if the stored sheet's
+    /// entity tag is `etag`, or if there's no stored sheet when `etag` is `None`, returning the
+    /// new entity tag if there is one.
+    fn store(&self, from: &Path, etag: Option<&str>) -> Result<Option<String>, RemoteError>;
+}
+
+/// What fetching a stored sheet found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fetched {
+    /// The sheet hasn't changed.
+    Unchanged,
+    /// There's no sheet stored.
+    Missing,
+    /// The sheet has changed, and has been downloaded, with its new entity tag if there is one.
+    Changed(Option<String>),
+}
+
+/// What bringing the local copy of the sheet up to date did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pulled {
+    /// Neither the local copy nor the server's sheet had changed.
+    Unchanged,
+    /// The local copy was replaced with the server's sheet.
+    Fetched,
+    /// Changes to the local copy were stored on the server.
+    Stored,
+    /// Both had changed, so the given number of local tracking periods were merged into the
+    /// server's sheet.
+    Merged(usize),
+}
+
+/// A sheet on a WebDAV server.
+struct WebDav {
+    url: String,
+    login: Option<String>,
+}
+
+impl SheetStore for WebDav {
+    fn fetch(&self, to: &Path, etag: Option<&str>) -> Result<Fetched, RemoteError> {
+        fetch_with(&self.url, &self.options(), to, etag)
+    }
+
+    fn store(&self, from: &Path, etag: Option<&str>) -> Result<Option<String>, RemoteError> {
+        store_with(&self.url, &self.options(), from, etag)
+    }
+}
+
+impl WebDav {
+    /// The options passed to `curl` to log in.
+    fn options(&self) -> Vec<(&str, String)> {
+        self.login
+            .iter()
+            .map(|login| ("user", login.clone()))
+            .collect()
+    }
+}
+
+/// A sheet in an S3 bucket.
+struct S3 {
+    url: String,
+    login: String,
+    region: String,
+}
+
+impl SheetStore for S3 {
+    fn fetch(&self, to: &Path, etag: Option<&str>) -> Result<Fetched, RemoteError> {
+        fetch_with(&self.url, &self.options(), to, etag)
+    }
+
+    fn store(&self, from: &Path, etag: Option<&str>) -> Result<Option<String>, RemoteError> {
+        store_with(&self.url, &self.options(), from, etag)
+    }
+}
+
+impl S3 {
+    /// The options passed to `curl` to sign requests.
+    fn options(&self) -> Vec<(&str, String)> {
+        vec![
+            ("user", self.login.clone()),
+            ("aws-sigv4", format!("aws:amz:{}:s3", self.region)),
+        ]
+    }
+}
+
+/// What's known about the server's sheet, kept next to the local copy.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct State {
+    /// The entity tag of the server's sheet when it was last fetched or stored.
+    etag: Option<String>,
+    /// The checksum of the local copy at the time.
+    sha256: Option<String>,
+}
+
+impl State {
+    /// Get the path to the file the state is kept in for the sheet at the given path.
+    fn loc_for(sheet_path: &Path) -> PathBuf {
+        sheet_path.with_file_name("remote.json")
+    }
+
+    /// Load the state for the sheet at the given path, or the default state if there's none.
+    fn load(sheet_path: &Path) -> State {
+        fs::read(Self::loc_for(sheet_path))
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Record the state for the sheet at the given path, with the given entity tag and the
+    /// checksum of the sheet as it is now.
+    fn record(sheet_path: &Path, etag: Option<String>) -> Result<(), RemoteError> {
+        let state = State {
+            etag,
+            sha256: Some(checksum::digest(sheet_path).map_err(RemoteError::ReadCopy)?),
+        };
+
+        fs::write(
+            Self::loc_for(sheet_path),
+            serde_json::to_vec(&state).unwrap(),
+        )
+        .map_err(RemoteError::WriteCopy)
+    }
+}
+
+/// Errors arising while keeping the sheet on a server.
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("no access key: set storage.remote.username")]
+    NoAccessKey,
+    #[error("unable to run password command")]
+    RunPasswordCommand(#[source] io::Error),
+    #[error("no password: set PUNCH_REMOTE_PASSWORD or storage.remote.password_command")]
+    NoPassword,
+    #[error("{0}")]
+    Http(#[from] HttpError),
+    #[error("the server responded with status {0}")]
+    Status(u16),
+    #[error("the sheet on the server has changed since it was last fetched")]
+    Changed,
+    #[error("unable to read the local copy of the sheet")]
+    ReadCopy(#[source] io::Error),
+    #[error("unable to write the local copy of the sheet")]
+    WriteCopy(#[source] io::Error),
+    #[error("unable to merge the local copy into the sheet on the server: {0}")]
+    Merge(SheetError),
+}
+
+/// Bring the local copy of the sheet at the given path up to date with the server's sheet, storing
+/// local changes that haven't been stored yet.
+pub fn pull(config: &RemoteConfig, path: &Path) -> Result<Pulled, RemoteError> {
+    let Some(store) = open(config)? else {
+        return Ok(Pulled::Unchanged);
+    };
+
+    let state = State::load(path);
+    let local = checksum::digest(path).ok();
+    let changed_locally = local.is_some() && local != state.sha256;
+
+    // Local changes have to be merged into a changed sheet, so it's fetched next to the copy.
+    let fetched_path = if changed_locally {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("remote.{}", name))
+    } else {
+        path.to_owned()
+    };
+
+    match store.fetch(&fetched_path, state.etag.as_deref())? {
+        Fetched::Unchanged if changed_locally => {
+            let etag = store.store(path, state.etag.as_deref())?;
+            State::record(path, etag)?;
+            Ok(Pulled::Stored)
+        }
+        Fetched::Missing if local.is_some() => {
+            let etag = store.store(path, None)?;
+            State::record(path, etag)?;
+            Ok(Pulled::Stored)
+        }
+        Fetched::Unchanged | Fetched::Missing => Ok(Pulled::Unchanged),
+        Fetched::Changed(etag) if changed_locally => {
+            let mut merged = Sheet::load(&fetched_path).map_err(RemoteError::Merge)?;
+            let local = Sheet::load(path).map_err(RemoteError::Merge)?;
+            let revision = merged.revision.max(local.revision) + 1;
+            let added = merged
+                .merge(local.into_events())
+                .map_err(RemoteError::Merge)?;
+            merged.revision = revision;
+
+            let _ = fs::remove_file(&fetched_path);
+            merged.write(path).map_err(RemoteError::Merge)?;
+            record_checksum(path, merged.events().len());
+
+            let etag = store.store(path, etag.as_deref())?;
+            State::record(path, etag)?;
+            Ok(Pulled::Merged(added.len()))
+        }
+        Fetched::Changed(etag) => {
+            let events = Sheet::load(path)
+                .map_err(RemoteError::Merge)?
+                .events()
+                .len();
+            record_checksum(path, events);
+
+            State::record(path, etag)?;
+            Ok(Pulled::Fetched)
+        }
+    }
+}
+
+/// Store the local copy of the sheet at the given path on the server, unless the server's sheet
+/// has changed since it was last fetched.
+pub fn push(config: &RemoteConfig, path: &Path) -> Result<(), RemoteError> {
+    let Some(store) = open(config)? else {
+        return Ok(());
+    };
+
+    let state = State::load(path);
+    let etag = store.store(path, state.etag.as_deref())?;
+
+    State::record(path, etag)
+}
+
+/// Get where the sheet is stored according to the given config, or `None` if it's only kept
+/// locally.
+fn open(config: &RemoteConfig) -> Result<Option<Box<dyn SheetStore>>, RemoteError> {
+    let Some(url) = config.url.clone() else {
+        return Ok(None);
+    };
+
+    let login = match &config.username {
+        Some(username) => http::token("PUNCH_REMOTE_PASSWORD", config.password_command.as_deref())
+            .map_err(RemoteError::RunPasswordCommand)?
+            .ok_or(RemoteError::NoPassword)
+            .map(|password| Some(format!("{}:{}", username, password)))?,
+        None => None,
+    };
+
+    Ok(Some(match config.kind {
+        RemoteKind::Webdav => Box::new(WebDav { url, login }),
+        RemoteKind::S3 => Box::new(S3 {
+            url,
+            login: login.ok_or(RemoteError::NoAccessKey)?,
+            region: config
+                .region
+                .clone()
+                .unwrap_or_else(|| "us-east-1".to_owned()),
+        }),
+    }))
+}
+
+/// Download the sheet at the given URL to the given path with the given options for `curl`,
+/// unless its entity tag is `etag`.
+fn fetch_with(
+    url: &str,
+    options: &[(&str, String)],
+    to: &Path,
+    etag: Option<&str>,
+) -> Result<Fetched, RemoteError> {
+    // The response is only moved into place if it's the sheet, rather than an error page.
+    let download = to.with_file_name("remote.part");
+
+    if let Some(dir) = download.parent() {
+        fs::create_dir_all(dir).map_err(RemoteError::WriteCopy)?;
+    }
+
+    let output = download.to_string_lossy();
+    let mut options: Vec<(&str, &str)> = options
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    options.push(("output", &output));
+
+    let header = etag.map(|etag| format!("If-None-Match: {}", etag));
+    let headers: Vec<&str> = header.iter().map(String::as_str).collect();
+    let response = http::transfer("GET", url, &options, &headers)?;
+
+    let fetched = match response.status {
+        200 => fs::rename(&download, to)
+            .map(|_| Fetched::Changed(response.header("etag").map(str::to_owned)))
+            .map_err(RemoteError::WriteCopy),
+        304 => Ok(Fetched::Unchanged),
+        404 => Ok(Fetched::Missing),
+        status => Err(RemoteError::Status(status)),
+    };
+
+    let _ = fs::remove_file(&download);
+
+    fetched
+}
+
+/// Upload the file at the given path as the sheet at the given URL with the given options for
+/// `curl`, but only if the stored sheet's entity tag is `etag`, or there's none if it's `None`.
+fn store_with(
+    url: &str,
+    options: &[(&str, String)],
+    from: &Path,
+    etag: Option<&str>,
+) -> Result<Option<String>, RemoteError> {
+    let upload = from.to_string_lossy();
+    let mut options: Vec<(&str, &str)> = options
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    options.push(("upload-file", &upload));
+    options.push(("output", if cfg!(windows) { "NUL" } else { "/dev/null" }));
+
+    let header = match etag {
+        Some(etag) => format!("If-Match: {}", etag),
+        None => "If-None-Match: *".to_owned(),
+    };
+    let response = http::transfer("PUT", url, &options, &[header.as_str()])?;
+
+    match response.status {
+        200..=299 => Ok(response.header("etag").map(str::to_owned)),
+        412 => Err(RemoteError::Changed),
+        status => Err(RemoteError::Status(status)),
+    }
+}
+
+/// Record the checksum of the local copy of the sheet after replacing it.
+fn record_checksum(path: &Path, events: usize) {
+    if let Err(err) = Checksum::record(path, events) {
+        warn!("Unable to record the checksum of the sheet: {}", err);
+    }
+}
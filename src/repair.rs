@@ -0,0 +1,217 @@
+//! Salvaging what can be read from a sheet file that can't be loaded, such as one that was cut
+//! short or edited by hand.
+//!
+//! Every tracking period that can be parsed on its own is kept, and the rest of the file is set
+//! aside as it is, so that it can be looked at and fixed by hand. The parts set aside are kept in a
+//! file next to the sheet, named after it with `.broken` added (e.g. `sheet.json.broken`).
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    sheet::{parse_line, Line, SheetFormat},
+    Event, Sheet,
+};
+
+/// What could be salvaged from a sheet file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Salvaged {
+    /// The tracking periods that could be parsed, and the revision the file was at, if it could be
+    /// read.
+    pub sheet: Sheet,
+    /// The parts of the file that couldn't be parsed, in the order they appear in it.
+    pub broken: Vec<String>,
+}
+
+impl Salvaged {
+    /// Get the path to the file the broken parts of the sheet at the given path are set aside in.
+    pub fn loc_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut path = sheet_path.as_ref().as_os_str().to_owned();
+        path.push(".broken");
+        PathBuf::from(path)
+    }
+
+    /// Set aside the broken parts of the sheet at the given path, adding them to any set aside
+    /// before.
+    pub fn set_aside<P>(&self, sheet_path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::loc_for(sheet_path))?;
+
+        for part in &self.broken {
+            writeln!(file, "{}", part)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Salvage the tracking periods from the contents of a sheet file in the given format, or return
+/// `None` if sheets in that format can't be salvaged, which is the case for MessagePack.
+pub fn salvage(contents: &[u8], format: SheetFormat) -> Option<Salvaged> {
+    let text = String::from_utf8_lossy(contents);
+
+    match format {
+        SheetFormat::Json => Some(salvage_json(&text)),
+        SheetFormat::JsonLines => Some(salvage_json_lines(&text)),
+        SheetFormat::Toml => Some(salvage_toml(&text)),
+        SheetFormat::MessagePack => None,
+    }
+}
+
+/// Salvage the tracking periods from a sheet stored as JSON, by picking out each object in the
+/// `events` array and parsing it on its own.
+fn salvage_json(text: &str) -> Salvaged {
+    let mut salvaged = Salvaged::default();
+
+    let Some(start) = events_array(text) else {
+        salvaged.broken.push(text.trim().to_owned());
+        return salvaged;
+    };
+
+    let mut events = Vec::new();
+    let mut rest = &text[start..];
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+
+        if rest.is_empty() || rest.starts_with(']') {
+            break;
+        }
+
+        if let Some((event, end)) = parse_event(rest) {
+            events.push(event);
+            rest = &rest[end..];
+            continue;
+        }
+
+        // Anything else is set aside up to the next tracking period that can be parsed, or if
+        // there's none, such as when the file was cut short, up to the end of the array.
+        let end = rest
+            .match_indices('{')
+            .map(|(i, _)| i)
+            .filter(|&i| i > 0)
+            .find(|&i| parse_event(&rest[i..]).is_some())
+            .or_else(|| rest.rfind(']'))
+            .unwrap_or(rest.len());
+
+        salvaged
+            .broken
+            .push(rest[..end].trim().trim_end_matches(',').to_owned());
+        rest = &rest[end..];
+    }
+
+    salvaged.sheet =
+        Sheet::from_events(events).with_revision(json_revision(&text[..start]).unwrap_or_default());
+
+    salvaged
+}
+
+/// Salvage the tracking periods from a sheet stored as JSON Lines, line by line.
+fn salvage_json_lines(text: &str) -> Salvaged {
+    let mut salvaged = Salvaged::default();
+    let mut events = Vec::new();
+    let mut revision = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        match parse_line(line, i + 1) {
+            Ok(Line::Header(header_revision)) => revision = revision.max(header_revision),
+            Ok(Line::Event(event)) => events.push(event),
+            Err(_) => salvaged.broken.push(line.to_owned()),
+        }
+    }
+
+    salvaged.sheet = Sheet::from_events(events).with_revision(revision);
+
+    salvaged
+}
+
+/// Salvage the tracking periods from a sheet stored as TOML, by parsing the table for each
+/// tracking period on its own.
+fn salvage_toml(text: &str) -> Salvaged {
+    /// A part of a sheet stored as TOML: the fields before the first tracking period, or the
+    /// table for a single tracking period.
+    #[derive(Deserialize)]
+    struct Part {
+        #[serde(default)]
+        revision: u64,
+        #[serde(default)]
+        events: Vec<Event>,
+    }
+
+    let mut salvaged = Salvaged::default();
+    let mut parts = Vec::new();
+    let mut part = String::new();
+
+    for line in text.lines() {
+        if line.trim() == "[[events]]" {
+            parts.push(std::mem::take(&mut part));
+        }
+
+        part.push_str(line);
+        part.push('\n');
+    }
+
+    parts.push(part);
+
+    let mut events = Vec::new();
+    let mut revision = 0;
+
+    for part in parts.into_iter().filter(|part| !part.trim().is_empty()) {
+        match toml::from_str::<Part>(&part) {
+            Ok(parsed) => {
+                revision = revision.max(parsed.revision);
+                events.extend(parsed.events);
+            }
+            Err(_) => salvaged.broken.push(part.trim_end().to_owned()),
+        }
+    }
+
+    salvaged.sheet = Sheet::from_events(events).with_revision(revision);
+
+    salvaged
+}
+
+/// Find where the contents of the `events` array start in a sheet stored as JSON.
+fn events_array(text: &str) -> Option<usize> {
+    let key = text.find("\"events\"")? + "\"events\"".len();
+    let rest = text[key..].trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('[')?;
+
+    Some(text.len() - rest.len())
+}
+
+/// Parse the tracking period at the start of the given text, returning it along with where it
+/// ends, or `None` if there isn't one.
+fn parse_event(text: &str) -> Option<(Event, usize)> {
+    let mut events = serde_json::Deserializer::from_str(text).into_iter::<Event>();
+
+    match events.next() {
+        Some(Ok(event)) => Some((event, events.byte_offset())),
+        _ => None,
+    }
+}
+
+/// Find the revision recorded before the `events` array in a sheet stored as JSON, if it can be
+/// read.
+fn json_revision(text: &str) -> Option<u64> {
+    let key = text.find("\"revision\"")? + "\"revision\"".len();
+    let rest = text[key..].trim_start().strip_prefix(':')?.trim_start();
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+
+    rest[..digits].parse().ok()
+}
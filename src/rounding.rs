@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{totals::split_days, Event};
+
+/// How tracked time is rounded when it's counted, reported and invoiced, by default and on
+/// particular projects.
+///
+/// Rounding is written in the config file as follows:
+///
+/// ```toml
+/// [rounding]
+/// minutes = 15
+/// mode = "up"
+/// scope = "event"
+///
+/// [rounding.projects.acme]
+/// minutes = 30
+/// mode = "nearest"
+/// scope = "total"
+/// ```
+///
+/// Projects without a policy of their own are rounded by the default policy. Time isn't rounded
+/// at all unless a policy has a number of minutes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rounding {
+    /// The policy for projects that aren't listed in [`projects`][projects].
+    ///
+    /// [projects]: #structfield.projects
+    #[serde(flatten)]
+    pub default: RoundingPolicy,
+    /// The policy for each project, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, RoundingPolicy>,
+}
+
+impl Rounding {
+    /// Whether no policy rounds time at all.
+    pub fn is_none(&self) -> bool {
+        self.default.minutes == 0 && self.projects.values().all(|policy| policy.minutes == 0)
+    }
+
+    /// Get the policy for the given project: its own, or if it has none, the default policy.
+    pub fn policy_for(&self, project: Option<&str>) -> &RoundingPolicy {
+        project
+            .and_then(|project| self.projects.get(project))
+            .unwrap_or(&self.default)
+    }
+
+    /// Get the total time tracked by the parts of the given events between the two given instants,
+    /// rounded by each project's policy. An ongoing event is counted up to the current time.
+    pub fn total<'a, I>(&self, events: I, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration
+    where
+        I: IntoIterator<Item = &'a Event>,
+    {
+        self.round_each(events, begin, end)
+            .into_iter()
+            .fold(Duration::zero(), |sum, time| sum + time)
+    }
+
+    /// Get the time tracked by the part of each of the given events between the two given
+    /// instants, rounded by its project's policy, in the same order, as described for
+    /// [`RoundingPolicy::round_each()`][round_each].
+    ///
+    /// [round_each]: struct.RoundingPolicy.html#method.round_each
+    pub fn round_each<'a, I>(
+        &self,
+        events: I,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<Duration>
+    where
+        I: IntoIterator<Item = &'a Event>,
+    {
+        let events: Vec<&Event> = events.into_iter().collect();
+        let mut by_project: BTreeMap<Option<&str>, Vec<usize>> = BTreeMap::new();
+
+        for (index, event) in events.iter().enumerate() {
+            by_project
+                .entry(event.project.as_deref())
+                .or_default()
+                .push(index);
+        }
+
+        let mut rounded = vec![Duration::zero(); events.len()];
+
+        for (project, indices) in by_project {
+            let times = self.policy_for(project).round_each(
+                indices.iter().map(|&index| events[index]),
+                begin,
+                end,
+            );
+
+            for (index, time) in indices.into_iter().zip(times) {
+                rounded[index] = time;
+            }
+        }
+
+        rounded
+    }
+}
+
+/// A way of rounding tracked time.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoundingPolicy {
+    /// The number of minutes to round to a multiple of, or 0 not to round.
+    pub minutes: u32,
+    /// Which way to round.
+    pub mode: RoundingMode,
+    /// What is rounded: each tracking period, each day's time or only the total.
+    pub scope: RoundingScope,
+}
+
+impl RoundingPolicy {
+    /// Round a length of time to a multiple of the policy's number of minutes.
+    pub fn round(&self, time: Duration) -> Duration {
+        if self.minutes == 0 {
+            return time;
+        }
+
+        let step = i64::from(self.minutes) * 60;
+        let seconds = time.num_seconds();
+        let steps = match self.mode {
+            RoundingMode::Up => (seconds + step - 1).div_euclid(step),
+            RoundingMode::Down => seconds.div_euclid(step),
+            RoundingMode::Nearest => (seconds + step / 2).div_euclid(step),
+        };
+
+        Duration::seconds(steps * step)
+    }
+
+    /// Get the total time tracked by the parts of the given events between the two given instants,
+    /// rounded by this policy. An ongoing event is counted up to the current time.
+    pub fn total<'a, I>(&self, events: I, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration
+    where
+        I: IntoIterator<Item = &'a Event>,
+    {
+        self.round_each(events, begin, end)
+            .into_iter()
+            .fold(Duration::zero(), |sum, time| sum + time)
+    }
+
+    /// Get the time tracked by the part of each of the given events between the two given
+    /// instants, rounded by this policy, in the same order. An ongoing event is counted up to the
+    /// current time.
+    ///
+    /// When each day's time or only the total is rounded, the rounded time is shared out among the
+    /// events in proportion to their time, so that the times always add up to the
+    /// [total][total].
+    ///
+    /// [total]: #method.total
+    pub fn round_each<'a, I>(
+        &self,
+        events: I,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<Duration>
+    where
+        I: IntoIterator<Item = &'a Event>,
+    {
+        let times: Vec<_> = events
+            .into_iter()
+            .map(|event| {
+                let start = event.start.max(begin);
+                let stop = event.stop.unwrap_or_else(Utc::now).min(end);
+
+                (start, stop.max(start))
+            })
+            .collect();
+        let mut rounded = vec![Duration::zero(); times.len()];
+
+        match self.scope {
+            RoundingScope::Event => {
+                for (time, (start, stop)) in rounded.iter_mut().zip(&times) {
+                    *time = self.round(*stop - *start);
+                }
+            }
+            RoundingScope::Day => {
+                let mut days: BTreeMap<NaiveDate, Vec<(usize, Duration)>> = BTreeMap::new();
+
+                for (index, (start, stop)) in times.iter().enumerate() {
+                    for (date, time) in split_days(*start, *stop) {
+                        days.entry(date).or_default().push((index, time));
+                    }
+                }
+
+                for parts in days.values() {
+                    self.share(parts, &mut rounded);
+                }
+            }
+            RoundingScope::Total => {
+                let parts: Vec<_> = times
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (start, stop))| (index, *stop - *start))
+                    .collect();
+
+                self.share(&parts, &mut rounded);
+            }
+        }
+
+        rounded
+    }
+
+    /// Round the total of the given parts of events' time, and share the rounded time out among
+    /// them in proportion to their time, adding each part's share to the time of the event at its
+    /// index.
+    fn share(&self, parts: &[(usize, Duration)], rounded: &mut [Duration]) {
+        let total = parts
+            .iter()
+            .fold(Duration::zero(), |sum, (_, time)| sum + *time);
+        let (seconds, target) = (total.num_seconds(), self.round(total).num_seconds());
+
+        if seconds == 0 {
+            return;
+        }
+
+        // Sharing out the running total keeps the shares adding up to exactly the rounded time.
+        let (mut counted, mut shared) = (Duration::zero(), 0);
+
+        for (index, time) in parts {
+            counted += *time;
+            let share = (i128::from(target) * i128::from(counted.num_seconds())
+                / i128::from(seconds)) as i64;
+            rounded[*index] += Duration::seconds(share - shared);
+            shared = share;
+        }
+    }
+}
+
+/// Which way time is rounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingMode {
+    /// Round up to the next multiple.
+    #[default]
+    Up,
+    /// Round down to the previous multiple.
+    Down,
+    /// Round to the nearest multiple, rounding halfway up.
+    Nearest,
+}
+
+/// What is rounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingScope {
+    /// Each tracking period is rounded separately.
+    #[default]
+    Event,
+    /// The time on each local date is rounded separately.
+    Day,
+    /// Only the total is rounded.
+    Total,
+}
@@ -0,0 +1,209 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::period::{local_instant, midnight};
+
+/// The number of hours of work expected on each day of the week, together with any changes to
+/// those expectations that took effect on later dates.
+///
+/// A schedule is written in the config file as follows:
+///
+/// ```toml
+/// [schedule]
+/// mon = 8
+/// tue = 8
+/// wed = 8
+/// thu = 8
+/// fri = 6
+///
+/// [[schedule.changes]]
+/// from = "2024-07-01"
+/// mon = 4
+/// tue = 4
+/// wed = 4
+/// thu = 4
+/// fri = 0
+/// ```
+///
+/// Any days that are left out take their default values, which are 8 hours from Monday to Friday
+/// and no hours at the weekend.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    /// The expected hours that apply before the first change (or always, if there are none).
+    #[serde(flatten)]
+    pub week: WeekHours,
+    /// Changes to the expected hours, each of which applies from its date onwards.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<ScheduleChange>,
+    /// The time of day during which work is usually done, on days when any is expected.
+    pub working_hours: WorkingHours,
+}
+
+impl Schedule {
+    /// Get the weekly expected hours that apply on the given date.
+    pub fn week_on(&self, date: NaiveDate) -> &WeekHours {
+        self.changes
+            .iter()
+            .filter(|change| change.from <= date)
+            .max_by_key(|change| change.from)
+            .map(|change| &change.week)
+            .unwrap_or(&self.week)
+    }
+
+    /// Get the amount of time that is expected to be worked on the given date.
+    pub fn expected_on(&self, date: NaiveDate) -> Duration {
+        self.week_on(date).expected_on(date.weekday())
+    }
+
+    /// Get the amount of time that is expected to be worked between the two given instants.
+    ///
+    /// Every local day that starts before `end` and ends after `begin` counts in full, so a range
+    /// that ends partway through today includes all of the time expected today.
+    pub fn expected_range(&self, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        let mut date = begin.with_timezone(&Local).date_naive();
+        let mut total = Duration::zero();
+
+        while midnight(date) < end {
+            total += self.expected_on(date);
+            date = date + Days::new(1);
+        }
+
+        total
+    }
+}
+
+/// A change to the expected hours that takes effect from a certain date.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleChange {
+    /// The first date on which the new expected hours apply.
+    pub from: NaiveDate,
+    /// The new expected hours.
+    #[serde(flatten)]
+    pub week: WeekHours,
+}
+
+/// The number of hours of work expected on each day of the week.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeekHours {
+    pub mon: f64,
+    pub tue: f64,
+    pub wed: f64,
+    pub thu: f64,
+    pub fri: f64,
+    pub sat: f64,
+    pub sun: f64,
+}
+
+impl WeekHours {
+    /// Get the amount of time that is expected to be worked on the given day of the week.
+    pub fn expected_on(&self, weekday: Weekday) -> Duration {
+        let hours = match weekday {
+            Weekday::Mon => self.mon,
+            Weekday::Tue => self.tue,
+            Weekday::Wed => self.wed,
+            Weekday::Thu => self.thu,
+            Weekday::Fri => self.fri,
+            Weekday::Sat => self.sat,
+            Weekday::Sun => self.sun,
+        };
+
+        Duration::seconds((hours * 3600.0).round() as i64)
+    }
+}
+
+impl Default for WeekHours {
+    fn default() -> Self {
+        WeekHours {
+            mon: 8.0,
+            tue: 8.0,
+            wed: 8.0,
+            thu: 8.0,
+            fri: 8.0,
+            sat: 0.0,
+            sun: 0.0,
+        }
+    }
+}
+
+/// The time of day during which work is usually done, such as from 09:00 to 17:00.
+///
+/// Working hours are written in the config file as `"09:00-17:00"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct WorkingHours {
+    /// The local time at which work starts.
+    pub start: NaiveTime,
+    /// The local time at which work ends.
+    pub end: NaiveTime,
+}
+
+impl WorkingHours {
+    /// Get the instants at which work starts and ends on the given local date.
+    ///
+    /// Times that don't exist on that date in the local timezone (because of a daylight saving
+    /// transition) are taken as UTC instead.
+    pub fn on(&self, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+        (
+            local_instant(date, self.start).into(),
+            local_instant(date, self.end).into(),
+        )
+    }
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        WorkingHours {
+            start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        }
+    }
+}
+
+impl FromStr for WorkingHours {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a range of times, such as \"09:00-17:00\"", raw);
+        let (start, end) = raw.split_once('-').ok_or_else(invalid)?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?;
+
+        if end <= start {
+            return Err(invalid());
+        }
+
+        Ok(WorkingHours { start, end })
+    }
+}
+
+impl TryFrom<String> for WorkingHours {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<WorkingHours> for String {
+    fn from(hours: WorkingHours) -> Self {
+        hours.to_string()
+    }
+}
+
+impl Display for WorkingHours {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
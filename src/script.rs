@@ -0,0 +1,146 @@
+//! Running user scripts written in [Rhai][rhai] against the sheet, for reports and checks that
+//! don't belong in the core commands.
+//!
+//! Scripts can use the following in addition to the Rhai standard library:
+//!
+//! + `EVENTS`: every tracking period in the sheet, oldest first, as maps with the keys `start` and
+//!   `stop` (RFC 3339 times, `stop` being `()` if ongoing), `project`, `tags`, `billable`, `note`
+//!   and `seconds` (the time tracked so far)
+//! + `ARGS`: the arguments given after the script's path
+//! + `events(period)`: the tracking periods overlapping the given period, such as `"today"` or
+//!   `"last week"` (accepting the same values as `punch count`)
+//! + `count(period)` and `expected(period)`: the number of seconds worked and expected by the
+//!   schedule in the given period
+//! + `format_duration(seconds)`: a number of seconds formatted like the output of `punch count`
+//!
+//! `print()` prints to standard output, and `throw` stops the script and reports a failure.
+//!
+//! [rhai]: https://rhai.rs
+
+use std::{path::Path, rc::Rc};
+
+use chrono::{DateTime, Duration, Local, SecondsFormat, Utc};
+use punch_clock::{Config, Event, Period, Sheet, YearStart};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+use crate::format_duration;
+
+/// Run the script at the given path with the given arguments.
+pub fn run(
+    path: &Path,
+    args: Vec<String>,
+    config: &Config,
+    sheet: &Sheet,
+) -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    let sheet = Rc::new(sheet.clone());
+    let config = Rc::new(config.clone());
+    let year_start = config.fiscal_year_start;
+
+    engine.on_print(|text| say!("{}", text));
+
+    {
+        let sheet = sheet.clone();
+        engine.register_fn(
+            "events",
+            move |period: &str| -> Result<Array, Box<EvalAltResult>> {
+                let (start, end) = range(period, year_start)?;
+
+                Ok(sheet
+                    .events_in_range(start, end)
+                    .map(|event| Dynamic::from_map(to_map(event)))
+                    .collect())
+            },
+        );
+    }
+
+    {
+        let sheet = sheet.clone();
+        engine.register_fn(
+            "count",
+            move |period: &str| -> Result<i64, Box<EvalAltResult>> {
+                let (start, end) = range(period, year_start)?;
+
+                Ok(sheet.count_range(start, end).num_seconds())
+            },
+        );
+    }
+
+    engine.register_fn(
+        "expected",
+        move |period: &str| -> Result<i64, Box<EvalAltResult>> {
+            let (start, end) = range(period, year_start)?;
+
+            Ok(config.schedule.expected_range(start, end).num_seconds())
+        },
+    );
+
+    engine.register_fn("format_duration", |seconds: i64| {
+        format_duration(Duration::seconds(seconds))
+    });
+
+    let events: Array = sheet
+        .events()
+        .iter()
+        .map(|event| Dynamic::from_map(to_map(event)))
+        .collect();
+    let args: Array = args.into_iter().map(Dynamic::from).collect();
+
+    let mut scope = Scope::new();
+    scope.push_constant("EVENTS", events);
+    scope.push_constant("ARGS", args);
+
+    engine.run_file_with_scope(&mut scope, path.to_owned())
+}
+
+/// Get the range of time covered by the period with the given name.
+fn range(
+    period: &str,
+    year_start: YearStart,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Box<EvalAltResult>> {
+    let period: Period = period.parse().map_err(|err: String| err)?;
+
+    Ok(period.range_in(Local::now(), year_start))
+}
+
+/// Convert an event into a map that scripts can read.
+fn to_map(event: &Event) -> Map {
+    let mut map = Map::new();
+    let stop = event.stop.unwrap_or_else(Utc::now);
+
+    map.insert(
+        "start".into(),
+        event
+            .start
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+            .into(),
+    );
+    map.insert(
+        "stop".into(),
+        event.stop.map_or(Dynamic::UNIT, |stop| {
+            stop.to_rfc3339_opts(SecondsFormat::Secs, true).into()
+        }),
+    );
+    map.insert(
+        "project".into(),
+        event.project.clone().map_or(Dynamic::UNIT, Dynamic::from),
+    );
+    map.insert(
+        "tags".into(),
+        event
+            .tags
+            .iter()
+            .cloned()
+            .map(Dynamic::from)
+            .collect::<Array>()
+            .into(),
+    );
+    map.insert("billable".into(), event.billable.into());
+    map.insert(
+        "note".into(),
+        event.note.clone().map_or(Dynamic::UNIT, Dynamic::from),
+    );
+    map.insert("seconds".into(), (stop - event.start).num_seconds().into());
+
+    map
+}
@@ -0,0 +1,192 @@
+//! Searching the projects, tags and notes of events for text.
+//!
+//! A query is either a list of words, all of which have to appear in an event, or a regular
+//! expression in the syntax of the [`regex`][regex] crate, which finds matches in time linear in
+//! the length of the text whatever the pattern. Both ignore case.
+//!
+//! [regex]: https://docs.rs/regex
+
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::Event;
+
+/// A query to search events for.
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// Words that all have to appear in an event's project, tags or note.
+    Words(Vec<String>),
+    /// A regular expression that has to match some of an event's project, tags or note.
+    Regex(Regex),
+}
+
+impl Query {
+    /// Create a query for the words in the given text, separated by whitespace.
+    pub fn words(text: &str) -> Query {
+        Query::Words(text.split_whitespace().map(str::to_owned).collect())
+    }
+
+    /// Create a query for the given regular expression.
+    pub fn regex(pattern: &str) -> Result<Query, regex::Error> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Query::Regex)
+    }
+
+    /// Whether the given event matches the query.
+    pub fn matches(&self, event: &Event) -> bool {
+        let fields: Vec<&str> = event
+            .project
+            .as_deref()
+            .into_iter()
+            .chain(event.tags.iter().map(String::as_str))
+            .chain(event.note.as_deref())
+            .collect();
+
+        match self {
+            Query::Words(words) => words
+                .iter()
+                .all(|word| fields.iter().any(|field| find_text(field, word).is_some())),
+            Query::Regex(regex) => fields.iter().any(|field| regex.is_match(field)),
+        }
+    }
+
+    /// Get the byte ranges of the parts of the given text that match the query, in order and
+    /// without overlapping, for highlighting them.
+    pub fn find_in(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = match self {
+            Query::Words(words) => words
+                .iter()
+                .flat_map(|word| {
+                    let mut from = 0;
+
+                    std::iter::from_fn(move || {
+                        let range = find_text(&text[from..], word)?;
+                        let range = from + range.start..from + range.end;
+                        from = range.end;
+                        Some(range)
+                    })
+                })
+                .collect(),
+            // Empty matches have nothing to highlight.
+            Query::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect(),
+        };
+
+        ranges.sort_by_key(|range| range.start);
+        ranges.dedup_by(|later, earlier| later.start < earlier.end);
+
+        ranges
+    }
+}
+
+/// Get the indices of the events that match the given query, in order.
+pub fn search(events: &[Event], query: &Query) -> Vec<usize> {
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| query.matches(event))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Find the first appearance of a word in some text, ignoring case, as a byte range.
+fn find_text(text: &str, word: &str) -> Option<Range<usize>> {
+    let word: Vec<char> = word.chars().collect();
+
+    text.char_indices().find_map(|(start, _)| {
+        let mut chars = text[start..].char_indices();
+
+        for expected in &word {
+            match chars.next() {
+                Some((_, c)) if same_letter(c, *expected) => {}
+                _ => return None,
+            }
+        }
+
+        let end = chars
+            .next()
+            .map_or(text.len(), |(offset, _)| start + offset);
+        Some(start..end)
+    })
+}
+
+/// Whether two characters are the same, ignoring case.
+fn same_letter(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn event(note: &str) -> Event {
+        let mut event = Event::new(Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap())
+            .with_project(Some("Acme".to_owned()))
+            .with_tags(vec!["review".to_owned()]);
+        event.note = Some(note.to_owned());
+        event
+    }
+
+    #[test]
+    fn words_all_have_to_appear_ignoring_case() {
+        let event = event("Fixed the LOGIN page");
+
+        assert!(Query::words("login acme").matches(&event));
+        assert!(Query::words("REVIEW fixed").matches(&event));
+        assert!(!Query::words("login signup").matches(&event));
+    }
+
+    #[test]
+    fn regex_matches_ignoring_case() {
+        let event = event("Ticket ABC-123 closed");
+
+        assert!(Query::regex(r"abc-\d+").unwrap().matches(&event));
+        assert!(Query::regex("^acme$").unwrap().matches(&event));
+        assert!(!Query::regex(r"xyz-\d+").unwrap().matches(&event));
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(Query::regex("(unclosed").is_err());
+        assert!(Query::regex("*").is_err());
+    }
+
+    #[test]
+    fn finds_ranges_in_order_without_overlaps() {
+        let query = Query::words("bar ba");
+
+        assert_eq!(query.find_in("foo bar baz"), vec![4..7, 8..10]);
+        assert_eq!(
+            Query::regex("a+").unwrap().find_in("caaab a"),
+            vec![1..4, 6..7]
+        );
+    }
+
+    #[test]
+    fn skips_empty_regex_matches() {
+        assert_eq!(Query::regex("x*").unwrap().find_in("abxc"), vec![2..3]);
+    }
+
+    #[test]
+    fn finds_ranges_in_multibyte_text() {
+        assert_eq!(Query::words("über").find_in("Grün ÜBER"), vec![6..11]);
+        assert_eq!(Query::regex("ü").unwrap().find_in("Grün"), vec![2..4]);
+    }
+
+    #[test]
+    fn long_notes_and_nested_repetition_match_quickly() {
+        let long = event(&"a".repeat(100_000));
+        assert!(!Query::regex("a*b").unwrap().matches(&long));
+
+        let short = event(&"a".repeat(28));
+        assert!(!Query::regex("(a|a)*b").unwrap().matches(&short));
+    }
+}
@@ -0,0 +1,260 @@
+//! Serving the tracking periods over HTTP, as an iCalendar feed that calendar applications can
+//! subscribe to and show tracked time alongside other events, and (if built with the `graphql`
+//! feature) as a GraphQL API for dashboards.
+//!
+//! The sheet is read again for each request, so the feed follows punching in and out. Only `GET`
+//! and `HEAD` requests for `/punch.ics` are answered, and nothing can be changed through the feed.
+//! The GraphQL API at `/graphql` can punch in and out and change tracking periods.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use punch_clock::Config;
+use tracing::{debug, warn};
+
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::{ical, load_sheet};
+
+/// The path the feed is served at.
+const FEED_PATH: &str = "/punch.ics";
+/// The path the GraphQL API is served at.
+const GRAPHQL_PATH: &str = "/graphql";
+
+/// How long to wait for a client to send its request or take the response before giving up on it,
+/// so that a client that stalls can't hold up the requests after it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest request body that's read, in bytes.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// What to serve.
+pub struct Services {
+    /// Whether to serve the iCalendar feed.
+    pub ical: bool,
+    /// Whether to serve the GraphQL API.
+    pub graphql: bool,
+    /// The origin of the web pages allowed to send requests to the GraphQL API from a browser.
+    pub allow_origin: Option<String>,
+}
+
+/// Serve the given services at the given address until the process is killed.
+pub fn serve(config: &Config, address: &str, services: &Services) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let local = listener.local_addr()?;
+
+    if services.ical {
+        say!("Serving tracked time at webcal://{}{}", local, FEED_PATH);
+    }
+
+    if services.graphql {
+        say!("Serving a GraphQL API at http://{}{}", local, GRAPHQL_PATH);
+    }
+
+    let server = Server {
+        config,
+        services,
+        #[cfg(feature = "graphql")]
+        schema: graphql::schema(config),
+    };
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| server.respond(stream));
+
+        if let Err(err) = result {
+            warn!("Unable to answer request: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// A request read from a client.
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Read a request from the given stream, returning `None` if its body is too large to read.
+    fn read(stream: &TcpStream) -> io::Result<Option<Request>> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        let mut headers = Vec::new();
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? <= 2 {
+                break;
+            }
+            headers.push(header.trim_end().to_owned());
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let mut request = Request {
+            method: parts.next().unwrap_or("").to_owned(),
+            path: parts.next().unwrap_or("").to_owned(),
+            headers,
+            body: Vec::new(),
+        };
+
+        let length = request
+            .header("Content-Length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+
+        if length > MAX_BODY {
+            return Ok(None);
+        }
+
+        request.body.resize(length, 0);
+        reader.read_exact(&mut request.body)?;
+
+        Ok(Some(request))
+    }
+
+    /// Get the value of the header with the given name, if the request has it.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find_map(|header| {
+            let (key, value) = header.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+/// A response to send to a client.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    /// Create a response with the given status and no body.
+    fn empty(status: &'static str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: String::new(),
+        }
+    }
+}
+
+/// The state shared by the requests the server answers.
+struct Server<'a> {
+    config: &'a Config,
+    services: &'a Services,
+    #[cfg(feature = "graphql")]
+    schema: graphql::ApiSchema,
+}
+
+impl Server<'_> {
+    /// Read a request from the given stream and answer it.
+    fn respond(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let (method, response) = match Request::read(&stream)? {
+            Some(request) => {
+                debug!(method = %request.method, path = %request.path, "answering request");
+                (request.method.clone(), self.route(&request))
+            }
+            None => (String::new(), Response::empty("413 Payload Too Large")),
+        };
+
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: \
+             no-cache\r\nConnection: close\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        );
+
+        if let Some(origin) = &self.services.allow_origin {
+            head.push_str(&format!(
+                "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: GET, \
+                 POST\r\nAccess-Control-Allow-Headers: Content-Type\r\n",
+                origin
+            ));
+        }
+
+        head.push_str("\r\n");
+
+        if method != "HEAD" {
+            head.push_str(&response.body);
+        }
+
+        (&stream).write_all(head.as_bytes())
+    }
+
+    /// Answer the given request with the service at its path.
+    fn route(&self, request: &Request) -> Response {
+        // Calendar applications may add a query string to bust caches.
+        let path = request.path.split('?').next().unwrap_or("");
+
+        match (request.method.as_str(), path) {
+            ("GET" | "HEAD", FEED_PATH) if self.services.ical => match load_sheet(self.config) {
+                Ok(sheet) => Response {
+                    status: "200 OK",
+                    content_type: "text/calendar; charset=utf-8",
+                    body: ical::calendar(sheet.events()),
+                },
+                Err(err) => {
+                    warn!("Unable to serve the calendar: {}", err.describe());
+                    Response::empty("500 Internal Server Error")
+                }
+            },
+            (_, FEED_PATH) if self.services.ical => Response::empty("405 Method Not Allowed"),
+            #[cfg(feature = "graphql")]
+            (_, GRAPHQL_PATH) if self.services.graphql => self.graphql(request),
+            _ => Response::empty("404 Not Found"),
+        }
+    }
+
+    /// Answer a request to the GraphQL API, returning the schema for `GET` requests and running
+    /// the query in the body of `POST` requests.
+    #[cfg(feature = "graphql")]
+    fn graphql(&self, request: &Request) -> Response {
+        match request.method.as_str() {
+            "GET" | "HEAD" => Response {
+                status: "200 OK",
+                content_type: "text/plain; charset=utf-8",
+                body: self.schema.sdl(),
+            },
+            // Browsers only send preflight requests for other origins when asked for anything
+            // beyond a simple form post, such as a JSON body. Insisting on one means pages from
+            // origins that aren't allowed can't change the sheet.
+            "POST"
+                if !request
+                    .header("Content-Type")
+                    .is_some_and(|kind| kind.starts_with("application/json")) =>
+            {
+                Response::empty("415 Unsupported Media Type")
+            }
+            "POST" => {
+                let body = String::from_utf8_lossy(&request.body);
+
+                match graphql::execute(&self.schema, &body) {
+                    Ok(body) => Response {
+                        status: "200 OK",
+                        content_type: "application/json",
+                        body,
+                    },
+                    Err(err) => Response {
+                        status: "400 Bad Request",
+                        content_type: "text/plain; charset=utf-8",
+                        body: format!("Invalid GraphQL request: {}", err),
+                    },
+                }
+            }
+            "OPTIONS" => Response::empty("204 No Content"),
+            _ => Response::empty("405 Method Not Allowed"),
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Running `punch watch` in the background as a service of the operating system.
+//!
+//! On Windows, `punch service install` registers a service that starts with the system and runs
+//! `punch service run`, which answers the service control manager and checks the sheet as
+//! `punch watch` does, writing what it would otherwise print to the Application event log. The
+//! service is given the locations of the installing user's config and data, and their name, as it
+//! doesn't run as them.
+//!
+//! On macOS, `punch service install` writes a launchd agent that runs `punch watch` whenever the
+//! user is logged in, and loads it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use punch_clock::Config;
+
+/// Whether the process is running as a service, so that there's no terminal to print to.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the process is running as a service.
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+#[cfg(target_os = "macos")]
+pub use macos::{install, uninstall};
+#[cfg(windows)]
+pub use windows::{init_logging, install, run, uninstall};
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn install(_config: &Config) {
+    fail!("Installing a service isn't supported on this platform.");
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn uninstall() {
+    fail!("Installing a service isn't supported on this platform.");
+}
+
+#[cfg(not(windows))]
+pub fn run(_config: Config) {
+    fail!("Running as a service isn't supported on this platform.");
+}
+
+#[cfg(not(windows))]
+pub fn init_logging() {}
+
+#[cfg(windows)]
+mod windows {
+    use std::{
+        ffi::c_void,
+        io::{self, Write},
+        process::Command,
+        ptr,
+        sync::{atomic::Ordering, OnceLock},
+    };
+
+    use punch_clock::{config::env_path, Config, Sheet};
+    use tracing::{Level, Metadata};
+    use tracing_subscriber::fmt::MakeWriter;
+    use windows_sys::Win32::System::{
+        EventLog::{
+            RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+            EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+        },
+        Services::{
+            RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+            SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_SHUTDOWN,
+            SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE,
+            SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW,
+            SERVICE_WIN32_OWN_PROCESS,
+        },
+    };
+
+    use super::RUNNING;
+    use crate::watch;
+
+    /// The name the service is registered under, which is also the source of its events.
+    const NAME: &str = "PunchClock";
+    /// The key the service's settings are kept under in the registry.
+    const KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services\PunchClock";
+
+    /// The config the service checks the sheet with.
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    /// The handle the service reports its state through, once it's registered.
+    static STATUS: OnceLock<usize> = OnceLock::new();
+    /// The handle of the event log, once it's opened.
+    static EVENT_LOG: OnceLock<usize> = OnceLock::new();
+
+    /// Register the service and start it, to run with the given config's user.
+    pub fn install(config: &Config) {
+        let exe = match std::env::current_exe() {
+            Ok(exe) => exe,
+            Err(err) => {
+                fail!("Can't install the service: {}.", err);
+                return;
+            }
+        };
+        let (config_loc, data_dir) = match (Config::default_loc(), Sheet::default_dir()) {
+            (Ok(config_loc), Ok(data_dir)) => (config_loc, data_dir),
+            _ => {
+                fail!("Can't install the service: unable to find the config and sheet.");
+                return;
+            }
+        };
+
+        let command = format!(
+            "\"{}\" --user \"{}\" service run",
+            exe.display(),
+            config.user()
+        );
+        let mut environment = format!(
+            "PUNCH_CONFIG={}\\0PUNCH_DATA_DIR={}",
+            config_loc.display(),
+            data_dir.display()
+        );
+
+        if let Some(sheet) = env_path("PUNCH_SHEET") {
+            environment.push_str(&format!("\\0PUNCH_SHEET={}", sheet.display()));
+        }
+
+        let steps: [&[&str]; 4] = [
+            &[
+                "sc.exe",
+                "create",
+                NAME,
+                "binPath=",
+                &command,
+                "start=",
+                "auto",
+                "DisplayName=",
+                "Punch Clock",
+            ],
+            &[
+                "sc.exe",
+                "description",
+                NAME,
+                "Checks the Punch Clock sheet in the background, as punch watch does.",
+            ],
+            &[
+                "reg.exe",
+                "add",
+                KEY,
+                "/v",
+                "Environment",
+                "/t",
+                "REG_MULTI_SZ",
+                "/d",
+                &environment,
+                "/f",
+            ],
+            &["sc.exe", "start", NAME],
+        ];
+
+        for step in steps {
+            if let Err(err) = run_step(step) {
+                fail!("Can't install the service: {}.", err);
+                return;
+            }
+        }
+
+        say!("Installed and started the {} service.", NAME);
+    }
+
+    /// Stop the service and remove it.
+    pub fn uninstall() {
+        // Stopping fails if the service isn't running, which doesn't matter.
+        let _ = run_step(&["sc.exe", "stop", NAME]);
+
+        match run_step(&["sc.exe", "delete", NAME]) {
+            Ok(()) => say!("Removed the {} service.", NAME),
+            Err(err) => fail!("Can't remove the service: {}.", err),
+        }
+    }
+
+    /// Run a program as one step of installing or removing the service, failing if it fails.
+    fn run_step(args: &[&str]) -> io::Result<()> {
+        let output = Command::new(args[0]).args(&args[1..]).output()?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            let message = String::from_utf8_lossy(&output.stdout);
+            Err(io::Error::other(format!(
+                "{} failed: {}",
+                args[0],
+                message.trim()
+            )))
+        }
+    }
+
+    /// Hand the process over to the service control manager, which runs the service until it's
+    /// stopped.
+    pub fn run(config: Config) {
+        RUNNING.store(true, Ordering::Relaxed);
+        let _ = CONFIG.set(config);
+
+        let mut name = wide(NAME);
+        let table = [
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: name.as_mut_ptr(),
+                lpServiceProc: Some(service_main),
+            },
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: ptr::null_mut(),
+                lpServiceProc: None,
+            },
+        ];
+
+        // Returns once the service has stopped, or straight away if not started as a service.
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            fail!("Can't run as a service: {}.", io::Error::last_os_error());
+        }
+    }
+
+    /// Register the service's control handler, report it running and check the sheet until it's
+    /// stopped.
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let name = wide(NAME);
+        let handle = RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control), ptr::null());
+
+        if handle.is_null() {
+            return;
+        }
+
+        let _ = STATUS.set(handle as usize);
+        set_state(SERVICE_RUNNING);
+
+        let config = CONFIG.get().cloned().unwrap_or_default();
+        watch::watch(&config);
+    }
+
+    /// Handle a request from the service control manager, stopping the process if asked to.
+    unsafe extern "system" fn control(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        if control == SERVICE_CONTROL_STOP || control == SERVICE_CONTROL_SHUTDOWN {
+            set_state(SERVICE_STOPPED);
+            std::process::exit(0);
+        }
+
+        0
+    }
+
+    /// Report the given state of the service to the service control manager.
+    fn set_state(state: SERVICE_STATUS_CURRENT_STATE) {
+        let Some(&handle) = STATUS.get() else {
+            return;
+        };
+
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN,
+            dwWin32ExitCode: 0,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: 0,
+        };
+
+        unsafe { SetServiceStatus(handle as SERVICE_STATUS_HANDLE, &status) };
+    }
+
+    /// Write log messages to the Application event log, at the level of each.
+    pub fn init_logging() {
+        tracing_subscriber::fmt()
+            .with_max_level(Level::INFO)
+            .with_ansi(false)
+            .with_target(false)
+            .without_time()
+            .with_writer(EventLog)
+            .init();
+    }
+
+    /// Makes writers to the event log.
+    struct EventLog;
+
+    impl<'a> MakeWriter<'a> for EventLog {
+        type Writer = EventWriter;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            EventWriter(EVENTLOG_INFORMATION_TYPE)
+        }
+
+        fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+            let level = *meta.level();
+
+            EventWriter(if level == Level::ERROR {
+                EVENTLOG_ERROR_TYPE
+            } else if level == Level::WARN {
+                EVENTLOG_WARNING_TYPE
+            } else {
+                EVENTLOG_INFORMATION_TYPE
+            })
+        }
+    }
+
+    /// Writes each message given to it as an event of its type.
+    struct EventWriter(REPORT_EVENT_TYPE);
+
+    impl Write for EventWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let source = *EVENT_LOG.get_or_init(|| {
+                let name = wide(NAME);
+                unsafe { RegisterEventSourceW(ptr::null(), name.as_ptr()) as usize }
+            });
+
+            if source != 0 {
+                let message = wide(String::from_utf8_lossy(buf).trim_end());
+                let strings = [message.as_ptr()];
+
+                unsafe {
+                    ReportEventW(
+                        source as *mut c_void,
+                        self.0,
+                        0,
+                        0,
+                        ptr::null_mut(),
+                        1,
+                        0,
+                        strings.as_ptr(),
+                        ptr::null(),
+                    )
+                };
+            }
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Encode a string as UTF-16, ending with a null, as Windows functions take them.
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{fs, io, path::PathBuf, process::Command};
+
+    use directories::BaseDirs;
+    use punch_clock::Config;
+
+    /// The label the agent is loaded under, which is also the name of its file.
+    const LABEL: &str = "dev.neros.PunchClock";
+    /// The environment variables giving where files are kept, passed on to the agent.
+    const ENV_VARS: [&str; 3] = ["PUNCH_CONFIG", "PUNCH_DATA_DIR", "PUNCH_SHEET"];
+
+    /// Write the agent, running `punch watch` for the given config's user, and load it.
+    pub fn install(config: &Config) {
+        let (Ok(exe), Some(dirs)) = (std::env::current_exe(), BaseDirs::new()) else {
+            fail!("Can't install the service: unable to find punch and the home directory.");
+            return;
+        };
+        let agent = agent_loc(&dirs);
+        let log = dirs.home_dir().join("Library/Logs/punch-clock.log");
+        let plist = plist(
+            &exe.display().to_string(),
+            &config.user(),
+            &log.display().to_string(),
+        );
+
+        let installed = agent
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&agent, plist))
+            .and_then(|()| launchctl(&["load", "-w", &agent.display().to_string()]));
+
+        match installed {
+            Ok(()) => say!("Installed and started the {} agent.", LABEL),
+            Err(err) => fail!("Can't install the service: {}.", err),
+        }
+    }
+
+    /// Unload the agent and remove it.
+    pub fn uninstall() {
+        let Some(dirs) = BaseDirs::new() else {
+            fail!("Can't remove the service: unable to find the home directory.");
+            return;
+        };
+        let agent = agent_loc(&dirs);
+
+        if !agent.exists() {
+            say!("The {} agent isn't installed.", LABEL);
+            return;
+        }
+
+        // Unloading fails if the agent isn't loaded, which doesn't matter.
+        let _ = launchctl(&["unload", "-w", &agent.display().to_string()]);
+
+        match fs::remove_file(&agent) {
+            Ok(()) => say!("Removed the {} agent.", LABEL),
+            Err(err) => fail!("Can't remove the service: {}.", err),
+        }
+    }
+
+    /// Get the path the agent's file is written to.
+    fn agent_loc(dirs: &BaseDirs) -> PathBuf {
+        dirs.home_dir()
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", LABEL))
+    }
+
+    /// Run `launchctl` with the given arguments, failing if it fails.
+    fn launchctl(args: &[&str]) -> io::Result<()> {
+        let status = Command::new("launchctl").args(args).status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("launchctl failed ({})", status)))
+        }
+    }
+
+    /// Write the property list describing the agent, which runs `punch watch` at login and
+    /// restarts it if it stops, appending its output to the given log file.
+    fn plist(exe: &str, user: &str, log: &str) -> String {
+        let args: String = [exe, "--user", user, "watch"]
+            .iter()
+            .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+            .collect();
+        let env: String = ENV_VARS
+            .iter()
+            .filter_map(|var| Some((var, std::env::var(var).ok()?)))
+            .map(|(var, value)| {
+                format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    var,
+                    escape(&value)
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{args}    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+{env}    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+            label = LABEL,
+            args = args,
+            env = env,
+            log = escape(log),
+        )
+    }
+
+    /// Escape the characters with special meaning in XML.
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+}
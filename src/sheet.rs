@@ -1,26 +1,153 @@
 //! Working with recorded timesheets (lists of events).
 
 use std::{
-    fs::File,
-    io::{Read, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, Local, Utc};
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::Error as _, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+};
 use thiserror::Error;
 
-use crate::Event;
+use crate::{config::env_path, period::midnight, Event, Filter, Period, Review};
+
+/// The version of the format in which sheets are serialized.
+///
+/// This is written to every sheet file, and files with a later version are refused. The format is
+/// described by the JSON Schema in [`SCHEMA`][schema].
+///
+/// [schema]: constant.SCHEMA.html
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A JSON Schema describing the format of serialized sheets, which is also the format of JSON
+/// exports.
+pub const SCHEMA: &str = include_str!("../schema/sheet.schema.json");
+
+/// How far in the future punch-ins and punch-outs may be recorded, to allow for the clocks of
+/// different machines sharing a sheet being slightly out of step.
+pub const MAX_CLOCK_SKEW: Duration = Duration::seconds(60);
 
 /// List of events, together comprising a log of work from which totals can be calculated for
 /// various periods of time.
-#[derive(Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+///
+/// The events are kept in order of their start times. Loading a sheet sorts its events, and every
+/// method that adds events keeps them sorted, which lets lookups by time use binary search.
+///
+/// A sheet shared by more than one user holds events that may overlap, so its methods should be
+/// called on a single user's [view][view] of it.
+///
+/// [view]: #method.user_view
+#[derive(Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Sheet {
-    pub events: Vec<Event>,
+    /// The recorded events, in order of their start times. They're private so that they can
+    /// only be changed in ways that keep them in order.
+    events: Vec<Event>,
+    /// The number of times the sheet file had been written when the sheet was loaded from it.
+    ///
+    /// Methods that change the events keep the revision, so that it can be checked before writing
+    /// the sheet that the file hasn't been written by another process in the meantime.
+    pub revision: u64,
+}
+
+impl Serialize for Sheet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut file = serializer.serialize_struct("Sheet", 3)?;
+        file.serialize_field("version", &FORMAT_VERSION)?;
+
+        // Exports aren't sheet files, so they're left without a revision.
+        if self.revision > 0 {
+            file.serialize_field("revision", &self.revision)?;
+        } else {
+            file.skip_field("revision")?;
+        }
+
+        file.serialize_field("events", &self.events)?;
+        file.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Sheet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        /// The serialized form of a sheet.
+        #[derive(Deserialize)]
+        struct SheetFile {
+            #[serde(default = "first_version")]
+            version: u32,
+            #[serde(default)]
+            revision: u64,
+            events: Vec<Event>,
+        }
+
+        fn first_version() -> u32 {
+            1
+        }
+
+        let file = SheetFile::deserialize(deserializer)?;
+
+        if file.version > FORMAT_VERSION {
+            return Err(D::Error::custom(format!(
+                "sheet format version {} is newer than the latest supported version {}",
+                file.version, FORMAT_VERSION
+            )));
+        }
+
+        Ok(Sheet {
+            revision: file.revision,
+            ..Sheet::from_events(file.events)
+        })
+    }
 }
 
 impl Sheet {
+    /// Create a sheet from the given events, sorting them by their start times.
+    pub fn from_events(mut events: Vec<Event>) -> Sheet {
+        // Sheets are almost always sorted already, in which case a stable sort is linear.
+        events.sort_by_key(|e| e.start);
+
+        Sheet {
+            events,
+            revision: 0,
+        }
+    }
+
+    /// Set the revision the sheet was loaded at.
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    /// Get the events, in order of their start times.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Take the events out of the sheet, in order of their start times.
+    pub fn into_events(self) -> Vec<Event> {
+        self.events
+    }
+
+    /// Replace the events with the given ones, sorting them by their start times.
+    pub fn set_events(&mut self, events: Vec<Event>) {
+        self.events = Sheet::from_events(events).events;
+    }
+
+    /// Apply `change` to every event, sorting them by their start times again afterwards.
+    pub fn update_events(&mut self, change: impl FnMut(&mut Event)) {
+        self.events.iter_mut().for_each(change);
+        self.events.sort_by_key(|e| e.start);
+    }
+
     /// Attempt to load a sheet from the file at the default location, as determined by
     /// [`default_loc()`][default].
     ///
@@ -30,38 +157,243 @@ impl Sheet {
     }
 
     /// Attempt to load a sheet from the file at the given path.
+    ///
+    /// The file is parsed as it's read, rather than being read into memory in full first.
     pub fn load<P>(path: P) -> Result<Sheet, SheetError>
     where
         P: AsRef<Path>,
     {
-        let mut sheet_json = String::new();
+        let sheet_file = File::open(&path).map_err(SheetError::OpenSheet)?;
+        let len = sheet_file.metadata().map_err(SheetError::ReadSheet)?.len();
 
-        {
-            let mut sheet_file = File::open(&path).map_err(SheetError::OpenSheet)?;
+        if len == 0 {
+            Ok(Sheet::default())
+        } else {
+            Self::read(BufReader::new(sheet_file), SheetFormat::from_path(&path))
+        }
+    }
 
-            sheet_file
-                .read_to_string(&mut sheet_json)
-                .map_err(SheetError::ReadSheet)?;
+    /// Attempt to load only the last event from the file at the given path.
+    ///
+    /// For sheets stored as [JSON Lines][jsonl], only the end of the file is read, so this stays
+    /// fast however long the sheet grows. Other formats are loaded in full.
+    ///
+    /// [jsonl]: enum.SheetFormat.html#variant.JsonLines
+    pub fn load_last<P>(path: P) -> Result<Option<Event>, SheetError>
+    where
+        P: AsRef<Path>,
+    {
+        if SheetFormat::from_path(&path) != SheetFormat::JsonLines {
+            return Self::load(path).map(|sheet| sheet.events.into_iter().last());
         }
 
-        if sheet_json.is_empty() {
-            Ok(Sheet::default())
+        let mut sheet_file = File::open(&path).map_err(SheetError::OpenSheet)?;
+
+        match last_line(&mut sheet_file).map_err(SheetError::ReadSheet)? {
+            Some((_, line)) => match parse_line(&line, 0)? {
+                Line::Header(_) => Ok(None),
+                Line::Event(event) => Ok(Some(event)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Attempt to load a sheet containing only the last event from the file at the given path.
+    ///
+    /// Changes to the returned sheet that only touch its end, such as punching in or out, can be
+    /// saved with [`write_tail()`][tail].
+    ///
+    /// [tail]: #method.write_tail
+    pub fn load_tail<P>(path: P) -> Result<Sheet, SheetError>
+    where
+        P: AsRef<Path>,
+    {
+        if SheetFormat::from_path(&path) != SheetFormat::JsonLines {
+            return Self::load(path).map(|sheet| Sheet {
+                events: sheet.events.into_iter().last().into_iter().collect(),
+                revision: sheet.revision,
+            });
+        }
+
+        Ok(Sheet {
+            events: Self::load_last(&path)?.into_iter().collect(),
+            revision: Self::load_revision(&path)?,
+        })
+    }
+
+    /// Attempt to load only the [revision][revision] of the sheet in the file at the given path,
+    /// or 0 if there's no file there yet.
+    ///
+    /// For sheets stored as [JSON Lines][jsonl], only the first line is read. Other formats are
+    /// loaded in full.
+    ///
+    /// [revision]: #structfield.revision
+    /// [jsonl]: enum.SheetFormat.html#variant.JsonLines
+    pub fn load_revision<P>(path: P) -> Result<u64, SheetError>
+    where
+        P: AsRef<Path>,
+    {
+        let sheet_file = match File::open(&path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            result => result.map_err(SheetError::OpenSheet)?,
+        };
+
+        if SheetFormat::from_path(&path) != SheetFormat::JsonLines {
+            return Self::load(path).map(|sheet| sheet.revision);
+        }
+
+        let mut first = String::new();
+
+        BufReader::new(sheet_file)
+            .read_line(&mut first)
+            .map_err(SheetError::ReadSheet)?;
+
+        match parse_line(&first, 1)? {
+            Line::Header(revision) => Ok(revision),
+            Line::Event(_) => Ok(0),
+        }
+    }
+
+    /// Find the copies of the sheet at the given path that sync tools left next to it after it was
+    /// changed on two machines at once, such as `sheet (conflicted copy).json` from Dropbox or
+    /// `sheet.sync-conflict-20240102-150405-ABCDEFG.json` from Syncthing.
+    ///
+    /// A directory that can't be read is taken to have none.
+    pub fn conflicted_copies<P>(path: P) -> Vec<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+            return Vec::new();
+        };
+        let stem = stem.to_string_lossy();
+        let ext = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+
+        let is_conflicted = |name: &str| {
+            let Some(rest) = name
+                .strip_prefix(stem.as_ref())
+                .and_then(|rest| rest.strip_suffix(&ext))
+            else {
+                return false;
+            };
+
+            rest.starts_with(".sync-conflict-")
+                || (rest.starts_with(" (")
+                    && rest.ends_with(')')
+                    && rest.contains("conflicted copy"))
+        };
+
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
         } else {
-            serde_json::from_str(&sheet_json).map_err(SheetError::ParseSheet)
+            dir
+        };
+        let mut copies: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| is_conflicted(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+
+        copies.sort();
+        copies
+    }
+
+    /// Attempt to read a sheet in the given format from the given reader.
+    pub fn read<R>(mut reader: R, format: SheetFormat) -> Result<Sheet, SheetError>
+    where
+        R: BufRead,
+    {
+        match format {
+            SheetFormat::Json => {
+                let mut recorder = LineRecorder::new(reader);
+
+                serde_json::from_reader(&mut recorder).map_err(|err| SheetError::ParseSheet {
+                    line: err.line(),
+                    column: err.column(),
+                    snippet: recorder.snippet(),
+                    source: err,
+                })
+            }
+            SheetFormat::JsonLines => {
+                let mut events = Vec::new();
+
+                let mut revision = 0;
+
+                for (i, line) in reader.lines().enumerate() {
+                    let line = line.map_err(SheetError::ReadSheet)?;
+
+                    match parse_line(&line, i + 1)? {
+                        Line::Header(header_revision) => revision = revision.max(header_revision),
+                        Line::Event(event) => events.push(event),
+                    }
+                }
+
+                Ok(Sheet {
+                    revision,
+                    ..Sheet::from_events(events)
+                })
+            }
+            SheetFormat::Toml => {
+                let mut sheet_toml = String::new();
+
+                reader
+                    .read_to_string(&mut sheet_toml)
+                    .map_err(SheetError::ReadSheet)?;
+
+                toml::from_str(&sheet_toml).map_err(SheetError::ParseToml)
+            }
+            SheetFormat::MessagePack => {
+                rmp_serde::from_read(reader).map_err(SheetError::ParseMessagePack)
+            }
+        }
+    }
+
+    /// Attempt to serialize the sheet in the given format.
+    pub fn to_bytes(&self, format: SheetFormat) -> Result<Vec<u8>, SheetError> {
+        match format {
+            SheetFormat::Json => serde_json::to_vec(self).map_err(SheetError::SerializeJson),
+            SheetFormat::JsonLines => {
+                let mut bytes = header_bytes(self.revision, HEADER_WIDTH);
+
+                for event in &self.events {
+                    bytes.push(b'\n');
+                    serde_json::to_writer(&mut bytes, event).map_err(SheetError::SerializeJson)?;
+                }
+
+                bytes.push(b'\n');
+                Ok(bytes)
+            }
+            SheetFormat::Toml => toml::to_string(self)
+                .map(String::into_bytes)
+                .map_err(SheetError::SerializeToml),
+            SheetFormat::MessagePack => {
+                rmp_serde::to_vec_named(self).map_err(SheetError::SerializeMessagePack)
+            }
         }
     }
 
     /// Get the default directory in which sheets are stored.
     ///
-    /// The directory is determined using the [directories][directories] crate by platform as
-    /// follows:
+    /// This is the directory given by the `PUNCH_DATA_DIR` environment variable, if it's set.
+    /// Otherwise, the directory is determined using the [directories][directories] crate by
+    /// platform as follows:
     ///
-    /// + Linux: `$XDG_CONFIG_HOME/punchclock/sheet.json`
-    /// + macOS: `$HOME/Library/Application Support/dev.neros.PunchClock/sheet.json`
-    /// + Windows: `%APPDATA%\Local\Neros\PunchClock\sheet.json`
+    /// + Linux: `$XDG_DATA_HOME/punchclock`
+    /// + macOS: `$HOME/Library/Application Support/dev.neros.PunchClock`
+    /// + Windows: `%APPDATA%\Neros\PunchClock\data`
     ///
     /// [directories]: https://crates.io/crates/directories
     pub fn default_dir() -> Result<PathBuf, SheetError> {
+        if let Some(dir) = env_path("PUNCH_DATA_DIR") {
+            return Ok(dir);
+        }
+
         ProjectDirs::from("dev", "neros", "PunchClock")
             .ok_or(SheetError::FindSheet)
             .map(|dirs| dirs.data_dir().to_owned())
@@ -69,14 +401,30 @@ impl Sheet {
 
     /// Get the path to the file the default sheet is stored in.
     ///
-    /// This is the file `sheet.json` inside the directory returned from
-    /// [`default_dir()`][default].
+    /// This is the file given by the `PUNCH_SHEET` environment variable, if it's set. Otherwise,
+    /// it's the file `sheet.json` inside the directory returned from [`default_dir()`][default],
+    /// unless it doesn't exist and a sheet in one of the other [formats][formats] (e.g.
+    /// `sheet.toml`) does, in which case it's that file instead.
     ///
     /// [default]: #method.default_dir
+    /// [formats]: enum.SheetFormat.html
     pub fn default_loc() -> Result<PathBuf, SheetError> {
-        Self::default_dir().map(|mut dir| {
-            dir.push("sheet.json");
-            dir
+        if let Some(path) = env_path("PUNCH_SHEET") {
+            return Ok(path);
+        }
+
+        Self::default_dir().map(|dir| {
+            let json = dir.join(SheetFormat::Json.file_name());
+
+            if json.exists() {
+                return json;
+            }
+
+            SheetFormat::ALL
+                .iter()
+                .map(|format| dir.join(format.file_name()))
+                .find(|path| path.exists())
+                .unwrap_or(json)
         })
     }
 
@@ -89,20 +437,104 @@ impl Sheet {
     }
 
     /// Attempt to write a sheet to the file at the given path.
+    ///
+    /// The sheet's [revision][revision] is written as it is, so it's up to the caller to move it
+    /// on from the revision the file was at.
+    ///
+    /// [revision]: #structfield.revision
     pub fn write<P>(&self, path: P) -> Result<(), SheetError>
     where
         P: AsRef<Path>,
     {
-        let new_sheet_json = serde_json::to_string(self).unwrap();
+        let new_sheet_bytes = self.to_bytes(SheetFormat::from_path(&path))?;
 
         match File::create(&path) {
-            Ok(mut sheet_file) => {
-                write!(&mut sheet_file, "{}", new_sheet_json).map_err(SheetError::WriteSheet)
-            }
+            Ok(mut sheet_file) => sheet_file
+                .write_all(&new_sheet_bytes)
+                .map_err(SheetError::WriteSheet),
             Err(e) => Err(SheetError::WriteSheet(e)),
         }
     }
 
+    /// Attempt to save changes made to a sheet loaded with [`load_tail()`][tail] to the file at
+    /// the given path, which must be a sheet stored as [JSON Lines][jsonl].
+    ///
+    /// `loaded` is the sheet as it was loaded. If its event is unchanged, any new events are
+    /// appended to the file. Otherwise, only the last line of the file is rewritten. Either way,
+    /// the rest of the file is left untouched, apart from the sheet's revision being recorded in
+    /// the first line.
+    ///
+    /// [tail]: #method.load_tail
+    /// [jsonl]: enum.SheetFormat.html#variant.JsonLines
+    pub fn write_tail<P>(&self, path: P, loaded: &Sheet) -> Result<(), SheetError>
+    where
+        P: AsRef<Path>,
+    {
+        if SheetFormat::from_path(&path) != SheetFormat::JsonLines {
+            return Err(SheetError::NotJsonLines);
+        }
+
+        let mut sheet_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(SheetError::WriteSheet)?;
+
+        let end = sheet_file
+            .seek(SeekFrom::End(0))
+            .map_err(SheetError::WriteSheet)?;
+
+        let (offset, new_events) = if self.events.starts_with(&loaded.events) {
+            (end, &self.events[loaded.events.len()..])
+        } else {
+            let offset = last_line(&mut sheet_file)
+                .map_err(SheetError::WriteSheet)?
+                .map_or(end, |(offset, _)| offset);
+
+            (offset, &self.events[..])
+        };
+
+        let mut bytes = Vec::new();
+
+        if offset == 0 {
+            bytes.extend(header_bytes(self.revision, HEADER_WIDTH));
+            bytes.push(b'\n');
+        } else {
+            // Make sure that a file edited by hand without a trailing newline doesn't end up with
+            // two events on one line.
+            let mut last_byte = [0];
+
+            sheet_file
+                .seek(SeekFrom::Start(offset - 1))
+                .and_then(|_| sheet_file.read_exact(&mut last_byte))
+                .map_err(SheetError::WriteSheet)?;
+
+            if last_byte[0] != b'\n' {
+                bytes.push(b'\n');
+            }
+        }
+
+        for event in new_events {
+            serde_json::to_writer(&mut bytes, event).map_err(SheetError::SerializeJson)?;
+            bytes.push(b'\n');
+        }
+
+        sheet_file
+            .set_len(offset)
+            .and_then(|_| sheet_file.seek(SeekFrom::Start(offset)))
+            .and_then(|_| sheet_file.write_all(&bytes))
+            .map_err(SheetError::WriteSheet)?;
+
+        if offset == 0 {
+            Ok(())
+        } else {
+            write_revision(path.as_ref(), &mut sheet_file, self.revision)
+                .map_err(SheetError::WriteSheet)
+        }
+    }
+
     /// Record a punch-in (start of a time-tracking period) at the current time.
     pub fn punch_in(&mut self) -> Result<DateTime<Utc>, SheetError> {
         self.punch_in_at(Utc::now())
@@ -110,10 +542,37 @@ impl Sheet {
 
     /// Record a punch-in (start of a time-tracking period) at the given time.
     pub fn punch_in_at(&mut self, time: DateTime<Utc>) -> Result<DateTime<Utc>, SheetError> {
+        self.punch_in_with(Event::new(time))
+    }
+
+    /// Record a punch-in (start of a time-tracking period) with the given unfinished event.
+    ///
+    /// Any stop time on `event` is ignored.
+    ///
+    /// Punching in before the end of the last time-tracking period is refused, as the new period
+    /// would overlap with it, as is punching in more than [`MAX_CLOCK_SKEW`][skew] in the future.
+    ///
+    /// [skew]: constant.MAX_CLOCK_SKEW.html
+    pub fn punch_in_with(&mut self, event: Event) -> Result<DateTime<Utc>, SheetError> {
+        if event.start > Utc::now() + MAX_CLOCK_SKEW {
+            return Err(SheetError::InFuture(event.start));
+        }
+
         match self.events.last() {
+            Some(
+                last @ Event {
+                    stop: Some(stop), ..
+                },
+            ) if event.start < *stop => Err(SheetError::PunchInBeforeStop {
+                time: event.start,
+                event: Box::new(last.clone()),
+            }),
             Some(Event { stop: Some(_), .. }) | None => {
-                let event = Event::new(time);
-                self.events.push(event);
+                let time = event.start;
+                self.events.push(Event {
+                    stop: None,
+                    ..event
+                });
                 Ok(time)
             }
             Some(Event {
@@ -122,14 +581,63 @@ impl Sheet {
         }
     }
 
+    /// Record a punch-in with the given unfinished event like
+    /// [`punch_in_with()`][punch_in_with], but if the last time-tracking period ends after the
+    /// event starts, or is ongoing, cut it short to end when the event starts instead of refusing.
+    ///
+    /// Punching in at or before the start of the last time-tracking period is still refused, as is
+    /// cutting short a [frozen][frozen] one.
+    ///
+    /// [punch_in_with]: #method.punch_in_with
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn force_punch_in_with(&mut self, event: Event) -> Result<DateTime<Utc>, SheetError> {
+        if event.start > Utc::now() + MAX_CLOCK_SKEW {
+            return Err(SheetError::InFuture(event.start));
+        }
+
+        if let Some(last) = self.events.last_mut() {
+            if last.stop.is_none_or(|stop| stop > event.start) {
+                if event.start <= last.start {
+                    return Err(SheetError::PunchInBeforeStop {
+                        time: event.start,
+                        event: Box::new(last.clone()),
+                    });
+                }
+
+                if last.is_frozen() {
+                    return Err(SheetError::Frozen(last.start, last.review));
+                }
+
+                last.stop = Some(event.start);
+            }
+        }
+
+        self.punch_in_with(event)
+    }
+
     /// Record a punch-out (end of a time-tracking period) at the current time.
     pub fn punch_out(&mut self) -> Result<DateTime<Utc>, SheetError> {
         self.punch_out_at(Utc::now())
     }
 
     /// Record a punch-out (end of a time-tracking period) at the given time.
+    ///
+    /// Punching out before the punch-in it ends, or more than [`MAX_CLOCK_SKEW`][skew] in the
+    /// future, is refused.
+    ///
+    /// [skew]: constant.MAX_CLOCK_SKEW.html
     pub fn punch_out_at(&mut self, time: DateTime<Utc>) -> Result<DateTime<Utc>, SheetError> {
+        if time > Utc::now() + MAX_CLOCK_SKEW {
+            return Err(SheetError::InFuture(time));
+        }
+
         match self.events.last_mut() {
+            Some(event @ Event { stop: None, .. }) if time < event.start => {
+                Err(SheetError::PunchOutBeforePunchIn {
+                    time,
+                    event: Box::new(event.clone()),
+                })
+            }
             Some(ref mut event @ Event { stop: None, .. }) => {
                 event.stop = Some(time);
                 Ok(time)
@@ -142,22 +650,609 @@ impl Sheet {
         }
     }
 
+    /// Record a punch-out at the given time like [`punch_out_at()`][punch_out_at], but if the time
+    /// is before the start of the ongoing time-tracking period, record the period between the two
+    /// times instead of refusing, as long as it doesn't overlap the period before.
+    ///
+    /// [punch_out_at]: #method.punch_out_at
+    pub fn force_punch_out_at(&mut self, time: DateTime<Utc>) -> Result<DateTime<Utc>, SheetError> {
+        let previous_stop = self.events.iter().rev().nth(1).and_then(|e| e.stop);
+
+        match self.events.last_mut() {
+            Some(event @ Event { stop: None, .. }) if time < event.start => {
+                if previous_stop.is_some_and(|stop| stop > time) {
+                    return Err(SheetError::Overlap(time, event.start));
+                }
+
+                event.stop = Some(event.start);
+                event.start = time;
+                Ok(time)
+            }
+            _ => self.punch_out_at(time),
+        }
+    }
+
+    /// Add a line of text to the note of the ongoing time-tracking period.
+    pub fn append_note(&mut self, text: &str) -> Result<(), SheetError> {
+        match self.events.last_mut() {
+            Some(event @ Event { stop: None, .. }) => {
+                event.append_note(text);
+                Ok(())
+            }
+            Some(Event {
+                stop: Some(stop_time),
+                ..
+            }) => Err(SheetError::PunchedOut(*stop_time)),
+            None => Err(SheetError::NoPunches),
+        }
+    }
+
     /// Get the current status of time-tracking, including the time at which the status last
     /// changed.
     pub fn status(&self) -> SheetStatus {
-        match self.events.last() {
-            Some(Event {
-                stop: Some(stop), ..
-            }) => SheetStatus::PunchedOut(*stop),
-            Some(Event { start, .. }) => SheetStatus::PunchedIn(*start),
-            None => SheetStatus::Empty,
+        SheetStatus::of(self.events.last())
+    }
+
+    /// Summarise whether time is currently being tracked, for how long, and how much time has been
+    /// worked today and this week, as of the instant `now`.
+    pub fn summary(&self, now: DateTime<Local>) -> StatusSummary {
+        let status = self.status();
+        let (project, session) = match (&status, self.events.last()) {
+            (SheetStatus::PunchedIn(start), Some(last)) => {
+                (last.project.clone(), Some(now.with_timezone(&Utc) - *start))
+            }
+            _ => (None, None),
+        };
+
+        let (today_start, today_end) = Period::Today.range(now);
+        let (week_start, week_end) = Period::Week.range(now);
+
+        StatusSummary {
+            status,
+            project,
+            session,
+            today: self.count_range(today_start, today_end),
+            week: self.count_range(week_start, week_end),
+        }
+    }
+
+    /// Get the start of the ongoing time-tracking period, if there is one and it has lasted for
+    /// longer than `max` as of the instant `now`.
+    ///
+    /// A period this long has probably been left running by mistake.
+    pub fn stale_since(&self, max: Duration, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.status() {
+            SheetStatus::PunchedIn(start) if now - start > max => Some(start),
+            _ => None,
+        }
+    }
+
+    /// Punch out of the ongoing time-tracking period if it has lasted for longer than `cap` as of
+    /// the instant `now`, ending it at its start plus `cap` and adding `note` to its note, so that
+    /// it can be told apart from one that really lasted that long. Returns the time punched out at,
+    /// if the period was closed.
+    pub fn auto_close(
+        &mut self,
+        cap: Duration,
+        now: DateTime<Utc>,
+        note: &str,
+    ) -> Option<DateTime<Utc>> {
+        let start = self.stale_since(cap, now)?;
+        let stop = self.punch_out_at(start + cap).ok()?;
+
+        self.events.last_mut().unwrap().append_note(note);
+
+        Some(stop)
+    }
+
+    /// Split the ongoing time-tracking period at each local midnight it has run past as of the
+    /// instant `now`, so that each day's time is in a period of its own, returning the number of
+    /// splits made.
+    ///
+    /// Each period starting at one of the inserted midnights is given the tag `tag`, so that the
+    /// splits can be found and reviewed later. A [frozen][frozen] period is left as it is.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn split_at_midnights(&mut self, now: DateTime<Utc>, tag: &str) -> usize {
+        let Some(ongoing) = self.events.last() else {
+            return 0;
+        };
+
+        if ongoing.stop.is_some() || ongoing.is_frozen() {
+            return 0;
+        }
+
+        let mut date = ongoing.start.with_timezone(&Local).date_naive();
+        let mut splits = 0;
+
+        loop {
+            date = date.succ_opt().unwrap();
+            let at = midnight(date).with_timezone(&Utc);
+            let start = self.events.last().unwrap().start;
+
+            if at >= now || self.split_event(start, at).is_err() {
+                return splits;
+            }
+
+            let event = self.events.last_mut().unwrap();
+            if !event.tags.iter().any(|t| t == tag) {
+                event.tags.push(tag.to_owned());
+            }
+
+            splits += 1;
+        }
+    }
+
+    /// Get the events that overlap with the time between the two given instants, including an
+    /// ongoing time-tracking period if there is one.
+    pub fn events_in_range(
+        &self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = &Event> {
+        self.slice_range(begin, end)
+            .iter()
+            .filter(move |e| overlaps(e, begin, end))
+    }
+
+    /// Get the stretches of time between the two given instants that aren't covered by any event,
+    /// as pairs of their start and end, in order.
+    pub fn untracked(
+        &self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut gaps = Vec::new();
+        let mut covered_until = begin;
+
+        for event in self.events_in_range(begin, end) {
+            if event.start > covered_until {
+                gaps.push((covered_until, event.start.min(end)));
+            }
+
+            covered_until = covered_until.max(event.stop.unwrap_or_else(Utc::now));
+        }
+
+        if covered_until < end {
+            gaps.push((covered_until, end));
         }
+
+        gaps
+    }
+
+    /// Get the smallest slice of the events that contains every event overlapping with the time
+    /// between the two given instants, found by binary search.
+    ///
+    /// This relies on the events being sorted and not overlapping with one another, so that their
+    /// stop times are sorted too.
+    fn slice_range(&self, begin: DateTime<Utc>, end: DateTime<Utc>) -> &[Event] {
+        let first = self
+            .events
+            .partition_point(|e| e.stop.is_some_and(|stop| stop < begin));
+        let last = self.events.partition_point(|e| e.start <= end);
+
+        &self.events[first..last.max(first)]
+    }
+
+    /// Replace every event that overlaps with the time between the two given instants with the
+    /// given events, returning the events that were replaced.
+    ///
+    /// If the resulting list of events isn't [valid][check], or a [frozen][frozen] event would be
+    /// changed or removed, the sheet is left unchanged and the problem is returned as an error.
+    ///
+    /// [check]: #method.check
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn replace_range(
+        &mut self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+        events: Vec<Event>,
+    ) -> Result<Vec<Event>, SheetError> {
+        let (replaced, mut kept): (Vec<_>, Vec<_>) = self
+            .events
+            .iter()
+            .cloned()
+            .partition(|e| overlaps(e, begin, end));
+
+        if let Some(frozen) = replaced
+            .iter()
+            .find(|e| e.is_frozen() && !events.contains(e))
+        {
+            return Err(SheetError::Frozen(frozen.start, frozen.review));
+        }
+
+        kept.extend(events);
+        kept.sort_by_key(|e| e.start);
+
+        let new = Sheet {
+            events: kept,
+            revision: self.revision,
+        };
+        new.check()?;
+        *self = new;
+
+        Ok(replaced)
+    }
+
+    /// Move every finished event that overlaps with the time between the two given instants, and
+    /// whose state [allows][allows] it, into the given review state, returning the number of
+    /// events moved.
+    ///
+    /// [allows]: ../enum.Review.html#method.allows
+    pub fn review_range(
+        &mut self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+        review: Review,
+    ) -> usize {
+        let mut moved = 0;
+
+        for event in &mut self.events {
+            if event.stop.is_some() && overlaps(event, begin, end) && event.review.allows(review) {
+                event.review = review;
+                moved += 1;
+            }
+        }
+
+        moved
+    }
+
+    /// Remove every event selected by the given filter, returning the events removed.
+    ///
+    /// If a [frozen][frozen] event would be removed, the sheet is left unchanged and the problem is
+    /// returned as an error.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn remove_matching(&mut self, filter: &Filter) -> Result<Vec<Event>, SheetError> {
+        if let Some(frozen) = self
+            .events
+            .iter()
+            .find(|e| filter.matches(e) && e.is_frozen())
+        {
+            return Err(SheetError::Frozen(frozen.start, frozen.review));
+        }
+
+        Ok(self.retain(|e| !filter.matches(e)))
+    }
+
+    /// Remove the event starting at the given instant, returning it.
+    ///
+    /// Events are identified by their start times, which are unique within a sheet (or a user's
+    /// [view][view] of a shared one). If there's no such event, or it's [frozen][frozen], the sheet
+    /// is left unchanged and the problem is returned as an error.
+    ///
+    /// [view]: #method.user_view
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn remove_event(&mut self, start: DateTime<Utc>) -> Result<Event, SheetError> {
+        let index = self
+            .events
+            .binary_search_by_key(&start, |e| e.start)
+            .map_err(|_| SheetError::NoEvent(start))?;
+
+        let event = &self.events[index];
+        if event.is_frozen() {
+            return Err(SheetError::Frozen(event.start, event.review));
+        }
+
+        Ok(self.events.remove(index))
+    }
+
+    /// Split the event starting at `start` in two at the instant `at`, which must be after its
+    /// start and before its stop (or now, if it's ongoing). Both halves keep the event's project,
+    /// tags, billable flag, note and user.
+    ///
+    /// If there's no such event, `at` isn't within it, or it's [frozen][frozen], the sheet is left
+    /// unchanged and the problem is returned as an error.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn split_event(
+        &mut self,
+        start: DateTime<Utc>,
+        at: DateTime<Utc>,
+    ) -> Result<(), SheetError> {
+        let index = self
+            .events
+            .binary_search_by_key(&start, |e| e.start)
+            .map_err(|_| SheetError::NoEvent(start))?;
+
+        let event = &mut self.events[index];
+        if at <= event.start || at >= event.stop.unwrap_or_else(Utc::now) {
+            return Err(SheetError::SplitOutside(start, at));
+        }
+
+        if event.is_frozen() {
+            return Err(SheetError::Frozen(event.start, event.review));
+        }
+
+        let second = Event {
+            start: at,
+            ..event.clone()
+        };
+        event.stop = Some(at);
+        self.events.insert(index + 1, second);
+
+        Ok(())
+    }
+
+    /// Merge the events starting at `first` and `second` into one, as long as they're next to each
+    /// other in the sheet with a gap of at most `max_gap` between them, returning the merged event.
+    ///
+    /// The merged event has the project of whichever has one, every tag of either, and both notes
+    /// (or one, if they're the same).
+    /// It's only billable if both were. If either event is [frozen][frozen], or they're on
+    /// different projects, the sheet is left unchanged and the problem is returned as an error.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn merge_events(
+        &mut self,
+        first: DateTime<Utc>,
+        second: DateTime<Utc>,
+        max_gap: Duration,
+    ) -> Result<Event, SheetError> {
+        let (first, second) = (first.min(second), first.max(second));
+        let find = |start| {
+            self.events
+                .binary_search_by_key(&start, |e: &Event| e.start)
+                .map_err(|_| SheetError::NoEvent(start))
+        };
+        let index = find(first)?;
+
+        if find(second)? != index + 1 {
+            return Err(SheetError::NotAdjacent(first, second));
+        }
+
+        let (a, b) = (&self.events[index], &self.events[index + 1]);
+
+        if a.stop.is_none_or(|stop| b.start - stop > max_gap) {
+            return Err(SheetError::NotAdjacent(first, second));
+        }
+
+        if let Some(frozen) = [a, b].into_iter().find(|e| e.is_frozen()) {
+            return Err(SheetError::Frozen(frozen.start, frozen.review));
+        }
+
+        if a.project.is_some() && b.project.is_some() && a.project != b.project {
+            return Err(SheetError::DifferentProjects(first, second));
+        }
+
+        let b = self.events.remove(index + 1);
+        let merged = &mut self.events[index];
+
+        merged.stop = b.stop;
+        merged.project = merged.project.take().or(b.project);
+        merged.billable &= b.billable;
+
+        for tag in b.tags {
+            if !merged.tags.contains(&tag) {
+                merged.tags.push(tag);
+            }
+        }
+
+        // The halves of a split event have the same note, which shouldn't be doubled up.
+        if let Some(note) = b.note.filter(|note| merged.note.as_ref() != Some(note)) {
+            merged.append_note(&note);
+        }
+
+        Ok(merged.clone())
+    }
+
+    /// Keep only the events for which `keep` returns `true`, returning the events removed.
+    ///
+    /// Unlike the other methods that remove events, this removes [frozen][frozen] events too.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn retain(&mut self, mut keep: impl FnMut(&Event) -> bool) -> Vec<Event> {
+        let (kept, removed) = self.events.drain(..).partition(|e| keep(e));
+        self.events = kept;
+
+        removed
+    }
+
+    /// Remove every event that overlaps with the time between the two given instants, returning
+    /// the events removed.
+    ///
+    /// If a [frozen][frozen] event would be removed, the sheet is left unchanged and the problem is
+    /// returned as an error.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn drain_range(
+        &mut self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Event>, SheetError> {
+        self.replace_range(begin, end, Vec::new())
+    }
+
+    /// Apply `change` to every event selected by the given filter, returning the number of events
+    /// it changed.
+    ///
+    /// If the resulting list of events isn't [valid][check], or a [frozen][frozen] event would be
+    /// changed, the sheet is left unchanged and the problem is returned as an error.
+    ///
+    /// [check]: #method.check
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn modify_matching(
+        &mut self,
+        filter: &Filter,
+        mut change: impl FnMut(&mut Event),
+    ) -> Result<usize, SheetError> {
+        let mut events = self.events.clone();
+        let mut changed = 0;
+
+        for event in events.iter_mut().filter(|e| filter.matches(e)) {
+            let original = event.clone();
+            change(event);
+
+            if *event != original {
+                if original.is_frozen() {
+                    return Err(SheetError::Frozen(original.start, original.review));
+                }
+
+                changed += 1;
+            }
+        }
+
+        events.sort_by_key(|e| e.start);
+
+        let new = Sheet {
+            events,
+            revision: self.revision,
+        };
+        new.check()?;
+        *self = new;
+
+        Ok(changed)
+    }
+
+    /// Insert a finished event into the sheet, in order of its start time.
+    ///
+    /// If the event overlaps any events already in the sheet, including an ongoing one, the sheet
+    /// is left unchanged and the overlapping events are returned in the error. An ongoing event can
+    /// only be inserted after every other event.
+    pub fn insert_event(&mut self, event: Event) -> Result<(), SheetError> {
+        if event.stop.is_some_and(|stop| stop < event.start) {
+            return Err(SheetError::StopBeforeStart(event.start));
+        }
+
+        let index = self.events.partition_point(|e| e.start <= event.start);
+
+        let overlapping: Vec<Event> = self.events[..index]
+            .last()
+            .filter(|e| e.stop.is_none_or(|stop| stop > event.start))
+            .into_iter()
+            .chain(
+                self.events[index..]
+                    .iter()
+                    .take_while(|e| event.stop.is_none_or(|stop| e.start < stop)),
+            )
+            .cloned()
+            .collect();
+
+        if !overlapping.is_empty() {
+            return Err(SheetError::Overlapping {
+                event: Box::new(event),
+                overlapping,
+            });
+        }
+
+        if event.stop.is_none() && index < self.events.len() {
+            return Err(SheetError::Unfinished(event.start));
+        }
+
+        self.events.insert(index, event);
+
+        Ok(())
+    }
+
+    /// Add the given events to the sheet with [`insert_event()`][insert], returning the ones that
+    /// were added.
+    ///
+    /// Events with the same start and stop times as an event already in the sheet are skipped. If
+    /// any of the others can't be inserted, the sheet is left unchanged and the problem is returned
+    /// as an error.
+    ///
+    /// [insert]: #method.insert_event
+    pub fn merge(&mut self, events: Vec<Event>) -> Result<Vec<Event>, SheetError> {
+        let mut merged = self.clone();
+        let mut added = Vec::new();
+
+        for event in events {
+            let duplicate = merged
+                .events
+                .iter()
+                .any(|e| e.start == event.start && e.stop == event.stop);
+
+            if !duplicate {
+                merged.insert_event(event.clone())?;
+                added.push(event);
+            }
+        }
+
+        *self = merged;
+
+        Ok(added)
+    }
+
+    /// Remove the events that stopped at or before the given instant, returning them.
+    ///
+    /// [Frozen][frozen] events are kept, as they can't be changed until they're unlocked.
+    ///
+    /// [frozen]: ../struct.Event.html#method.is_frozen
+    pub fn remove_finished_before(&mut self, time: DateTime<Utc>) -> Vec<Event> {
+        let (removed, kept) = self
+            .events
+            .drain(..)
+            .partition(|event| event.stop.is_some_and(|stop| stop <= time) && !event.is_frozen());
+
+        self.events = kept;
+        removed
+    }
+
+    /// Get the events [belonging to][belongs] the user with the given name as a sheet of their
+    /// own.
+    ///
+    /// On a sheet shared by more than one user, the events of different users may overlap, so
+    /// operations such as punching in are made on a user's view of the sheet, which is then put
+    /// back with [`replace_user_view()`][replace].
+    ///
+    /// [belongs]: ../struct.Event.html#method.belongs_to
+    /// [replace]: #method.replace_user_view
+    pub fn user_view(&self, user: &str) -> Sheet {
+        Sheet {
+            events: self
+                .events
+                .iter()
+                .filter(|event| event.belongs_to(user))
+                .cloned()
+                .collect(),
+            revision: self.revision,
+        }
+    }
+
+    /// Replace the events belonging to the user with the given name with the events in `view`,
+    /// as taken from [`user_view()`][view] and then changed.
+    ///
+    /// [view]: #method.user_view
+    pub fn replace_user_view(&mut self, user: &str, view: Sheet) {
+        let mut events: Vec<Event> = self
+            .events
+            .drain(..)
+            .filter(|event| !event.belongs_to(user))
+            .collect();
+        events.extend(view.events);
+
+        *self = Sheet {
+            revision: self.revision,
+            ..Sheet::from_events(events)
+        };
+    }
+
+    /// Check that the events in the sheet are in chronological order and don't overlap, that
+    /// none of them stop before they start, and that only the last one is unfinished.
+    pub fn check(&self) -> Result<(), SheetError> {
+        for event in &self.events {
+            if event.stop.is_some_and(|stop| stop < event.start) {
+                return Err(SheetError::StopBeforeStart(event.start));
+            }
+        }
+
+        for pair in self.events.windows(2) {
+            match pair[0].stop {
+                None => return Err(SheetError::Unfinished(pair[0].start)),
+                Some(stop) if stop > pair[1].start => {
+                    return Err(SheetError::Overlap(pair[0].start, pair[1].start))
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(())
     }
 
     /// Count the amount of time for which there was recorded work between the two given instants,
     /// including an ongoing time-tracking period if there is one.
     pub fn count_range(&self, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
-        self.events
+        self.slice_range(begin, end)
             .iter()
             .map(|e| (e.start, e.stop.unwrap_or_else(Utc::now)))
             .filter(|(start, stop)| {
@@ -174,6 +1269,305 @@ impl Sheet {
             })
             .fold(Duration::zero(), |acc, next| acc + next)
     }
+
+    /// Count the breaks taken between the two given instants: the time between the end of one
+    /// tracking period and the start of the next, where both fall on the same local date.
+    pub fn count_breaks(&self, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        let date = |time: DateTime<Utc>| time.with_timezone(&Local).date_naive();
+
+        self.events
+            .windows(2)
+            .filter_map(|pair| {
+                let (stop, start) = (pair[0].stop?, pair[1].start);
+                (date(stop) == date(start)).then_some((stop.max(begin), start.min(end)))
+            })
+            .filter(|(from, to)| from < to)
+            .fold(Duration::zero(), |acc, (from, to)| acc + (to - from))
+    }
+
+    /// Count the amount of time worked on the given project between the two given instants,
+    /// including an ongoing time-tracking period if there is one.
+    pub fn count_project_range(
+        &self,
+        project: &str,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Duration {
+        self.count_range_where(begin, end, |e| e.project.as_deref() == Some(project))
+    }
+
+    /// Count the amount of time worked between the two given instants in the events matching the
+    /// given predicate, including an ongoing time-tracking period if it matches.
+    pub fn count_range_where(
+        &self,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+        predicate: impl Fn(&Event) -> bool,
+    ) -> Duration {
+        self.events_in_range(begin, end)
+            .filter(|e| predicate(e))
+            .map(|e| {
+                std::cmp::min(end, e.stop.unwrap_or_else(Utc::now)) - std::cmp::max(begin, e.start)
+            })
+            .filter(|time| *time > Duration::zero())
+            .fold(Duration::zero(), |acc, next| acc + next)
+    }
+}
+
+/// Whether the given event overlaps with the time between the two given instants.
+fn overlaps(event: &Event, begin: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    event.start <= end && event.stop.is_none_or(|stop| stop >= begin)
+}
+
+/// The first line of a sheet stored as JSON Lines, recording the format version and revision.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    version: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    revision: u64,
+}
+
+/// The width the first line of a sheet stored as JSON Lines is padded to with spaces, which leaves
+/// room for any revision, so that it can be updated in place when appending to the file.
+const HEADER_WIDTH: usize = 48;
+
+fn is_zero(revision: &u64) -> bool {
+    *revision == 0
+}
+
+/// The number of bytes of a sheet shown before the point where it couldn't be parsed.
+const SNIPPET_WIDTH: usize = 40;
+
+/// A reader that keeps the end of the line being read, so that an error parsing what it reads can
+/// show the text leading up to it.
+struct LineRecorder<R> {
+    reader: R,
+    line: Vec<u8>,
+}
+
+impl<R> LineRecorder<R> {
+    fn new(reader: R) -> LineRecorder<R> {
+        LineRecorder {
+            reader,
+            line: Vec::new(),
+        }
+    }
+
+    /// The last [`SNIPPET_WIDTH`] bytes read on the current line.
+    fn snippet(&self) -> String {
+        let start = self.line.len().saturating_sub(SNIPPET_WIDTH);
+
+        String::from_utf8_lossy(&self.line[start..])
+            .trim_start_matches(char::REPLACEMENT_CHARACTER)
+            .trim()
+            .to_owned()
+    }
+}
+
+impl<R: Read> Read for LineRecorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        for &byte in &buf[..read] {
+            if byte == b'\n' {
+                self.line.clear();
+            } else {
+                self.line.push(byte);
+            }
+        }
+
+        // A sheet written as JSON is all on one line, so only the end of it is kept.
+        if self.line.len() > SNIPPET_WIDTH * 64 {
+            self.line.drain(..self.line.len() - SNIPPET_WIDTH);
+        }
+
+        Ok(read)
+    }
+}
+
+/// A parsed line of a sheet stored as JSON Lines, with the revision recorded by a header.
+pub(crate) enum Line {
+    Header(u64),
+    Event(Event),
+}
+
+/// Serialize the first line of a sheet stored as JSON Lines with the given revision, padded with
+/// spaces to the given width, without the newline.
+fn header_bytes(revision: u64, width: usize) -> Vec<u8> {
+    let mut bytes = serde_json::to_vec(&Header {
+        version: FORMAT_VERSION,
+        revision,
+    })
+    .unwrap();
+
+    if bytes.len() < width {
+        bytes.resize(width, b' ');
+    }
+
+    bytes
+}
+
+/// Record the given revision in the first line of the sheet file stored as JSON Lines at the given
+/// path, open as `file`, rewriting just that line if there's room for it. Otherwise, such as for a
+/// file written before revisions were recorded, the whole file is written to a temporary file next
+/// to it, which then replaces it, so that the sheet isn't lost if writing fails partway.
+fn write_revision(path: &Path, file: &mut File, revision: u64) -> io::Result<()> {
+    let mut first = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    BufReader::new(&mut *file).read_until(b'\n', &mut first)?;
+
+    let first_end = first.strip_suffix(b"\n").unwrap_or(&first).len();
+    let line = String::from_utf8_lossy(&first[..first_end]);
+    let has_header = !line.trim().is_empty() && matches!(parse_line(&line, 1), Ok(Line::Header(_)));
+    let header = header_bytes(revision, first_end);
+
+    if has_header && header.len() == first_end {
+        file.seek(SeekFrom::Start(0))?;
+        return file.write_all(&header);
+    }
+
+    let rest = if has_header { first.len() } else { 0 };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".new");
+
+    {
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        writer.write_all(&header_bytes(revision, HEADER_WIDTH))?;
+        writer.write_all(b"\n")?;
+
+        file.seek(SeekFrom::Start(rest as u64))?;
+        io::copy(file, &mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+
+    fs::rename(&temporary, path)
+}
+
+/// Parse a single line of a sheet stored as JSON Lines. `number` is the line number to report in
+/// errors.
+pub(crate) fn parse_line(line: &str, number: usize) -> Result<Line, SheetError> {
+    if line.trim().is_empty() {
+        return Ok(Line::Header(0));
+    }
+
+    if let Ok(header) = serde_json::from_str::<Header>(line) {
+        if header.version > FORMAT_VERSION {
+            return Err(SheetError::NewerVersion(header.version));
+        }
+
+        return Ok(Line::Header(header.revision));
+    }
+
+    serde_json::from_str(line)
+        .map(Line::Event)
+        .map_err(|err| SheetError::ParseLine(number, err))
+}
+
+/// Read the last non-empty line of the given file, together with the offset at which it starts,
+/// by reading backwards from the end of the file.
+fn last_line(file: &mut File) -> io::Result<Option<(u64, String)>> {
+    const CHUNK: u64 = 4096;
+
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut end = len;
+    let mut tail: Vec<u8> = Vec::new();
+
+    loop {
+        let start = end.saturating_sub(CHUNK);
+        let mut chunk = vec![0; (end - start) as usize];
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        end = start;
+
+        // Ignore trailing whitespace, then look for the newline before the last line.
+        let content_end = match tail.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(i) => i + 1,
+            None if start == 0 => return Ok(None),
+            None => continue,
+        };
+
+        let line_start = match tail[..content_end].iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None if start == 0 => 0,
+            None => continue,
+        };
+
+        let line = String::from_utf8(tail[line_start..content_end].to_vec())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        return Ok(Some((start + line_start as u64, line)));
+    }
+}
+
+/// A format in which a sheet can be stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SheetFormat {
+    /// JSON, used for files with any extension other than those below.
+    Json,
+    /// JSON Lines, used for files with the extension `.jsonl`. The first line records the format
+    /// version, and each following line is a single event, so the last event can be read without
+    /// reading the rest of the file.
+    #[serde(rename = "jsonl")]
+    JsonLines,
+    /// TOML, used for files with the extension `.toml`.
+    Toml,
+    /// MessagePack, a compact binary format that is faster to read and write than the text
+    /// formats. Used for files with the extension `.msgpack`.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl SheetFormat {
+    /// Every supported format.
+    pub const ALL: [SheetFormat; 4] = [
+        SheetFormat::Json,
+        SheetFormat::JsonLines,
+        SheetFormat::Toml,
+        SheetFormat::MessagePack,
+    ];
+
+    /// Get the format used for the sheet file at the given path, based on its extension.
+    pub fn from_path<P>(path: P) -> SheetFormat
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") => SheetFormat::JsonLines,
+            Some("toml") => SheetFormat::Toml,
+            Some("msgpack") => SheetFormat::MessagePack,
+            _ => SheetFormat::Json,
+        }
+    }
+
+    /// Get the name of a sheet file in this format in the default directory.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SheetFormat::Json => "sheet.json",
+            SheetFormat::JsonLines => "sheet.jsonl",
+            SheetFormat::Toml => "sheet.toml",
+            SheetFormat::MessagePack => "sheet.msgpack",
+        }
+    }
+}
+
+impl FromStr for SheetFormat {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "json" => Ok(SheetFormat::Json),
+            "jsonl" => Ok(SheetFormat::JsonLines),
+            "toml" => Ok(SheetFormat::Toml),
+            "msgpack" | "messagepack" => Ok(SheetFormat::MessagePack),
+            _ => Err("Sheet format not recognised.".into()),
+        }
+    }
 }
 
 /// Whether or not time is currently being tracked.
@@ -187,6 +1581,34 @@ pub enum SheetStatus {
     Empty,
 }
 
+impl SheetStatus {
+    /// Get the status of a sheet whose last event is the one given.
+    pub fn of(last: Option<&Event>) -> SheetStatus {
+        match last {
+            Some(Event {
+                stop: Some(stop), ..
+            }) => SheetStatus::PunchedOut(*stop),
+            Some(Event { start, .. }) => SheetStatus::PunchedIn(*start),
+            None => SheetStatus::Empty,
+        }
+    }
+}
+
+/// A summary of the time tracked recently, as shown by `punch status`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusSummary {
+    /// Whether time is currently being tracked.
+    pub status: SheetStatus,
+    /// The project of the ongoing time-tracking period, if there is one.
+    pub project: Option<String>,
+    /// How long the ongoing time-tracking period has lasted, if there is one.
+    pub session: Option<Duration>,
+    /// The time worked today, including the ongoing time-tracking period.
+    pub today: Duration,
+    /// The time worked this week, including the ongoing time-tracking period.
+    pub week: Duration,
+}
+
 /// Errors arising through the use of [`Sheet`][sheet].
 ///
 /// [sheet]: ./struct.Sheet.html
@@ -198,14 +1620,69 @@ pub enum SheetError {
     PunchedOut(DateTime<Utc>),
     #[error("not punched in, no punch-ins recorded")]
     NoPunches,
+    #[error("{0} is in the future")]
+    InFuture(DateTime<Utc>),
+    #[error("punching in at {time} would be before the end of the event starting at {}", .event.start)]
+    PunchInBeforeStop {
+        time: DateTime<Utc>,
+        event: Box<Event>,
+    },
+    #[error("punching out at {time} would be before punching in at {}", .event.start)]
+    PunchOutBeforePunchIn {
+        time: DateTime<Utc>,
+        event: Box<Event>,
+    },
+    #[error("event starting at {0} stops before it starts")]
+    StopBeforeStart(DateTime<Utc>),
+    #[error("event starting at {0} is unfinished, but isn't the last event")]
+    Unfinished(DateTime<Utc>),
+    #[error("events starting at {0} and {1} overlap")]
+    Overlap(DateTime<Utc>, DateTime<Utc>),
+    #[error("event starting at {} overlaps {} other events", .event.start, .overlapping.len())]
+    Overlapping {
+        event: Box<Event>,
+        overlapping: Vec<Event>,
+    },
+    #[error("event starting at {0} is {1}, and has to be unlocked before it can be changed")]
+    Frozen(DateTime<Utc>, Review),
+    #[error("no event starting at {0}")]
+    NoEvent(DateTime<Utc>),
+    #[error("{1} isn't within the event starting at {0}")]
+    SplitOutside(DateTime<Utc>, DateTime<Utc>),
+    #[error("events starting at {0} and {1} aren't back to back")]
+    NotAdjacent(DateTime<Utc>, DateTime<Utc>),
+    #[error("events starting at {0} and {1} are on different projects")]
+    DifferentProjects(DateTime<Utc>, DateTime<Utc>),
     #[error("unable to find sheet file")]
     FindSheet,
     #[error("unable to open sheet file")]
     OpenSheet(#[source] std::io::Error),
     #[error("unable to read sheet file")]
     ReadSheet(#[source] std::io::Error),
+    #[error("unable to parse sheet at line {line}, column {column}, near `{snippet}`")]
+    ParseSheet {
+        line: usize,
+        column: usize,
+        snippet: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("unable to parse line {0} of sheet")]
+    ParseLine(usize, #[source] serde_json::Error),
+    #[error("sheet format version {0} is newer than the latest supported version")]
+    NewerVersion(u32),
+    #[error("unable to parse sheet")]
+    ParseToml(#[source] toml::de::Error),
     #[error("unable to parse sheet")]
-    ParseSheet(#[source] serde_json::Error),
+    ParseMessagePack(#[source] rmp_serde::decode::Error),
+    #[error("unable to serialize sheet")]
+    SerializeJson(#[source] serde_json::Error),
+    #[error("unable to serialize sheet")]
+    SerializeToml(#[source] toml::ser::Error),
+    #[error("unable to serialize sheet")]
+    SerializeMessagePack(#[source] rmp_serde::encode::Error),
+    #[error("only sheets stored as JSON Lines can be appended to")]
+    NotJsonLines,
     #[error("unable to write sheet to file")]
     WriteSheet(#[source] std::io::Error),
 }
@@ -0,0 +1,244 @@
+//! An interactive shell for running one command after another in the same process.
+//!
+//! While the shell runs, the sheet is kept in memory between commands, and only read again when
+//! its file has been modified since. Lines are edited with [rustyline], which completes the names
+//! of commands, options, projects and tags, and keeps a history of the commands entered in
+//! `shell_history` next to the config file.
+//!
+//! [rustyline]: https://crates.io/crates/rustyline
+
+use std::{
+    ffi::OsString,
+    fs, iter,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use punch_clock::{config::split_args, sheet::SheetError, Config, Sheet};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+use structopt::{
+    clap::{App, AppSettings},
+    StructOpt,
+};
+use tracing::warn;
+
+use crate::{completions, expand_alias, i18n, load_sheet, opt::Opt, output, report, run};
+
+/// Whether the shell is running, so that sheets loaded are kept.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// The last sheet loaded in the shell, with its location and when its file was last modified.
+static LOADED: Mutex<Option<(PathBuf, SystemTime, Sheet)>> = Mutex::new(None);
+
+/// Read commands, without the leading `punch`, and run them until `exit` or the end of input.
+pub fn repl() {
+    ACTIVE.store(true, Ordering::Relaxed);
+
+    // Each command selects the language again, but the greeting comes first.
+    let config = Config::load_default().unwrap_or_default();
+    i18n::init(config.language.as_deref());
+
+    let mut editor = match Editor::<Completion, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            fail!("Can't start the shell: {}.", err);
+            return;
+        }
+    };
+    let history = Config::default_dir()
+        .ok()
+        .map(|dir| dir.join("shell_history"));
+
+    // There's no history the first time the shell is run.
+    if let Some(history) = &history {
+        editor.load_history(history).ok();
+    }
+
+    editor.set_helper(Some(Completion {
+        app: Opt::clap(),
+        config: config.clone(),
+    }));
+
+    say!("Enter commands as for punch, such as \"count week\", or \"exit\" to leave.");
+
+    loop {
+        let line = match editor.readline("punch> ") {
+            Ok(line) => line,
+            // Ctrl-C abandons the line being entered, as in other shells.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                warn!("Unable to read command: {}", err);
+                break;
+            }
+        };
+        let args = split_args(&line);
+
+        match args.first().map(String::as_str) {
+            None => continue,
+            Some("exit" | "quit") => break,
+            _ => {}
+        }
+
+        editor.add_history_entry(line.trim()).ok();
+
+        let args: Vec<OsString> = iter::once("punch".into())
+            .chain(args.into_iter().map(OsString::from))
+            .collect();
+        let opt = Opt::from_iter_safe(&args).or_else(|err| match expand_alias(&args) {
+            Some(expanded) => Opt::from_iter_safe(expanded),
+            None => Err(err),
+        });
+
+        match opt {
+            Ok(opt) => {
+                if let Err(err) = run(opt) {
+                    report(&err);
+                }
+            }
+            // Errors and help are printed as clap would, without exiting.
+            Err(err) => println!("{}", err.message),
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(err) = editor.save_history(history) {
+            warn!("Unable to save shell history: {}", err);
+        }
+    }
+
+    // A command failing in the shell doesn't make the shell itself fail.
+    output::clear_failed();
+}
+
+/// Completes the names of commands, and of the options, projects and tags they take.
+struct Completion {
+    /// The command line parser, which commands and options are looked up in.
+    app: App<'static, 'static>,
+    config: Config,
+}
+
+impl Completion {
+    /// Get the candidates for the word following the given words, which may not start with the
+    /// part of the word entered so far.
+    fn candidates(&self, words: &[String], word: &str) -> Vec<String> {
+        // clap 2 only exposes the commands and options of a parser through its hidden fields.
+        let mut app = &self.app;
+
+        for word in words {
+            if let Some(command) = app.p.subcommands.iter().find(|c| c.p.meta.name == *word) {
+                app = command;
+            }
+        }
+
+        let previous = words.last().map(String::as_str).unwrap_or_default();
+        let names = completions::names_for(previous).or_else(|| {
+            (words.len() == 1 && previous == "in" && !word.starts_with('-')).then_some("projects")
+        });
+
+        if let Some(names) = names {
+            return match load_sheet(&self.config) {
+                Ok(sheet) => completions::names(&self.config, &sheet, names)
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+        }
+
+        if word.starts_with('-') {
+            // Global options are only kept by the top-level parser until parsing.
+            return [app, &self.app]
+                .iter()
+                .flat_map(|app| {
+                    let flags = app.p.flags.iter().map(|flag| flag.s.long);
+                    let options = app.p.opts.iter().map(|option| option.s.long);
+                    flags.chain(options)
+                })
+                .flatten()
+                .map(|long| format!("--{}", long))
+                .collect();
+        }
+
+        let mut candidates: Vec<String> = app
+            .p
+            .subcommands
+            .iter()
+            .filter(|command| !command.p.is_set(AppSettings::Hidden))
+            .map(|command| command.p.meta.name.clone())
+            .collect();
+
+        if words.is_empty() {
+            candidates.extend(self.config.aliases.keys().cloned());
+            candidates.extend(["exit".to_owned(), "quit".to_owned()]);
+        }
+
+        candidates
+    }
+}
+
+impl Completer for Completion {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let word = line.rsplit(char::is_whitespace).next().unwrap_or_default();
+        let start = line.len() - word.len();
+
+        let mut candidates: Vec<String> = self
+            .candidates(&split_args(&line[..start]), word)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+/// Load the sheet at the given path, reusing the sheet loaded before if the shell is running and
+/// the file hasn't been modified since.
+pub fn load(path: PathBuf) -> Result<Sheet, SheetError> {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return Sheet::load(path);
+    }
+
+    let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+    let mut loaded = LOADED.lock().unwrap();
+
+    if let (Some(modified), Some((loaded_path, loaded_modified, sheet))) = (modified, &*loaded) {
+        if *loaded_path == path && *loaded_modified == modified {
+            return Ok(sheet.clone());
+        }
+    }
+
+    let sheet = Sheet::load(&path)?;
+
+    if let Some(modified) = modified {
+        *loaded = Some((path, modified, sheet.clone()));
+    }
+
+    Ok(sheet)
+}
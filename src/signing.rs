@@ -0,0 +1,200 @@
+//! Signing tracking periods, so that they can be shown not to have been changed since they were
+//! recorded.
+//!
+//! Finished tracking periods are signed with an Ed25519 key kept in `signing.key` next to the
+//! config file, which is generated the first time it's needed. The signature covers the times,
+//! project, tags, billability, note and user of a tracking period, but not its review state, so
+//! that submitting it for review doesn't break the signature. Anyone with the public key can check
+//! the signatures, such as a client the sheet is shared with.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{config::ConfigError, Config, Event};
+
+/// The parts of an event that are signed, serialized as JSON to get the message that's signed.
+#[derive(Serialize)]
+struct Signed<'a> {
+    start: DateTime<Utc>,
+    stop: Option<DateTime<Utc>>,
+    project: &'a Option<String>,
+    tags: &'a [String],
+    billable: bool,
+    note: &'a Option<String>,
+    user: &'a Option<String>,
+}
+
+impl<'a> From<&'a Event> for Signed<'a> {
+    fn from(event: &'a Event) -> Self {
+        Signed {
+            start: event.start,
+            stop: event.stop,
+            project: &event.project,
+            tags: &event.tags,
+            billable: event.billable,
+            note: &event.note,
+            user: &event.user,
+        }
+    }
+}
+
+/// Whether an event's signature shows it to be unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The event hasn't been signed.
+    Unsigned,
+    /// The signature matches the event.
+    Valid,
+    /// The signature doesn't match the event, which has been changed since it was signed, or was
+    /// signed with a different key.
+    Invalid,
+}
+
+/// Get the path to the file the signing key is kept in.
+///
+/// This is the file `signing.key` in the same directory as the config file.
+pub fn key_loc() -> Result<PathBuf, SigningError> {
+    Config::default_loc()
+        .map(|path| path.with_file_name("signing.key"))
+        .map_err(SigningError::FindKey)
+}
+
+/// Load the signing key from the file at the given path.
+pub fn load<P>(path: P) -> Result<SigningKey, SigningError>
+where
+    P: AsRef<Path>,
+{
+    let hex = fs::read_to_string(path).map_err(SigningError::ReadKey)?;
+    let bytes = from_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SigningError::ParseKey)?;
+
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Load the signing key from the file at the given path, generating a new key and writing it
+/// there if the file doesn't exist.
+pub fn load_or_generate<P>(path: P) -> Result<SigningKey, SigningError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+
+    match load(path) {
+        Err(SigningError::ReadKey(err)) if err.kind() == io::ErrorKind::NotFound => {
+            let mut bytes = [0; 32];
+            getrandom::getrandom(&mut bytes).map_err(SigningError::GenerateKey)?;
+            let key = SigningKey::from_bytes(&bytes);
+
+            write_key(path, &key).map_err(SigningError::WriteKey)?;
+
+            Ok(key)
+        }
+        result => result,
+    }
+}
+
+/// Write the signing key to a new file at the given path, which only its owner can read on Unix.
+fn write_key(path: &Path, key: &SigningKey) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", to_hex(&key.to_bytes()))
+}
+
+/// Parse a public key given in hexadecimal.
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey, SigningError> {
+    from_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or(SigningError::ParsePublicKey)
+}
+
+/// Get the public key for the given signing key, in hexadecimal.
+pub fn public_key(key: &SigningKey) -> String {
+    to_hex(key.verifying_key().as_bytes())
+}
+
+/// Sign the given event with the given key, returning the signature in hexadecimal.
+pub fn sign(event: &Event, key: &SigningKey) -> String {
+    to_hex(&key.sign(&message(event)).to_bytes())
+}
+
+/// Check the signature of the given event against the given public key.
+pub fn verify(event: &Event, key: &VerifyingKey) -> Verification {
+    let Some(signature) = &event.signature else {
+        return Verification::Unsigned;
+    };
+
+    let valid = from_hex(signature)
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .is_some_and(|signature| key.verify(&message(event), &signature).is_ok());
+
+    if valid {
+        Verification::Valid
+    } else {
+        Verification::Invalid
+    }
+}
+
+/// Get the message signed for the given event.
+fn message(event: &Event) -> Vec<u8> {
+    serde_json::to_vec(&Signed::from(event)).unwrap()
+}
+
+/// Format bytes in hexadecimal.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Parse bytes given in hexadecimal, or `None` if they aren't valid hexadecimal.
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Errors arising while signing events or checking their signatures.
+#[derive(Error, Debug)]
+pub enum SigningError {
+    #[error("unable to find signing key")]
+    FindKey(#[source] ConfigError),
+    #[error("unable to read signing key")]
+    ReadKey(#[source] io::Error),
+    #[error("signing key isn't 32 bytes in hexadecimal")]
+    ParseKey,
+    #[error("unable to generate signing key")]
+    GenerateKey(#[source] getrandom::Error),
+    #[error("unable to write signing key")]
+    WriteKey(#[source] io::Error),
+    #[error("public key isn't a valid Ed25519 key in hexadecimal")]
+    ParsePublicKey,
+}
@@ -0,0 +1,164 @@
+//! Named copies of the sheet, kept in the `snapshots` directory next to it, such as from before a
+//! big cleanup, so that the sheet can be put back as it was.
+//!
+//! Each snapshot is a copy of the sheet file, named after the snapshot with the extension of the
+//! sheet (e.g. `snapshots/before-cleanup.json`).
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::{sheet::SheetError, Sheet};
+
+/// A named copy of the sheet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The name of the snapshot.
+    pub name: String,
+    /// The path to the copy of the sheet.
+    pub path: PathBuf,
+    /// When the snapshot was taken.
+    pub created: DateTime<Utc>,
+}
+
+impl Snapshot {
+    /// Get the path to the directory snapshots of the sheet at the given path are kept in.
+    ///
+    /// This is the directory `snapshots` in the same directory as the sheet.
+    pub fn dir_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        sheet_path.as_ref().with_file_name("snapshots")
+    }
+
+    /// Take a snapshot with the given name of the sheet at the given path, replacing any snapshot
+    /// with the same name if `replace` is set.
+    pub fn create<P>(sheet_path: P, name: &str, replace: bool) -> Result<Snapshot, SnapshotError>
+    where
+        P: AsRef<Path>,
+    {
+        check_name(name)?;
+
+        let sheet_path = sheet_path.as_ref();
+        let existing = Self::find(sheet_path, name);
+
+        match existing {
+            Ok(_) if !replace => return Err(SnapshotError::Exists(name.to_owned())),
+            Ok(snapshot) => fs::remove_file(&snapshot.path).map_err(SnapshotError::Write)?,
+            Err(_) => {}
+        }
+
+        let dir = Self::dir_for(sheet_path);
+        let mut path = dir.join(name);
+
+        if let Some(ext) = sheet_path.extension() {
+            path.as_mut_os_string().push(".");
+            path.as_mut_os_string().push(ext);
+        }
+
+        fs::create_dir_all(&dir)
+            .and_then(|_| fs::copy(sheet_path, &path))
+            .map_err(SnapshotError::Write)?;
+
+        Self::at(path)
+    }
+
+    /// List the snapshots of the sheet at the given path, oldest first.
+    pub fn list<P>(sheet_path: P) -> Result<Vec<Snapshot>, SnapshotError>
+    where
+        P: AsRef<Path>,
+    {
+        let entries = match fs::read_dir(Self::dir_for(sheet_path)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(SnapshotError::Read(err)),
+        };
+
+        let mut snapshots = Vec::new();
+
+        for entry in entries {
+            let path = entry.map_err(SnapshotError::Read)?.path();
+
+            if path.is_file() {
+                snapshots.push(Self::at(path)?);
+            }
+        }
+
+        snapshots.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(snapshots)
+    }
+
+    /// Find the snapshot with the given name of the sheet at the given path.
+    pub fn find<P>(sheet_path: P, name: &str) -> Result<Snapshot, SnapshotError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::list(sheet_path)?
+            .into_iter()
+            .find(|snapshot| snapshot.name == name)
+            .ok_or_else(|| SnapshotError::NotFound(name.to_owned()))
+    }
+
+    /// Attempt to load the copy of the sheet in the snapshot.
+    pub fn load(&self) -> Result<Sheet, SnapshotError> {
+        Sheet::load(&self.path).map_err(SnapshotError::Load)
+    }
+
+    /// Delete the snapshot.
+    pub fn delete(&self) -> Result<(), SnapshotError> {
+        fs::remove_file(&self.path).map_err(SnapshotError::Write)
+    }
+
+    /// Get the snapshot kept in the file at the given path.
+    fn at(path: PathBuf) -> Result<Snapshot, SnapshotError> {
+        let created = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(SnapshotError::Read)?;
+
+        Ok(Snapshot {
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            created: created.into(),
+            path,
+        })
+    }
+}
+
+/// Check that the given name can be used as the name of a snapshot file.
+fn check_name(name: &str) -> Result<(), SnapshotError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\', ':'])
+        && !name.chars().any(char::is_control);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(SnapshotError::InvalidName(name.to_owned()))
+    }
+}
+
+/// Errors arising while taking, listing or restoring snapshots.
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("{0:?} can't be used as the name of a snapshot")]
+    InvalidName(String),
+    #[error("a snapshot named {0} already exists")]
+    Exists(String),
+    #[error("no snapshot named {0}")]
+    NotFound(String),
+    #[error("unable to read snapshots")]
+    Read(#[source] io::Error),
+    #[error("unable to write snapshot")]
+    Write(#[source] io::Error),
+    #[error("unable to load snapshot: {0}")]
+    Load(SheetError),
+}
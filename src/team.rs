@@ -0,0 +1,101 @@
+//! Totals of the time worked by each member of a team on each project.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{Event, Sheet};
+
+/// The time worked by each user on each project over some period of time, gathered from a sheet
+/// shared by the team or from a sheet for each member.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TeamTotals {
+    /// The time worked on each project (or `None` for time without a project), by user.
+    pub users: BTreeMap<String, BTreeMap<Option<String>, Duration>>,
+}
+
+impl TeamTotals {
+    /// Add the time worked between the two given instants in the given sheet, counting events
+    /// without a user as `default_user`'s.
+    pub fn add_sheet(
+        &mut self,
+        sheet: &Sheet,
+        default_user: &str,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) {
+        // Different users' events can overlap, so each user's events are looked up on their own.
+        let mut by_user: BTreeMap<&str, Vec<Event>> = BTreeMap::new();
+
+        for event in sheet.events() {
+            let user = event.user.as_deref().unwrap_or(default_user);
+            by_user.entry(user).or_default().push(event.clone());
+        }
+
+        for (user, events) in by_user {
+            let sheet = Sheet::from_events(events);
+            let projects = self.users.entry(user.to_owned()).or_default();
+
+            for event in sheet.events_in_range(begin, end) {
+                let time = Sheet::from_events(vec![event.clone()]).count_range(begin, end);
+
+                *projects
+                    .entry(event.project.clone())
+                    .or_insert_with(Duration::zero) += time;
+            }
+        }
+    }
+
+    /// Get every project worked on by anyone, with `None` (for time without a project) last.
+    pub fn projects(&self) -> Vec<Option<String>> {
+        let projects: BTreeSet<&Option<String>> = self
+            .users
+            .values()
+            .flat_map(|projects| projects.keys())
+            .collect();
+        let (mut named, unnamed): (Vec<_>, Vec<_>) = projects
+            .into_iter()
+            .cloned()
+            .partition(|project| project.is_some());
+
+        named.extend(unnamed);
+        named
+    }
+
+    /// Get the time worked by the given user on the given project.
+    pub fn get(&self, user: &str, project: &Option<String>) -> Duration {
+        self.users
+            .get(user)
+            .and_then(|projects| projects.get(project))
+            .copied()
+            .unwrap_or_else(Duration::zero)
+    }
+
+    /// Get the total time worked by the given user.
+    pub fn user_total(&self, user: &str) -> Duration {
+        self.users
+            .get(user)
+            .map(|projects| {
+                projects
+                    .values()
+                    .fold(Duration::zero(), |acc, &next| acc + next)
+            })
+            .unwrap_or_else(Duration::zero)
+    }
+
+    /// Get the total time worked on the given project by everyone.
+    pub fn project_total(&self, project: &Option<String>) -> Duration {
+        self.users
+            .keys()
+            .map(|user| self.get(user, project))
+            .fold(Duration::zero(), |acc, next| acc + next)
+    }
+
+    /// Get the total time worked by everyone.
+    pub fn total(&self) -> Duration {
+        self.users
+            .keys()
+            .map(|user| self.user_total(user))
+            .fold(Duration::zero(), |acc, next| acc + next)
+    }
+}
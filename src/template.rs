@@ -0,0 +1,21 @@
+//! Rendering the output templates that can be configured in place of the default output of some
+//! commands.
+
+use minijinja::{Environment, Value};
+
+/// Render the given template with the given variables and print the result, or if rendering
+/// fails, print why.
+pub fn print(template: &str, variables: Value) {
+    match render(template, variables) {
+        Ok(output) => say!("{}", output),
+        Err(err) => fail!("Can't render output template: {}.", err),
+    }
+}
+
+/// Render the given template with the given variables.
+pub fn render(template: &str, variables: Value) -> Result<String, minijinja::Error> {
+    let mut env = Environment::new();
+    env.add_template("output", template)?;
+
+    env.get_template("output")?.render(variables)
+}
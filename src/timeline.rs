@@ -0,0 +1,104 @@
+//! Drawing the tracking periods on each day as bars on a 24-hour axis.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use punch_clock::{local_instant, Sheet};
+
+/// The number of columns each day's 24 hours are drawn across, so that each is half an hour.
+const WIDTH: usize = 48;
+/// The characters that tracking periods on different projects are drawn with, in turn.
+const BARS: [char; 4] = ['█', '▓', '▒', '░'];
+/// The character that untracked time is drawn with.
+const GAP: char = '·';
+/// The width of the dates in front of the bars.
+const LABEL: usize = 12;
+
+/// Draw a bar for each local date between the two given instants, on which each half hour is
+/// filled in with the project most worked on in it, followed by a legend of the projects.
+pub fn print(sheet: &Sheet, start: DateTime<Utc>, end: DateTime<Utc>) {
+    // As with `punch balance`, nothing is drawn before the first tracked event.
+    let start = match sheet.events().first() {
+        Some(first) => start.max(first.start),
+        None => return,
+    };
+    let end = end.min(Utc::now());
+
+    let mut projects: Vec<Option<&str>> = Vec::new();
+    let mut date = start.with_timezone(&Local).date_naive();
+    let last = end.with_timezone(&Local).date_naive();
+
+    say!("{}{}", " ".repeat(LABEL), axis().trim_end());
+
+    while date <= last {
+        let mut bar = String::new();
+
+        for cell in 0..WIDTH {
+            let (cell_start, cell_end) = (instant(date, cell), instant(date, cell + 1));
+            let mut times: BTreeMap<Option<&str>, Duration> = BTreeMap::new();
+
+            for event in sheet.events_in_range(cell_start, cell_end) {
+                let time =
+                    event.stop.unwrap_or_else(Utc::now).min(cell_end) - event.start.max(cell_start);
+
+                if time > Duration::zero() {
+                    *times
+                        .entry(event.project.as_deref())
+                        .or_insert_with(Duration::zero) += time;
+                }
+            }
+
+            let most = times
+                .into_iter()
+                .max_by_key(|(_, time)| *time)
+                .map(|(project, _)| project);
+
+            bar.push(match most {
+                Some(project) => {
+                    let index = projects
+                        .iter()
+                        .position(|p| *p == project)
+                        .unwrap_or_else(|| {
+                            projects.push(project);
+                            projects.len() - 1
+                        });
+
+                    BARS[index % BARS.len()]
+                }
+                None => GAP,
+            });
+        }
+
+        say!(
+            "{}{}",
+            format!("{:LABEL$}", date.format("%a %e %b").to_string()),
+            bar
+        );
+        date = date.succ_opt().unwrap();
+    }
+
+    for (index, project) in projects.iter().enumerate() {
+        let name = match project {
+            Some(project) => (*project).to_owned(),
+            None => tr!("(no project)"),
+        };
+
+        say!("{} {}", BARS[index % BARS.len()], name);
+    }
+}
+
+/// Get the labels of the hours along the top of the bars, every three hours.
+fn axis() -> String {
+    (0..24)
+        .step_by(3)
+        .map(|hour| format!("{:<width$}", format!("{:02}", hour), width = WIDTH / 8))
+        .collect()
+}
+
+/// Get the instant at which the given column of the bar for the given date begins.
+fn instant(date: NaiveDate, cell: usize) -> DateTime<Utc> {
+    let minutes = (cell * 24 * 60 / WIDTH) as i64;
+    let naive = date.and_time(NaiveTime::MIN) + Duration::minutes(minutes);
+
+    local_instant(naive.date(), naive.time()).into()
+}
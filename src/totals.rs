@@ -0,0 +1,384 @@
+//! A cache of the total time worked on each day, kept in a file next to the sheet.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Days, Duration, IsoWeek, Local, Months, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{period::midnight, Sheet};
+
+/// The total time worked on each local date, counted from the finished events at the start of a
+/// sheet.
+///
+/// The cache is updated incrementally: events added since it was last updated are counted on top
+/// of the existing totals. If the last event it counted no longer matches the sheet, it's rebuilt
+/// from scratch. Changes to earlier events can't be detected this way, so anything that edits past
+/// events should [`clear()`][clear] the cache.
+///
+/// [clear]: #method.clear
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyTotals {
+    /// The number of events at the start of the sheet that have been counted.
+    counted: usize,
+    /// The start and stop of the last event that was counted.
+    last: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// The number of seconds worked on each date.
+    days: BTreeMap<NaiveDate, i64>,
+}
+
+impl DailyTotals {
+    /// Get the path to the cache file for the sheet at the given path.
+    ///
+    /// This is the file `totals.json` in the same directory as the sheet.
+    pub fn loc_for<P>(sheet_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        sheet_path.as_ref().with_file_name("totals.json")
+    }
+
+    /// Attempt to load the cache from the file at the given path.
+    pub fn load<P>(path: P) -> Result<DailyTotals, TotalsError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path).map_err(TotalsError::ReadTotals)?;
+
+        serde_json::from_reader(BufReader::new(file)).map_err(TotalsError::ParseTotals)
+    }
+
+    /// Attempt to write the cache to the file at the given path.
+    pub fn write<P>(&self, path: P) -> Result<(), TotalsError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).map_err(TotalsError::WriteTotals)?;
+
+        serde_json::to_writer(BufWriter::new(file), self)
+            .map_err(|err| TotalsError::WriteTotals(err.into()))
+    }
+
+    /// Empty the cache, so that the next [`update()`][update] counts every event again.
+    ///
+    /// [update]: #method.update
+    pub fn clear(&mut self) {
+        *self = DailyTotals::default();
+    }
+
+    /// Bring the cache up to date with the given sheet, returning whether anything changed.
+    pub fn update(&mut self, sheet: &Sheet) -> bool {
+        let matches_sheet = match self.counted.checked_sub(1) {
+            Some(i) => sheet
+                .events()
+                .get(i)
+                .is_some_and(|event| event.stop.map(|stop| (event.start, stop)) == self.last),
+            None => true,
+        };
+
+        if !matches_sheet {
+            self.clear();
+        }
+
+        let mut changed = !matches_sheet;
+
+        for event in &sheet.events()[self.counted..] {
+            let Some(stop) = event.stop else {
+                break;
+            };
+
+            for (date, time) in split_days(event.start, stop) {
+                *self.days.entry(date).or_insert(0) += time.num_seconds();
+            }
+
+            self.counted += 1;
+            self.last = Some((event.start, stop));
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Get the total time worked on the given local date, not including an ongoing time-tracking
+    /// period.
+    pub fn on(&self, date: NaiveDate) -> Duration {
+        Duration::seconds(self.days.get(&date).copied().unwrap_or(0))
+    }
+
+    /// Count the amount of time worked between the two given instants, like
+    /// [`Sheet::count_range()`][count], using the cached totals for each day.
+    ///
+    /// Only whole days are counted from the cache: a day is included if `begin` falls at or
+    /// before its start and `end` falls after its start. This is exact for ranges starting at
+    /// midnight and ending at midnight or now, as [`Period`][period]s do. The cache must be
+    /// [up to date][update] with `sheet`, whose ongoing time-tracking period is counted
+    /// separately.
+    ///
+    /// [count]: ../sheet/struct.Sheet.html#method.count_range
+    /// [period]: ../enum.Period.html
+    /// [update]: #method.update
+    pub fn count_range(&self, sheet: &Sheet, begin: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        // A range starting before the first cached day, such as `all` starting at the earliest
+        // instant there is, which can't be converted to local time, counts from that day.
+        let first = match self.days.first_key_value() {
+            Some((&earliest, _)) if midnight(earliest) >= begin => earliest,
+            Some(_) => {
+                let begin_date = begin.with_timezone(&Local).date_naive();
+
+                if midnight(begin_date) >= begin {
+                    begin_date
+                } else {
+                    begin_date + Days::new(1)
+                }
+            }
+            None => NaiveDate::MAX,
+        };
+
+        let cached = self
+            .days
+            .range(first..)
+            .take_while(|(date, _)| midnight(**date) < end)
+            .map(|(_, seconds)| Duration::seconds(*seconds))
+            .fold(Duration::zero(), |acc, next| acc + next);
+
+        let ongoing =
+            Sheet::from_events(sheet.events()[self.counted.min(sheet.events().len())..].to_vec())
+                .count_range(begin, end);
+
+        cached + ongoing
+    }
+}
+
+/// The time worked in two periods of time, for comparing one with the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    /// The time worked in the period being compared.
+    pub time: Duration,
+    /// The time worked in the period it's compared with.
+    pub baseline: Duration,
+}
+
+impl Comparison {
+    /// Get how much more time was worked in the period being compared than in the baseline.
+    pub fn difference(&self) -> Duration {
+        self.time - self.baseline
+    }
+
+    /// Get the difference as a percentage of the time worked in the baseline, if any was.
+    pub fn percent_change(&self) -> Option<f64> {
+        (self.baseline > Duration::zero()).then(|| {
+            self.difference().num_seconds() as f64 / self.baseline.num_seconds() as f64 * 100.0
+        })
+    }
+}
+
+/// Whether totals count the breaks between tracking periods on the same day as time worked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Counting {
+    /// Only the tracking periods are counted.
+    #[default]
+    Net,
+    /// The [breaks][breaks] between them are counted too.
+    ///
+    /// [breaks]: ../sheet/struct.Sheet.html#method.count_breaks
+    Gross,
+}
+
+impl Counting {
+    /// Get the total time between the two given instants on the given sheet, given the time
+    /// counted in its tracking periods, which may have been rounded.
+    pub fn total(
+        self,
+        sheet: &Sheet,
+        worked: Duration,
+        begin: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Duration {
+        match self {
+            Counting::Net => worked,
+            Counting::Gross => worked + sheet.count_breaks(begin, end),
+        }
+    }
+}
+
+/// Total the time worked on each local date between the two given instants, including an ongoing
+/// time-tracking period if there is one. Dates on which nothing was worked are left out.
+pub fn totals_by_date(
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BTreeMap<NaiveDate, Duration> {
+    let mut days = BTreeMap::new();
+
+    for event in sheet.events_in_range(begin, end) {
+        let start = event.start.max(begin);
+        let stop = event.stop.unwrap_or_else(Utc::now).min(end);
+
+        for (date, time) in split_days(start, stop) {
+            *days.entry(date).or_insert_with(Duration::zero) += time;
+        }
+    }
+
+    days
+}
+
+/// Total the time worked in each ISO 8601 week between the two given instants, in order,
+/// including weeks in which nothing was worked. Weeks are counted from the first event, if the
+/// range begins before it.
+pub fn totals_by_week(
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(IsoWeek, Duration)> {
+    let Some(first) = sheet.events().first() else {
+        return Vec::new();
+    };
+
+    let begin = begin.max(first.start);
+    if begin >= end {
+        return Vec::new();
+    }
+
+    let mut weeks: BTreeMap<IsoWeek, Duration> = BTreeMap::new();
+    let (first, last) = (
+        begin.with_timezone(&Local).iso_week(),
+        (end - Duration::nanoseconds(1))
+            .with_timezone(&Local)
+            .iso_week(),
+    );
+
+    let mut monday = NaiveDate::from_isoywd_opt(first.year(), first.week(), Weekday::Mon);
+    while let Some(date) = monday.filter(|date| date.iso_week() <= last) {
+        weeks.insert(date.iso_week(), Duration::zero());
+        monday = date.checked_add_days(Days::new(7));
+    }
+
+    for (date, time) in totals_by_date(sheet, begin, end) {
+        *weeks.entry(date.iso_week()).or_insert_with(Duration::zero) += time;
+    }
+
+    weeks.into_iter().collect()
+}
+
+/// Total the time worked in each calendar month between the two given instants, in order, with
+/// each month given by its first day, including months in which nothing was worked. Months are
+/// counted from the first event, if the range begins before it.
+pub fn totals_by_month(
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(NaiveDate, Duration)> {
+    let Some(first) = sheet.events().first() else {
+        return Vec::new();
+    };
+
+    let begin = begin.max(first.start);
+    if begin >= end {
+        return Vec::new();
+    }
+
+    let mut months: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+    let first_of = |date: NaiveDate| date.with_day(1).unwrap();
+    let last = first_of(
+        (end - Duration::nanoseconds(1))
+            .with_timezone(&Local)
+            .date_naive(),
+    );
+
+    let mut month = Some(first_of(begin.with_timezone(&Local).date_naive()));
+    while let Some(date) = month.filter(|date| *date <= last) {
+        months.insert(date, Duration::zero());
+        month = date.checked_add_months(Months::new(1));
+    }
+
+    for (date, time) in totals_by_date(sheet, begin, end) {
+        *months.entry(first_of(date)).or_insert_with(Duration::zero) += time;
+    }
+
+    months.into_iter().collect()
+}
+
+/// Average the time worked on each day of the week between the two given instants, Monday first,
+/// together with the number of days averaged over.
+///
+/// Every occurrence of a day of the week in the range counts towards its average, including days
+/// on which nothing was worked. Days are counted from the first event, if the range begins before
+/// it, and days of the week that don't occur in the range are left out.
+pub fn average_by_weekday(
+    sheet: &Sheet,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(Weekday, Duration, u32)> {
+    let Some(first) = sheet.events().first() else {
+        return Vec::new();
+    };
+
+    let begin = begin.max(first.start);
+    if begin >= end {
+        return Vec::new();
+    }
+
+    let mut days = [0; 7];
+    let mut totals = [Duration::zero(); 7];
+    let last = (end - Duration::nanoseconds(1))
+        .with_timezone(&Local)
+        .date_naive();
+
+    for date in begin.with_timezone(&Local).date_naive().iter_days() {
+        if date > last {
+            break;
+        }
+
+        days[date.weekday().num_days_from_monday() as usize] += 1;
+    }
+
+    for (date, time) in totals_by_date(sheet, begin, end) {
+        totals[date.weekday().num_days_from_monday() as usize] += time;
+    }
+
+    (0..7)
+        .filter(|&day| days[day] > 0)
+        .map(|day| {
+            (
+                Weekday::try_from(day as u8).unwrap(),
+                totals[day] / days[day] as i32,
+                days[day],
+            )
+        })
+        .collect()
+}
+
+/// Split the time between the two given instants into the parts that fall on each local date.
+pub fn split_days(start: DateTime<Utc>, stop: DateTime<Utc>) -> Vec<(NaiveDate, Duration)> {
+    let mut parts = Vec::new();
+    let mut from = start;
+
+    while from < stop {
+        let date = from.with_timezone(&Local).date_naive();
+        let next = std::cmp::min(stop, midnight(date + Days::new(1)).into());
+
+        parts.push((date, next - from));
+        from = next;
+    }
+
+    parts
+}
+
+/// Errors arising through the use of [`DailyTotals`][totals].
+///
+/// [totals]: ./struct.DailyTotals.html
+#[derive(Error, Debug)]
+pub enum TotalsError {
+    #[error("unable to read totals cache")]
+    ReadTotals(#[source] std::io::Error),
+    #[error("unable to parse totals cache")]
+    ParseTotals(#[source] serde_json::Error),
+    #[error("unable to write totals cache")]
+    WriteTotals(#[source] std::io::Error),
+}
@@ -0,0 +1,128 @@
+//! Watch mode, in which the sheet is checked periodically in the background.
+
+use std::{thread, time::Duration as StdDuration};
+
+use chrono::{Duration, Local, NaiveDate};
+use punch_clock::{Config, Period};
+use tracing::warn;
+
+use crate::{
+    auto_close, format_duration, load_sheet, notify, publish_status, record_change,
+    split_at_midnights, write_sheet,
+};
+
+/// Check the sheet every `watch.interval` seconds until the process is killed, notifying the user
+/// of anything that needs their attention.
+pub fn watch(config: &Config) -> ! {
+    let mut target = TargetWatch::default();
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    crate::dbus::spawn(config.clone());
+
+    if config.watch.presence.enabled {
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        crate::presence::spawn(config.clone());
+
+        #[cfg(all(target_os = "linux", not(feature = "dbus")))]
+        warn!("Punching in and out on screen lock needs punch to be built with the dbus feature.");
+
+        #[cfg(not(target_os = "linux"))]
+        warn!("Punching in and out on screen lock is only supported on Linux.");
+    }
+
+    loop {
+        let mut sheet = match load_sheet(config) {
+            Ok(sheet) => sheet,
+            Err(err) => {
+                warn!("Unable to check the sheet: {}", err.describe());
+                thread::sleep(StdDuration::from_secs(config.watch.interval));
+                continue;
+            }
+        };
+        let now = Local::now();
+
+        // A tracking period left running past the cap is closed, and one left running overnight
+        // is split up, as soon as possible, so that the time isn't all counted on the day it
+        // started.
+        let changes: Vec<_> = auto_close(config, &mut sheet)
+            .into_iter()
+            .chain(split_at_midnights(config, &mut sheet))
+            .collect();
+
+        if !changes.is_empty() {
+            match write_sheet(config, &sheet) {
+                Ok(true) => {
+                    for change in &changes {
+                        record_change(config, change);
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => warn!("Unable to write the sheet: {}", err.describe()),
+            }
+        }
+
+        let (start, end) = Period::Today.range(now);
+        let today_total = sheet.count_range(start, end);
+
+        target.check(config, now.date_naive(), today_total);
+
+        // Published on every check, so that the time shown keeps up with the time worked.
+        publish_status(config, &sheet);
+
+        thread::sleep(StdDuration::from_secs(config.watch.interval));
+    }
+}
+
+/// Tracks whether the daily target has been crossed since the last check.
+#[derive(Default)]
+struct TargetWatch {
+    /// The date and total time worked at the last check.
+    last: Option<(NaiveDate, Duration)>,
+}
+
+impl TargetWatch {
+    /// Notify the user if the time worked today has reached the expected amount since the last
+    /// check.
+    fn check(&mut self, config: &Config, today: NaiveDate, total: Duration) {
+        let target = config.schedule.expected_on(today);
+
+        // The first check only records the current total, so that starting to watch after the
+        // target has already been reached doesn't cause a notification.
+        let crossed = match self.last {
+            Some((date, last_total)) if date == today => last_total < target && total >= target,
+            Some(_) => total >= target,
+            None => false,
+        };
+
+        self.last = Some((today, total));
+
+        if !crossed || target <= Duration::zero() {
+            return;
+        }
+
+        let message = tr!(
+            "You've worked {} today, reaching your target of {}.",
+            format_duration(total),
+            format_duration(target),
+        );
+
+        say!("{}", message);
+
+        if config.watch.notify_target {
+            if let Err(err) = notify::desktop(&tr!("Daily target reached"), &message) {
+                warn!("Unable to send notification: {}", err);
+            }
+        }
+
+        if let Some(hook) = &config.watch.target_hook {
+            let env = [
+                ("PUNCH_TODAY_TOTAL", total.num_seconds().to_string()),
+                ("PUNCH_TODAY_TARGET", target.num_seconds().to_string()),
+            ];
+
+            if let Err(err) = notify::run_hook(hook, &env) {
+                warn!("Unable to run target hook: {}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Looking at the sheet as it was in the past with `--as-of` after past tracking periods have been
+//! edited, which has to count the sheet as it was without touching the cache of daily totals.
+
+#![cfg(unix)]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use chrono::{Days, SecondsFormat, Utc};
+
+/// Run `punch` with the given arguments in the given home directory, returning what it printed.
+fn punch(home: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_punch"))
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("TZ", "UTC")
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "punch {:?} failed", args);
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Create an empty home directory for a test.
+fn home(name: &str) -> PathBuf {
+    let home = std::env::temp_dir().join(format!("punch-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home).unwrap();
+
+    home
+}
+
+#[test]
+fn as_of_counts_sheet_as_it_was_without_cache() {
+    let home = home("as-of");
+    let yesterday = (Utc::now().date_naive() - Days::new(1)).format("%Y-%m-%d");
+
+    for (command, time) in [
+        ("in", "09:00"),
+        ("out", "10:00"),
+        ("in", "11:00"),
+        ("out", "12:00"),
+    ] {
+        punch(
+            &home,
+            &[command, "-t", &format!("{}T{}:00Z", yesterday, time)],
+        );
+    }
+
+    // The history records changes to the second, so the edit has to come in a later second than
+    // the time looked back to.
+    thread::sleep(Duration::from_millis(1100));
+    let before_edit = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    thread::sleep(Duration::from_millis(1100));
+
+    let editor = home.join("editor.sh");
+    fs::write(&editor, "#!/bin/sh\nsed -i 's/10:00:00/10:45:00/' \"$1\"\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    punch(
+        &home,
+        &["edit", "yesterday", "--editor", editor.to_str().unwrap()],
+    );
+
+    let edited = "Time worked yesterday: 2 hours, 45 minutes.\n";
+    let original = "Time worked yesterday: 2 hours, 0 minutes.\n";
+
+    assert_eq!(
+        punch(&home, &["count", "yesterday", "--as-of", &before_edit]),
+        original
+    );
+    assert_eq!(punch(&home, &["count", "yesterday"]), edited);
+    assert_eq!(
+        punch(&home, &["count", "yesterday", "--as-of", &before_edit]),
+        original
+    );
+    assert_eq!(punch(&home, &["count", "yesterday"]), edited);
+
+    fs::remove_dir_all(&home).unwrap();
+}